@@ -196,6 +196,22 @@ fn slice_whole_slice(bench: &mut Bencher) {
 
 //----
 
+fn find_iter(bench: &mut Bencher) {
+    let rope = Rope::from_str(TEXT);
+    bench.iter(|| {
+        rope.find_iter("dolor").count();
+    })
+}
+
+fn find_iter_ignore_case(bench: &mut Bencher) {
+    let rope = Rope::from_str(TEXT);
+    bench.iter(|| {
+        rope.find_iter_ignore_case("dolor").count();
+    })
+}
+
+//----
+
 benchmark_group!(
     benches,
     byte_to_char,
@@ -217,6 +233,8 @@ benchmark_group!(
     slice_small,
     slice_from_small_rope,
     slice_whole_rope,
-    slice_whole_slice
+    slice_whole_slice,
+    find_iter,
+    find_iter_ignore_case
 );
 benchmark_main!(benches);
@@ -0,0 +1,344 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Which side of an edit at its position an anchor sticks to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Bias {
+    /// Sticks to the char before it: text inserted at the anchor's position
+    /// ends up after the anchor.
+    Left,
+    /// Sticks to the char after it: text inserted at the anchor's position
+    /// ends up before the anchor.
+    Right,
+}
+
+/// Identifies an anchor in an `Anchors`.
+///
+/// Ids aren't reused: the id of an anchor that has been removed doesn't
+/// refer to any later anchor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AnchorId {
+    index: usize,
+    generation: usize,
+}
+
+/// A set of char positions in a text that follow along with edits to it.
+///
+/// Anchors are useful for anything that needs to keep pointing at the same
+/// place in a text while it's being edited, such as cursors, bookmarks, or
+/// the positions of diagnostics.
+///
+/// `Anchors` doesn't hold on to the text itself.  Instead, each edit made
+/// to the text must also be passed to `edit()`, which shifts the anchors
+/// accordingly.  An anchor at the position of an insertion is shifted
+/// according to its [`Bias`], and anchors within removed text end up at the
+/// start of the removal.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use ropey::{Anchors, Bias};
+///
+/// let mut rope = Rope::from_str("Hello world!");
+/// let mut anchors = Anchors::new();
+/// let start = anchors.add(6, Bias::Left);
+/// let end = anchors.add(11, Bias::Right);
+///
+/// rope.insert(6, "big ");
+/// anchors.edit(6..6, 4);
+/// rope.insert(15, " again");
+/// anchors.edit(15..15, 6);
+///
+/// let (start, end) = (anchors.get(start).unwrap(), anchors.get(end).unwrap());
+/// assert_eq!(rope.slice(start..end), "big world again");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Anchors {
+    slots: Vec<Slot>,
+    // Indices of the slots that aren't in use.
+    free: Vec<usize>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: usize,
+    anchor: Option<(usize, Bias)>,
+}
+
+impl Anchors {
+    /// Creates an empty `Anchors`.
+    #[inline]
+    pub fn new() -> Self {
+        Anchors {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of anchors.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether there are no anchors.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds an anchor at `char_idx` with the given bias, and returns its id.
+    ///
+    /// Runs in O(1) amortized time.
+    pub fn add(&mut self, char_idx: usize, bias: Bias) -> AnchorId {
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index];
+            slot.anchor = Some((char_idx, bias));
+            AnchorId {
+                index: index,
+                generation: slot.generation,
+            }
+        } else {
+            self.slots.push(Slot {
+                generation: 0,
+                anchor: Some((char_idx, bias)),
+            });
+            AnchorId {
+                index: self.slots.len() - 1,
+                generation: 0,
+            }
+        }
+    }
+
+    /// Removes the anchor `id`, returning its char index.
+    ///
+    /// Returns `None` if there is no such anchor.
+    ///
+    /// Runs in O(1) time.
+    pub fn remove(&mut self, id: AnchorId) -> Option<usize> {
+        let slot = self.slot_mut(id)?;
+        let (char_idx, _) = slot.anchor.take()?;
+        slot.generation += 1;
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(char_idx)
+    }
+
+    /// Returns the char index of the anchor `id`.
+    ///
+    /// Returns `None` if there is no such anchor.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn get(&self, id: AnchorId) -> Option<usize> {
+        self.slot(id)?.anchor.map(|(char_idx, _)| char_idx)
+    }
+
+    /// Returns the bias of the anchor `id`.
+    ///
+    /// Returns `None` if there is no such anchor.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn bias(&self, id: AnchorId) -> Option<Bias> {
+        self.slot(id)?.anchor.map(|(_, bias)| bias)
+    }
+
+    /// Moves the anchor `id` to `char_idx`.
+    ///
+    /// Returns the char index it was at, or `None` if there is no such
+    /// anchor.
+    ///
+    /// Runs in O(1) time.
+    pub fn set(&mut self, id: AnchorId, char_idx: usize) -> Option<usize> {
+        let anchor = self.slot_mut(id)?.anchor.as_mut()?;
+        Some(core::mem::replace(&mut anchor.0, char_idx))
+    }
+
+    /// Shifts the anchors for an edit to the text.
+    ///
+    /// `char_range` is the range of text that was removed or replaced, in
+    /// the text as it was before the edit, and `inserted_chars` is the
+    /// length in chars of the text that replaced it.  For pure insertions
+    /// `char_range` is the empty range at the insertion point, and for pure
+    /// removals `inserted_chars` is zero.
+    ///
+    /// Runs in O(N) time in the number of anchors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of `char_range` is greater than its end.
+    pub fn edit(&mut self, char_range: Range<usize>, inserted_chars: usize) {
+        let Range { start, end } = char_range;
+        assert!(
+            start <= end,
+            "Invalid char range {}..{}: start must be less than or equal to end",
+            start,
+            end
+        );
+
+        for slot in &mut self.slots {
+            if let Some((ref mut char_idx, bias)) = slot.anchor {
+                *char_idx = shift_anchor(*char_idx, bias, start, end, inserted_chars);
+            }
+        }
+    }
+
+    /// Creates an iterator over the anchors' ids and char indices.
+    ///
+    /// The anchors are visited in no particular order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (AnchorId, usize)> + 'a {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.anchor.map(|(char_idx, _)| {
+                (
+                    AnchorId {
+                        index: index,
+                        generation: slot.generation,
+                    },
+                    char_idx,
+                )
+            })
+        })
+    }
+
+    //-----------------------------------------------------------------
+    // Internal utilities.
+
+    #[inline]
+    fn slot(&self, id: AnchorId) -> Option<&Slot> {
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+    }
+
+    #[inline]
+    fn slot_mut(&mut self, id: AnchorId) -> Option<&mut Slot> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+    }
+}
+
+/// Returns where an anchor at `char_idx` ends up after an edit that
+/// replaces the chars in `start..end` with `inserted_chars` chars.
+#[inline]
+pub(crate) fn shift_anchor(
+    char_idx: usize,
+    bias: Bias,
+    start: usize,
+    end: usize,
+    inserted_chars: usize,
+) -> usize {
+    if char_idx > end || (char_idx == end && start < end) {
+        // After the edit.
+        char_idx - (end - start) + inserted_chars
+    } else if char_idx >= start {
+        // At the insertion point, or in the removed text.
+        match bias {
+            Bias::Left => start,
+            Bias::Right => start + inserted_chars,
+        }
+    } else {
+        char_idx
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_remove_01() {
+        let mut anchors = Anchors::new();
+        let a = anchors.add(3, Bias::Left);
+        let b = anchors.add(5, Bias::Right);
+        assert_eq!(anchors.len(), 2);
+        assert_eq!(anchors.get(a), Some(3));
+        assert_eq!(anchors.bias(b), Some(Bias::Right));
+
+        assert_eq!(anchors.remove(a), Some(3));
+        assert_eq!(anchors.remove(a), None);
+        assert_eq!(anchors.get(a), None);
+        assert_eq!(anchors.len(), 1);
+
+        // The slot is reused, but the old id stays dead.
+        let c = anchors.add(7, Bias::Left);
+        assert_ne!(a, c);
+        assert_eq!(anchors.get(a), None);
+        assert_eq!(anchors.set(a, 1), None);
+        assert_eq!(anchors.get(c), Some(7));
+
+        assert_eq!(anchors.set(c, 1), Some(7));
+        let mut all: Vec<_> = anchors.iter().collect();
+        all.sort_by_key(|&(_, char_idx)| char_idx);
+        assert_eq!(all, [(c, 1), (b, 5)]);
+    }
+
+    #[test]
+    fn edit_01() {
+        // Insertions.
+        let mut anchors = Anchors::new();
+        let before = anchors.add(2, Bias::Right);
+        let left = anchors.add(5, Bias::Left);
+        let right = anchors.add(5, Bias::Right);
+        let after = anchors.add(6, Bias::Left);
+
+        anchors.edit(5..5, 3);
+        assert_eq!(anchors.get(before), Some(2));
+        assert_eq!(anchors.get(left), Some(5));
+        assert_eq!(anchors.get(right), Some(8));
+        assert_eq!(anchors.get(after), Some(9));
+    }
+
+    #[test]
+    fn edit_02() {
+        // Removals.
+        let mut anchors = Anchors::new();
+        let at_start = anchors.add(2, Bias::Right);
+        let inside_l = anchors.add(4, Bias::Left);
+        let inside_r = anchors.add(4, Bias::Right);
+        let at_end = anchors.add(6, Bias::Left);
+        let after = anchors.add(9, Bias::Right);
+
+        anchors.edit(2..6, 0);
+        assert_eq!(anchors.get(at_start), Some(2));
+        assert_eq!(anchors.get(inside_l), Some(2));
+        assert_eq!(anchors.get(inside_r), Some(2));
+        assert_eq!(anchors.get(at_end), Some(2));
+        assert_eq!(anchors.get(after), Some(5));
+    }
+
+    #[test]
+    fn edit_03() {
+        // Replacements.
+        let mut anchors = Anchors::new();
+        let at_start = anchors.add(2, Bias::Right);
+        let inside_l = anchors.add(4, Bias::Left);
+        let inside_r = anchors.add(4, Bias::Right);
+        let at_end_l = anchors.add(6, Bias::Left);
+        let at_end_r = anchors.add(6, Bias::Right);
+        let after = anchors.add(9, Bias::Left);
+
+        anchors.edit(2..6, 1);
+        assert_eq!(anchors.get(at_start), Some(3));
+        assert_eq!(anchors.get(inside_l), Some(2));
+        assert_eq!(anchors.get(inside_r), Some(3));
+        assert_eq!(anchors.get(at_end_l), Some(3));
+        assert_eq!(anchors.get(at_end_r), Some(3));
+        assert_eq!(anchors.get(after), Some(6));
+    }
+
+    #[test]
+    #[should_panic]
+    fn edit_04() {
+        let mut anchors = Anchors::new();
+        #[allow(clippy::reversed_empty_ranges)]
+        anchors.edit(3..2, 0);
+    }
+}
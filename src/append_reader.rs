@@ -0,0 +1,149 @@
+use std::io;
+
+use smallvec::SmallVec;
+
+use rope::Rope;
+use rope_builder::{write_utf8, RopeBuilder};
+use tree::MAX_BYTES;
+
+/// A reader wrapper for repeatedly appending the output of a growing
+/// stream to a `Rope`, as with `tail -f`.
+///
+/// Used with [`Rope::append_from_reader()`](struct.Rope.html#method.append_from_reader).
+/// The wrapper holds on to any incomplete utf8 sequence at the end of what
+/// has been read so far, so that it can be completed by the next call.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ropey::{AppendReader, Rope};
+/// # fn main() -> std::io::Result<()> {
+/// let mut log = Rope::new();
+/// let mut reader = AppendReader::new(std::fs::File::open("app.log")?);
+/// loop {
+///     log.append_from_reader(&mut reader)?;
+///     // Display the new lines, wait a bit, etc.
+/// #   break;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AppendReader<T> {
+    reader: T,
+    // An incomplete utf8 sequence left over from the last read.
+    partial: SmallVec<[u8; 4]>,
+}
+
+impl<T: io::Read> AppendReader<T> {
+    /// Creates a new `AppendReader`, reading from `reader`.
+    pub fn new(reader: T) -> AppendReader<T> {
+        AppendReader {
+            reader: reader,
+            partial: SmallVec::new(),
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &T {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.reader
+    }
+
+    /// Unwraps this `AppendReader`, returning the underlying reader.
+    ///
+    /// Any incomplete utf8 sequence that was read is discarded.
+    pub fn into_inner(self) -> T {
+        self.reader
+    }
+
+    /// The guts of `Rope::append_from_reader()`.
+    pub(crate) fn append_to(&mut self, rope: &mut Rope) -> io::Result<usize> {
+        let mut builder = RopeBuilder::new();
+        let mut buffer = [0u8; MAX_BYTES * 2];
+        let mut bytes_read = 0;
+
+        let result = 'read: loop {
+            let read_count = match self.reader.read(&mut buffer) {
+                Ok(0) => break Ok(bytes_read),
+                Ok(read_count) => read_count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(bytes_read),
+                Err(e) => break Err(e),
+            };
+
+            let mut input = &buffer[..read_count];
+            while !input.is_empty() {
+                match write_utf8(&mut self.partial, input, |text| builder.append(text)) {
+                    Ok(consumed) => input = &input[consumed..],
+                    Err(e) => break 'read Err(e),
+                }
+            }
+            bytes_read += read_count;
+        };
+
+        // Append whatever valid text was read, even if there was an error.
+        rope.append(builder.finish());
+        result
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// A reader over data that can grow, like a file being appended to.
+    #[derive(Default)]
+    struct GrowingReader {
+        data: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Read for GrowingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = (self.data.len() - self.pos).min(buf.len());
+            buf[..len].copy_from_slice(&self.data[self.pos..(self.pos + len)]);
+            self.pos += len;
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn append_from_reader_01() {
+        let text = "Hello there!\r\nHow're you doing?\r\nこんにちは、みんなさん！\n";
+        let mut r = Rope::from_str("Log:\n");
+        let mut reader = AppendReader::new(GrowingReader::default());
+
+        // Grow the data a few bytes at a time, splitting chars and crlf
+        // pairs.
+        let full = format!("Log:\n{}", text);
+        for chunk in text.as_bytes().chunks(5) {
+            reader.get_mut().data.extend_from_slice(chunk);
+            assert_eq!(r.append_from_reader(&mut reader).unwrap(), chunk.len());
+            assert!(full.starts_with(&r.to_string()));
+        }
+        assert_eq!(r.append_from_reader(&mut reader).unwrap(), 0);
+
+        assert_eq!(r, full.as_str());
+        assert_eq!(r.len_lines(), 5);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn append_from_reader_02() {
+        // Invalid utf8 appends the valid text before it.
+        let mut r = Rope::from_str("Log:\n");
+        let mut reader = AppendReader::new(&b"abc\xFFdef"[..]);
+        let err = r.append_from_reader(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(r, "Log:\nabc");
+    }
+}
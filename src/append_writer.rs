@@ -0,0 +1,129 @@
+use std::io;
+
+use rope::Rope;
+use rope_builder::RopeBuilder;
+
+/// An `io::Write` adapter that appends utf8 text to the end of a `Rope`.
+///
+/// Written text is collected with a `RopeBuilder`, and appended to the
+/// `Rope` in one go when the writer is flushed or dropped.  This is much
+/// faster than inserting each write separately.
+///
+/// As with `RopeBuilder`, multi-byte utf8 sequences may be split across
+/// writes.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use std::io::Write;
+///
+/// let mut rope = Rope::from_str("Log:\n");
+/// {
+///     let mut writer = rope.append_writer();
+///     writeln!(writer, "Started {} workers", 4).unwrap();
+///     writeln!(writer, "Finished").unwrap();
+/// }
+///
+/// assert_eq!(rope, "Log:\nStarted 4 workers\nFinished\n");
+/// ```
+///
+/// # Errors
+///
+/// - If the written data is not valid utf8, returns an error of kind
+///   `InvalidData`.  All data before the invalid bytes is still appended.
+/// - If `flush()` is called in the middle of a split sequence, returns an
+///   error of kind `InvalidData`, after appending all of the text before
+///   it.  Dropping the writer discards any such incomplete sequence, so
+///   call `flush()` first if this needs to be checked.
+#[derive(Debug)]
+pub struct AppendWriter<'a> {
+    rope: &'a mut Rope,
+    builder: RopeBuilder,
+}
+
+impl<'a> AppendWriter<'a> {
+    pub(crate) fn new(rope: &'a mut Rope) -> AppendWriter<'a> {
+        AppendWriter {
+            rope: rope,
+            builder: RopeBuilder::new(),
+        }
+    }
+
+    fn append_built(&mut self) {
+        let text = self.builder.take_rope();
+        self.rope.append(text);
+    }
+}
+
+impl<'a> io::Write for AppendWriter<'a> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.builder.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.append_built();
+        self.builder.flush()
+    }
+}
+
+impl<'a> Drop for AppendWriter<'a> {
+    fn drop(&mut self) {
+        self.append_built();
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn append_writer_01() {
+        let mut r = Rope::from_str(&TEXT[..50]);
+        {
+            let mut writer = r.append_writer();
+            for chunk in TEXT.as_bytes()[50..].chunks(3) {
+                writer.write_all(chunk).unwrap();
+            }
+        }
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn append_writer_02() {
+        // Flushing in the middle of a split sequence.
+        let mut r = Rope::from_str("Hello ");
+        let bytes = "みんな".as_bytes();
+        let mut writer = r.append_writer();
+        writer.write_all(&bytes[..4]).unwrap();
+        assert!(writer.flush().is_err());
+        writer.write_all(&bytes[4..]).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert_eq!(r, "Hello みんな");
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn append_writer_03() {
+        // Crlf pairs split between the rope and the written text.
+        let mut r = Rope::from_str("Hello\r");
+        write!(r.append_writer(), "\nthere").unwrap();
+        assert_eq!(r, "Hello\r\nthere");
+        assert_eq!(r.len_lines(), 2);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+}
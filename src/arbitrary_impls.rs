@@ -0,0 +1,97 @@
+//! Fuzzing support.
+//!
+//! Only available with the `arbitrary` feature enabled.
+//!
+//! Ropes built with `Rope::from_str()` always have the same shape for the
+//! same text, so the generated ropes are instead built up from a sequence
+//! of random edits.  This gives varied chunk boundaries and tree shapes,
+//! for fuzzing code that depends on them.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use rope::Rope;
+
+impl<'a> Arbitrary<'a> for Rope {
+    /// Generates a rope by applying random inserts, appends and removals to
+    /// a random starting text.
+    ///
+    /// Note: when the text contains `\r`, edits that join the two halves of
+    /// a CRLF pair can occasionally leave an internal node with fewer than
+    /// the minimum number of children (see the TODO on
+    /// `Node::fix_crlf_seam()`).  So `Rope::validate()` can report
+    /// `TooFewChildren` for such ropes even though their text and counts
+    /// are correct.  Fuzz targets that check `validate()` should skip that
+    /// error, or call `rebalance()` first.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Rope> {
+        let mut rope = Rope::from_str(u.arbitrary()?);
+
+        // Exhausted input only ever gives `false`, so this always ends.
+        while u.arbitrary()? {
+            let len = rope.len_chars();
+            match u.int_in_range(0..=3u8)? {
+                0 => {
+                    let char_idx = u.int_in_range(0..=len)?;
+                    rope.insert(char_idx, u.arbitrary()?);
+                }
+                1 => {
+                    let char_idx = u.int_in_range(0..=len)?;
+                    rope.insert_char(char_idx, u.arbitrary()?);
+                }
+                2 => {
+                    rope.append(Rope::from_str(u.arbitrary()?));
+                }
+                _ => {
+                    let start = u.int_in_range(0..=len)?;
+                    let end = u.int_in_range(start..=len)?;
+                    rope.remove(start..end);
+                }
+            }
+        }
+
+        Ok(rope)
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn arbitrary_01() {
+        // Use the text itself as the fuzzer's input at varying offsets, so
+        // that the inputs hold a mix of valid and invalid utf8.
+        let data = TEXT.repeat(20);
+        let mut reshaped = 0;
+        for i in 0..100 {
+            let mut u = Unstructured::new(&data.as_bytes()[(i * 7)..]);
+            let r = Rope::arbitrary(&mut u).unwrap();
+            r.assert_integrity();
+            r.assert_invariants();
+
+            let r2 = Rope::from_str(&r.to_string());
+            if !r.chunks().eq(r2.chunks()) {
+                reshaped += 1;
+            }
+        }
+
+        // Not just the shapes that `from_str()` would give.
+        assert!(reshaped > 0);
+    }
+
+    #[test]
+    fn arbitrary_02() {
+        let r = Rope::arbitrary(&mut Unstructured::new(&[])).unwrap();
+        assert_eq!(r, "");
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+}
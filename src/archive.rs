@@ -0,0 +1,761 @@
+//! Zero-copy archiving with `rkyv`.
+//!
+//! Only available with the `rkyv` feature enabled.
+//!
+//! `Rope` implements rkyv's `Archive`, `Serialize` and `Deserialize`, with
+//! an [`ArchivedRope`](struct.ArchivedRope.html) as its archived form.  An
+//! `ArchivedRope` can be queried directly from the archive's bytes (e.g. a
+//! memory-mapped file) without deserializing it into a `Rope` first.
+//!
+//! The archived form stores the rope's chunks along with the accumulated
+//! byte, char, and line break counts at the end of each chunk, so index
+//! conversions run in O(log N) time, the same as with a `Rope`.
+//!
+//! # Example
+//!
+//! ```
+//! # use ropey::Rope;
+//! use ropey::archive::ArchivedRope;
+//!
+//! let rope = Rope::from_str("Hello world!\nHow are you?");
+//! let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&rope).unwrap();
+//!
+//! let archived = rkyv::access::<ArchivedRope, rkyv::rancor::Error>(&bytes).unwrap();
+//! assert_eq!(archived.len_lines(), 2);
+//! assert_eq!(archived.line_to_char(1), 13);
+//! assert_eq!(archived.slice(6..11).to_string(), "world");
+//!
+//! let rope2: Rope = rkyv::deserialize::<_, rkyv::rancor::Error>(archived).unwrap();
+//! assert_eq!(rope2, rope);
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeBounds;
+
+use rkyv::bytecheck::{CheckBytes, Verify};
+use rkyv::primitive::ArchivedU64;
+use rkyv::rancor::{fail, Fallible, Source};
+use rkyv::ser::{Allocator, Writer};
+use rkyv::string::{ArchivedString, StringResolver};
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::{munge::munge, Archive, Deserialize, Place, Portable, Serialize, SerializeUnsized};
+
+use rope::Rope;
+use rope_builder::RopeBuilder;
+use slice::{end_bound_to_num, start_bound_to_num};
+use str_utils::{byte_to_char_idx, char_to_byte_idx, char_to_line_idx, line_to_char_idx};
+use tree::{Node, TextInfo};
+
+/// The archived form of a `Rope`.
+///
+/// Provides the read-only queries of a `Rope` that don't depend on its tree
+/// structure.  See the [module documentation](index.html) for an example.
+#[derive(Portable, CheckBytes)]
+#[bytecheck(crate = ::rkyv::bytecheck, verify)]
+#[rkyv(crate = ::rkyv)]
+#[repr(C)]
+pub struct ArchivedRope {
+    chunks: ArchivedVec<ArchivedString>,
+    // The accumulated counts at the end of each chunk.
+    byte_ends: ArchivedVec<ArchivedU64>,
+    char_ends: ArchivedVec<ArchivedU64>,
+    line_break_ends: ArchivedVec<ArchivedU64>,
+}
+
+impl ArchivedRope {
+    /// Total number of bytes in the archived `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_bytes(&self) -> usize {
+        count_before(&self.byte_ends, self.byte_ends.len())
+    }
+
+    /// Total number of chars in the archived `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_chars(&self) -> usize {
+        count_before(&self.char_ends, self.char_ends.len())
+    }
+
+    /// Total number of lines in the archived `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_lines(&self) -> usize {
+        count_before(&self.line_break_ends, self.line_break_ends.len()) + 1
+    }
+
+    /// Returns the char index of the given byte.
+    ///
+    /// Same as [`Rope::byte_to_char()`](../struct.Rope.html#method.byte_to_char).
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        // Bounds check
+        assert!(
+            byte_idx <= self.len_bytes(),
+            "Attempt to index past end of ArchivedRope: byte index {}, ArchivedRope byte length {}",
+            byte_idx,
+            self.len_bytes()
+        );
+
+        let i = chunk_idx(&self.byte_ends, |end| end <= byte_idx);
+        let chunk = self.chunks[i].as_str();
+        count_before(&self.char_ends, i)
+            + byte_to_char_idx(chunk, byte_idx - count_before(&self.byte_ends, i))
+    }
+
+    /// Returns the byte index of the given char.
+    ///
+    /// Same as [`Rope::char_to_byte()`](../struct.Rope.html#method.char_to_byte).
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        // Bounds check
+        assert!(
+            char_idx <= self.len_chars(),
+            "Attempt to index past end of ArchivedRope: char index {}, ArchivedRope char length {}",
+            char_idx,
+            self.len_chars()
+        );
+
+        let i = chunk_idx(&self.char_ends, |end| end <= char_idx);
+        let chunk = self.chunks[i].as_str();
+        count_before(&self.byte_ends, i)
+            + char_to_byte_idx(chunk, char_idx - count_before(&self.char_ends, i))
+    }
+
+    /// Returns the line index of the given char.
+    ///
+    /// Same as [`Rope::char_to_line()`](../struct.Rope.html#method.char_to_line).
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        // Bounds check
+        assert!(
+            char_idx <= self.len_chars(),
+            "Attempt to index past end of ArchivedRope: char index {}, ArchivedRope char length {}",
+            char_idx,
+            self.len_chars()
+        );
+
+        let i = chunk_idx(&self.char_ends, |end| end <= char_idx);
+        let chunk = self.chunks[i].as_str();
+        count_before(&self.line_break_ends, i)
+            + char_to_line_idx(chunk, char_idx - count_before(&self.char_ends, i))
+    }
+
+    /// Returns the char index of the start of the given line.
+    ///
+    /// Same as [`Rope::line_to_char()`](../struct.Rope.html#method.line_to_char).
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `line_idx` is out of bounds (i.e. `line_idx > len_lines()`).
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        // Bounds check
+        assert!(
+            line_idx <= self.len_lines(),
+            "Attempt to index past end of ArchivedRope: line index {}, ArchivedRope line length {}",
+            line_idx,
+            self.len_lines()
+        );
+
+        if line_idx == self.len_lines() {
+            self.len_chars()
+        } else {
+            let i = chunk_idx(&self.line_break_ends, |end| end < line_idx);
+            let chunk = self.chunks[i].as_str();
+            count_before(&self.char_ends, i)
+                + line_to_char_idx(chunk, line_idx - count_before(&self.line_break_ends, i))
+        }
+    }
+
+    /// Returns the char at `char_idx`.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `char_idx` is out of bounds (i.e. `char_idx >= len_chars()`).
+    pub fn char(&self, char_idx: usize) -> char {
+        // Bounds check
+        assert!(
+            char_idx < self.len_chars(),
+            "Attempt to index past end of ArchivedRope: char index {}, ArchivedRope char length {}",
+            char_idx,
+            self.len_chars()
+        );
+
+        let byte_idx = self.char_to_byte(char_idx);
+        let i = chunk_idx(&self.byte_ends, |end| end <= byte_idx);
+        let chunk = self.chunks[i].as_str();
+        chunk[(byte_idx - count_before(&self.byte_ends, i))..]
+            .chars()
+            .next()
+            .unwrap()
+    }
+
+    /// Gets an immutable slice of the archived `Rope`, using char indices.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// - If the start of the range is greater than the end.
+    /// - If the end is out of bounds (i.e. `end > len_chars()`).
+    pub fn slice<R>(&self, char_range: R) -> ArchivedRopeSlice<'_>
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = start_bound_to_num(char_range.start_bound()).unwrap_or(0);
+        let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
+
+        // Bounds check
+        assert!(start <= end);
+        assert!(
+            end <= self.len_chars(),
+            "Attempt to slice past end of ArchivedRope: slice end {}, ArchivedRope char length {}",
+            end,
+            self.len_chars()
+        );
+
+        ArchivedRopeSlice {
+            rope: self,
+            start_byte: self.char_to_byte(start),
+            end_byte: self.char_to_byte(end),
+            start_char: start,
+            end_char: end,
+        }
+    }
+
+    /// Creates an iterator over the chunks of the archived `Rope`.
+    ///
+    /// The chunks are the same as those of the `Rope` that was archived.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn chunks(&self) -> ArchivedChunks<'_> {
+        self.slice(..).chunks()
+    }
+
+    /// Creates an iterator over the chars of the archived `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn chars(&self) -> ArchivedChars<'_> {
+        self.slice(..).chars()
+    }
+}
+
+impl fmt::Debug for ArchivedRope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.chunks()).finish()
+    }
+}
+
+impl fmt::Display for ArchivedRope {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.slice(..), f)
+    }
+}
+
+impl PartialEq<Rope> for ArchivedRope {
+    #[inline]
+    fn eq(&self, other: &Rope) -> bool {
+        self.len_bytes() == other.len_bytes() && self.bytes_eq(other.chunks())
+    }
+}
+
+impl PartialEq<ArchivedRope> for Rope {
+    #[inline]
+    fn eq(&self, other: &ArchivedRope) -> bool {
+        other == self
+    }
+}
+
+impl ArchivedRope {
+    fn bytes_eq<'a, I: Iterator<Item = &'a str>>(&self, chunks: I) -> bool {
+        self.chunks()
+            .flat_map(str::bytes)
+            .eq(chunks.flat_map(str::bytes))
+    }
+}
+
+unsafe impl<C> Verify<C> for ArchivedRope
+where
+    C: Fallible + ?Sized,
+    C::Error: Source,
+{
+    /// Checks that the accumulated counts are consistent with each other
+    /// and with the chunks, so that queries can't index out of bounds.
+    ///
+    /// The char and line break counts themselves aren't re-counted.
+    fn verify(&self, _context: &mut C) -> Result<(), C::Error> {
+        let n = self.chunks.len();
+        if n == 0
+            || self.byte_ends.len() != n
+            || self.char_ends.len() != n
+            || self.line_break_ends.len() != n
+        {
+            fail!(InvalidArchive);
+        }
+        // The count of chunk `i` in `ends`, if it isn't negative.
+        let count = |ends: &ArchivedVec<ArchivedU64>, i| {
+            count_before(ends, i + 1).checked_sub(count_before(ends, i))
+        };
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            let bytes = count(&self.byte_ends, i);
+            let chars = count(&self.char_ends, i);
+            let line_breaks = count(&self.line_break_ends, i);
+            if bytes != Some(chunk.len())
+                || chars.is_none_or(|c| c > chunk.len())
+                || line_breaks.is_none()
+            {
+                fail!(InvalidArchive);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error reported when an `ArchivedRope` fails validation.
+#[derive(Debug)]
+struct InvalidArchive;
+
+impl fmt::Display for InvalidArchive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("inconsistent ArchivedRope")
+    }
+}
+
+impl core::error::Error for InvalidArchive {}
+
+//==============================================================
+
+/// An immutable view into part of an `ArchivedRope`.
+///
+/// Created with [`ArchivedRope::slice()`](struct.ArchivedRope.html#method.slice).
+#[derive(Copy, Clone)]
+pub struct ArchivedRopeSlice<'a> {
+    rope: &'a ArchivedRope,
+    start_byte: usize,
+    end_byte: usize,
+    start_char: usize,
+    end_char: usize,
+}
+
+impl<'a> ArchivedRopeSlice<'a> {
+    /// Total number of bytes in the slice.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_bytes(&self) -> usize {
+        self.end_byte - self.start_byte
+    }
+
+    /// Total number of chars in the slice.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_chars(&self) -> usize {
+        self.end_char - self.start_char
+    }
+
+    /// Gets a sub-slice of the slice, using char indices.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// - If the start of the range is greater than the end.
+    /// - If the end is out of bounds (i.e. `end > len_chars()`).
+    pub fn slice<R>(&self, char_range: R) -> ArchivedRopeSlice<'a>
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = start_bound_to_num(char_range.start_bound()).unwrap_or(0);
+        let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
+
+        // Bounds check
+        assert!(start <= end);
+        assert!(
+            end <= self.len_chars(),
+            "Attempt to slice past end of ArchivedRopeSlice: slice end {}, ArchivedRopeSlice char length {}",
+            end,
+            self.len_chars()
+        );
+
+        self.rope
+            .slice((self.start_char + start)..(self.start_char + end))
+    }
+
+    /// Creates an iterator over the chunks of the slice.
+    ///
+    /// Runs in O(log N) time.
+    pub fn chunks(&self) -> ArchivedChunks<'a> {
+        let chunk_idx = chunk_idx(&self.rope.byte_ends, |end| end <= self.start_byte);
+        ArchivedChunks {
+            rope: self.rope,
+            chunk_idx: chunk_idx,
+            byte_idx: self.start_byte,
+            end_byte: self.end_byte,
+        }
+    }
+
+    /// Creates an iterator over the chars of the slice.
+    ///
+    /// Runs in O(log N) time.
+    #[inline]
+    pub fn chars(&self) -> ArchivedChars<'a> {
+        ArchivedChars {
+            chunks: self.chunks(),
+            chars: "".chars(),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for ArchivedRopeSlice<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.chunks()).finish()
+    }
+}
+
+impl<'a> fmt::Display for ArchivedRopeSlice<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for chunk in self.chunks() {
+            write!(f, "{}", chunk)?
+        }
+        Ok(())
+    }
+}
+
+impl<'a> PartialEq<str> for ArchivedRopeSlice<'a> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.len_bytes() == other.len() && self.chunks().flat_map(str::bytes).eq(other.bytes())
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for ArchivedRopeSlice<'a> {
+    #[inline]
+    fn eq(&self, other: &&'b str) -> bool {
+        *self == **other
+    }
+}
+
+//==============================================================
+
+/// An iterator over the chunks of an `ArchivedRope` or `ArchivedRopeSlice`.
+#[derive(Debug, Clone)]
+pub struct ArchivedChunks<'a> {
+    rope: &'a ArchivedRope,
+    chunk_idx: usize,
+    byte_idx: usize,
+    end_byte: usize,
+}
+
+impl<'a> Iterator for ArchivedChunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.byte_idx >= self.end_byte {
+            return None;
+        }
+
+        let chunk = self.rope.chunks[self.chunk_idx].as_str();
+        let chunk_start = count_before(&self.rope.byte_ends, self.chunk_idx);
+        let chunk_end = (self.end_byte - chunk_start).min(chunk.len());
+        let text = &chunk[(self.byte_idx - chunk_start)..chunk_end];
+
+        self.byte_idx = chunk_start + chunk_end;
+        self.chunk_idx += 1;
+        Some(text)
+    }
+}
+
+/// An iterator over the chars of an `ArchivedRope` or `ArchivedRopeSlice`.
+#[derive(Debug, Clone)]
+pub struct ArchivedChars<'a> {
+    chunks: ArchivedChunks<'a>,
+    chars: core::str::Chars<'a>,
+}
+
+impl<'a> Iterator for ArchivedChars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            if let Some(c) = self.chars.next() {
+                return Some(c);
+            }
+            self.chars = self.chunks.next()?.chars();
+        }
+    }
+}
+
+//==============================================================
+
+/// The resolver for an archived `Rope`.
+pub struct RopeResolver {
+    len: usize,
+    chunks: VecResolver,
+    byte_ends: VecResolver,
+    char_ends: VecResolver,
+    line_break_ends: VecResolver,
+}
+
+impl Archive for Rope {
+    type Archived = ArchivedRope;
+    type Resolver = RopeResolver;
+
+    fn resolve(&self, resolver: RopeResolver, out: Place<ArchivedRope>) {
+        munge!(let ArchivedRope { chunks, byte_ends, char_ends, line_break_ends } = out);
+        ArchivedVec::resolve_from_len(resolver.len, resolver.chunks, chunks);
+        ArchivedVec::resolve_from_len(resolver.len, resolver.byte_ends, byte_ends);
+        ArchivedVec::resolve_from_len(resolver.len, resolver.char_ends, char_ends);
+        ArchivedVec::resolve_from_len(resolver.len, resolver.line_break_ends, line_break_ends);
+    }
+}
+
+impl<S> Serialize<S> for Rope
+where
+    S: Fallible + Allocator + Writer + ?Sized,
+    S::Error: Source,
+    str: SerializeUnsized<S>,
+{
+    // `Count` is only a `u64` without the `u32_counts` feature.
+    #[allow(clippy::unnecessary_cast)]
+    fn serialize(&self, serializer: &mut S) -> Result<RopeResolver, S::Error> {
+        // Use the chunks' text info from the tree, rather than counting
+        // their chars and line breaks again.
+        let mut chunks = Vec::new();
+        let mut info = Vec::new();
+        if let Node::Leaf(ref text) = *self.root {
+            chunks.push(Chunk(text));
+            info.push(self.root.text_info());
+        } else {
+            collect_leaves(&self.root, &mut chunks, &mut info);
+        }
+
+        let mut acc = TextInfo::new();
+        let mut byte_ends = Vec::with_capacity(info.len());
+        let mut char_ends = Vec::with_capacity(info.len());
+        let mut line_break_ends = Vec::with_capacity(info.len());
+        for i in info {
+            acc += i;
+            byte_ends.push(acc.bytes as u64);
+            char_ends.push(acc.chars as u64);
+            line_break_ends.push(acc.line_breaks as u64);
+        }
+
+        Ok(RopeResolver {
+            len: chunks.len(),
+            chunks: ArchivedVec::serialize_from_slice(&chunks, serializer)?,
+            byte_ends: ArchivedVec::serialize_from_slice(&byte_ends, serializer)?,
+            char_ends: ArchivedVec::serialize_from_slice(&char_ends, serializer)?,
+            line_break_ends: ArchivedVec::serialize_from_slice(&line_break_ends, serializer)?,
+        })
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<Rope, D> for ArchivedRope {
+    fn deserialize(&self, _: &mut D) -> Result<Rope, D::Error> {
+        let mut builder = RopeBuilder::new();
+        for chunk in self.chunks() {
+            builder.append(chunk);
+        }
+        Ok(builder.finish())
+    }
+}
+
+/// A leaf's text, archived as an `ArchivedString`.
+struct Chunk<'a>(&'a str);
+
+impl<'a> Archive for Chunk<'a> {
+    type Archived = ArchivedString;
+    type Resolver = StringResolver;
+
+    #[inline]
+    fn resolve(&self, resolver: StringResolver, out: Place<ArchivedString>) {
+        ArchivedString::resolve_from_str(self.0, resolver, out);
+    }
+}
+
+impl<'a, S> Serialize<S> for Chunk<'a>
+where
+    S: Fallible + ?Sized,
+    S::Error: Source,
+    str: SerializeUnsized<S>,
+{
+    #[inline]
+    fn serialize(&self, serializer: &mut S) -> Result<StringResolver, S::Error> {
+        ArchivedString::serialize_from_str(self.0, serializer)
+    }
+}
+
+fn collect_leaves<'a>(node: &'a Node, chunks: &mut Vec<Chunk<'a>>, info: &mut Vec<TextInfo>) {
+    if let Node::Internal(ref children) = *node {
+        for (child_info, child) in children.iter() {
+            match **child {
+                Node::Leaf(ref text) => {
+                    chunks.push(Chunk(text));
+                    info.push(*child_info);
+                }
+                Node::Internal(_) => collect_leaves(child, chunks, info),
+            }
+        }
+    }
+}
+
+//==============================================================
+
+/// Returns the accumulated count before chunk `i`, i.e. at the end of
+/// chunk `i - 1`.
+#[inline]
+fn count_before(ends: &ArchivedVec<ArchivedU64>, i: usize) -> usize {
+    if i == 0 {
+        0
+    } else {
+        ends[i - 1].to_native() as usize
+    }
+}
+
+/// Returns the index of the first chunk whose accumulated end count doesn't
+/// satisfy `before`, clamped to the last chunk.
+#[inline]
+fn chunk_idx<F: Fn(usize) -> bool>(ends: &ArchivedVec<ArchivedU64>, before: F) -> usize {
+    ends.partition_point(|end| before(end.to_native() as usize))
+        .min(ends.len() - 1)
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use rkyv::rancor::Error;
+
+    use super::*;
+
+    // 126 bytes, 102 chars, 4 lines
+    const TEXT: &str = "Hello there!  How're you doing?\r\nIt's \
+                        a fine day, isn't it?\nAren't you glad \
+                        we're alive?\r\nこんにちは、みんなさん！";
+
+    fn archive(r: &Rope) -> rkyv::util::AlignedVec {
+        rkyv::to_bytes::<Error>(r).unwrap()
+    }
+
+    #[test]
+    fn archive_01() {
+        let mut r = Rope::from_str(&TEXT.repeat(3));
+        r.insert(50, "Hi!\n");
+        let bytes = archive(&r);
+        let a = rkyv::access::<ArchivedRope, Error>(&bytes).unwrap();
+
+        assert_eq!(a.len_bytes(), r.len_bytes());
+        assert_eq!(a.len_chars(), r.len_chars());
+        assert_eq!(a.len_lines(), r.len_lines());
+        assert_eq!(*a, r);
+        assert_eq!(a.to_string(), r.to_string());
+        assert!(a.chunks().eq(r.chunks()));
+        assert!(a.chars().eq(r.chars()));
+
+        for i in 0..=r.len_bytes() {
+            assert_eq!(a.byte_to_char(i), r.byte_to_char(i));
+        }
+        for i in 0..=r.len_chars() {
+            assert_eq!(a.char_to_byte(i), r.char_to_byte(i));
+            assert_eq!(a.char_to_line(i), r.char_to_line(i));
+        }
+        for i in 0..r.len_chars() {
+            assert_eq!(a.char(i), r.char(i));
+        }
+        for i in 0..=r.len_lines() {
+            assert_eq!(a.line_to_char(i), r.line_to_char(i));
+        }
+    }
+
+    #[test]
+    fn archive_02() {
+        let r = Rope::new();
+        let bytes = archive(&r);
+        let a = rkyv::access::<ArchivedRope, Error>(&bytes).unwrap();
+        assert_eq!(a.len_chars(), 0);
+        assert_eq!(a.len_lines(), 1);
+        assert_eq!(a.chunks().count(), 0);
+        assert_eq!(a.line_to_char(1), 0);
+        assert_eq!(*a, r);
+    }
+
+    #[test]
+    fn archive_slice_01() {
+        let r = Rope::from_str(TEXT);
+        let bytes = archive(&r);
+        let a = rkyv::access::<ArchivedRope, Error>(&bytes).unwrap();
+
+        for &(start, end) in &[(0, 102), (5, 98), (14, 31), (40, 40), (91, 102)] {
+            let s = a.slice(start..end);
+            let expected = r.slice(start..end);
+            assert_eq!(s.len_chars(), expected.len_chars());
+            assert_eq!(s.len_bytes(), expected.len_bytes());
+            assert_eq!(s.to_string(), expected.to_string());
+            assert!(s.chars().eq(expected.chars()));
+            assert_eq!(s, expected.to_string().as_str());
+        }
+
+        let s = a.slice(14..).slice(..17);
+        assert_eq!(s, "How're you doing?");
+    }
+
+    #[test]
+    #[should_panic]
+    fn archive_slice_02() {
+        let r = Rope::from_str(TEXT);
+        let bytes = archive(&r);
+        let a = rkyv::access::<ArchivedRope, Error>(&bytes).unwrap();
+        a.slice(50..103);
+    }
+
+    #[test]
+    fn deserialize_01() {
+        let r = Rope::from_str(&TEXT.repeat(5));
+        let bytes = archive(&r);
+        let a = rkyv::access::<ArchivedRope, Error>(&bytes).unwrap();
+        let r2: Rope = rkyv::deserialize::<Rope, Error>(a).unwrap();
+        assert_eq!(r2, r);
+        r2.assert_integrity();
+        r2.assert_invariants();
+    }
+
+    #[test]
+    fn validate_01() {
+        let r = Rope::from_str(TEXT);
+        let mut bytes = archive(&r).to_vec();
+
+        // Corrupting a chunk's text.
+        let idx = bytes.windows(5).position(|w| w == b"Hello").unwrap();
+        bytes[idx] = 0xFF;
+        let mut aligned = rkyv::util::AlignedVec::<16>::new();
+        aligned.extend_from_slice(&bytes);
+        assert!(rkyv::access::<ArchivedRope, Error>(&aligned).is_err());
+    }
+}
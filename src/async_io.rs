@@ -0,0 +1,266 @@
+//! Async I/O support.
+//!
+//! Only available with the `futures` feature enabled.
+
+use std::future::Future;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use iter::Chunks;
+use rope::Rope;
+use rope_builder::RopeBuilder;
+use slice::RopeSlice;
+use tree::MAX_BYTES;
+
+const BUFFER_SIZE: usize = MAX_BYTES * 2;
+
+/// A future that creates a `Rope` from an `AsyncRead`.
+///
+/// Returned by [`Rope::from_async_reader()`](struct.Rope.html#method.from_async_reader).
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct FromAsyncReader<R> {
+    reader: R,
+    builder: Option<RopeBuilder>,
+    buffer: Box<[u8]>,
+}
+
+impl<R: AsyncRead + Unpin> FromAsyncReader<R> {
+    pub(crate) fn new(reader: R) -> FromAsyncReader<R> {
+        FromAsyncReader {
+            reader: reader,
+            builder: Some(RopeBuilder::new()),
+            buffer: vec![0u8; BUFFER_SIZE].into_boxed_slice(),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Future for FromAsyncReader<R> {
+    type Output = io::Result<Rope>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<Rope>> {
+        let this = &mut *self;
+        loop {
+            let read_count = match Pin::new(&mut this.reader).poll_read(cx, &mut this.buffer) {
+                Poll::Ready(Ok(read_count)) => read_count,
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let builder = this
+                .builder
+                .as_mut()
+                .expect("FromAsyncReader polled after completion");
+
+            if read_count == 0 {
+                // Done reading, so make sure we didn't stop in the middle of
+                // a char.
+                builder.flush()?;
+                return Poll::Ready(Ok(this.builder.take().unwrap().finish()));
+            }
+
+            builder.write_all(&this.buffer[..read_count])?;
+        }
+    }
+}
+
+/// A future that writes the contents of a `Rope`/`RopeSlice` to an
+/// `AsyncWrite`.
+///
+/// Returned by [`Rope::write_to_async()`](struct.Rope.html#method.write_to_async)
+/// and [`RopeSlice::write_to_async()`](struct.RopeSlice.html#method.write_to_async).
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct WriteToAsync<'a, W> {
+    chunks: Chunks<'a>,
+    // The part of the current chunk that's still to be written.
+    chunk: &'a [u8],
+    writer: W,
+}
+
+impl<'a, W: AsyncWrite + Unpin> WriteToAsync<'a, W> {
+    pub(crate) fn new(slice: &RopeSlice<'a>, writer: W) -> WriteToAsync<'a, W> {
+        WriteToAsync {
+            chunks: slice.chunks(),
+            chunk: &[],
+            writer: writer,
+        }
+    }
+}
+
+impl<'a, W: AsyncWrite + Unpin> Future for WriteToAsync<'a, W> {
+    type Output = io::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.chunk.is_empty() {
+                match this.chunks.next() {
+                    Some(chunk) => this.chunk = chunk.as_bytes(),
+                    None => return Poll::Ready(Ok(())),
+                }
+                continue;
+            }
+
+            match Pin::new(&mut this.writer).poll_write(cx, this.chunk) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    )));
+                }
+                Poll::Ready(Ok(written)) => this.chunk = &this.chunk[written..],
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_executor::block_on;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    /// A reader that returns a few bytes at a time, and is pending every
+    /// other call.
+    struct TrickleReader<'a> {
+        data: &'a [u8],
+        pending: bool,
+    }
+
+    impl<'a> AsyncRead for TrickleReader<'a> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let len = self.data.len().min(buf.len()).min(5);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    /// A writer that accepts a few bytes at a time, and is pending every
+    /// other call.
+    #[derive(Default)]
+    struct TrickleWriter {
+        data: Vec<u8>,
+        pending: bool,
+    }
+
+    impl AsyncWrite for TrickleWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let len = buf.len().min(5);
+            self.data.extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn from_async_reader_01() {
+        let r = block_on(Rope::from_async_reader(TEXT.as_bytes())).unwrap();
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn from_async_reader_02() {
+        // Pending reads, and chars split across reads.
+        let reader = TrickleReader {
+            data: TEXT.as_bytes(),
+            pending: false,
+        };
+        let r = block_on(Rope::from_async_reader(reader)).unwrap();
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn from_async_reader_03() {
+        // Invalid utf8.
+        let data = b"Hello \xFFthere";
+        let err = block_on(Rope::from_async_reader(&data[..])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let data = &"こんにちは".as_bytes()[..7];
+        let err = block_on(Rope::from_async_reader(data)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn write_to_async_01() {
+        let r = Rope::from_str(TEXT);
+        let mut writer = TrickleWriter::default();
+        block_on(r.write_to_async(&mut writer)).unwrap();
+        assert_eq!(writer.data, TEXT.as_bytes());
+    }
+
+    #[test]
+    fn write_to_async_02() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(5..98);
+        let mut writer = TrickleWriter::default();
+        block_on(s.write_to_async(&mut writer)).unwrap();
+        assert_eq!(String::from_utf8(writer.data).unwrap(), s);
+
+        // A full writer.
+        struct FullWriter;
+        impl AsyncWrite for FullWriter {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _cx: &mut Context,
+                _buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                Poll::Ready(Ok(0))
+            }
+            fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+            fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+        let err = block_on(s.write_to_async(FullWriter)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
+}
@@ -0,0 +1,81 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+use tree::{Arc, Node};
+
+/// Identifies one of a `Rope`'s chunks, for caching results computed from
+/// its text.
+///
+/// Two `ChunkId`s are only equal if they refer to the same chunk in memory,
+/// with the same text.  Edits leave the chunks they don't touch in place, so
+/// after an edit, a cache keyed by `ChunkId` only has to recompute the
+/// chunks whose ids are new.  The ids of unedited chunks stay the same even
+/// when their position in the text changes, so cache results that don't
+/// depend on where a chunk is.
+///
+/// A `ChunkId` holds on to its chunk just like a clone of the `Rope` would.
+/// So while it exists, editing the chunk copies it rather than changing it
+/// in place, and an id always refers to the text it was created for.  This
+/// has two costs for a cache keyed by `ChunkId`: every chunk it has an entry
+/// for stays in memory, including chunks that edits have since replaced,
+/// and the first edit to each of those chunks copies it (along with its
+/// path up the tree).  Caches should therefore drop the entries for ids
+/// that are no longer in the `Rope`, e.g. by keeping only the ids from
+/// `chunk_ids()` after a round of edits.
+/// Operations that restructure the tree, such as `Rope::compact()`, may
+/// give chunks new ids without changing their text.
+///
+/// Created by `Rope::chunk_ids()` and the `Rope::chunk_id_at_*()` methods.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// let mut rope = Rope::from_str(&"Hello world!\n".repeat(1000));
+/// let before: Vec<_> = rope.chunk_ids().collect();
+///
+/// rope.insert(5000, "!");
+/// let after: Vec<_> = rope.chunk_ids().collect();
+///
+/// // Only the edited chunk was replaced, by two chunks since it was full.
+/// let new = after.iter().filter(|id| !before.contains(id)).count();
+/// assert_eq!(new, 2);
+/// assert_eq!(after.len(), before.len() + 1);
+/// ```
+#[derive(Clone)]
+pub struct ChunkId(pub(crate) Arc<Node>);
+
+impl ChunkId {
+    /// Returns the text of the chunk.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.leaf_text()
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        &*self.0 as *const Node as usize
+    }
+}
+
+impl PartialEq for ChunkId {
+    #[inline]
+    fn eq(&self, other: &ChunkId) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ChunkId {}
+
+impl Hash for ChunkId {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
+    }
+}
+
+impl fmt::Debug for ChunkId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ChunkId({:#x})", self.addr())
+    }
+}
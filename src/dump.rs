@@ -0,0 +1,112 @@
+use alloc::string::String;
+use core::fmt::Write;
+
+use tree::{Node, TextInfo, MAX_BYTES};
+
+// How many chars of each leaf's text to show.
+const PREVIEW_CHARS: usize = 16;
+
+/// Renders the tree under `root` as indented text, one node per line.
+pub(crate) fn dump_text(root: &Node) -> String {
+    let mut out = String::new();
+    visit(
+        root,
+        root.text_info(),
+        0,
+        0,
+        &mut |node, info, byte_idx, depth| {
+            let indent = depth * 4;
+            let _ = writeln!(
+                out,
+                "{:indent$}{}",
+                "",
+                describe(node, info, byte_idx, ", "),
+                indent = indent
+            );
+        },
+    );
+    out
+}
+
+/// Renders the tree under `root` as a Graphviz DOT graph.
+pub(crate) fn dump_dot(root: &Node) -> String {
+    let mut out = String::from("digraph rope {\n    node [shape=box, fontname=monospace];\n");
+    let mut parents = vec![];
+    let mut next_id = 0;
+    visit(
+        root,
+        root.text_info(),
+        0,
+        0,
+        &mut |node, info, byte_idx, depth| {
+            // Previews are already escaped, so the only newlines are the
+            // separators, which DOT spells `\n`.
+            let label = describe(node, info, byte_idx, "\n")
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            let _ = writeln!(out, "    n{} [label=\"{}\"];", next_id, label);
+
+            parents.truncate(depth);
+            if let Some(parent_id) = parents.last() {
+                let _ = writeln!(out, "    n{} -> n{};", parent_id, next_id);
+            }
+            parents.push(next_id);
+            next_id += 1;
+        },
+    );
+    out.push_str("}\n");
+    out
+}
+
+// Calls `f` on every node in pre-order, with its text info, starting byte
+// and depth.
+fn visit<F>(node: &Node, info: TextInfo, byte_idx: usize, depth: usize, f: &mut F)
+where
+    F: FnMut(&Node, TextInfo, usize, usize),
+{
+    f(node, info, byte_idx, depth);
+    if let Node::Internal(ref children) = *node {
+        let mut child_byte_idx = byte_idx;
+        for (&info, child) in children.iter() {
+            visit(child, info, child_byte_idx, depth + 1, f);
+            child_byte_idx += info.bytes as usize;
+        }
+    }
+}
+
+// Describes a single node, with its details separated by `separator`.
+fn describe(node: &Node, info: TextInfo, byte_idx: usize, separator: &str) -> String {
+    let end = byte_idx + info.bytes as usize;
+    match *node {
+        Node::Leaf(ref text) => {
+            let mut preview: String = text
+                .chars()
+                .take(PREVIEW_CHARS)
+                .flat_map(char::escape_debug)
+                .collect();
+            if text.chars().count() > PREVIEW_CHARS {
+                preview.push_str("...");
+            }
+            format!(
+                "leaf{}bytes {}..{} ({}% full){}\"{}\"",
+                separator,
+                byte_idx,
+                end,
+                text.len() * 100 / MAX_BYTES,
+                separator,
+                preview
+            )
+        }
+        Node::Internal(ref children) => format!(
+            "internal{}bytes {}..{}{}{} children, {} chars, {} line breaks",
+            separator,
+            byte_idx,
+            end,
+            separator,
+            children.len(),
+            info.chars,
+            info.line_breaks
+        ),
+    }
+}
@@ -0,0 +1,83 @@
+//! Support for text encodings other than utf8.
+//!
+//! Only available with the `encoding` feature enabled.
+
+use std::io;
+
+use encoding_rs::{EncoderResult, Encoding, UTF_16BE, UTF_16LE};
+
+use iter::Chunks;
+
+/// What to do with chars that can't be represented in the target encoding
+/// when encoding text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unmappable {
+    /// Stop, and return an error of kind `InvalidData`.
+    Error,
+    /// Replace the char with a question mark.
+    Replace,
+}
+
+/// Writes `chunks` to `writer`, encoded with `encoding`.
+pub(crate) fn write_encoded<T: io::Write>(
+    chunks: Chunks,
+    mut writer: T,
+    encoding: &'static Encoding,
+    unmappable: Unmappable,
+) -> io::Result<()> {
+    const BUFFER_SIZE: usize = 1024;
+    let mut buffer = [0u8; BUFFER_SIZE];
+
+    // encoding_rs only decodes UTF-16, so encode it by hand.  Every char can
+    // be represented in UTF-16.
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let mut len = 0;
+        for chunk in chunks {
+            for unit in chunk.encode_utf16() {
+                let bytes = if encoding == UTF_16LE {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                };
+                buffer[len..(len + 2)].copy_from_slice(&bytes);
+                len += 2;
+                if len == BUFFER_SIZE {
+                    writer.write_all(&buffer)?;
+                    len = 0;
+                }
+            }
+        }
+        return writer.write_all(&buffer[..len]);
+    }
+
+    let mut encoder = encoding.new_encoder();
+    for (chunk, last) in chunks.map(|chunk| (chunk, false)).chain(Some(("", true))) {
+        let mut input = chunk;
+        loop {
+            let (result, read, written) =
+                encoder.encode_from_utf8_without_replacement(input, &mut buffer, last);
+            writer.write_all(&buffer[..written])?;
+            input = &input[read..];
+
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(c) => match unmappable {
+                    Unmappable::Error => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("char {:?} can't be represented in {}", c, encoding.name()),
+                        ));
+                    }
+                    Unmappable::Replace => {
+                        let (_, _, written) =
+                            encoder.encode_from_utf8_without_replacement("?", &mut buffer, false);
+                        writer.write_all(&buffer[..written])?;
+                    }
+                },
+            }
+        }
+    }
+
+    Ok(())
+}
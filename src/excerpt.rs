@@ -0,0 +1,211 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use slice::RopeSlice;
+
+/// A line of an excerpt, as returned by `Rope::excerpt()` and
+/// `RopeSlice::excerpt()`.
+///
+/// Excerpts are meant for rendering compiler-style diagnostics, e.g.:
+///
+/// ```text
+/// 2 | let x = foo(;
+///   |             ^
+/// ```
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// let rope = Rope::from_str("fn main() {\n    let x = foo(;\n}\n");
+///
+/// for line in rope.excerpt(28..29, 1) {
+///     println!("{:>2} | {}", line.line_idx + 1, line.text);
+///     if let Some(columns) = line.columns {
+///         let marker = "^".repeat(columns.len().max(1));
+///         println!("   | {}{}", " ".repeat(columns.start), marker);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExcerptLine<'a> {
+    /// The index of the line.
+    pub line_idx: usize,
+    /// The text of the line, without its line break.
+    pub text: RopeSlice<'a>,
+    /// The char columns of the line, relative to its start, that are within
+    /// the excerpted range.  `None` for context lines.
+    ///
+    /// Empty when the range is empty, and when the range only covers the
+    /// line's line break, with the column where the range starts.
+    pub columns: Option<Range<usize>>,
+}
+
+pub(crate) fn excerpt<'a>(
+    slice: &RopeSlice<'a>,
+    start: usize,
+    end: usize,
+    context_lines: usize,
+) -> Vec<ExcerptLine<'a>> {
+    let first_line_idx = slice.char_to_line(start);
+    let last_line_idx = if end > start {
+        slice.char_to_line(end - 1)
+    } else {
+        first_line_idx
+    };
+
+    let context_start = first_line_idx.saturating_sub(context_lines);
+    let context_end = (last_line_idx + context_lines).min(slice.len_lines() - 1);
+
+    (context_start..=context_end)
+        .map(|line_idx| {
+            let text = without_line_break(slice.line(line_idx));
+            let columns = if line_idx >= first_line_idx && line_idx <= last_line_idx {
+                let line_char_idx = slice.line_to_char(line_idx);
+                let col_start = start.saturating_sub(line_char_idx).min(text.len_chars());
+                let col_end = (end - line_char_idx).min(text.len_chars());
+                Some(col_start..col_end.max(col_start))
+            } else {
+                None
+            };
+
+            ExcerptLine {
+                line_idx: line_idx,
+                text: text,
+                columns: columns,
+            }
+        })
+        .collect()
+}
+
+fn without_line_break(line: RopeSlice) -> RopeSlice {
+    let len = line.len_chars();
+    if len == 0 {
+        return line;
+    }
+
+    match line.char(len - 1) {
+        '\n' if len > 1 && line.char(len - 2) == '\r' => line.slice(..(len - 2)),
+        '\n' | '\u{000B}' | '\u{000C}' | '\r' | '\u{0085}' | '\u{2028}' | '\u{2029}' => {
+            line.slice(..(len - 1))
+        }
+        _ => line,
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::ExcerptLine;
+    use Rope;
+
+    // 124 bytes, 100 chars, 4 lines
+    const TEXT_LINES: &str = "Hello there!  How're you doing?\nIt's \
+                              a fine day, isn't it?\nAren't you glad \
+                              we're alive?\nこんにちは、みんなさん！";
+
+    fn simplify(lines: Vec<ExcerptLine>) -> Vec<(usize, String, Option<(usize, usize)>)> {
+        lines
+            .into_iter()
+            .map(|l| {
+                let columns = l.columns.map(|c| (c.start, c.end));
+                (l.line_idx, l.text.to_string(), columns)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn excerpt_01() {
+        let r = Rope::from_str(TEXT_LINES);
+        assert_eq!(
+            simplify(r.excerpt(37..41, 0)),
+            vec![(1, "It's a fine day, isn't it?".into(), Some((5, 9)))]
+        );
+        assert_eq!(
+            simplify(r.excerpt(37..41, 1)),
+            vec![
+                (0, "Hello there!  How're you doing?".into(), None),
+                (1, "It's a fine day, isn't it?".into(), Some((5, 9))),
+                (2, "Aren't you glad we're alive?".into(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn excerpt_02() {
+        // Spanning lines.
+        let r = Rope::from_str(TEXT_LINES);
+        assert_eq!(
+            simplify(r.excerpt(26..65, 0)),
+            vec![
+                (0, "Hello there!  How're you doing?".into(), Some((26, 31))),
+                (1, "It's a fine day, isn't it?".into(), Some((0, 26))),
+                (2, "Aren't you glad we're alive?".into(), Some((0, 6))),
+            ]
+        );
+    }
+
+    #[test]
+    fn excerpt_03() {
+        // Empty ranges, and ranges of only a line break.
+        let r = Rope::from_str(TEXT_LINES);
+        assert_eq!(
+            simplify(r.excerpt(34..34, 0)),
+            vec![(1, "It's a fine day, isn't it?".into(), Some((2, 2)))]
+        );
+        assert_eq!(
+            simplify(r.excerpt(31..32, 0)),
+            vec![(0, "Hello there!  How're you doing?".into(), Some((31, 31)))]
+        );
+        assert_eq!(
+            simplify(r.excerpt(100..100, 2)),
+            vec![
+                (1, "It's a fine day, isn't it?".into(), None),
+                (2, "Aren't you glad we're alive?".into(), None),
+                (3, "こんにちは、みんなさん！".into(), Some((12, 12))),
+            ]
+        );
+    }
+
+    #[test]
+    fn excerpt_04() {
+        // CRLF and an empty last line.
+        let r = Rope::from_str("ab\r\ncd\r\n");
+        assert_eq!(
+            simplify(r.excerpt(1..5, 5)),
+            vec![
+                (0, "ab".into(), Some((1, 2))),
+                (1, "cd".into(), Some((0, 1))),
+                (2, "".into(), None),
+            ]
+        );
+
+        let r = Rope::from_str("");
+        assert_eq!(
+            simplify(r.excerpt(.., 1)),
+            vec![(0, "".into(), Some((0, 0)))]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn excerpt_05() {
+        let r = Rope::from_str(TEXT_LINES);
+        r.excerpt(99..101, 0);
+    }
+
+    #[test]
+    fn excerpt_sliced_01() {
+        let r = Rope::from_str(TEXT_LINES);
+        let s = r.slice(37..);
+        assert_eq!(
+            simplify(s.excerpt(24..30, 1)),
+            vec![
+                (0, "a fine day, isn't it?".into(), None),
+                (1, "Aren't you glad we're alive?".into(), Some((2, 8))),
+                (2, "こんにちは、みんなさん！".into(), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,741 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use rope::Rope;
+use stats::MemUsage;
+use tree::{Arc, Node};
+
+/// An undo/redo history of a `Rope`'s edits.
+///
+/// The history records snapshots of the `Rope`, which are cheap: a
+/// snapshot shares all of its unedited text with the snapshots it was
+/// edited from, the same way `Rope::clone()` does.  Each step only costs
+/// the memory of the nodes that its edits replaced.
+///
+/// After each edit (or group of edits) the `Rope` is passed to `record()`,
+/// which adds it as a new step.  Passing it to `amend()` instead folds it
+/// into the latest step, so that both are undone at once, e.g. to coalesce
+/// consecutive typing into a single step.
+///
+/// The recorded states form a tree: recording a step after undoing others
+/// starts a new branch, rather than discarding the undone steps.  `redo()`
+/// follows the branch that was most recently visited, and any state can be
+/// returned to with `jump()`.  `states()`, `parent()` and `children()` can
+/// be used to walk the tree, e.g. to present it to the user.
+///
+/// The memory the history holds on to can be limited with
+/// `set_max_bytes()`, in which case the oldest states are forgotten as
+/// needed.  The memory is counted by the nodes that the snapshots don't
+/// share with each other, so the budget reflects what the history actually
+/// uses rather than the number of steps.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use ropey::History;
+///
+/// let mut rope = Rope::from_str("Hello");
+/// let mut history = History::new(&rope);
+///
+/// // Typing a word, as a single step.
+/// rope.insert(5, " w");
+/// history.record(&rope);
+/// rope.insert(7, "orld");
+/// history.amend(&rope);
+/// let world = history.current();
+///
+/// assert!(history.undo(&mut rope));
+/// assert_eq!(rope, "Hello");
+///
+/// // Starts a second branch.
+/// rope.insert(5, "!");
+/// history.record(&rope);
+/// assert_eq!(history.children(history.root()).len(), 2);
+///
+/// assert!(history.jump(world, &mut rope));
+/// assert_eq!(rope, "Hello world");
+/// ```
+#[derive(Clone)]
+pub struct History {
+    // The recorded states, keyed by the order they were recorded in.
+    states: BTreeMap<usize, State>,
+    root: usize,
+    // The state that the `Rope` is currently at.
+    current: usize,
+    next_id: usize,
+    // How many times each node is held by the states' roots and by the
+    // internal nodes in here, keyed by address.  The states keep all of
+    // these nodes alive, so the addresses can't be reused while they're in
+    // here.
+    refs: BTreeMap<usize, usize>,
+    // The memory used by the nodes in `refs`.
+    usage: MemUsage,
+    max_bytes: usize,
+}
+
+/// Identifies a state recorded in a `History`.
+///
+/// Ids are ordered by when their states were recorded, and aren't reused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StateId(usize);
+
+#[derive(Clone)]
+struct State {
+    rope: Rope,
+    parent: Option<usize>,
+    children: Vec<StateId>,
+    // The child that `redo()` goes to.
+    redo_child: Option<usize>,
+    // How many ancestors the state had when it was recorded.
+    depth: usize,
+}
+
+impl History {
+    /// Creates a new `History`, starting at `rope`.
+    ///
+    /// The memory the history can use isn't limited.
+    ///
+    /// Runs in O(N log N) time.
+    pub fn new(rope: &Rope) -> Self {
+        let mut history = History {
+            states: BTreeMap::new(),
+            root: 0,
+            current: 0,
+            next_id: 1,
+            refs: BTreeMap::new(),
+            usage: MemUsage::default(),
+            max_bytes: usize::MAX,
+        };
+        history.retain(&rope.root);
+        history.states.insert(
+            0,
+            State {
+                rope: rope.clone(),
+                parent: None,
+                children: Vec::new(),
+                redo_child: None,
+                depth: 0,
+            },
+        );
+        history
+    }
+
+    /// Records `rope` as a new step, following the current state.
+    ///
+    /// If steps were undone from the current state, this starts a new
+    /// branch next to them.  If the history uses more memory than it's
+    /// allowed to afterwards, the oldest states are forgotten.
+    ///
+    /// Runs in O(M log K) time, where M is the number of nodes that were
+    /// edited since the current state and K is the number of nodes the
+    /// history holds.
+    pub fn record(&mut self, rope: &Rope) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let parent = self.current;
+        let state = self.new_state(parent, rope);
+        self.states.insert(id, state);
+
+        let parent = self.states.get_mut(&parent).unwrap();
+        parent.children.push(StateId(id));
+        parent.redo_child = Some(id);
+        self.current = id;
+        self.enforce_budget();
+    }
+
+    /// Folds `rope` into the latest step, so that undoing it goes back to
+    /// the state before that step.
+    ///
+    /// If there are no steps to undo, or steps were undone from the
+    /// current state, this is the same as `record()`.
+    ///
+    /// Runs in O(M log K) time, where M is the number of nodes that were
+    /// edited since the state before the latest step and K is the number of
+    /// nodes the history holds.
+    pub fn amend(&mut self, rope: &Rope) {
+        let parent = match self.states[&self.current] {
+            State {
+                parent: Some(parent),
+                ref children,
+                ..
+            } if children.is_empty() => parent,
+            _ => {
+                self.record(rope);
+                return;
+            }
+        };
+
+        let state = self.new_state(parent, rope);
+        let old = self.states.insert(self.current, state).unwrap();
+        self.release(&old.rope.root);
+        self.enforce_budget();
+    }
+
+    /// Undoes the latest step, setting `rope` to the state before it.
+    ///
+    /// Returns false, and leaves `rope` as it is, if there's nothing to
+    /// undo.
+    ///
+    /// Runs in O(log N) time, in the number of states.
+    pub fn undo(&mut self, rope: &mut Rope) -> bool {
+        let parent = match self.states[&self.current].parent {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let state = self.states.get_mut(&parent).unwrap();
+        state.redo_child = Some(self.current);
+        *rope = state.rope.clone();
+        self.current = parent;
+        true
+    }
+
+    /// Redoes the most recently undone step from the current state, setting
+    /// `rope` to the state after it.
+    ///
+    /// Returns false, and leaves `rope` as it is, if there's nothing to
+    /// redo.
+    ///
+    /// Runs in O(log N) time, in the number of states.
+    pub fn redo(&mut self, rope: &mut Rope) -> bool {
+        let child = match self.states[&self.current].redo_child {
+            Some(child) => child,
+            None => return false,
+        };
+        *rope = self.states[&child].rope.clone();
+        self.current = child;
+        true
+    }
+
+    /// Sets `rope` to the state `id`, which becomes the current state.
+    ///
+    /// Undoing from there retraces the path to it, and redoing from any of
+    /// the states along that path follows it back.
+    ///
+    /// Returns false, and leaves `rope` as it is, if there's no such state.
+    ///
+    /// Runs in O(D log N) time, where D is the number of steps from `id` to
+    /// the nearest state whose `redo()` already leads towards it.
+    pub fn jump(&mut self, id: StateId, rope: &mut Rope) -> bool {
+        match self.states.get(&id.0) {
+            Some(state) => *rope = state.rope.clone(),
+            None => return false,
+        }
+        self.current = id.0;
+
+        let mut child = id.0;
+        while let Some(parent) = self.states[&child].parent {
+            let state = self.states.get_mut(&parent).unwrap();
+            if state.redo_child == Some(child) {
+                break;
+            }
+            state.redo_child = Some(child);
+            child = parent;
+        }
+        true
+    }
+
+    /// Returns the number of steps that can be undone.
+    ///
+    /// Runs in O(log N) time.
+    #[inline]
+    pub fn undo_len(&self) -> usize {
+        self.states[&self.current].depth - self.states[&self.root].depth
+    }
+
+    /// Returns the number of steps that can be redone in a row.
+    ///
+    /// Runs in O(R log N) time, where R is the returned length.
+    pub fn redo_len(&self) -> usize {
+        let mut len = 0;
+        let mut state = &self.states[&self.current];
+        while let Some(child) = state.redo_child {
+            state = &self.states[&child];
+            len += 1;
+        }
+        len
+    }
+
+    /// Returns the id of the current state.
+    #[inline]
+    pub fn current(&self) -> StateId {
+        StateId(self.current)
+    }
+
+    /// Returns the id of the oldest state, which all others follow from.
+    #[inline]
+    pub fn root(&self) -> StateId {
+        StateId(self.root)
+    }
+
+    /// Creates an iterator over the ids of the recorded states, in the
+    /// order they were recorded in.
+    pub fn states<'a>(&'a self) -> impl Iterator<Item = StateId> + 'a {
+        self.states.keys().map(|&id| StateId(id))
+    }
+
+    /// Returns the id of the state that `id` follows from.
+    ///
+    /// Returns `None` for the root, or if there's no such state.
+    #[inline]
+    pub fn parent(&self, id: StateId) -> Option<StateId> {
+        self.states.get(&id.0)?.parent.map(StateId)
+    }
+
+    /// Returns the ids of the states that follow from `id`, in the order
+    /// they were recorded in.
+    ///
+    /// Returns an empty slice if there's no such state.
+    #[inline]
+    pub fn children(&self, id: StateId) -> &[StateId] {
+        self.states
+            .get(&id.0)
+            .map_or(&[][..], |state| &state.children[..])
+    }
+
+    /// Returns the snapshot of the state `id`, without changing the current
+    /// state.
+    ///
+    /// Returns `None` if there's no such state.
+    #[inline]
+    pub fn get(&self, id: StateId) -> Option<&Rope> {
+        self.states.get(&id.0).map(|state| &state.rope)
+    }
+
+    /// The heap memory used by the history's snapshots between them.
+    ///
+    /// This includes the memory shared with the `Rope` itself.
+    #[inline]
+    pub fn mem_usage(&self) -> MemUsage {
+        self.usage
+    }
+
+    /// Limits the memory the history can use to `max_bytes`, as measured
+    /// by `mem_usage().total_bytes()`.
+    ///
+    /// The oldest states are forgotten as needed to stay within the limit,
+    /// both now and as steps are recorded.  Only the root and the ends of
+    /// branches can be forgotten, so that the rest of the tree stays
+    /// connected, and the current state is always kept even if it doesn't
+    /// fit.
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.enforce_budget();
+    }
+
+    //-----------------------------------------------------------------
+    // Internal utilities.
+
+    // Creates the state for `rope` following `parent`, and adds its memory
+    // to the usage.
+    fn new_state(&mut self, parent: usize, rope: &Rope) -> State {
+        self.retain(&rope.root);
+        State {
+            rope: rope.clone(),
+            parent: Some(parent),
+            children: Vec::new(),
+            redo_child: None,
+            depth: self.states[&parent].depth + 1,
+        }
+    }
+
+    // Counts a reference to `node`, adding it and the nodes under it that
+    // aren't counted yet to the usage.
+    fn retain(&mut self, node: &Arc<Node>) {
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            let count = self.refs.entry(node_addr(node)).or_insert(0);
+            *count += 1;
+            if *count > 1 {
+                continue;
+            }
+            add(&mut self.usage, node.heap_usage());
+            if let Node::Internal(ref children) = **node {
+                stack.extend(children.nodes());
+            }
+        }
+    }
+
+    // Undoes a `retain()` of `node`, removing the nodes that are no longer
+    // referenced from the usage.  `node` must still be alive.
+    fn release(&mut self, node: &Arc<Node>) {
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            let addr = node_addr(node);
+            let count = self.refs.get_mut(&addr).unwrap();
+            *count -= 1;
+            if *count > 0 {
+                continue;
+            }
+            self.refs.remove(&addr);
+            sub(&mut self.usage, node.heap_usage());
+            if let Node::Internal(ref children) = **node {
+                stack.extend(children.nodes());
+            }
+        }
+    }
+
+    fn enforce_budget(&mut self) {
+        while self.usage.total_bytes() > self.max_bytes {
+            let root = self.root;
+            let current = self.current;
+            let id = self.states.iter().find(|&(&id, state)| {
+                id != current
+                    && (state.children.is_empty() || (id == root && state.children.len() == 1))
+            });
+            let id = match id {
+                Some((&id, _)) => id,
+                None => break,
+            };
+
+            let state = self.states.remove(&id).unwrap();
+            self.release(&state.rope.root);
+            if id == root {
+                let child = state.children[0].0;
+                self.states.get_mut(&child).unwrap().parent = None;
+                self.root = child;
+            } else {
+                let parent = self.states.get_mut(&state.parent.unwrap()).unwrap();
+                parent.children.retain(|&child| child != StateId(id));
+                if parent.redo_child == Some(id) {
+                    parent.redo_child = parent.children.last().map(|child| child.0);
+                }
+            }
+        }
+    }
+}
+
+impl core::fmt::Debug for History {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("History")
+            .field("states", &self.states.len())
+            .field("undo_len", &self.undo_len())
+            .field("redo_len", &self.redo_len())
+            .field("mem_usage", &self.usage)
+            .finish()
+    }
+}
+
+//-------------------------------------------------------------
+
+#[inline]
+fn node_addr(node: &Arc<Node>) -> usize {
+    &**node as *const Node as usize
+}
+
+#[inline]
+fn add(usage: &mut MemUsage, other: MemUsage) {
+    usage.text_bytes += other.text_bytes;
+    usage.overhead_bytes += other.overhead_bytes;
+}
+
+#[inline]
+fn sub(usage: &mut MemUsage, other: MemUsage) {
+    usage.text_bytes -= other.text_bytes;
+    usage.overhead_bytes -= other.overhead_bytes;
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeSet;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    // The memory used by the history's states, counting each node once.
+    fn exact_usage(history: &History) -> MemUsage {
+        let mut usage = MemUsage::default();
+        let mut seen = BTreeSet::new();
+        for state in history.states.values() {
+            let mut stack = vec![&state.rope.root];
+            while let Some(node) = stack.pop() {
+                if !seen.insert(node_addr(node)) {
+                    continue;
+                }
+                add(&mut usage, node.heap_usage());
+                if let Node::Internal(ref children) = **node {
+                    stack.extend(children.nodes());
+                }
+            }
+        }
+        usage
+    }
+
+    #[test]
+    fn undo_redo_01() {
+        let mut r = Rope::from_str(TEXT);
+        let mut history = History::new(&r);
+        let mut texts = vec![r.to_string()];
+        for i in 0..10 {
+            r.insert(i * 7, "abc");
+            history.record(&r);
+            texts.push(r.to_string());
+        }
+        assert_eq!(history.undo_len(), 10);
+
+        for text in texts.iter().rev().skip(1) {
+            assert!(history.undo(&mut r));
+            assert_eq!(r, text.as_str());
+        }
+        assert!(!history.undo(&mut r));
+        assert_eq!(history.redo_len(), 10);
+
+        for text in texts.iter().skip(1) {
+            assert!(history.redo(&mut r));
+            assert_eq!(r, text.as_str());
+        }
+        assert!(!history.redo(&mut r));
+    }
+
+    #[test]
+    fn record_01() {
+        // Recording after undoing starts a new branch.
+        let mut r = Rope::from_str(TEXT);
+        let mut history = History::new(&r);
+        r.remove(0..5);
+        history.record(&r);
+        let first = history.current();
+        r.remove(0..5);
+        history.record(&r);
+        history.undo(&mut r);
+        history.undo(&mut r);
+
+        r.insert(3, "xyz");
+        history.record(&r);
+        let second = history.current();
+        assert_eq!(history.undo_len(), 1);
+        assert_eq!(history.redo_len(), 0);
+        assert_eq!(history.children(history.root()), [first, second]);
+        assert_eq!(history.parent(second), Some(history.root()));
+        assert_eq!(history.states().count(), 4);
+
+        // Redoing follows the latest branch.
+        history.undo(&mut r);
+        assert_eq!(r, TEXT);
+        assert_eq!(history.redo_len(), 1);
+        history.redo(&mut r);
+        assert_eq!(history.current(), second);
+    }
+
+    #[test]
+    fn jump_01() {
+        let mut r = Rope::from_str(TEXT);
+        let mut history = History::new(&r);
+        let mut ids = vec![(history.current(), r.clone())];
+        for i in 0..20 {
+            r.insert(i * 3, "ab");
+            history.record(&r);
+            ids.push((history.current(), r.clone()));
+            if i % 4 == 3 {
+                history.undo(&mut r);
+                history.undo(&mut r);
+            }
+        }
+
+        for &(id, ref text) in ids.iter().rev() {
+            assert!(history.jump(id, &mut r));
+            assert_eq!(r, *text);
+            assert_eq!(history.get(id), Some(text));
+        }
+
+        // Undoing and then redoing all the way retraces the path.
+        let (id, ref text) = ids[13];
+        history.jump(id, &mut r);
+        let len = history.undo_len();
+        while history.undo(&mut r) {}
+        assert_eq!(r, TEXT);
+        for _ in 0..len {
+            history.redo(&mut r);
+        }
+        assert_eq!(history.current(), id);
+        assert_eq!(r, *text);
+
+        assert!(!history.jump(StateId(1000), &mut r));
+        assert!(history.get(StateId(1000)).is_none());
+    }
+
+    #[test]
+    fn amend_01() {
+        let mut r = Rope::from_str(TEXT);
+        let mut history = History::new(&r);
+        r.insert(0, "a");
+        history.amend(&r);
+        r.insert(1, "b");
+        history.amend(&r);
+        r.insert(2, "c");
+        history.record(&r);
+        r.insert(3, "d");
+        history.amend(&r);
+        assert_eq!(history.undo_len(), 2);
+
+        history.undo(&mut r);
+        assert_eq!(r.slice(..3), "abH");
+        history.undo(&mut r);
+        assert_eq!(r, TEXT);
+    }
+
+    #[test]
+    fn mem_usage_01() {
+        let mut r = Rope::from_str(&TEXT.repeat(20));
+        let mut history = History::new(&r);
+        assert_eq!(history.mem_usage(), exact_usage(&history));
+
+        for i in 0..50 {
+            r.insert(i * 37, "みんな");
+            r.remove((i * 13)..(i * 13 + 2));
+            if i % 3 == 0 {
+                history.amend(&r);
+            } else {
+                history.record(&r);
+            }
+            if i % 10 == 9 {
+                history.undo(&mut r);
+                history.undo(&mut r);
+            }
+            if i % 15 == 14 {
+                let id = history.states().nth(i / 2).unwrap();
+                history.jump(id, &mut r);
+            }
+            assert_eq!(history.mem_usage(), exact_usage(&history));
+        }
+    }
+
+    #[test]
+    fn mem_usage_02() {
+        // Clones of the snapshots held outside of the history don't throw
+        // off the count.
+        let mut r = Rope::from_str(&TEXT.repeat(20));
+        let mut history = History::new(&r);
+        let mut clones = vec![r.clone()];
+        for i in 0..30 {
+            r.insert(i * 37, "みんな");
+            clones.push(r.clone());
+            if i % 3 == 0 {
+                history.amend(&r);
+            } else {
+                history.record(&r);
+            }
+            clones.push(r.clone());
+            assert_eq!(history.mem_usage(), exact_usage(&history));
+        }
+
+        let usage = history.mem_usage().total_bytes();
+        let max_bytes = (usage + r.mem_usage().total_bytes()) / 2;
+        history.set_max_bytes(max_bytes);
+        assert!(history.mem_usage().total_bytes() <= max_bytes);
+        assert!(history.undo_len() > 0);
+        assert_eq!(history.mem_usage(), exact_usage(&history));
+
+        history.set_max_bytes(0);
+        assert_eq!(history.undo_len(), 0);
+        assert_eq!(history.mem_usage(), r.mem_usage());
+        assert_eq!(clones.last(), Some(&r));
+    }
+
+    #[test]
+    fn set_max_bytes_01() {
+        let mut r = Rope::from_str(&TEXT.repeat(20));
+        let mut history = History::new(&r);
+        for i in 0..50 {
+            r.insert(i * 37, "みんな");
+            history.record(&r);
+        }
+        let usage = history.mem_usage().total_bytes();
+        let initial = Rope::from_str(&TEXT.repeat(20)).mem_usage().total_bytes();
+        assert!(usage > initial);
+
+        // Only the oldest steps are forgotten.
+        history.set_max_bytes(initial + (usage - initial) / 2);
+        assert!(history.mem_usage().total_bytes() <= initial + (usage - initial) / 2);
+        assert_eq!(history.mem_usage(), exact_usage(&history));
+        assert!(history.undo_len() > 0 && history.undo_len() < 50);
+        let expected = r.clone();
+        while history.undo(&mut r) {}
+        while history.redo(&mut r) {}
+        assert_eq!(r, expected);
+
+        // The current state is always kept.
+        history.set_max_bytes(0);
+        assert_eq!(history.undo_len(), 0);
+        assert_eq!(history.mem_usage(), exact_usage(&history));
+    }
+
+    #[test]
+    fn set_max_bytes_02() {
+        // Forgetting states from a tree.
+        let mut r = Rope::from_str(&TEXT.repeat(20));
+        let mut history = History::new(&r);
+        for i in 0..60 {
+            r.insert(i * 29, "みんな");
+            history.record(&r);
+            if i % 5 == 4 {
+                history.undo(&mut r);
+                history.undo(&mut r);
+                history.undo(&mut r);
+            }
+        }
+        let usage = history.mem_usage().total_bytes();
+        let expected = r.clone();
+        let current = history.current();
+
+        history.set_max_bytes(usage * 2 / 3);
+        assert!(history.mem_usage().total_bytes() <= usage * 2 / 3);
+        assert_eq!(history.mem_usage(), exact_usage(&history));
+        assert_eq!(history.current(), current);
+        assert_eq!(r, expected);
+        for id in history.states() {
+            assert_eq!(history.parent(id).is_none(), id == history.root());
+            for &child in history.children(id) {
+                assert_eq!(history.parent(child), Some(id));
+            }
+        }
+
+        history.set_max_bytes(0);
+        assert_eq!(history.states().collect::<Vec<_>>(), [current]);
+        assert_eq!(history.mem_usage(), exact_usage(&history));
+    }
+
+    #[test]
+    fn set_max_bytes_03() {
+        // Forgetting branches and roots while clones of the snapshots are
+        // held outside of the history, and after jumping around the tree.
+        let mut r = Rope::from_str(&TEXT.repeat(20));
+        let mut history = History::new(&r);
+        let mut clones = vec![r.clone()];
+        for i in 0..60 {
+            r.insert(i * 29, "みんな");
+            clones.push(r.clone());
+            history.record(&r);
+            if i % 5 == 4 {
+                history.undo(&mut r);
+                history.undo(&mut r);
+                clones.push(r.clone());
+            }
+            if i % 7 == 6 {
+                let n = history.states().count();
+                let id = history.states().nth(i / 3 % n).unwrap();
+                history.jump(id, &mut r);
+                clones.push(r.clone());
+            }
+            if i % 20 == 19 {
+                let usage = history.mem_usage().total_bytes();
+                history.set_max_bytes(usage * 3 / 4);
+            }
+            assert_eq!(history.mem_usage(), exact_usage(&history));
+        }
+
+        let expected = r.clone();
+        let current = history.current();
+        history.set_max_bytes(0);
+        assert_eq!(history.states().collect::<Vec<_>>(), [current]);
+        assert_eq!(history.mem_usage(), r.mem_usage());
+        assert!(history.jump(current, &mut r));
+        assert_eq!(r, expected);
+        assert!(clones.len() > 60);
+    }
+}
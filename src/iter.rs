@@ -47,15 +47,16 @@
 //! use the `prev()` method to iterate backwards over all of that `Rope`'s
 //! chars.
 
-use std::str;
-use std::sync::Arc;
+use alloc::vec::Vec;
+use core::str;
 
+use chunk_id::ChunkId;
 use slice::RopeSlice;
 use str_utils::{
     byte_to_line_idx, char_to_byte_idx, count_chars, ends_with_line_break, line_to_byte_idx,
     line_to_char_idx, prev_line_end_char_idx,
 };
-use tree::{Node, TextInfo};
+use tree::{Arc, Count, Node, TextInfo};
 
 //==========================================================
 
@@ -805,9 +806,9 @@ impl<'a> Chunks<'a> {
                             byte_idx =
                                 (info.bytes as isize + text.len() as isize) - start_byte as isize;
                             info = TextInfo {
-                                bytes: byte_idx_range.1 as u64,
-                                chars: char_idx_range.1 as u64,
-                                line_breaks: line_break_idx_range.1 as u64 - 1,
+                                bytes: byte_idx_range.1 as Count,
+                                chars: char_idx_range.1 as Count,
+                                line_breaks: line_break_idx_range.1 as Count - 1,
                             };
                             (*node_stack.last_mut().unwrap()).1 += 1;
                         }
@@ -1037,6 +1038,57 @@ impl<'a> Iterator for Chunks<'a> {
     }
 }
 
+//==========================================================
+
+/// An iterator over the ids of a `Rope`'s chunks.
+///
+/// Yields a [`ChunkId`](../struct.ChunkId.html) for each non-empty chunk, in
+/// the same order as `Chunks` yields their text.  Unlike the other
+/// iterators, this one only moves forward.
+#[derive(Debug, Clone)]
+pub struct ChunkIds<'a> {
+    node_stack: Vec<(&'a Arc<Node>, usize)>, // (node ref, index of next child)
+}
+
+impl<'a> ChunkIds<'a> {
+    #[inline]
+    pub(crate) fn new(node: &'a Arc<Node>) -> ChunkIds<'a> {
+        ChunkIds {
+            node_stack: vec![(node, 0)],
+        }
+    }
+}
+
+impl<'a> Iterator for ChunkIds<'a> {
+    type Item = ChunkId;
+
+    /// Advances the iterator forward and returns the next value.
+    ///
+    /// Runs in amortized O(1) time and worst-case O(log N) time.
+    fn next(&mut self) -> Option<ChunkId> {
+        while let Some(&mut (node, ref mut child_i)) = self.node_stack.last_mut() {
+            match **node {
+                Node::Leaf(ref text) => {
+                    self.node_stack.pop();
+                    if !text.is_empty() {
+                        return Some(ChunkId(Arc::clone(node)));
+                    }
+                }
+                Node::Internal(ref children) => {
+                    if *child_i < children.len() {
+                        let child = &children.nodes()[*child_i];
+                        *child_i += 1;
+                        self.node_stack.push((child, 0));
+                    } else {
+                        self.node_stack.pop();
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
 //===========================================================
 
 #[cfg(test)]
@@ -6,12 +6,14 @@
 //! in terms of char indices which prevents accidental creation of invalid
 //! utf8 data.
 //!
-//! The library is made up of four main components:
+//! The library is made up of five main components:
 //!
 //! - [`Rope`](struct.Rope.html): the main rope type.
 //! - [`RopeSlice`](struct.RopeSlice.html): an immutable view into part of a
 //!   `Rope`.
 //! - [`iter`](iter/index.html): iterators over `Rope`/`RopeSlice` data.
+//! - [`search`](search/index.html): searching for text in `Rope`/`RopeSlice`
+//!   data.
 //! - [`RopeBuilder`](struct.RopeBuilder.html): an efficient incremental
 //!   `Rope` builder.
 //!
@@ -28,6 +30,7 @@
 //! use std::io::{BufReader, BufWriter};
 //! use ropey::Rope;
 //!
+//! # #[cfg(feature = "std")]
 //! # fn do_stuff() -> Result<()> {
 //! // Load a text file.
 //! let mut text = Rope::from_reader(
@@ -59,6 +62,7 @@
 //! )?;
 //! # Ok(())
 //! # }
+//! # #[cfg(feature = "std")]
 //! # do_stuff().unwrap();
 //! ```
 //!
@@ -130,24 +134,131 @@
 //!
 //! CRLF pairs are always treated as a single line break, and are never split
 //! across chunks.  Note, however, that slicing can still split them.
+//!
+//!
+//! # Using Ropey Without `std`
+//!
+//! Ropey only needs `core` and `alloc` when its default `std` feature is
+//! disabled.  That removes the APIs built on `std::io`, such as
+//! [`Rope::from_reader()`](struct.Rope.html#method.from_reader) and
+//! [`Rope::write_to()`](struct.Rope.html#method.write_to), along with the
+//! features that depend on them.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![allow(clippy::collapsible_if)]
 #![allow(clippy::inline_always)]
 #![allow(clippy::needless_return)]
 #![allow(clippy::redundant_field_names)]
 #![allow(clippy::type_complexity)]
 
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "shared")]
+extern crate arc_swap;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "encoding")]
+extern crate encoding_rs;
+#[cfg(feature = "futures")]
+extern crate futures_io;
+extern crate memchr;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "quickcheck")]
+extern crate quickcheck;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rkyv")]
+extern crate rkyv;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "mmap")]
+extern crate simdutf8;
 extern crate smallvec;
+#[cfg(feature = "triomphe")]
+extern crate triomphe;
+
+#[cfg(feature = "regex")]
+extern crate regex_automata;
+
+#[cfg(all(test, feature = "futures"))]
+extern crate futures_executor;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_test;
 
+mod anchors;
+#[cfg(feature = "std")]
+mod append_reader;
+#[cfg(feature = "std")]
+mod append_writer;
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+#[cfg(feature = "futures")]
+mod async_io;
+mod chunk_id;
 mod crlf;
+mod dump;
+mod excerpt;
+mod history;
+#[cfg(feature = "std")]
+mod loader;
+#[cfg(feature = "quickcheck")]
+mod quickcheck_impls;
+#[cfg(feature = "std")]
+mod reader;
 mod rope;
 mod rope_builder;
+#[cfg(feature = "std")]
+mod rope_file;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "shared")]
+mod shared_rope;
 mod slice;
+mod span_map;
+mod stats;
 mod tree;
+#[cfg(feature = "std")]
+mod tree_format;
+mod validate;
 
+#[cfg(feature = "rkyv")]
+pub mod archive;
+#[cfg(feature = "encoding")]
+pub mod encoding;
 pub mod iter;
+#[cfg(feature = "rayon")]
+pub mod par_iter;
+pub mod search;
 pub mod str_utils;
+#[cfg(feature = "proptest")]
+pub mod strategies;
 
+pub use anchors::{AnchorId, Anchors, Bias};
+#[cfg(feature = "std")]
+pub use append_reader::AppendReader;
+#[cfg(feature = "std")]
+pub use append_writer::AppendWriter;
+#[cfg(feature = "futures")]
+pub use async_io::{FromAsyncReader, WriteToAsync};
+pub use chunk_id::ChunkId;
+pub use excerpt::ExcerptLine;
+pub use history::{History, StateId};
+#[cfg(feature = "std")]
+pub use loader::RopeLoader;
+#[cfg(feature = "std")]
+pub use reader::RopeReader;
 pub use rope::Rope;
 pub use rope_builder::RopeBuilder;
+#[cfg(feature = "std")]
+pub use rope_file::RopeFile;
+#[cfg(feature = "shared")]
+pub use shared_rope::SharedRope;
 pub use slice::RopeSlice;
+pub use span_map::SpanMap;
+pub use stats::{MemUsage, TreeStats};
+pub use validate::{ValidationError, ValidationErrorKind};
@@ -0,0 +1,176 @@
+use std::io::{self, Write};
+
+use rope::Rope;
+use rope_builder::RopeBuilder;
+use tree::MAX_BYTES;
+
+/// Loads a `Rope` from a reader in bounded steps.
+///
+/// This is like [`Rope::from_reader()`](struct.Rope.html#method.from_reader),
+/// except that the loading is driven by the caller a piece at a time.  This
+/// allows e.g. a UI to stay responsive while opening a huge file, and to
+/// abort the load partway through: `finish()` can be called at any time,
+/// and returns the text loaded so far.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::RopeLoader;
+/// use std::io::Cursor;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// let text = "Hello world!\n".repeat(1000);
+/// let cancel = AtomicBool::new(false);
+///
+/// let mut loader = RopeLoader::new(Cursor::new(&text));
+/// while !cancel.load(Ordering::Relaxed) {
+///     if loader.load(4096).unwrap() {
+///         break;
+///     }
+///     // Update the progress bar with `loader.bytes_read()`, etc.
+/// }
+/// let rope = loader.finish();
+///
+/// assert_eq!(rope, text.as_str());
+/// ```
+#[derive(Debug)]
+pub struct RopeLoader<T> {
+    reader: T,
+    builder: RopeBuilder,
+    buffer: Box<[u8]>,
+    bytes_read: usize,
+    done: bool,
+}
+
+impl<T: io::Read> RopeLoader<T> {
+    /// Creates a new loader, reading from `reader`.
+    ///
+    /// Nothing is read until `load()` is called.
+    pub fn new(reader: T) -> RopeLoader<T> {
+        RopeLoader {
+            reader: reader,
+            builder: RopeBuilder::new(),
+            buffer: vec![0u8; MAX_BYTES * 2].into_boxed_slice(),
+            bytes_read: 0,
+            done: false,
+        }
+    }
+
+    /// Reads at least `max_bytes` more bytes from the reader, or until the
+    /// end of its data, whichever comes first.
+    ///
+    /// Returns `true` once all of the reader's data has been loaded, after
+    /// which further calls do nothing.  Note that slightly more than
+    /// `max_bytes` may be read, up to the size of the internal buffer.
+    ///
+    /// Runs in O(M) time, where M is the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// - If the reader returns an error, `load` stops and returns that
+    ///   error.  The loader can still be used afterwards.
+    /// - If non-utf8 data is encountered, an IO error with kind
+    ///   `InvalidData` is returned.
+    pub fn load(&mut self, max_bytes: usize) -> io::Result<bool> {
+        let mut read_total = 0;
+        while !self.done && read_total < max_bytes.max(1) {
+            let read_count = match self.reader.read(&mut self.buffer) {
+                Ok(read_count) => read_count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            if read_count == 0 {
+                // Make sure we didn't stop in the middle of a char.
+                self.builder.flush()?;
+                self.done = true;
+            } else {
+                self.builder.write_all(&self.buffer[..read_count])?;
+                self.bytes_read += read_count;
+                read_total += read_count;
+            }
+        }
+
+        Ok(self.done)
+    }
+
+    /// Returns the total number of bytes read so far.
+    #[inline]
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
+    /// Returns whether all of the reader's data has been loaded.
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Finishes loading, and returns the `Rope`.
+    ///
+    /// If the loading isn't done, the returned `Rope` contains the text
+    /// loaded so far, minus any trailing incomplete char.
+    pub fn finish(self) -> Rope {
+        self.builder.finish()
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn loader_01() {
+        let mut loader = RopeLoader::new(Cursor::new(TEXT));
+        let mut steps = 0;
+        while !loader.load(10).unwrap() {
+            steps += 1;
+            assert!(loader.bytes_read() <= TEXT.len());
+        }
+        assert!(steps > 1);
+        assert!(loader.is_done());
+        assert!(loader.load(10).unwrap());
+        assert_eq!(loader.bytes_read(), TEXT.len());
+
+        let r = loader.finish();
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn loader_02() {
+        // Cancelling partway through, in the middle of a char.
+        let mut loader = RopeLoader::new(Cursor::new(TEXT));
+        assert!(!loader.load(1).unwrap());
+        let bytes_read = loader.bytes_read();
+        assert!(bytes_read > 0 && bytes_read < TEXT.len());
+        assert_eq!(loader.finish(), &TEXT[..bytes_read]);
+
+        let mut loader = RopeLoader::new(Cursor::new(TEXT));
+        while loader.bytes_read() < 120 {
+            loader.load(1).unwrap();
+        }
+        let bytes_read = loader.bytes_read();
+        let r = loader.finish();
+        assert!(r.len_bytes() <= bytes_read);
+        assert!(TEXT.starts_with(&r.to_string()));
+    }
+
+    #[test]
+    fn loader_03() {
+        // Invalid utf8.
+        let mut loader = RopeLoader::new(&b"Hello \xFFthere"[..]);
+        let err = loader.load(usize::MAX).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(loader.finish(), "Hello ");
+    }
+}
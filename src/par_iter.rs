@@ -0,0 +1,523 @@
+//! Parallel iterators over a `Rope`'s data, for use with
+//! [Rayon](https://docs.rs/rayon).
+//!
+//! Only available with the `rayon` feature enabled.
+//!
+//! These iterators divide the work along the rope's tree, so that CPU-heavy
+//! processing of large texts can use all cores without having to split the
+//! text up by hand.  Like any Rayon iterator, they yield their items in
+//! order where that matters, e.g. for `collect()`.
+
+use core::ops::Range;
+use core::str;
+
+use rayon::iter::plumbing::{
+    bridge, bridge_unindexed, Consumer, Folder, Producer, ProducerCallback, UnindexedConsumer,
+    UnindexedProducer,
+};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+use iter::Lines;
+use slice::RopeSlice;
+use tree::{Arc, Node};
+
+//==========================================================
+
+/// A parallel iterator over a `Rope`'s contiguous `str` chunks.
+///
+/// This is the parallel counterpart of
+/// [`Chunks`](../iter/struct.Chunks.html), and yields the same non-empty
+/// chunks.
+///
+/// Created by `Rope::par_chunks()` and `RopeSlice::par_chunks()`.
+#[derive(Debug, Clone)]
+pub struct ParChunks<'a>(ChunksProducer<'a>);
+
+#[derive(Debug, Clone)]
+enum ChunksProducer<'a> {
+    Full {
+        node: &'a Node,
+        start_byte: usize, // Start of the data range, relative to `node`.
+        end_byte: usize,   // End of the data range, relative to `node`.
+    },
+    Light(&'a str),
+}
+
+impl<'a> ParChunks<'a> {
+    #[inline]
+    pub(crate) fn new(node: &'a Arc<Node>, start_byte: usize, end_byte: usize) -> ParChunks<'a> {
+        ParChunks(ChunksProducer::Full {
+            node: node,
+            start_byte: start_byte,
+            end_byte: end_byte,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn from_str(text: &'a str) -> ParChunks<'a> {
+        ParChunks(ChunksProducer::Light(text))
+    }
+}
+
+impl<'a> ParallelIterator for ParChunks<'a> {
+    type Item = &'a str;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge_unindexed(self.0, consumer)
+    }
+}
+
+impl<'a> UnindexedProducer for ChunksProducer<'a> {
+    type Item = &'a str;
+
+    fn split(self) -> (Self, Option<Self>) {
+        let (mut node, mut start_byte, mut end_byte) = match self {
+            ChunksProducer::Full {
+                node,
+                start_byte,
+                end_byte,
+            } => (node, start_byte, end_byte),
+            ChunksProducer::Light(_) => return (self, None),
+        };
+
+        // Descend until the range spans more than one child, and then split
+        // it between them.
+        while let Node::Internal(ref children) = *node {
+            if start_byte >= end_byte {
+                break;
+            }
+            let (first_i, _) = children.search_byte_idx(start_byte);
+            let (last_i, acc_info) = children.search_byte_idx(end_byte - 1);
+
+            if first_i == last_i {
+                node = &children.nodes()[first_i];
+                start_byte -= acc_info.bytes as usize;
+                end_byte -= acc_info.bytes as usize;
+            } else {
+                let mid_i = (first_i + last_i).div_ceil(2);
+                let mid_byte = children.info()[..mid_i]
+                    .iter()
+                    .map(|info| info.bytes as usize)
+                    .sum();
+                return (
+                    ChunksProducer::Full {
+                        node: node,
+                        start_byte: start_byte,
+                        end_byte: mid_byte,
+                    },
+                    Some(ChunksProducer::Full {
+                        node: node,
+                        start_byte: mid_byte,
+                        end_byte: end_byte,
+                    }),
+                );
+            }
+        }
+
+        (
+            ChunksProducer::Full {
+                node: node,
+                start_byte: start_byte,
+                end_byte: end_byte,
+            },
+            None,
+        )
+    }
+
+    fn fold_with<F>(self, folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        match self {
+            ChunksProducer::Full {
+                node,
+                start_byte,
+                end_byte,
+            } => fold_chunks(node, start_byte, end_byte, folder),
+            ChunksProducer::Light(text) => {
+                if text.is_empty() {
+                    folder
+                } else {
+                    folder.consume(text)
+                }
+            }
+        }
+    }
+}
+
+// Feeds the non-empty chunks of `node` within the given byte range to
+// `folder`, in order.
+fn fold_chunks<'a, F>(node: &'a Node, start_byte: usize, end_byte: usize, folder: F) -> F
+where
+    F: Folder<&'a str>,
+{
+    match *node {
+        Node::Leaf(ref text) => {
+            if start_byte < end_byte {
+                folder.consume(&text[start_byte..end_byte])
+            } else {
+                folder
+            }
+        }
+        Node::Internal(ref children) => {
+            let mut folder = folder;
+            let mut child_start = 0;
+            for (info, child) in children.iter() {
+                let child_end = child_start + info.bytes as usize;
+                if child_end > start_byte && child_start < end_byte {
+                    folder = fold_chunks(
+                        child,
+                        start_byte.saturating_sub(child_start),
+                        end_byte.min(child_end) - child_start,
+                        folder,
+                    );
+                    if folder.full() {
+                        break;
+                    }
+                }
+                child_start = child_end;
+            }
+            folder
+        }
+    }
+}
+
+//==========================================================
+
+/// A parallel iterator over the bytes of a `Rope`.
+///
+/// This is the parallel counterpart of
+/// [`Bytes`](../iter/struct.Bytes.html).
+///
+/// Created by `Rope::par_bytes()` and `RopeSlice::par_bytes()`.
+#[derive(Debug, Clone)]
+pub struct ParBytes<'a>(ParChunks<'a>);
+
+impl<'a> ParBytes<'a> {
+    #[inline]
+    pub(crate) fn new(chunks: ParChunks<'a>) -> ParBytes<'a> {
+        ParBytes(chunks)
+    }
+}
+
+impl<'a> ParallelIterator for ParBytes<'a> {
+    type Item = u8;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.0.flat_map_iter(str::bytes).drive_unindexed(consumer)
+    }
+}
+
+//===========================================================
+
+/// A parallel iterator over a `Rope`'s lines.
+///
+/// This is the parallel counterpart of
+/// [`Lines`](../iter/struct.Lines.html), and yields the same lines.  It's
+/// an indexed iterator, so adaptors like `enumerate()` give each line its
+/// line index, and results are collected in line order.
+///
+/// Created by `Rope::par_lines()` and `RopeSlice::par_lines()`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate rayon;
+/// # extern crate ropey;
+/// use rayon::prelude::*;
+/// use ropey::Rope;
+///
+/// let rope = Rope::from_str("error: a\nok\nerror: b, error: c\n");
+///
+/// // The number of errors on each line, in line order.
+/// let errors: Vec<usize> = rope
+///     .par_lines()
+///     .map(|line| line.to_string().matches("error").count())
+///     .collect();
+/// assert_eq!(errors, [1, 0, 2, 0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParLines<'a>(RopeSlice<'a>);
+
+impl<'a> ParLines<'a> {
+    #[inline]
+    pub(crate) fn new(slice: RopeSlice<'a>) -> ParLines<'a> {
+        ParLines(slice)
+    }
+
+    /// Folds the lines in ranges of `lines_per_range` lines each, yielding
+    /// every range of line indices along with its folded value.
+    ///
+    /// Each range is folded in order, starting from `identity()`, and the
+    /// ranges are yielded in line order.  The last range may be shorter.
+    /// Folding a range of lines at a time rather than mapping every line
+    /// keeps the per-item overhead down when the work per line is small.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate rayon;
+    /// # extern crate ropey;
+    /// use rayon::prelude::*;
+    /// use ropey::Rope;
+    ///
+    /// let rope = Rope::from_str("a\nbb\n\nccc\nd");
+    ///
+    /// // The number of chars in every two lines.
+    /// let counts: Vec<_> = rope
+    ///     .par_lines()
+    ///     .fold_line_ranges(2, || 0, |n, line| n + line.len_chars())
+    ///     .collect();
+    /// assert_eq!(counts, [(0..2, 5), (2..4, 5), (4..5, 1)]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lines_per_range` is zero.
+    pub fn fold_line_ranges<T, ID, F>(
+        self,
+        lines_per_range: usize,
+        identity: ID,
+        fold_op: F,
+    ) -> impl IndexedParallelIterator<Item = (Range<usize>, T)> + 'a
+    where
+        T: Send + 'a,
+        ID: Fn() -> T + Send + Sync + 'a,
+        F: Fn(T, RopeSlice<'a>) -> T + Send + Sync + 'a,
+    {
+        assert!(lines_per_range != 0, "lines_per_range must not be zero");
+        let len = self.len();
+        self.fold_chunks(lines_per_range, identity, fold_op)
+            .enumerate()
+            .map(move |(i, acc)| {
+                let start = i * lines_per_range;
+                (start..(start + lines_per_range).min(len), acc)
+            })
+    }
+}
+
+impl<'a> ParallelIterator for ParLines<'a> {
+    type Item = RopeSlice<'a>;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    #[inline]
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'a> IndexedParallelIterator for ParLines<'a> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.0.len_lines()
+    }
+
+    fn drive<C>(self, consumer: C) -> C::Result
+    where
+        C: Consumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB>(self, callback: CB) -> CB::Output
+    where
+        CB: ProducerCallback<Self::Item>,
+    {
+        let end_line = self.len();
+        callback.callback(LinesProducer {
+            slice: self.0,
+            start_line: 0,
+            end_line: end_line,
+        })
+    }
+}
+
+struct LinesProducer<'a> {
+    slice: RopeSlice<'a>,
+    start_line: usize,
+    end_line: usize,
+}
+
+impl<'a> Producer for LinesProducer<'a> {
+    type Item = RopeSlice<'a>;
+    type IntoIter = LineRange<'a>;
+
+    fn into_iter(self) -> LineRange<'a> {
+        LineRange {
+            lines: self.slice.lines_at(self.start_line),
+            slice: self.slice,
+            start_line: self.start_line,
+            end_line: self.end_line,
+        }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let mid_line = self.start_line + index;
+        (
+            LinesProducer {
+                slice: self.slice,
+                start_line: self.start_line,
+                end_line: mid_line,
+            },
+            LinesProducer {
+                slice: self.slice,
+                start_line: mid_line,
+                end_line: self.end_line,
+            },
+        )
+    }
+}
+
+// The lines of a slice within a range of line indices.
+struct LineRange<'a> {
+    lines: Lines<'a>, // Positioned at `start_line`.
+    slice: RopeSlice<'a>,
+    start_line: usize,
+    end_line: usize,
+}
+
+impl<'a> Iterator for LineRange<'a> {
+    type Item = RopeSlice<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<RopeSlice<'a>> {
+        if self.start_line < self.end_line {
+            self.start_line += 1;
+            self.lines.next()
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end_line - self.start_line;
+        (len, Some(len))
+    }
+}
+
+impl<'a> DoubleEndedIterator for LineRange<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<RopeSlice<'a>> {
+        if self.start_line < self.end_line {
+            self.end_line -= 1;
+            Some(self.slice.line(self.end_line))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for LineRange<'a> {}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+    use {Rope, RopeSlice};
+
+    // 124 bytes, 100 chars, 4 lines
+    const TEXT_LINES: &str = "Hello there!  How're you doing?\nIt's \
+                              a fine day, isn't it?\nAren't you glad \
+                              we're alive?\nこんにちは、みんなさん！";
+
+    #[test]
+    fn par_chunks_01() {
+        let r = Rope::from_str(&TEXT_LINES.repeat(20));
+        let chunks: Vec<_> = r.par_chunks().collect();
+        assert_eq!(chunks, r.chunks().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn par_chunks_02() {
+        // Slices, including ones smaller than a chunk.
+        let r = Rope::from_str(&TEXT_LINES.repeat(20));
+        let len = r.len_chars();
+        for (start, end) in [(0, 0), (3, 5), (5, 400), (37, 1000), (999, len)] {
+            let s = r.slice(start..end);
+            let chunks: Vec<_> = s.par_chunks().collect();
+            let expected: Vec<_> = s.chunks().filter(|c| !c.is_empty()).collect();
+            assert_eq!(chunks, expected);
+        }
+        assert_eq!(Rope::new().par_chunks().count(), 0);
+    }
+
+    #[test]
+    fn par_lines_01() {
+        let r = Rope::from_str(&TEXT_LINES.repeat(20));
+        let lines: Vec<_> = r.par_lines().collect();
+        assert_eq!(lines, r.lines().collect::<Vec<_>>());
+        assert_eq!(r.par_lines().len(), r.len_lines());
+
+        let lines: Vec<_> = r.par_lines().rev().collect();
+        let mut expected: Vec<_> = r.lines().collect();
+        expected.reverse();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn par_lines_02() {
+        // Slices, including ones starting and ending mid-line.
+        let r = Rope::from_str(&TEXT_LINES.repeat(20));
+        let len = r.len_chars();
+        for (start, end) in [(0, 0), (3, 5), (5, 400), (37, 1000), (999, len)] {
+            let s = r.slice(start..end);
+            let lines: Vec<_> = s.par_lines().collect();
+            assert_eq!(lines, s.lines().collect::<Vec<_>>());
+        }
+        assert_eq!(Rope::new().par_lines().count(), 1);
+
+        let s: RopeSlice = TEXT_LINES.into();
+        let lines: Vec<_> = s.par_lines().collect();
+        assert_eq!(lines, s.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn fold_line_ranges_01() {
+        let r = Rope::from_str(&TEXT_LINES.repeat(20));
+        let ranges: Vec<_> = r
+            .par_lines()
+            .fold_line_ranges(7, || 0, |n, line| n + line.len_chars())
+            .collect();
+        assert_eq!(ranges.len(), 9);
+        assert_eq!(ranges[8].0, 56..61);
+        for (range, chars) in ranges {
+            let expected: usize = r
+                .lines()
+                .skip(range.start)
+                .take(range.len())
+                .map(|l| l.len_chars())
+                .sum();
+            assert_eq!(chars, expected);
+        }
+    }
+
+    #[test]
+    fn par_bytes_01() {
+        let r = Rope::from_str(&TEXT_LINES.repeat(20));
+        let bytes: Vec<_> = r.par_bytes().collect();
+        assert_eq!(bytes, r.bytes().collect::<Vec<_>>());
+
+        let s = r.slice(37..1000);
+        let bytes: Vec<_> = s.par_bytes().collect();
+        assert_eq!(bytes, s.bytes().collect::<Vec<_>>());
+        assert_eq!(
+            r.par_bytes().filter(|&b| b == b'\n').count(),
+            r.len_lines() - 1
+        );
+    }
+}
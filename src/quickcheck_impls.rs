@@ -0,0 +1,164 @@
+//! QuickCheck support.
+//!
+//! Only available with the `quickcheck` feature enabled.
+//!
+//! Generated ropes are built up from random inserts into an empty rope, so
+//! their chunk boundaries and tree shapes vary.  Their text mixes ASCII with
+//! CRLF and other line breaks, multi-byte chars, emoji, and combining marks.
+//!
+//! Shrinking edits the rope in place rather than rebuilding it, so that
+//! shrunk ropes keep most of the original's shape.  It first tries removing
+//! ranges of text, largest first, and then replacing chars other than ASCII
+//! letters, digits and spaces with `a`.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use core::iter;
+
+use quickcheck::{Arbitrary, Gen};
+
+use rope::Rope;
+
+impl Arbitrary for Rope {
+    fn arbitrary(g: &mut Gen) -> Rope {
+        let mut rope = Rope::new();
+        let insert_count = 1 + usize::arbitrary(g) % 8;
+        for _ in 0..insert_count {
+            let len = usize::arbitrary(g) % (g.size() + 1);
+            let text: String = (0..len).map(|_| arbitrary_text_element(g)).collect();
+            let char_idx = usize::arbitrary(g) % (rope.len_chars() + 1);
+            rope.insert(char_idx, &text);
+        }
+        rope
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Rope>> {
+        let len = self.len_chars();
+
+        // Remove ranges of halving sizes, like quickcheck does for `Vec`s.
+        let rope = self.clone();
+        let sizes =
+            iter::successors(Some(len), |&size| Some(size / 2)).take_while(|&size| size > 0);
+        let removals = sizes.flat_map(move |size| {
+            let rope = rope.clone();
+            (0..=(len - size)).step_by(size).map(move |start| {
+                let mut rope = rope.clone();
+                rope.remove(start..(start + size));
+                rope
+            })
+        });
+
+        // Replace chars with simpler ones.
+        let rope = self.clone();
+        let replacements = (0..len).filter_map(move |char_idx| {
+            if is_simple(rope.char(char_idx)) {
+                return None;
+            }
+            let mut rope = rope.clone();
+            rope.remove(char_idx..(char_idx + 1));
+            rope.insert_char(char_idx, 'a');
+            Some(rope)
+        });
+
+        Box::new(removals.chain(replacements))
+    }
+}
+
+fn is_simple(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == ' '
+}
+
+/// Returns one char, or two for CRLF pairs and chars with a combining mark.
+fn arbitrary_text_element(g: &mut Gen) -> String {
+    fn char_in(g: &mut Gen, first: char, last: char) -> char {
+        let span = last as u32 - first as u32 + 1;
+        core::char::from_u32(first as u32 + u32::arbitrary(g) % span).unwrap()
+    }
+
+    match u8::arbitrary(g) % 20 {
+        0..=11 => char_in(g, ' ', '~').to_string(),
+        12 | 13 => {
+            let line_breaks = ["\n", "\r\n", "\r", "\u{0085}", "\u{2028}"];
+            g.choose(&line_breaks).unwrap().to_string()
+        }
+        14 | 15 => char_in(g, '\u{00C0}', '\u{00FF}').to_string(),
+        16 | 17 => char_in(g, '\u{3041}', '\u{3096}').to_string(),
+        18 => char_in(g, '\u{1F600}', '\u{1F64F}').to_string(),
+        _ => {
+            let c = char_in(g, 'a', 'z');
+            let mark = char_in(g, '\u{0300}', '\u{036F}');
+            [c, mark].iter().collect()
+        }
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use quickcheck::{Arbitrary, Gen};
+
+    use super::is_simple;
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn arbitrary_01() {
+        let mut g = Gen::new(100);
+        let mut reshaped = 0;
+        for _ in 0..100 {
+            let r = Rope::arbitrary(&mut g);
+            r.assert_integrity();
+
+            let r2 = Rope::from_str(&r.to_string());
+            if !r.chunks().eq(r2.chunks()) {
+                reshaped += 1;
+            }
+        }
+
+        // Not just the shapes that `from_str()` would give.
+        assert!(reshaped > 0);
+    }
+
+    #[test]
+    fn shrink_01() {
+        // Each candidate is shorter, or has fewer chars to simplify.
+        let complexity = |r: &Rope| r.chars().filter(|&c| !is_simple(c)).count();
+        let r = Rope::from_str(TEXT);
+        for r2 in r.shrink() {
+            r2.assert_integrity();
+            assert!(
+                r2.len_chars() < r.len_chars()
+                    || (r2.len_chars() == r.len_chars() && complexity(&r2) < complexity(&r))
+            );
+        }
+        assert_eq!(Rope::new().shrink().count(), 0);
+        assert_eq!(Rope::from_str("a").shrink().collect::<Vec<_>>(), vec![""]);
+    }
+
+    #[test]
+    fn shrink_02() {
+        // Shrinking a failure down as far as it goes, as quickcheck would,
+        // gives a minimal failing input.
+        let fails = |r: &Rope| r.chars().any(|c| c == 'ん');
+        let mut r = Rope::from_str(TEXT);
+        while let Some(r2) = r.shrink().find(|r2| fails(r2)) {
+            r = r2;
+        }
+        assert_eq!(r, "ん");
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let fails = |r: &Rope| r.len_chars() > 2;
+        let mut r = Rope::from_str(TEXT);
+        while let Some(r2) = r.shrink().find(|r2| fails(r2)) {
+            r = r2;
+        }
+        assert_eq!(r.len_chars(), 3);
+        assert!(r.chars().all(is_simple));
+    }
+}
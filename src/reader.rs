@@ -0,0 +1,289 @@
+use std::io;
+
+use slice::RopeSlice;
+
+/// An `io::Read` adapter over the utf8 bytes of a `Rope`/`RopeSlice`.
+///
+/// This allows a rope's contents to be passed to anything that reads from
+/// a byte stream, without first copying them into a `String`.
+///
+/// `RopeReader` also implements `io::BufRead`, using the rope's own chunks
+/// as its buffer, so e.g. `lines()` and `read_until()` don't copy the text
+/// an extra time.  And it implements `io::Seek`, so it can be used with
+/// formats that need random access.  As with files, seeking past the end is
+/// allowed, and subsequent reads then return zero bytes.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use std::io::{Read, Seek, SeekFrom};
+///
+/// let rope = Rope::from_str("Hello world!");
+/// let mut reader = rope.reader();
+///
+/// let mut text = String::new();
+/// reader.read_to_string(&mut text).unwrap();
+/// assert_eq!(text, "Hello world!");
+///
+/// reader.seek(SeekFrom::End(-6)).unwrap();
+/// let mut buf = [0u8; 5];
+/// reader.read_exact(&mut buf).unwrap();
+/// assert_eq!(&buf, b"world");
+/// ```
+#[derive(Debug, Clone)]
+pub struct RopeReader<'a> {
+    slice: RopeSlice<'a>,
+    byte_idx: usize,
+    // The current chunk, and the byte index of its start.
+    chunk: &'a str,
+    chunk_byte_idx: usize,
+}
+
+impl<'a> RopeReader<'a> {
+    pub(crate) fn new(slice: RopeSlice<'a>) -> RopeReader<'a> {
+        RopeReader {
+            slice: slice,
+            byte_idx: 0,
+            chunk: "",
+            chunk_byte_idx: 0,
+        }
+    }
+
+    /// Returns the byte index that the next read will start from.
+    #[inline]
+    pub fn byte_idx(&self) -> usize {
+        self.byte_idx
+    }
+
+    /// Returns the bytes from the current position to the end of its chunk.
+    ///
+    /// Empty if the current position is at or past the end of the text.
+    fn current_bytes(&mut self) -> &'a [u8] {
+        if self.byte_idx >= self.slice.len_bytes() {
+            return &[];
+        }
+
+        // Move to the chunk containing the current position, if we're not
+        // already in it.
+        if self.byte_idx < self.chunk_byte_idx
+            || self.byte_idx >= (self.chunk_byte_idx + self.chunk.len())
+        {
+            let (chunk, chunk_byte_idx, _, _) = self.slice.chunk_at_byte(self.byte_idx);
+            self.chunk = chunk;
+            self.chunk_byte_idx = chunk_byte_idx;
+        }
+
+        &self.chunk.as_bytes()[(self.byte_idx - self.chunk_byte_idx)..]
+    }
+}
+
+impl<'a> io::Read for RopeReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = {
+            let bytes = self.current_bytes();
+            let len = bytes.len().min(buf.len());
+            buf[..len].copy_from_slice(&bytes[..len]);
+            len
+        };
+        self.byte_idx += len;
+
+        Ok(len)
+    }
+}
+
+impl<'a> io::BufRead for RopeReader<'a> {
+    /// Returns the rest of the current chunk, without copying.
+    ///
+    /// Runs in O(1) time, or O(log N) when moving to a new chunk.
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.current_bytes())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.byte_idx += amt;
+    }
+}
+
+impl<'a> io::Seek for RopeReader<'a> {
+    /// Seeks to a byte offset in the text.
+    ///
+    /// Runs in O(1) time.  The chunk lookup for the new position, which is
+    /// O(log N), is deferred until the next read.
+    ///
+    /// # Errors
+    ///
+    /// - If the new position would be before the start of the text, returns
+    ///   an error of kind `InvalidInput` and leaves the position unchanged.
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let new_idx = seek_idx(pos, self.byte_idx, self.slice.len_bytes())?;
+        self.byte_idx = new_idx;
+        Ok(new_idx as u64)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.byte_idx as u64)
+    }
+}
+
+/// Computes the byte index that `pos` seeks to, from the current index
+/// `byte_idx` in text of length `len`.
+pub(crate) fn seek_idx(pos: io::SeekFrom, byte_idx: usize, len: usize) -> io::Result<usize> {
+    let (base, offset) = match pos {
+        io::SeekFrom::Start(n) => (n, 0),
+        io::SeekFrom::End(n) => (len as u64, n),
+        io::SeekFrom::Current(n) => (byte_idx as u64, n),
+    };
+
+    let new_idx = if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.unsigned_abs())
+    };
+
+    match new_idx {
+        Some(n) if n <= usize::MAX as u64 => Ok(n as usize),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )),
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read, Seek, SeekFrom};
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn reader_01() {
+        let r = Rope::from_str(TEXT);
+        let mut text = String::new();
+        r.reader().read_to_string(&mut text).unwrap();
+        assert_eq!(text, TEXT);
+    }
+
+    #[test]
+    fn reader_02() {
+        // Small reads that straddle chunk boundaries.
+        let r = Rope::from_str(TEXT);
+        let mut reader = r.reader();
+        let mut bytes = Vec::new();
+        let mut buf = [0u8; 5];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(bytes, TEXT.as_bytes());
+        assert_eq!(reader.byte_idx(), TEXT.len());
+    }
+
+    #[test]
+    fn buf_read_01() {
+        let r = Rope::from_str(TEXT);
+        let mut reader = r.reader();
+        let mut bytes = Vec::new();
+        loop {
+            let len = {
+                let buf = reader.fill_buf().unwrap();
+                assert!(buf.len() <= r.chunk_at_byte(bytes.len()).0.len());
+                bytes.extend_from_slice(buf);
+                buf.len()
+            };
+            if len == 0 {
+                break;
+            }
+            reader.consume(len);
+        }
+        assert_eq!(bytes, TEXT.as_bytes());
+    }
+
+    #[test]
+    fn buf_read_02() {
+        let text = "Hello there!\nHow're you doing?\r\nこんにちは\n";
+        let r = Rope::from_str(text);
+        let lines: Vec<_> = r.reader().lines().map(|l| l.unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec!["Hello there!", "How're you doing?", "こんにちは"]
+        );
+
+        let mut reader = r.slice(6..).reader();
+        let mut buf = Vec::new();
+        reader.read_until(b'?', &mut buf).unwrap();
+        assert_eq!(buf, b"there!\nHow're you doing?");
+        assert_eq!(reader.byte_idx(), buf.len());
+    }
+
+    #[test]
+    fn reader_sliced_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(5..98);
+        let mut text = String::new();
+        s.reader().read_to_string(&mut text).unwrap();
+        assert_eq!(text, s);
+    }
+
+    #[test]
+    fn seek_01() {
+        let r = Rope::from_str(TEXT);
+        let mut reader = r.reader();
+        let mut buf = [0u8; 6];
+
+        assert_eq!(reader.seek(SeekFrom::Start(14)).unwrap(), 14);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"How're");
+
+        assert_eq!(reader.seek(SeekFrom::Current(-13)).unwrap(), 7);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"here! ");
+
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 124);
+        reader.read_exact(&mut buf[..3]).unwrap();
+        assert_eq!(&buf[..3], "！".as_bytes());
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        // Rewinding.
+        reader.rewind().unwrap();
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, TEXT);
+    }
+
+    #[test]
+    fn seek_02() {
+        // Seeking out of bounds.
+        let r = Rope::from_str(TEXT);
+        let mut reader = r.reader();
+        assert_eq!(reader.seek(SeekFrom::End(10)).unwrap(), 137);
+        assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 0);
+
+        reader.seek(SeekFrom::Start(20)).unwrap();
+        assert!(reader.seek(SeekFrom::Current(-21)).is_err());
+        assert_eq!(reader.stream_position().unwrap(), 20);
+    }
+
+    #[test]
+    fn seek_sliced_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(14..31);
+        let mut reader = s.reader();
+        let mut buf = [0u8; 5];
+        reader.seek(SeekFrom::End(-6)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"doing");
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"How'r");
+    }
+}
@@ -1,19 +1,61 @@
-use std;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core;
+use core::convert::TryFrom;
+use core::hash::Hasher;
+use core::iter::FromIterator;
+use core::ops::RangeBounds;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+#[cfg(feature = "std")]
 use std::io;
-use std::iter::FromIterator;
-use std::ops::RangeBounds;
-use std::ptr;
-use std::sync::Arc;
-
+#[cfg(feature = "mmap")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "encoding")]
+use encoding_rs::{CoderResult, Encoding};
+#[cfg(feature = "futures")]
+use futures_io::{AsyncRead, AsyncWrite};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+#[cfg(feature = "std")]
+use append_reader::AppendReader;
+#[cfg(feature = "std")]
+use append_writer::AppendWriter;
+#[cfg(feature = "futures")]
+use async_io::{FromAsyncReader, WriteToAsync};
+use chunk_id::ChunkId;
 use crlf;
-use iter::{Bytes, Chars, Chunks, Lines};
+use dump;
+#[cfg(feature = "encoding")]
+use encoding::{write_encoded, Unmappable};
+use excerpt::{self, ExcerptLine};
+use iter::{Bytes, Chars, ChunkIds, Chunks, Lines};
+#[cfg(feature = "rayon")]
+use par_iter::{ParBytes, ParChunks, ParLines};
+#[cfg(feature = "std")]
+use reader::RopeReader;
 use rope_builder::RopeBuilder;
+#[cfg(feature = "std")]
+use rope_file::RopeFile;
+#[cfg(feature = "fuzzy")]
+use search::FuzzyLines;
+#[cfg(feature = "regex")]
+use search::{regex_replace_all, Regex, RegexMatches};
+use search::{Matches, Pattern, Split};
 use slice::{end_bound_to_num, start_bound_to_num, RopeSlice};
+use stats::{MemUsage, TreeStats};
 use str_utils::{
     byte_to_char_idx, byte_to_line_idx, char_to_byte_idx, char_to_line_idx, line_to_byte_idx,
     line_to_char_idx,
 };
-use tree::{Count, Node, NodeChildren, TextInfo, MAX_BYTES};
+use tree::{assert_count_fits, Arc, Count, Node, NodeChildren, NodeText, TextInfo, MAX_BYTES};
+#[cfg(feature = "std")]
+use tree_format;
+use validate::{self, ValidationError};
 
 /// A utf8 text rope.
 ///
@@ -108,8 +150,23 @@ impl Rope {
     // Constructors
 
     /// Creates an empty `Rope`.
+    ///
+    /// With the `std` feature enabled this doesn't allocate: all empty ropes
+    /// share the same root node until they're edited, which makes them cheap
+    /// to create in bulk.
     #[inline]
     pub fn new() -> Self {
+        #[cfg(feature = "std")]
+        {
+            static EMPTY_ROOT: OnceLock<Arc<Node>> = OnceLock::new();
+            Rope {
+                root: Arc::clone(EMPTY_ROOT.get_or_init(|| Arc::new(Node::new()))),
+            }
+        }
+
+        // There's no `OnceLock` in `core`, so each empty rope gets its own
+        // root.
+        #[cfg(not(feature = "std"))]
         Rope {
             root: Arc::new(Node::new()),
         }
@@ -124,6 +181,157 @@ impl Rope {
         RopeBuilder::new().build_at_once(text)
     }
 
+    /// Creates a `Rope` from a byte slice of utf8 text.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// If `bytes` isn't valid utf8, returns an error that gives the byte
+    /// index of the first invalid sequence, via `Utf8Error::valid_up_to()`.
+    pub fn from_utf8(bytes: &[u8]) -> Result<Self, core::str::Utf8Error> {
+        let text = core::str::from_utf8(bytes)?;
+        Ok(RopeBuilder::new().build_at_once(text))
+    }
+
+    /// Creates a `Rope` from a byte slice, replacing any invalid utf8 with
+    /// U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// Invalid sequences are replaced in the same way as
+    /// `String::from_utf8_lossy()`, but the text is built up directly in
+    /// the `Rope`, without first building a `String` of the whole text.
+    ///
+    /// Runs in O(N) time.
+    pub fn from_utf8_lossy(bytes: &[u8]) -> Self {
+        let mut builder = RopeBuilder::new();
+        for chunk in bytes.utf8_chunks() {
+            builder.append(chunk.valid());
+            if !chunk.invalid().is_empty() {
+                builder.append("\u{FFFD}");
+            }
+        }
+        builder.finish()
+    }
+
+    /// Creates a `Rope` from an iterator of text chunks.
+    ///
+    /// The chunks are streamed into a `RopeBuilder`, so this is handy for
+    /// decoders and generators that produce text piece by piece.  The
+    /// chunks can be of any size, but larger chunks are more efficient.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let words = ["Hello", " ", "world", "!"];
+    /// let rope = Rope::from_chunks(words.iter().copied());
+    ///
+    /// assert_eq!(rope, "Hello world!");
+    /// ```
+    ///
+    /// Runs in O(N) time.
+    pub fn from_chunks<'a, I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut builder = RopeBuilder::new();
+        for chunk in chunks {
+            builder.append(chunk);
+        }
+        builder.finish()
+    }
+
+    /// Creates a `Rope` from lines of text, joined by `line_break`.
+    ///
+    /// The lines shouldn't contain their own line breaks: `line_break`,
+    /// usually `"\n"` or `"\r\n"`, is put between each pair of lines.  So
+    /// to end the text with a line break, end with an empty line.  This is
+    /// the inverse of `lines()`, once the lines' line breaks are removed,
+    /// which makes it handy for transforming a document line by line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("one\ntwo\nthree\n");
+    /// let upper = rope
+    ///     .lines()
+    ///     .map(|line| line.to_string().trim_end().to_uppercase());
+    ///
+    /// assert_eq!(Rope::from_lines(upper, "\r\n"), "ONE\r\nTWO\r\nTHREE\r\n");
+    /// ```
+    ///
+    /// Runs in O(N) time.
+    pub fn from_lines<I>(lines: I, line_break: &str) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut builder = RopeBuilder::new();
+        for (i, line) in lines.into_iter().enumerate() {
+            if i > 0 {
+                builder.append(line_break);
+            }
+            builder.append(line.as_ref());
+        }
+        builder.finish()
+    }
+
+    /// Creates a `Rope` from text chunks and their precomputed char and line
+    /// break counts, without re-scanning the text.
+    ///
+    /// Each item is a `(chunk, char_count, line_break_count)` tuple.  Each
+    /// chunk becomes a leaf of the `Rope` as-is, so this is meant for
+    /// chunks that came from another `Rope`, e.g. when restoring a snapshot
+    /// whose chunks and counts were saved with `Rope::chunks()`.  Empty
+    /// chunks are skipped, and chunks too large for a leaf are split up
+    /// and counted as usual.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// use ropey::str_utils::{count_chars, count_line_breaks};
+    ///
+    /// let rope = Rope::from_str("Hello world!\nHow's it going?");
+    /// let saved: Vec<_> = rope
+    ///     .chunks()
+    ///     .map(|c| (c.to_string(), count_chars(c), count_line_breaks(c)))
+    ///     .collect();
+    ///
+    /// let chunks = saved.iter().map(|(c, chars, lbs)| (c.as_str(), *chars, *lbs));
+    /// let restored = unsafe { Rope::from_chunks_unchecked(chunks) };
+    /// assert_eq!(restored, rope);
+    /// ```
+    ///
+    /// Runs in O(M) time, where M is the number of chunks.
+    ///
+    /// # Safety
+    ///
+    /// - `char_count` and `line_break_count` must be the chunk's char and
+    ///   line break counts, as given by `str_utils::count_chars()` and
+    ///   `str_utils::count_line_breaks()`.
+    /// - The chunks must not split CRLF pairs, i.e. no chunk may end with
+    ///   `\r` when the next one starts with `\n`.
+    ///
+    /// Otherwise the `Rope` is malformed, and using it is undefined
+    /// behavior.  The counts are checked in debug builds.
+    pub unsafe fn from_chunks_unchecked<'a, I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, usize, usize)>,
+    {
+        let mut builder = RopeBuilder::new();
+        for (chunk, char_count, line_break_count) in chunks {
+            let info = TextInfo {
+                bytes: chunk.len() as Count,
+                chars: char_count as Count,
+                line_breaks: line_break_count as Count,
+            };
+            builder.append_leaf_unchecked(chunk, info);
+        }
+        builder.finish()
+    }
+
     /// Creates a `Rope` from the output of a reader.
     ///
     /// This is a convenience function.  To do more sophisticated text loading,
@@ -140,82 +348,324 @@ impl Rope {
     ///
     /// Note: some data from the reader is likely consumed even if there is
     /// an error.
-    #[allow(unused_mut)]
-    pub fn from_reader<T: io::Read>(mut reader: T) -> io::Result<Self> {
+    #[cfg(feature = "std")]
+    pub fn from_reader<T: io::Read>(reader: T) -> io::Result<Self> {
+        Rope::from_reader_with_progress(reader, MAX_BYTES * 2, |_| {})
+    }
+
+    /// Creates a `Rope` from the output of a reader, decoding the text from
+    /// the given encoding.
+    ///
+    /// The text is decoded incrementally as it's read, so there is no need
+    /// to first decode the whole text into a `String`.  Only available with
+    /// the `encoding` feature enabled, and `encoding` is one of the
+    /// encodings of the [`encoding_rs`] crate.
+    ///
+    /// If the text starts with a UTF-8 or UTF-16 byte order mark, the BOM
+    /// takes precedence over `encoding`, and is removed.  So to auto-detect
+    /// Unicode text from its BOM, falling back to some legacy encoding,
+    /// pass the legacy encoding.
+    ///
+    /// Malformed sequences are replaced with U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// Like `Encoding::decode()` in `encoding_rs`, returns the `Rope`, the
+    /// encoding that was actually used, and whether there were malformed
+    /// sequences.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the reader returns an error, `from_reader_with_encoding` stops
+    ///   and returns that error.
+    ///
+    /// [`encoding_rs`]: https://docs.rs/encoding_rs
+    #[cfg(feature = "encoding")]
+    pub fn from_reader_with_encoding<T: io::Read>(
+        mut reader: T,
+        encoding: &'static Encoding,
+    ) -> io::Result<(Self, &'static Encoding, bool)> {
         const BUFFER_SIZE: usize = MAX_BYTES * 2;
+        let mut decoder = encoding.new_decoder();
         let mut builder = RopeBuilder::new();
         let mut buffer = [0u8; BUFFER_SIZE];
-        let mut fill_idx = 0; // How much `buffer` is currently filled with valid data
+        let mut decoded = String::with_capacity(BUFFER_SIZE * 3);
+        let mut had_errors = false;
         loop {
-            match reader.read(&mut buffer[fill_idx..]) {
-                Ok(read_count) => {
-                    fill_idx += read_count;
-
-                    // Determine how much of the buffer is valid utf8.
-                    let valid_count = match std::str::from_utf8(&buffer[..fill_idx]) {
-                        Ok(_) => fill_idx,
-                        Err(e) => e.valid_up_to(),
-                    };
+            let read_count = match reader.read(&mut buffer) {
+                Ok(read_count) => read_count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let last = read_count == 0;
+
+            // Decode the read bytes, as many times as it takes to fit the
+            // output in `decoded`.
+            let mut input = &buffer[..read_count];
+            loop {
+                decoded.clear();
+                let (result, consumed, replaced) =
+                    decoder.decode_to_string(input, &mut decoded, last);
+                had_errors |= replaced;
+                builder.append(&decoded);
+                input = &input[consumed..];
+                if let CoderResult::InputEmpty = result {
+                    break;
+                }
+            }
+
+            if last {
+                return Ok((builder.finish(), decoder.encoding(), had_errors));
+            }
+        }
+    }
+
+    /// Creates a `Rope` from the output of a reader, with a custom read
+    /// buffer size and a progress callback.
+    ///
+    /// Like [`from_reader()`](#method.from_reader), but reads in blocks of
+    /// up to `buffer_size` bytes, and calls `progress` after each read with
+    /// the total number of bytes read so far.  This is useful e.g. for
+    /// showing a progress bar while loading large files.
+    ///
+    /// `buffer_size` is clamped to a minimum of 4 bytes, so that any utf8
+    /// char fits in it.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// # use std::io::Cursor;
+    /// let text = "Hello world!\n".repeat(1000);
+    /// let mut progress = Vec::new();
+    /// let rope = Rope::from_reader_with_progress(Cursor::new(&text), 4096, |bytes_read| {
+    ///     progress.push(bytes_read)
+    /// })
+    /// .unwrap();
+    ///
+    /// assert_eq!(rope, text.as_str());
+    /// assert_eq!(progress, [4096, 8192, 12288, 13000]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - If the reader returns an error, `from_reader_with_progress` stops
+    ///   and returns that error.
+    /// - If non-utf8 data is encountered, an IO error with kind
+    ///   `InvalidData` is returned.
+    ///
+    /// Note: some data from the reader is likely consumed even if there is
+    /// an error.
+    #[cfg(feature = "std")]
+    pub fn from_reader_with_progress<T, F>(
+        mut reader: T,
+        buffer_size: usize,
+        mut progress: F,
+    ) -> io::Result<Self>
+    where
+        T: io::Read,
+        F: FnMut(usize),
+    {
+        let buffer_size = buffer_size.max(4);
+        let mut builder = RopeBuilder::new();
+        let mut buffer = vec![0u8; buffer_size];
+        let mut total_read = 0;
+        loop {
+            let read_count = reader.read(&mut buffer)?;
+            if read_count == 0 {
+                // Make sure the text didn't end mid-char.
+                io::Write::flush(&mut builder)?;
+                return Ok(builder.finish());
+            }
+
+            total_read += read_count;
+            progress(total_read);
+            builder.append_bytes(&buffer[..read_count])?;
+        }
+    }
 
-                    // Append the valid part of the buffer to the rope.
-                    if valid_count > 0 {
+    /// Creates a `Rope` from the contents of a file, by memory-mapping it.
+    ///
+    /// This avoids copying the file's contents through an intermediate read
+    /// buffer, and validates them as utf8 with a SIMD-accelerated
+    /// validator, so for large files it's substantially faster than
+    /// [`from_reader()`](#method.from_reader).  The file isn't kept mapped
+    /// afterwards.
+    ///
+    /// Only available with the `mmap` feature enabled.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the file can't be opened or mapped, returns that error.
+    /// - If the file isn't valid utf8, returns an IO error with kind
+    ///   `InvalidData`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified (by this or any other process) while
+    /// it's being loaded.  As with any memory-mapped file, a concurrent
+    /// modification could break the utf8 validity of the resulting `Rope`,
+    /// which is undefined behavior.  Once this returns, the file is no
+    /// longer mapped and may be modified freely.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            // Empty files can't be mapped on all platforms.
+            return Ok(Rope::new());
+        }
+
+        // The caller guarantees that the file isn't modified while mapped.
+        let map = Mmap::map(&file)?;
+        let text = simdutf8::basic::from_utf8(&map).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })?;
+
+        Ok(RopeBuilder::new().build_at_once(text))
+    }
+
+    /// Creates a `Rope` from the output of a reader, replacing any invalid
+    /// utf8 with U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// Like [`from_reader()`](#method.from_reader), but never fails because
+    /// of invalid data, so e.g. slightly corrupted files can still be
+    /// opened.  Invalid sequences are replaced in the same way as
+    /// `String::from_utf8_lossy()`.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the reader returns an error, `from_reader_lossy` stops and
+    ///   returns that error.
+    #[cfg(feature = "std")]
+    pub fn from_reader_lossy<T: io::Read>(mut reader: T) -> io::Result<Self> {
+        const BUFFER_SIZE: usize = MAX_BYTES * 2;
+        let mut builder = RopeBuilder::new();
+        let mut buffer = [0u8; BUFFER_SIZE];
+        let mut fill_idx = 0; // How much `buffer` is currently filled with valid data
+        loop {
+            let read_count = match reader.read(&mut buffer[fill_idx..]) {
+                Ok(read_count) => read_count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            fill_idx += read_count;
+
+            // Append everything we can, replacing invalid sequences.
+            let mut consumed = 0;
+            while consumed < fill_idx {
+                match core::str::from_utf8(&buffer[consumed..fill_idx]) {
+                    Ok(text) => {
+                        builder.append(text);
+                        consumed = fill_idx;
+                    }
+                    Err(e) => {
+                        let valid_idx = consumed + e.valid_up_to();
                         // The unsafe block here is reinterpreting the bytes as
                         // utf8.  This is safe because the bytes being
                         // reinterpreted have already been validated as utf8
                         // just above.
                         builder.append(unsafe {
-                            std::str::from_utf8_unchecked(&buffer[..valid_count])
+                            core::str::from_utf8_unchecked(&buffer[consumed..valid_idx])
                         });
-                    }
-
-                    // Shift the un-read part of the buffer to the beginning.
-                    if valid_count < fill_idx {
-                        // The unsafe here is just used for efficiency.  This
-                        // can be replaced with a safe call to `copy_within()`
-                        // on the slice once that API is stabalized in the
-                        // standard library.
-                        unsafe {
-                            ptr::copy(
-                                buffer.as_ptr().add(valid_count),
-                                buffer.as_mut_ptr().offset(0),
-                                fill_idx - valid_count,
-                            );
-                        }
-                    }
-                    fill_idx -= valid_count;
-
-                    if fill_idx == BUFFER_SIZE {
-                        // Buffer is full and none of it could be consumed.  Utf8
-                        // codepoints don't get that large, so it's clearly not
-                        // valid text.
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "stream did not contain valid UTF-8",
-                        ));
-                    }
-
-                    // If we're done reading
-                    if read_count == 0 {
-                        if fill_idx > 0 {
-                            // We couldn't consume all data.
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "stream contained invalid UTF-8",
-                            ));
-                        } else {
-                            return Ok(builder.finish());
+                        match e.error_len() {
+                            Some(len) => {
+                                builder.append("\u{FFFD}");
+                                consumed = valid_idx + len;
+                            }
+                            None if read_count == 0 => {
+                                // Incomplete sequence at the end of the data.
+                                builder.append("\u{FFFD}");
+                                consumed = fill_idx;
+                            }
+                            None => {
+                                // Incomplete sequence, which may be completed
+                                // by the next read.
+                                consumed = valid_idx;
+                                break;
+                            }
                         }
                     }
                 }
+            }
 
-                Err(e) => {
-                    // Read error
-                    return Err(e);
-                }
+            // Shift the un-read part of the buffer to the beginning.
+            buffer.copy_within(consumed..fill_idx, 0);
+            fill_idx -= consumed;
+
+            if read_count == 0 {
+                return Ok(builder.finish());
             }
         }
     }
 
+    /// Creates a `Rope` from the output of an async reader.
+    ///
+    /// This is the async equivalent of
+    /// [`from_reader()`](#method.from_reader), returning a future that reads
+    /// the text incrementally without ever holding all of it in a buffer.
+    ///
+    /// Only available with the `futures` feature enabled.  It works with any
+    /// [`futures_io::AsyncRead`], which e.g. Tokio's readers can be adapted
+    /// to with `tokio-util`'s `compat` module.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the reader returns an error, the future stops and returns that
+    ///   error.
+    /// - If non-utf8 data is encountered, an IO error with kind
+    ///   `InvalidData` is returned.
+    ///
+    /// [`futures_io::AsyncRead`]: https://docs.rs/futures-io/0.3/futures_io/trait.AsyncRead.html
+    #[cfg(feature = "futures")]
+    pub fn from_async_reader<T: AsyncRead + Unpin>(reader: T) -> FromAsyncReader<T> {
+        FromAsyncReader::new(reader)
+    }
+
+    /// Creates a `Rope` from data written by
+    /// [`write_tree_to()`](#method.write_tree_to).
+    ///
+    /// The tree is loaded as it was saved, so unlike
+    /// [`from_reader()`](#method.from_reader) the chars and line breaks of
+    /// the text don't need to be counted again.  The text is still checked
+    /// to be valid utf8.
+    ///
+    /// The node size limits of Ropey can differ between builds (e.g. on
+    /// 32-bit and 64-bit platforms).  If the data was written by a build
+    /// with different limits, the tree is instead rebuilt from the text,
+    /// which gives the same result, just not as quickly.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the reader returns an error, `from_tree_reader` stops and returns
+    ///   that error.
+    /// - If the data isn't in the format written by `write_tree_to()`,
+    ///   contains non-utf8 text, or describes a tree that doesn't meet the
+    ///   invariants checked by [`validate()`](#method.validate), an IO error
+    ///   with kind `InvalidData` is returned.
+    ///
+    /// Note: the stored char and line break counts are trusted, so data that
+    /// has been tampered with can result in a `Rope` that reports incorrect
+    /// positions or panics.  Only load data that was written by
+    /// `write_tree_to()`.
+    #[cfg(feature = "std")]
+    #[allow(unused_mut)]
+    pub fn from_tree_reader<T: io::Read>(mut reader: T) -> io::Result<Self> {
+        tree_format::read_tree(&mut reader)
+    }
+
     //-----------------------------------------------------------------------
     // Convenience output methods
 
@@ -232,6 +682,7 @@ impl Rope {
     ///   error.
     ///
     /// Note: some data may have been written even if an error is returned.
+    #[cfg(feature = "std")]
     #[allow(unused_mut)]
     pub fn write_to<T: io::Write>(&self, mut writer: T) -> io::Result<()> {
         for chunk in self.chunks() {
@@ -241,77 +692,416 @@ impl Rope {
         Ok(())
     }
 
-    //-----------------------------------------------------------------------
-    // Informational methods
-
-    /// Total number of bytes in the `Rope`.
+    /// Converts the `Rope` into a `Vec<u8>` of its utf8 bytes.
     ///
-    /// Runs in O(1) time.
-    #[inline]
-    pub fn len_bytes(&self) -> usize {
-        self.root.byte_count()
-    }
-
-    /// Total number of chars in the `Rope`.
+    /// This is equivalent to `rope.to_string().into_bytes()`, but is more
+    /// direct.  To stream the bytes instead, see
+    /// [`write_to()`](#method.write_to) or
+    /// [`bytes()`](#method.bytes).
     ///
-    /// Runs in O(1) time.
-    #[inline]
-    pub fn len_chars(&self) -> usize {
-        self.root.char_count()
+    /// Runs in O(N) time.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len_bytes());
+        for chunk in self.chunks() {
+            bytes.extend_from_slice(chunk.as_bytes());
+        }
+        bytes
     }
 
-    /// Total number of lines in the `Rope`.
+    /// Writes the contents of the `Rope` to a writer, encoded with the
+    /// given encoding.
     ///
-    /// Runs in O(1) time.
-    #[inline]
-    pub fn len_lines(&self) -> usize {
-        self.root.line_break_count() + 1
-    }
-
-    //-----------------------------------------------------------------------
-    // Memory management methods
-
-    /// Total size of the `Rope`'s text buffer space, in bytes.
+    /// The text is encoded chunk by chunk as it's written, without an
+    /// intermediate buffer of the whole text.  Only available with the
+    /// `encoding` feature enabled, and `encoding` is one of the encodings
+    /// of the [`encoding_rs`] crate.  No byte order mark is written.
     ///
-    /// This includes unoccupied text buffer space.  You can calculate
-    /// the unoccupied space with `capacity() - len_bytes()`.  In general,
-    /// there will always be some unoccupied buffer space.
+    /// `unmappable` determines what happens to chars that can't be
+    /// represented in `encoding`.
     ///
     /// Runs in O(N) time.
-    pub fn capacity(&self) -> usize {
-        let mut byte_count = 0;
-        for chunk in self.chunks() {
-            byte_count += chunk.len().max(MAX_BYTES);
-        }
-        byte_count
+    ///
+    /// # Errors
+    ///
+    /// - If the writer returns an error, `write_to_with_encoding` stops and
+    ///   returns that error.
+    /// - If a char can't be represented in `encoding` and `unmappable` is
+    ///   `Unmappable::Error`, returns an error of kind `InvalidData`.
+    ///
+    /// Note: some data may have been written even if an error is returned.
+    ///
+    /// [`encoding_rs`]: https://docs.rs/encoding_rs
+    #[cfg(feature = "encoding")]
+    pub fn write_to_with_encoding<T: io::Write>(
+        &self,
+        writer: T,
+        encoding: &'static Encoding,
+        unmappable: Unmappable,
+    ) -> io::Result<()> {
+        write_encoded(self.chunks(), writer, encoding, unmappable)
     }
 
-    /// Shrinks the `Rope`'s capacity to the minimum possible.
+    /// Updates a file-like writer holding the contents of `saved` to hold
+    /// the contents of this `Rope`, writing only the parts that changed.
     ///
-    /// This will rarely result in `capacity() == len_bytes()`.  `Rope`
-    /// stores text in a sequence of fixed-capacity chunks, so an exact fit
-    /// only happens for texts that are both a precise multiple of that
-    /// capacity _and_ have code point boundaries that line up exactly with
-    /// the capacity boundaries.
+    /// `saved` is typically a clone of the `Rope` made when it was last
+    /// saved.  Since clones share their unedited parts, the unchanged regions
+    /// can usually be skipped without even comparing them, which makes
+    /// saving small edits to huge files fast.
     ///
-    /// After calling this, the difference between `capacity()` and
-    /// `len_bytes()` is typically under 1KB per megabyte of text in the
-    /// `Rope`.
+    /// If the length of the text has changed, everything from the first
+    /// change onwards is rewritten.  In that case, if the text got shorter,
+    /// the caller must also truncate the file to `len_bytes()`, e.g. with
+    /// `File::set_len()`.
     ///
-    /// **NOTE:** calling this on a `Rope` clone causes it to stop sharing
-    /// all data with its other clones.  In such cases you will very likely
-    /// be _increasing_ total memory usage despite shrinking the `Rope`'s
-    /// capacity.
+    /// Returns the number of bytes written.
     ///
-    /// Runs in O(N) time, and uses O(log N) additional space during
-    /// shrinking.
-    pub fn shrink_to_fit(&mut self) {
-        let mut node_stack = Vec::new();
-        let mut builder = RopeBuilder::new();
-
-        node_stack.push(self.root.clone());
-        *self = Rope::new();
-
+    /// Runs in O(M log N) time in the typical case, where M is the number of
+    /// edited chunks, and O(N) at worst.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// use std::io::Cursor;
+    ///
+    /// let mut rope = Rope::from_str(&"Hello world!\n".repeat(1000));
+    /// let mut file = Cursor::new(rope.to_string().into_bytes());
+    /// let saved = rope.clone();
+    ///
+    /// rope.remove(6006..6011);
+    /// rope.insert(6006, "there");
+    ///
+    /// let bytes_written = rope.write_changes_to(&saved, &mut file).unwrap();
+    /// assert_eq!(bytes_written, 5);
+    /// assert_eq!(file.into_inner(), rope.to_string().into_bytes());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - If the writer returns an error, `write_changes_to` stops and
+    ///   returns that error.
+    ///
+    /// Note: some data may have been written even if an error is returned.
+    #[cfg(feature = "std")]
+    pub fn write_changes_to<T: io::Write + io::Seek>(
+        &self,
+        saved: &Rope,
+        mut writer: T,
+    ) -> io::Result<u64> {
+        let mut changes = Vec::new();
+        self.collect_changes(&self.root, 0, saved, &mut changes);
+
+        if self.len_bytes() != saved.len_bytes() {
+            // Rewrite everything from the first change.
+            let start = changes
+                .first()
+                .map(|r| r.start)
+                .unwrap_or_else(|| self.len_bytes().min(saved.len_bytes()));
+            changes.clear();
+            changes.push(start..self.len_bytes());
+        }
+
+        let mut bytes_written = 0;
+        for range in changes {
+            if range.start == range.end {
+                continue;
+            }
+            writer.seek(io::SeekFrom::Start(range.start as u64))?;
+            let mut byte_idx = range.start;
+            while byte_idx < range.end {
+                let (chunk, chunk_byte_idx, _, _) = self.chunk_at_byte(byte_idx);
+                let end = (range.end - chunk_byte_idx).min(chunk.len());
+                writer.write_all(&chunk.as_bytes()[(byte_idx - chunk_byte_idx)..end])?;
+                byte_idx = chunk_byte_idx + end;
+            }
+            bytes_written += (range.end - range.start) as u64;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Writes the `Rope` to a writer in a binary format that preserves its
+    /// internal tree structure.
+    ///
+    /// The data can be loaded with
+    /// [`from_tree_reader()`](#method.from_tree_reader), which is faster
+    /// than building the `Rope` from plain text.  This is intended for e.g.
+    /// saving and restoring editor sessions, not as an interchange format:
+    /// it's specific to Ropey, and mostly consists of the text plus some
+    /// bookkeeping.
+    ///
+    /// The data is written with many small writes, so a buffered writer
+    /// should be used.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the writer returns an error, `write_tree_to` stops and returns
+    ///   that error.
+    ///
+    /// Note: some data may have been written even if an error is returned.
+    #[cfg(feature = "std")]
+    #[allow(unused_mut)]
+    pub fn write_tree_to<T: io::Write>(&self, mut writer: T) -> io::Result<()> {
+        tree_format::write_tree(self, &mut writer)
+    }
+
+    /// Writes the contents of the `Rope` to an async writer.
+    ///
+    /// This is the async equivalent of [`write_to()`](#method.write_to),
+    /// returning a future that writes the text chunk by chunk, waiting
+    /// whenever the writer isn't ready for more.  The writer isn't flushed.
+    ///
+    /// Only available with the `futures` feature enabled.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the writer returns an error, the future stops and returns that
+    ///   error.
+    ///
+    /// Note: some data may have been written even if an error is returned.
+    #[cfg(feature = "futures")]
+    pub fn write_to_async<T: AsyncWrite + Unpin>(&self, writer: T) -> WriteToAsync<'_, T> {
+        WriteToAsync::new(&self.slice(..), writer)
+    }
+
+    /// Appends everything that a reader currently has to offer to the end of
+    /// the `Rope`.
+    ///
+    /// Reads until the reader reports the end of its data (or would block),
+    /// so this can be called repeatedly to follow a growing stream such as a
+    /// log file.  A multi-byte utf8 sequence split across calls is held in
+    /// the [`AppendReader`](struct.AppendReader.html) until it's completed.
+    ///
+    /// Returns the number of bytes read.
+    ///
+    /// Runs in O(M + log N) time, where M is the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// - If the reader returns an error, `append_from_reader` stops and
+    ///   returns that error.
+    /// - If non-utf8 data is encountered, an IO error with kind
+    ///   `InvalidData` is returned.
+    ///
+    /// In either case, the valid text read before the error is still
+    /// appended.
+    #[cfg(feature = "std")]
+    pub fn append_from_reader<T: io::Read>(
+        &mut self,
+        reader: &mut AppendReader<T>,
+    ) -> io::Result<usize> {
+        reader.append_to(self)
+    }
+
+    /// Creates a writer that appends utf8 text to the end of the `Rope`.
+    ///
+    /// The text is appended when the writer is flushed or dropped.  See
+    /// [`AppendWriter`](struct.AppendWriter.html) for details.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn append_writer(&mut self) -> AppendWriter<'_> {
+        AppendWriter::new(self)
+    }
+
+    /// Creates a file-like adapter for reading and editing the `Rope`
+    /// through `io::Read`, `io::Write` and `io::Seek`.
+    ///
+    /// See [`RopeFile`](struct.RopeFile.html) for details.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn as_file(&mut self) -> RopeFile<'_> {
+        RopeFile::new(self)
+    }
+
+    /// Creates a reader over the utf8 bytes of the `Rope`.
+    ///
+    /// The returned reader implements `io::Read` and `io::Seek`.  See
+    /// [`RopeReader`](struct.RopeReader.html) for details.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn reader(&self) -> RopeReader<'_> {
+        RopeReader::new(self.slice(..))
+    }
+
+    //-----------------------------------------------------------------------
+    // Informational methods
+
+    /// Total number of bytes in the `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_bytes(&self) -> usize {
+        self.root.byte_count()
+    }
+
+    /// Total number of chars in the `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_chars(&self) -> usize {
+        self.root.char_count()
+    }
+
+    /// Total number of lines in the `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_lines(&self) -> usize {
+        self.root.line_break_count() + 1
+    }
+
+    /// How many levels of internal nodes the `Rope`'s tree has above its
+    /// leaves.
+    ///
+    /// A `Rope` with a single leaf has depth 0.  The depth grows
+    /// logarithmically with the length of the text, so this is mostly
+    /// useful for spotting trees that are much deeper than they need to
+    /// be.  `compact()` and `rebalance()` make the tree as shallow as
+    /// possible.
+    ///
+    /// Runs in O(log N) time.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.root.depth()
+    }
+
+    /// Statistics about the shape of the `Rope`'s tree, such as how full
+    /// its leaves are.
+    ///
+    /// Runs in O(N) time.
+    pub fn tree_stats(&self) -> TreeStats {
+        let mut internal_node_count = 0;
+        let mut leaf_count = 0;
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            match **node {
+                Node::Leaf(_) => leaf_count += 1,
+                Node::Internal(ref children) => {
+                    internal_node_count += 1;
+                    stack.extend(children.nodes());
+                }
+            }
+        }
+
+        TreeStats {
+            depth: self.depth(),
+            internal_node_count: internal_node_count,
+            leaf_count: leaf_count,
+            average_leaf_fill: self.len_bytes() as f64 / (leaf_count * MAX_BYTES) as f64,
+        }
+    }
+
+    /// Returns whether the `Rope`'s tree is balanced: all of its leaves are
+    /// at the same depth, and all of its internal nodes have the minimum
+    /// number of children.
+    ///
+    /// Edits keep the tree balanced, so this should always be true.  If it
+    /// isn't, `rebalance()` repairs it.
+    ///
+    /// Runs in O(N) time.
+    pub fn is_balanced(&self) -> bool {
+        self.root.is_balanced(true)
+    }
+
+    //-----------------------------------------------------------------------
+    // Memory management methods
+
+    /// Total size of the `Rope`'s text buffer space, in bytes.
+    ///
+    /// This includes unoccupied text buffer space.  You can calculate
+    /// the unoccupied space with `capacity() - len_bytes()`.  In general,
+    /// there will always be some unoccupied buffer space.
+    ///
+    /// Runs in O(N) time.
+    pub fn capacity(&self) -> usize {
+        let mut byte_count = 0;
+        for chunk in self.chunks() {
+            byte_count += chunk.len().max(MAX_BYTES);
+        }
+        byte_count
+    }
+
+    /// The heap memory used by the `Rope`, split into its text and the
+    /// overhead of storing it.
+    ///
+    /// Nodes that are shared with other ropes (see `Rope::clone()`) are
+    /// counted in full, so the usage of several ropes can add up to more
+    /// than they actually use between them.  See `mem_usage_amortized()`
+    /// for an alternative.
+    ///
+    /// Runs in O(N) time.
+    pub fn mem_usage(&self) -> MemUsage {
+        self.mem_usage_internal(false)
+    }
+
+    /// Like `mem_usage()`, but with the memory of each shared node split
+    /// evenly between everything that shares it.
+    ///
+    /// This gives a fair share of the memory to each of a set of ropes that
+    /// share nodes, such as the revisions in an undo history, so that their
+    /// usages add up to the memory they actually use between them.  The
+    /// usage of a rope changes as its clones are created and dropped.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str(&"Hello world!\n".repeat(1000));
+    /// let usage = rope.mem_usage_amortized();
+    /// assert_eq!(usage, rope.mem_usage());
+    ///
+    /// let clone = rope.clone();
+    /// assert_eq!(rope.mem_usage_amortized().text_bytes, usage.text_bytes / 2);
+    /// ```
+    pub fn mem_usage_amortized(&self) -> MemUsage {
+        self.mem_usage_internal(true)
+    }
+
+    /// Shrinks the `Rope`'s capacity to the minimum possible.
+    ///
+    /// This will rarely result in `capacity() == len_bytes()`.  `Rope`
+    /// stores text in a sequence of fixed-capacity chunks, so an exact fit
+    /// only happens for texts that are both a precise multiple of that
+    /// capacity _and_ have code point boundaries that line up exactly with
+    /// the capacity boundaries.
+    ///
+    /// After calling this, the difference between `capacity()` and
+    /// `len_bytes()` is typically under 1KB per megabyte of text in the
+    /// `Rope`.
+    ///
+    /// This also makes the `Rope` faster to read: every leaf is filled as
+    /// much as possible and the tree is as shallow as possible, which speeds
+    /// up iteration and queries.  So it's worth calling on documents that
+    /// are done being edited, e.g. after loading and normalizing them.  The
+    /// `Rope` can still be edited afterwards as usual.
+    ///
+    /// **NOTE:** calling this on a `Rope` clone causes it to stop sharing
+    /// all data with its other clones.  In such cases you will very likely
+    /// be _increasing_ total memory usage despite shrinking the `Rope`'s
+    /// capacity.
+    ///
+    /// Runs in O(N) time, and uses O(log N) additional space during
+    /// shrinking.
+    pub fn shrink_to_fit(&mut self) {
+        let mut node_stack = Vec::new();
+        let mut builder = RopeBuilder::new();
+
+        node_stack.push(self.root.clone());
+        *self = Rope::new();
+
         loop {
             if node_stack.is_empty() {
                 break;
@@ -333,6 +1123,130 @@ impl Rope {
         *self = builder.finish();
     }
 
+    /// Merges runs of adjacent under-filled leaves, and rebuilds the tree
+    /// above them as shallow as it can be.
+    ///
+    /// This is a lighter alternative to `shrink_to_fit()` for documents that
+    /// are still being edited, e.g. for an editor to run when it's idle.
+    /// Only the leaves that get merged are copied: the rest are kept as they
+    /// are, so they stay shared with the `Rope`'s clones (such as undo
+    /// snapshots).  The leaves end up less full than with `shrink_to_fit()`,
+    /// but no two adjacent leaves could be merged any further.
+    ///
+    /// Runs in O(N) time.
+    pub fn compact(&mut self) {
+        let leaves = self.leaves();
+        let mut builder = RopeBuilder::new();
+        let mut i = 0;
+        while i < leaves.len() {
+            // Find the longest run of leaves from `i` that fits in one leaf.
+            let mut run_info = leaves[i].0;
+            let mut end = i + 1;
+            while end < leaves.len() && (run_info.bytes + leaves[end].0.bytes) as usize <= MAX_BYTES
+            {
+                run_info += leaves[end].0;
+                end += 1;
+            }
+
+            if end - i == 1 {
+                builder.push_leaf(leaves[i].clone());
+            } else {
+                let mut text = NodeText::new();
+                for (_, leaf) in &leaves[i..end] {
+                    text.push_str(leaf.leaf_text());
+                }
+                builder.push_leaf((run_info, Arc::new(Node::Leaf(text))));
+            }
+            i = end;
+        }
+
+        *self = builder.finish();
+    }
+
+    /// Rebuilds the tree above the `Rope`'s leaves, as shallow as it can be.
+    ///
+    /// Ropey keeps its tree balanced as it's edited, so this is rarely
+    /// needed.  But unusual edit patterns can leave it deeper than
+    /// necessary, or with internal nodes that have fewer children than
+    /// they should (see `is_balanced()`), which this repairs.  The leaves
+    /// themselves are kept as they are, and stay shared with the `Rope`'s
+    /// clones.  To also merge under-filled leaves, use `compact()`.
+    ///
+    /// Runs in O(N) time.
+    pub fn rebalance(&mut self) {
+        let mut builder = RopeBuilder::new();
+        for leaf in self.leaves() {
+            // Only the root is allowed to be an empty leaf.
+            if leaf.0.bytes > 0 {
+                builder.push_leaf(leaf);
+            }
+        }
+        *self = builder.finish();
+    }
+
+    /// Returns whether `self` and `other` share all of their data, i.e.
+    /// whether one is an unedited clone of the other.
+    ///
+    /// Ropes with the same text don't necessarily share their data, so
+    /// this is only meant as a cheap check, e.g. for deduplicating
+    /// snapshots.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Rope) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
+
+    /// Total number of bytes of `self`'s text that are stored in data
+    /// shared with `other`.
+    ///
+    /// Clones of a `Rope` share their data, and editing one only copies the
+    /// parts of it that the edit touches.  So after editing a clone, the
+    /// rest of its text, `len_bytes() - shared_bytes_with(&other)`, is
+    /// roughly how much memory keeping it around costs on top of `other`.
+    ///
+    /// Runs in O(N log N) time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let text = Rope::from_str(&"Hello world!\n".repeat(1000));
+    /// let mut edited = text.clone();
+    /// assert_eq!(edited.shared_bytes_with(&text), text.len_bytes());
+    ///
+    /// edited.insert(6, "big ");
+    /// let shared = edited.shared_bytes_with(&text);
+    /// assert!(shared > 0 && shared < text.len_bytes());
+    /// ```
+    pub fn shared_bytes_with(&self, other: &Rope) -> usize {
+        // The addresses of all of `other`'s nodes, sorted for searching.
+        let mut other_nodes = Vec::new();
+        let mut stack = vec![&other.root];
+        while let Some(node) = stack.pop() {
+            other_nodes.push(&**node as *const Node);
+            if let Node::Internal(ref children) = **node {
+                stack.extend(children.nodes());
+            }
+        }
+        other_nodes.sort_unstable();
+
+        let mut shared = 0;
+        let mut stack = vec![(self.len_bytes(), &self.root)];
+        while let Some((byte_count, node)) = stack.pop() {
+            if other_nodes.binary_search(&(&**node as *const Node)).is_ok() {
+                shared += byte_count;
+            } else if let Node::Internal(ref children) = **node {
+                stack.extend(
+                    children
+                        .iter()
+                        .map(|(info, child)| (info.bytes as usize, child)),
+                );
+            }
+        }
+        shared
+    }
+
     //-----------------------------------------------------------------------
     // Edit methods
 
@@ -344,6 +1258,9 @@ impl Rope {
     /// # Panics
     ///
     /// Panics if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
+    ///
+    /// With the `u32_counts` feature, also panics if the result would be 4 GB
+    /// or larger.
     #[inline]
     pub fn insert(&mut self, char_idx: usize, text: &str) {
         // Bounds check
@@ -353,6 +1270,7 @@ impl Rope {
             char_idx,
             self.len_chars()
         );
+        assert_count_fits(self.len_bytes(), text.len());
 
         // We have three cases here:
         // 1. The insertion text is very large, in which case building a new
@@ -407,6 +1325,9 @@ impl Rope {
     /// # Panics
     ///
     /// Panics if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
+    ///
+    /// With the `u32_counts` feature, also panics if the result would be 4 GB
+    /// or larger.
     #[inline]
     pub fn insert_char(&mut self, char_idx: usize, ch: char) {
         // Bounds check
@@ -416,6 +1337,7 @@ impl Rope {
             char_idx,
             self.len_chars()
         );
+        assert_count_fits(self.len_bytes(), ch.len_utf8());
 
         let mut buf = [0u8; 4];
         self.insert_internal(char_idx, ch.encode_utf8(&mut buf));
@@ -434,6 +1356,7 @@ impl Rope {
         let mut ins_text = ins_text;
         let mut left_seam = false;
         let root_info = self.root.text_info();
+        let at_end = char_idx == root_info.chars as usize;
 
         let (l_info, residual) = Arc::make_mut(&mut self.root).edit_chunk_at_char(
             char_idx,
@@ -487,6 +1410,35 @@ impl Rope {
                 }
                 // We're splitting the node
                 else {
+                    // When appending to the end of the rope, fill up the
+                    // leaf and start a new one with the rest, like
+                    // `RopeBuilder` does.  Splitting in the middle would
+                    // leave every leaf half empty as the rope grows.
+                    if at_end {
+                        // The leaf can already be oversized, if it couldn't
+                        // be validly split before.
+                        let mut split_idx = crlf::find_good_split(
+                            MAX_BYTES.saturating_sub(leaf_text.len()),
+                            ins_text.as_bytes(),
+                            true,
+                        );
+                        if split_idx == ins_text.len() {
+                            split_idx = 0;
+                        }
+                        leaf_text.push_str(&ins_text[..split_idx]);
+                        let mut r_text = NodeText::from_str(&ins_text[split_idx..]);
+                        // Don't split a CRLF pair at the seam.
+                        if !crlf::seam_is_break(leaf_text.as_bytes(), r_text.as_bytes()) {
+                            let len = leaf_text.len();
+                            leaf_text.truncate(len - 1);
+                            r_text.insert_str(0, "\r");
+                        }
+                        return (
+                            TextInfo::from_str(leaf_text),
+                            Some((TextInfo::from_str(&r_text), Arc::new(Node::Leaf(r_text)))),
+                        );
+                    }
+
                     let r_text = leaf_text.insert_str_split(byte_idx, ins_text);
                     let l_text_info = TextInfo::from_str(&leaf_text);
                     if r_text.len() > 0 {
@@ -506,7 +1458,7 @@ impl Rope {
         // Handle root splitting, if any.
         if let Some((r_info, r_node)) = residual {
             let mut l_node = Arc::new(Node::new());
-            std::mem::swap(&mut l_node, &mut self.root);
+            core::mem::swap(&mut l_node, &mut self.root);
 
             let mut children = NodeChildren::new();
             children.push((l_info, l_node));
@@ -561,7 +1513,7 @@ impl Rope {
             // Handle root splitting, if any.
             if let Some((r_info, r_node)) = residual {
                 let mut l_node = Arc::new(Node::new());
-                std::mem::swap(&mut l_node, &mut self.root);
+                core::mem::swap(&mut l_node, &mut self.root);
 
                 let mut children = NodeChildren::new();
                 children.push((l_info, l_node));
@@ -613,7 +1565,7 @@ impl Rope {
         // A special case that the rest of the logic doesn't handle
         // correctly.
         if start == 0 && end == self.len_chars() {
-            self.root = Arc::new(Node::new());
+            *self = Rope::new();
             return;
         }
 
@@ -626,7 +1578,9 @@ impl Rope {
 
             if crlf_seam {
                 let seam_idx = root.char_to_byte_and_line(start).0;
-                root.fix_crlf_seam(seam_idx as Count, false);
+                if root.splits_crlf(seam_idx) {
+                    root.fix_crlf_seam(seam_idx as Count, false);
+                }
             }
 
             if needs_fix {
@@ -657,7 +1611,7 @@ impl Rope {
         if char_idx == 0 {
             // Special case 1
             let mut new_rope = Rope::new();
-            std::mem::swap(self, &mut new_rope);
+            core::mem::swap(self, &mut new_rope);
             new_rope
         } else if char_idx == self.len_chars() {
             // Special case 2
@@ -681,13 +1635,32 @@ impl Rope {
     /// Appends a `Rope` to the end of this one, consuming the other `Rope`.
     ///
     /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// With the `u32_counts` feature, panics if the result would be 4 GB or
+    /// larger.
     pub fn append(&mut self, other: Self) {
+        assert_count_fits(self.len_bytes(), other.len_bytes());
         if self.len_chars() == 0 {
             // Special case
             let mut other = other;
-            std::mem::swap(self, &mut other);
+            core::mem::swap(self, &mut other);
+        } else if other.len_chars() > 0 && other.len_bytes() <= MAX_BYTES - 4 {
+            // Small ropes are copied into the last leaf rather than joined
+            // as leaves of their own, so that building a rope from many
+            // small appends doesn't leave it full of tiny leaves.
+            if let Node::Leaf(ref text) = *other.root {
+                let end = self.len_chars();
+                self.insert_internal(end, text);
+            } else {
+                for chunk in other.chunks() {
+                    let end = self.len_chars();
+                    self.insert_internal(end, chunk);
+                }
+            }
         } else if other.len_chars() > 0 {
-            let seam_byte_i = if other.char(0) == '\n' {
+            let seam_byte_i = if self.byte(self.len_bytes() - 1) == 0x0D && other.byte(0) == 0x0A {
                 Some(self.root.text_info().bytes)
             } else {
                 None
@@ -724,21 +1697,113 @@ impl Rope {
         }
     }
 
-    //-----------------------------------------------------------------------
-    // Index conversion methods
-
-    /// Returns the char index of the given byte.
-    ///
-    /// Notes:
+    /// Creates a new `Rope` with the text of this one repeated `n` times.
     ///
-    /// - If the byte is in the middle of a multi-byte char, returns the
-    ///   index of the char that the byte belongs to.
-    /// - `byte_idx` can be one-past-the-end, which will return
-    ///   one-past-the-end char index.
+    /// The copies are made by appending the rope to itself, doubling it each
+    /// time, so they share the same nodes in memory until they're edited.
+    /// This makes it cheap to create large repetitive texts, such as padding
+    /// or test fixtures, even when `n` is huge.
     ///
-    /// Runs in O(log N) time.
+    /// # Example
     ///
-    /// # Panics
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("ab\n");
+    /// let repeated = rope.repeat(1000);
+    /// assert_eq!(repeated.len_chars(), 3000);
+    /// assert_eq!(repeated.len_lines(), 1001);
+    /// assert_eq!(repeated.line(500), "ab\n");
+    /// ```
+    ///
+    /// Runs in O(log n * log(N * n)) time, and uses O(log n * log(N * n))
+    /// extra memory.
+    pub fn repeat(&self, n: usize) -> Rope {
+        let mut rope = Rope::new();
+        let mut power = self.clone();
+        let mut n = n;
+        while n > 0 {
+            if n & 1 == 1 {
+                rope.append(power.clone());
+            }
+            n >>= 1;
+            if n > 0 {
+                let copy = power.clone();
+                power.append(copy);
+            }
+        }
+        rope
+    }
+
+    /// Splits the `Rope` into `n` ropes of roughly equal char length.
+    ///
+    /// The ropes share their nodes with this one, rather than copying the
+    /// text, so this is a cheap way to divide up a large document for
+    /// parallel processing.  When the `Rope` has fewer than `n` chars, some
+    /// of the returned ropes are empty.
+    ///
+    /// To split only at the starts of lines, see `split_lines_into()`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("Hello world!");
+    /// let parts = rope.split_into(3);
+    ///
+    /// assert_eq!(parts, ["Hell", "o wo", "rld!"]);
+    /// ```
+    ///
+    /// Runs in O(n log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn split_into(&self, n: usize) -> Vec<Rope> {
+        self.split_into_internal(n, |char_idx| char_idx)
+    }
+
+    /// Splits the `Rope` at line starts into `n` ropes of roughly equal
+    /// char length.
+    ///
+    /// Like `split_into()`, except that each split point is moved back to
+    /// the start of its line, so that no line is split between two ropes.
+    /// Some of the returned ropes may be empty, e.g. when a single line
+    /// spans several split points.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("one\ntwo\nthree\nfour\n");
+    /// let parts = rope.split_lines_into(2);
+    ///
+    /// assert_eq!(parts, ["one\ntwo\n", "three\nfour\n"]);
+    /// ```
+    ///
+    /// Runs in O(n log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn split_lines_into(&self, n: usize) -> Vec<Rope> {
+        self.split_into_internal(n, |char_idx| self.line_to_char(self.char_to_line(char_idx)))
+    }
+
+    //-----------------------------------------------------------------------
+    // Index conversion methods
+
+    /// Returns the char index of the given byte.
+    ///
+    /// Notes:
+    ///
+    /// - If the byte is in the middle of a multi-byte char, returns the
+    ///   index of the char that the byte belongs to.
+    /// - `byte_idx` can be one-past-the-end, which will return
+    ///   one-past-the-end char index.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
     ///
     /// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
     #[inline]
@@ -926,161 +1991,472 @@ impl Rope {
 
     /// Returns the char at `char_idx`.
     ///
-    /// Runs in O(log N) time.
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `char_idx` is out of bounds (i.e. `char_idx >= len_chars()`).
+    #[inline]
+    pub fn char(&self, char_idx: usize) -> char {
+        // Bounds check
+        assert!(
+            char_idx < self.len_chars(),
+            "Attempt to index past end of Rope: char index {}, Rope char length {}",
+            char_idx,
+            self.len_chars()
+        );
+
+        let (chunk, _, chunk_char_idx, _) = self.chunk_at_char(char_idx);
+        let byte_idx = char_to_byte_idx(chunk, char_idx - chunk_char_idx);
+        chunk[byte_idx..].chars().nth(0).unwrap()
+    }
+
+    /// Returns the line at `line_idx`.
+    ///
+    /// Note: lines are zero-indexed.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `line_idx` is out of bounds (i.e. `line_idx >= len_lines()`).
+    #[inline]
+    pub fn line(&self, line_idx: usize) -> RopeSlice {
+        use slice::RSEnum;
+        use str_utils::count_chars;
+
+        let len_lines = self.len_lines();
+
+        // Bounds check
+        assert!(
+            line_idx < len_lines,
+            "Attempt to index past end of Rope: line index {}, Rope line length {}",
+            line_idx,
+            len_lines
+        );
+
+        let (chunk_1, _, c1, l1) = self.chunk_at_line_break(line_idx);
+        let (chunk_2, _, c2, l2) = self.chunk_at_line_break(line_idx + 1);
+        if c1 == c2 {
+            let text1 = &chunk_1[line_to_byte_idx(chunk_1, line_idx - l1)..];
+            let text2 = &text1[..line_to_byte_idx(text1, 1)];
+            RopeSlice(RSEnum::Light {
+                text: text2,
+                char_count: count_chars(text2) as Count,
+                line_break_count: if line_idx == (len_lines - 1) { 0 } else { 1 },
+            })
+        } else {
+            let start = c1 + line_to_char_idx(chunk_1, line_idx - l1);
+            let end = c2 + line_to_char_idx(chunk_2, line_idx + 1 - l2);
+            self.slice(start..end)
+        }
+    }
+
+    /// Returns the chunk containing the given byte index.
+    ///
+    /// Also returns the byte and char indices of the beginning of the chunk
+    /// and the index of the line that the chunk starts on.
+    ///
+    /// Note: for convenience, a one-past-the-end `byte_idx` returns the last
+    /// chunk of the `RopeSlice`.
+    ///
+    /// The return value is organized as
+    /// `(chunk, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
+    #[inline]
+    pub fn chunk_at_byte(&self, byte_idx: usize) -> (&str, usize, usize, usize) {
+        // Bounds check
+        assert!(
+            byte_idx <= self.len_bytes(),
+            "Attempt to index past end of Rope: byte index {}, Rope byte length {}",
+            byte_idx,
+            self.len_bytes()
+        );
+
+        self.root.get_chunk_at_byte(byte_idx)
+    }
+
+    /// Returns the chunk containing the given char index.
+    ///
+    /// Also returns the byte and char indices of the beginning of the chunk
+    /// and the index of the line that the chunk starts on.
+    ///
+    /// Note: for convenience, a one-past-the-end `char_idx` returns the last
+    /// chunk of the `RopeSlice`.
+    ///
+    /// The return value is organized as
+    /// `(chunk, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
+    #[inline]
+    pub fn chunk_at_char(&self, char_idx: usize) -> (&str, usize, usize, usize) {
+        // Bounds check
+        assert!(
+            char_idx <= self.len_chars(),
+            "Attempt to index past end of Rope: char index {}, Rope char length {}",
+            char_idx,
+            self.len_chars()
+        );
+
+        self.root.get_chunk_at_char(char_idx)
+    }
+
+    /// Returns the chunk containing the given line break.
+    ///
+    /// Also returns the byte and char indices of the beginning of the chunk
+    /// and the index of the line that the chunk starts on.
+    ///
+    /// Note: for convenience, both the beginning and end of the rope are
+    /// considered line breaks for the purposes of indexing.  For example, in
+    /// the string `"Hello \n world!"` 0 would give the first chunk, 1 would
+    /// give the chunk containing the newline character, and 2 would give the
+    /// last chunk.
+    ///
+    /// The return value is organized as
+    /// `(chunk, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `line_break_idx` is out of bounds (i.e. `line_break_idx > len_lines()`).
+    #[inline]
+    pub fn chunk_at_line_break(&self, line_break_idx: usize) -> (&str, usize, usize, usize) {
+        // Bounds check
+        assert!(
+            line_break_idx <= self.len_lines(),
+            "Attempt to index past end of Rope: line break index {}, max index {}",
+            line_break_idx,
+            self.len_lines()
+        );
+
+        self.root.get_chunk_at_line_break(line_break_idx)
+    }
+
+    /// Returns the id of the chunk containing the given byte index, for
+    /// caching results computed from the chunk.  See `ChunkId` for details.
+    ///
+    /// Also returns the byte and char indices of the beginning of the chunk
+    /// and the index of the line that the chunk starts on, like
+    /// `chunk_at_byte()`.
+    ///
+    /// The return value is organized as
+    /// `(chunk_id, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
+    #[inline]
+    pub fn chunk_id_at_byte(&self, byte_idx: usize) -> (ChunkId, usize, usize, usize) {
+        // Bounds check
+        assert!(
+            byte_idx <= self.len_bytes(),
+            "Attempt to index past end of Rope: byte index {}, Rope byte length {}",
+            byte_idx,
+            self.len_bytes()
+        );
+
+        let (leaf, b, c, l) = Node::get_leaf_at_byte(&self.root, byte_idx);
+        (ChunkId(Arc::clone(leaf)), b, c, l)
+    }
+
+    /// Returns the id of the chunk containing the given char index, for
+    /// caching results computed from the chunk.  See `ChunkId` for details.
+    ///
+    /// Also returns the byte and char indices of the beginning of the chunk
+    /// and the index of the line that the chunk starts on, like
+    /// `chunk_at_char()`.
+    ///
+    /// The return value is organized as
+    /// `(chunk_id, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
+    #[inline]
+    pub fn chunk_id_at_char(&self, char_idx: usize) -> (ChunkId, usize, usize, usize) {
+        // Bounds check
+        assert!(
+            char_idx <= self.len_chars(),
+            "Attempt to index past end of Rope: char index {}, Rope char length {}",
+            char_idx,
+            self.len_chars()
+        );
+
+        self.chunk_id_at_byte(self.char_to_byte(char_idx))
+    }
+
+    //-----------------------------------------------------------------------
+    // Slicing
+
+    /// Gets an immutable slice of the `Rope`.
+    ///
+    /// Uses range syntax, e.g. `2..7`, `2..`, etc.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("Hello world!");
+    /// let slice = rope.slice(..5);
+    ///
+    /// assert_eq!("Hello", slice);
+    /// ```
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, or if the
+    /// end is out of bounds (i.e. `end > len_chars()`).
+    #[inline]
+    pub fn slice<R>(&self, char_range: R) -> RopeSlice
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = start_bound_to_num(char_range.start_bound()).unwrap_or(0);
+        let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
+
+        // Bounds check
+        assert!(start <= end);
+        assert!(
+            end <= self.len_chars(),
+            "Attempt to slice past end of Rope: slice end {}, Rope length {}",
+            end,
+            self.len_chars()
+        );
+
+        RopeSlice::new_with_range(&self.root, start, end)
+    }
+
+    /// Gets the lines containing `char_range`, for rendering diagnostic
+    /// excerpts.
+    ///
+    /// Each returned line also holds the char columns within it that are in
+    /// `char_range`.  Up to `context_lines` lines before and after are
+    /// included as well, without columns.  See `ExcerptLine` for details.
+    ///
+    /// Uses range syntax, e.g. `2..7`, `2..`, etc.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("one\ntwo\nthree\nfour\n");
+    /// let lines = rope.excerpt(5..10, 1);
+    ///
+    /// assert_eq!(lines.len(), 4);
+    /// assert_eq!(lines[0].text, "one");
+    /// assert_eq!(lines[0].columns, None);
+    /// assert_eq!(lines[1].text, "two");
+    /// assert_eq!(lines[1].columns, Some(1..3));
+    /// assert_eq!(lines[2].text, "three");
+    /// assert_eq!(lines[2].columns, Some(0..2));
+    /// assert_eq!(lines[3].text, "four");
+    /// assert_eq!(lines[3].columns, None);
+    /// ```
+    ///
+    /// Runs in O(M log N) time, where M is the number of lines returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, or if the
+    /// end is out of bounds (i.e. `end > len_chars()`).
+    pub fn excerpt<R>(&self, char_range: R, context_lines: usize) -> Vec<ExcerptLine>
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = start_bound_to_num(char_range.start_bound()).unwrap_or(0);
+        let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
+
+        // Bounds check
+        assert!(start <= end);
+        assert!(
+            end <= self.len_chars(),
+            "Attempt to excerpt past end of Rope: excerpt end {}, Rope length {}",
+            end,
+            self.len_chars()
+        );
+
+        excerpt::excerpt(&self.slice(..), start, end, context_lines)
+    }
+
+    //-----------------------------------------------------------------------
+    // Search methods
+
+    /// Returns whether the `Rope` starts with `prefix`.
+    ///
+    /// Only the chunks overlapping the prefix are examined, and no
+    /// allocation is done.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("#!/bin/sh\necho hi\n");
+    ///
+    /// assert!(rope.starts_with("#!"));
+    /// assert!(!rope.starts_with("echo"));
+    /// ```
+    ///
+    /// Runs in O(M + log N) time, where M is the length of `prefix`.
+    #[inline]
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.slice(..).starts_with(prefix)
+    }
+
+    /// Returns whether the `Rope` ends with `suffix`.
+    ///
+    /// Only the chunks overlapping the suffix are examined, and no
+    /// allocation is done.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("#!/bin/sh\necho hi\n");
+    ///
+    /// assert!(rope.ends_with("\n"));
+    /// assert!(!rope.ends_with("echo"));
+    /// ```
+    ///
+    /// Runs in O(M + log N) time, where M is the length of `suffix`.
+    #[inline]
+    pub fn ends_with(&self, suffix: &str) -> bool {
+        self.slice(..).ends_with(suffix)
+    }
+
+    /// Returns the char index of the first match of `pattern` in the
+    /// `Rope`, or `None` if there is no match.
+    ///
+    /// `pattern` can be a `&str`, a `char`, a set of chars, or a
+    /// `FnMut(char) -> bool` predicate.  See [`Pattern`](search/trait.Pattern.html)
+    /// for details.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("Hello world!");
+    ///
+    /// assert_eq!(rope.find("world"), Some(6));
+    /// assert_eq!(rope.find("World"), None);
+    /// assert_eq!(rope.find_ignore_case("World"), Some(6));
+    ///
+    /// assert_eq!(rope.find('o'), Some(4));
+    /// assert_eq!(rope.find(['!', '?']), Some(11));
+    /// assert_eq!(rope.find(char::is_whitespace), Some(5));
+    /// ```
+    ///
+    /// Runs in O(N) time.
     ///
     /// # Panics
     ///
-    /// Panics if `char_idx` is out of bounds (i.e. `char_idx >= len_chars()`).
+    /// Panics if `pattern` is an empty `&str`.
     #[inline]
-    pub fn char(&self, char_idx: usize) -> char {
-        // Bounds check
-        assert!(
-            char_idx < self.len_chars(),
-            "Attempt to index past end of Rope: char index {}, Rope char length {}",
-            char_idx,
-            self.len_chars()
-        );
-
-        let (chunk, _, chunk_char_idx, _) = self.chunk_at_char(char_idx);
-        let byte_idx = char_to_byte_idx(chunk, char_idx - chunk_char_idx);
-        chunk[byte_idx..].chars().nth(0).unwrap()
+    pub fn find<'a, P: Pattern<'a>>(&'a self, pattern: P) -> Option<usize> {
+        self.slice(..).find(pattern)
     }
 
-    /// Returns the line at `line_idx`.
+    /// Like `find()`, but compares text case-insensitively.
     ///
-    /// Note: lines are zero-indexed.
+    /// See the [`search`](search/index.html) module documentation for
+    /// details on how case is folded.
     ///
-    /// Runs in O(log N) time.
+    /// Runs in O(N) time.
     ///
     /// # Panics
     ///
-    /// Panics if `line_idx` is out of bounds (i.e. `line_idx >= len_lines()`).
+    /// Panics if `pattern` is empty.
     #[inline]
-    pub fn line(&self, line_idx: usize) -> RopeSlice {
-        use slice::RSEnum;
-        use str_utils::count_chars;
-
-        let len_lines = self.len_lines();
-
-        // Bounds check
-        assert!(
-            line_idx < len_lines,
-            "Attempt to index past end of Rope: line index {}, Rope line length {}",
-            line_idx,
-            len_lines
-        );
-
-        let (chunk_1, _, c1, l1) = self.chunk_at_line_break(line_idx);
-        let (chunk_2, _, c2, l2) = self.chunk_at_line_break(line_idx + 1);
-        if c1 == c2 {
-            let text1 = &chunk_1[line_to_byte_idx(chunk_1, line_idx - l1)..];
-            let text2 = &text1[..line_to_byte_idx(text1, 1)];
-            RopeSlice(RSEnum::Light {
-                text: text2,
-                char_count: count_chars(text2) as Count,
-                line_break_count: if line_idx == (len_lines - 1) { 0 } else { 1 },
-            })
-        } else {
-            let start = c1 + line_to_char_idx(chunk_1, line_idx - l1);
-            let end = c2 + line_to_char_idx(chunk_2, line_idx + 1 - l2);
-            self.slice(start..end)
-        }
+    pub fn find_ignore_case(&self, pattern: &str) -> Option<usize> {
+        self.slice(..).find_ignore_case(pattern)
     }
 
-    /// Returns the chunk containing the given byte index.
+    /// Creates an iterator over the non-overlapping matches of `pattern` in
+    /// the `Rope`, yielding the char index range of each match.
     ///
-    /// Also returns the byte and char indices of the beginning of the chunk
-    /// and the index of the line that the chunk starts on.
+    /// Accepts the same patterns as `find()`.
     ///
-    /// Note: for convenience, a one-past-the-end `byte_idx` returns the last
-    /// chunk of the `RopeSlice`.
+    /// # Example
     ///
-    /// The return value is organized as
-    /// `(chunk, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("one fish, two fish");
+    /// let matches: Vec<_> = rope.find_iter("fish").collect();
     ///
-    /// Runs in O(log N) time.
+    /// assert_eq!(matches, vec![4..8, 14..18]);
+    /// ```
+    ///
+    /// Creating the iterator runs in O(M + log N) time, where M is the length of
+    /// `pattern`.  Iterating to the end runs in O(N + M) time.
     ///
     /// # Panics
     ///
-    /// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > len_bytes()`).
+    /// Panics if `pattern` is an empty `&str`.
     #[inline]
-    pub fn chunk_at_byte(&self, byte_idx: usize) -> (&str, usize, usize, usize) {
-        // Bounds check
-        assert!(
-            byte_idx <= self.len_bytes(),
-            "Attempt to index past end of Rope: byte index {}, Rope byte length {}",
-            byte_idx,
-            self.len_bytes()
-        );
-
-        self.root.get_chunk_at_byte(byte_idx)
+    pub fn find_iter<'a, P: Pattern<'a>>(&'a self, pattern: P) -> Matches<'a> {
+        pattern.into_matches(&self.slice(..))
     }
 
-    /// Returns the chunk containing the given char index.
-    ///
-    /// Also returns the byte and char indices of the beginning of the chunk
-    /// and the index of the line that the chunk starts on.
-    ///
-    /// Note: for convenience, a one-past-the-end `char_idx` returns the last
-    /// chunk of the `RopeSlice`.
+    /// Like `find_iter()`, but compares text case-insensitively.
     ///
-    /// The return value is organized as
-    /// `(chunk, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    /// See the [`search`](search/index.html) module documentation for
+    /// details on how case is folded.
     ///
-    /// Runs in O(log N) time.
+    /// Creating the iterator runs in O(M + log N) time, where M is the length of
+    /// `pattern`.  Iterating to the end runs in O(N + M) time.
     ///
     /// # Panics
     ///
-    /// Panics if `char_idx` is out of bounds (i.e. `char_idx > len_chars()`).
+    /// Panics if `pattern` is empty.
     #[inline]
-    pub fn chunk_at_char(&self, char_idx: usize) -> (&str, usize, usize, usize) {
-        // Bounds check
-        assert!(
-            char_idx <= self.len_chars(),
-            "Attempt to index past end of Rope: char index {}, Rope char length {}",
-            char_idx,
-            self.len_chars()
-        );
-
-        self.root.get_chunk_at_char(char_idx)
+    pub fn find_iter_ignore_case(&self, pattern: &str) -> Matches<'_> {
+        Matches::new(&self.slice(..), pattern, true)
     }
 
-    /// Returns the chunk containing the given line break.
+    /// Creates an iterator over the parts of the `Rope` separated by the
+    /// matches of `pattern`.
     ///
-    /// Also returns the byte and char indices of the beginning of the chunk
-    /// and the index of the line that the chunk starts on.
+    /// Behaves like `str::split()`, and accepts the same patterns as
+    /// `find()`.
     ///
-    /// Note: for convenience, both the beginning and end of the rope are
-    /// considered line breaks for the purposes of indexing.  For example, in
-    /// the string `"Hello \n world!"` 0 would give the first chunk, 1 would
-    /// give the chunk containing the newline character, and 2 would give the
-    /// last chunk.
+    /// # Example
     ///
-    /// The return value is organized as
-    /// `(chunk, chunk_byte_idx, chunk_char_idx, chunk_line_idx)`.
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("a, b;c");
+    /// let parts: Vec<_> = rope.split([',', ';']).collect();
     ///
-    /// Runs in O(log N) time.
+    /// assert_eq!(parts, vec!["a", " b", "c"]);
+    /// ```
+    ///
+    /// Creating the iterator runs in O(M + log N) time, where M is the length of
+    /// `pattern`.  Iterating to the end runs in O(N + M) time.
     ///
     /// # Panics
     ///
-    /// Panics if `line_break_idx` is out of bounds (i.e. `line_break_idx > len_lines()`).
+    /// Panics if `pattern` is an empty `&str`.
     #[inline]
-    pub fn chunk_at_line_break(&self, line_break_idx: usize) -> (&str, usize, usize, usize) {
-        // Bounds check
-        assert!(
-            line_break_idx <= self.len_lines(),
-            "Attempt to index past end of Rope: line break index {}, max index {}",
-            line_break_idx,
-            self.len_lines()
-        );
-
-        self.root.get_chunk_at_line_break(line_break_idx)
+    pub fn split<'a, P: Pattern<'a>>(&'a self, pattern: P) -> Split<'a> {
+        Split::new(&self.slice(..), pattern)
     }
 
-    //-----------------------------------------------------------------------
-    // Slicing
-
-    /// Gets an immutable slice of the `Rope`.
+    /// Returns the number of occurrences of `c` in the given char index
+    /// range of the `Rope`.
     ///
     /// Uses range syntax, e.g. `2..7`, `2..`, etc.
     ///
@@ -1088,12 +2464,38 @@ impl Rope {
     ///
     /// ```
     /// # use ropey::Rope;
-    /// let rope = Rope::from_str("Hello world!");
-    /// let slice = rope.slice(..5);
+    /// let rope = Rope::from_str("a\tb\tc\n\td\n");
     ///
-    /// assert_eq!("Hello", slice);
+    /// assert_eq!(rope.count_char(.., '\t'), 3);
+    /// assert_eq!(rope.count_char(2..6, '\t'), 1);
+    /// assert_eq!(rope.count_line_breaks(..), 2);
     /// ```
     ///
+    /// Runs in O(M + log N) time, where M is the length of the range.  The
+    /// chunks are scanned with SIMD-accelerated byte searching, so this is
+    /// far faster than counting via `chars()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, or if the
+    /// end is out of bounds (i.e. `end > len_chars()`).
+    #[inline]
+    pub fn count_char<R>(&self, char_range: R, c: char) -> usize
+    where
+        R: RangeBounds<usize>,
+    {
+        self.slice(..).count_char(char_range, c)
+    }
+
+    /// Returns the number of line breaks in the given char index range of
+    /// the `Rope`.
+    ///
+    /// Line breaks are counted the same way as for `len_lines()`, with CRLF
+    /// pairs counting as a single line break.  Unlike `count_char()`, this
+    /// doesn't need to scan the text.
+    ///
+    /// Uses range syntax, e.g. `2..7`, `2..`, etc.
+    ///
     /// Runs in O(log N) time.
     ///
     /// # Panics
@@ -1101,23 +2503,98 @@ impl Rope {
     /// Panics if the start of the range is greater than the end, or if the
     /// end is out of bounds (i.e. `end > len_chars()`).
     #[inline]
-    pub fn slice<R>(&self, char_range: R) -> RopeSlice
+    pub fn count_line_breaks<R>(&self, char_range: R) -> usize
     where
         R: RangeBounds<usize>,
     {
-        let start = start_bound_to_num(char_range.start_bound()).unwrap_or(0);
-        let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
+        self.slice(char_range).len_lines() - 1
+    }
 
-        // Bounds check
-        assert!(start <= end);
-        assert!(
-            end <= self.len_chars(),
-            "Attempt to slice past end of Rope: slice end {}, Rope length {}",
-            end,
-            self.len_chars()
-        );
+    /// Creates an iterator over the lines of the `Rope` that fuzzy-match
+    /// `pattern`, in the style of `fzf`.
+    ///
+    /// See [`FuzzyLines`](search/struct.FuzzyLines.html) for details.
+    ///
+    /// Only available with the `fuzzy` feature enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("fn open_file()\nfn close()\nfn flush_output()\n");
+    /// let mut matches: Vec<_> = rope.fuzzy_lines("ofl").collect();
+    /// matches.sort_by_key(|m| -m.score);
+    ///
+    /// assert_eq!(matches.len(), 1);
+    /// assert_eq!(matches[0].line_idx, 0);
+    /// ```
+    ///
+    /// Runs in O(log N) time.
+    #[cfg(feature = "fuzzy")]
+    #[inline]
+    pub fn fuzzy_lines(&self, pattern: &str) -> FuzzyLines<'_> {
+        FuzzyLines::new(&self.slice(..), pattern)
+    }
 
-        RopeSlice::new_with_range(&self.root, start, end)
+    /// Creates an iterator over the non-overlapping matches of `regex` in
+    /// the `Rope`, yielding the char index range of each match.
+    ///
+    /// The regex runs directly over the `Rope`'s chunks, so no part of the
+    /// text is copied.  See [`RegexMatches`](search/struct.RegexMatches.html)
+    /// for details.
+    ///
+    /// Only available with the `regex` feature enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// use ropey::search::Regex;
+    ///
+    /// let rope = Rope::from_str("one fish, two fish");
+    /// let re = Regex::new(r"[a-z]+ fish").unwrap();
+    /// let matches: Vec<_> = rope.regex_find_iter(&re).collect();
+    ///
+    /// assert_eq!(matches, vec![0..8, 10..18]);
+    /// ```
+    ///
+    /// Runs in O(log N) time.
+    #[cfg(feature = "regex")]
+    #[inline]
+    pub fn regex_find_iter<'a>(&'a self, regex: &'a Regex) -> RegexMatches<'a> {
+        RegexMatches::new(&self.slice(..), regex)
+    }
+
+    /// Replaces all non-overlapping matches of `regex` in the `Rope` with
+    /// `replacement`, returning the number of matches replaced.
+    ///
+    /// `replacement` can refer to capture groups of the match by index or
+    /// name, as `$1`/`${1}` or `$name`/`${name}`, and `$$` gives a literal
+    /// `$`.  This is the same syntax as `Regex::replace_all()` in the
+    /// `regex` crate.
+    ///
+    /// Where matches are sparse the edits are made in place, so the chunks
+    /// between them remain shared with any clones of the `Rope`.
+    ///
+    /// Only available with the `regex` feature enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// use ropey::search::Regex;
+    ///
+    /// let mut rope = Rope::from_str("2019-09-01, 2020-02-29");
+    /// let re = Regex::new(r"(?<y>\d{4})-(\d{2})-(\d{2})").unwrap();
+    ///
+    /// assert_eq!(rope.regex_replace_all(&re, "$3/$2/$y"), 2);
+    /// assert_eq!(rope, "01/09/2019, 29/02/2020");
+    /// ```
+    ///
+    /// Runs in O(N) time.
+    #[cfg(feature = "regex")]
+    pub fn regex_replace_all(&mut self, regex: &Regex, replacement: &str) -> usize {
+        regex_replace_all(self, regex, replacement)
     }
 
     //-----------------------------------------------------------------------
@@ -1358,53 +2835,274 @@ impl Rope {
         )
     }
 
-    //-----------------------------------------------------------------------
-    // Debugging
+    /// Creates an iterator over the ids of the chunks of the `Rope`, in the
+    /// same order as `chunks()`.  See `ChunkId` for details.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn chunk_ids(&self) -> ChunkIds<'_> {
+        ChunkIds::new(&self.root)
+    }
+
+    /// Creates a parallel iterator over the bytes of the `Rope`, for use
+    /// with Rayon.
+    ///
+    /// Only available with the `rayon` feature enabled.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_bytes(&self) -> ParBytes {
+        ParBytes::new(self.par_chunks())
+    }
+
+    /// Creates a parallel iterator over the chunks of the `Rope`, for use
+    /// with Rayon.
+    ///
+    /// Yields the same chunks as `chunks()`.
+    ///
+    /// Only available with the `rayon` feature enabled.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_chunks(&self) -> ParChunks {
+        ParChunks::new(&self.root, 0, self.len_bytes())
+    }
+
+    /// Creates a parallel iterator over the lines of the `Rope`, for use
+    /// with Rayon.
+    ///
+    /// Yields the same lines as `lines()`, and is indexed by line.
+    ///
+    /// Only available with the `rayon` feature enabled.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_lines(&self) -> ParLines {
+        ParLines::new(self.slice(..))
+    }
+
+    //-----------------------------------------------------------------------
+    // Debugging
+
+    /// Checks that all of the invariants of the `Rope`'s tree hold, and
+    /// describes the first one that doesn't if not.
+    ///
+    /// The invariants are:
+    ///
+    /// - The text info that each node keeps for its children matches their
+    ///   text.
+    /// - The tree is the same height everywhere.
+    /// - All internal nodes have the minimum number of children.
+    /// - All leaf nodes are non-empty, unless the whole text is.
+    /// - CRLF pairs are never split over chunk boundaries.
+    ///
+    /// Ropey maintains these itself, so this should never fail, and if it
+    /// does that's a bug in Ropey.  It's meant for fuzzers and bug reports,
+    /// where the returned error says which invariant broke and where.
+    ///
+    /// Runs in O(N) time.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate::check_integrity(&self.root)?;
+        validate::check_invariants(&self.root)
+    }
+
+    /// Renders the `Rope`'s tree as indented text, with one line per node.
+    ///
+    /// Each node shows the range of bytes it covers, internal nodes their
+    /// child and text counts, and leaves how full they are and the start of
+    /// their text.  This is for diagnosing performance problems such as
+    /// fragmented or unexpectedly deep trees, and the format may change
+    /// between versions.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ropey::Rope;
+    /// let rope = Rope::from_str("Hello world!");
+    /// assert_eq!(
+    ///     rope.dump_tree(),
+    ///     format!(
+    ///         "leaf, bytes 0..12 ({}% full), \"Hello world!\"\n",
+    ///         12 * 100 / rope.capacity(),
+    ///     ),
+    /// );
+    /// ```
+    pub fn dump_tree(&self) -> String {
+        dump::dump_text(&self.root)
+    }
+
+    /// Renders the `Rope`'s tree as a [Graphviz](https://graphviz.org) DOT
+    /// graph, with the same details as `dump_tree()`.
+    ///
+    /// Runs in O(N) time.
+    pub fn dump_tree_dot(&self) -> String {
+        dump::dump_dot(&self.root)
+    }
 
     /// NOT PART OF THE PUBLIC API (hidden from docs for a reason!)
     ///
     /// Debugging tool to make sure that all of the meta-data of the
-    /// tree is consistent with the actual data.
+    /// tree is consistent with the actual data.  Use `validate()` instead.
     #[doc(hidden)]
     pub fn assert_integrity(&self) {
-        self.root.assert_integrity();
+        if let Err(err) = validate::check_integrity(&self.root) {
+            panic!("{}", err);
+        }
     }
 
     /// NOT PART OF THE PUBLIC API (hidden from docs for a reason!)
     ///
-    /// Debugging tool to make sure that all of the following invariants
-    /// hold true throughout the tree:
-    ///
-    /// - The tree is the same height everywhere.
-    /// - All internal nodes have the minimum number of children.
-    /// - All leaf nodes are non-empty.
-    /// - CRLF pairs are never split over chunk boundaries.
+    /// Debugging tool to make sure that all of the invariants of the tree
+    /// that `validate()` checks, other than the integrity of its meta-data,
+    /// hold true.  Use `validate()` instead.
     #[doc(hidden)]
     pub fn assert_invariants(&self) {
-        self.root.assert_balance();
-        self.root.assert_node_size(true);
-        self.assert_crlf_seams();
-    }
-
-    /// Checks that CRLF pairs are never split over chunk boundaries.
-    fn assert_crlf_seams(&self) {
-        if self.chunks().count() > 0 {
-            let mut itr = self.chunks();
-            let mut last_chunk = itr.next().unwrap();
-            for chunk in itr {
-                if !chunk.is_empty() && !last_chunk.is_empty() {
-                    assert!(crlf::seam_is_break(last_chunk.as_bytes(), chunk.as_bytes()));
-                    last_chunk = chunk;
-                } else if last_chunk.is_empty() {
-                    last_chunk = chunk;
-                }
-            }
+        if let Err(err) = validate::check_invariants(&self.root) {
+            panic!("{}", err);
         }
     }
 
     //-----------------------------------------------------------------------
     // Internal utilities
 
+    // Internal workings of `split_into()` and `split_lines_into()`, which
+    // use `split_point` to adjust each evenly spaced split point.
+    fn split_into_internal<F>(&self, n: usize, split_point: F) -> Vec<Rope>
+    where
+        F: Fn(usize) -> usize,
+    {
+        assert!(n > 0, "Attempt to split Rope into zero parts");
+
+        let len = self.len_chars();
+        let mut parts = Vec::with_capacity(n);
+        let mut start = 0;
+        for i in 1..=n {
+            let end = if i == n {
+                len
+            } else {
+                // Computed in u128 so that it can't overflow.
+                split_point((len as u128 * i as u128 / n as u128) as usize).max(start)
+            };
+            parts.push(Rope::from(self.slice(start..end)));
+            start = end;
+        }
+        parts
+    }
+
+    /// Collects the byte ranges of `node`, which starts at `byte_idx`, that
+    /// differ from the same bytes of `saved`, in order.
+    ///
+    /// Adjacent ranges are merged.  Bytes past the end of `saved` always
+    /// count as different.
+    #[cfg(feature = "std")]
+    fn collect_changes(
+        &self,
+        node: &Arc<Node>,
+        byte_idx: usize,
+        saved: &Rope,
+        changes: &mut Vec<core::ops::Range<usize>>,
+    ) {
+        if node_at_byte(&saved.root, node, byte_idx) {
+            return;
+        }
+
+        match **node {
+            Node::Leaf(ref text) => {
+                let bytes = text.as_bytes();
+                let mut first = None;
+                let mut last = 0;
+                let mut i = 0;
+                while i < bytes.len() {
+                    if byte_idx + i >= saved.len_bytes() {
+                        first = first.or(Some(i));
+                        last = bytes.len();
+                        break;
+                    }
+                    let (chunk, chunk_byte_idx, _, _) = saved.chunk_at_byte(byte_idx + i);
+                    let saved_bytes = &chunk.as_bytes()[(byte_idx + i - chunk_byte_idx)..];
+                    let len = saved_bytes.len().min(bytes.len() - i);
+                    let diff = bytes[i..(i + len)]
+                        .iter()
+                        .zip(saved_bytes)
+                        .enumerate()
+                        .filter(|&(_, (a, b))| a != b)
+                        .map(|(j, _)| i + j);
+                    for j in diff {
+                        first = first.or(Some(j));
+                        last = j + 1;
+                    }
+                    i += len;
+                }
+
+                if let Some(first) = first {
+                    let range = (byte_idx + first)..(byte_idx + last);
+                    match changes.last_mut() {
+                        Some(prev) if prev.end == range.start => prev.end = range.end,
+                        _ => changes.push(range),
+                    }
+                }
+            }
+            Node::Internal(ref children) => {
+                let mut child_byte_idx = byte_idx;
+                for (info, child) in children.iter() {
+                    self.collect_changes(child, child_byte_idx, saved, changes);
+                    child_byte_idx += info.bytes as usize;
+                }
+            }
+        }
+    }
+
+    // Internal workings of `mem_usage()` and `mem_usage_amortized()`.
+    fn mem_usage_internal(&self, amortized: bool) -> MemUsage {
+        // Summed as fractions of bytes, so that rounding doesn't add up
+        // over many nodes.
+        let mut text_bytes_sum = 0.0;
+        let mut overhead_bytes_sum = 0.0;
+
+        // Each node along with how many ways its memory is split, which
+        // is the product of the reference counts of it and its ancestors.
+        let mut stack = vec![(&self.root, 1.0)];
+        while let Some((node, sharers)) = stack.pop() {
+            let sharers = if amortized {
+                sharers * Arc::strong_count(node) as f64
+            } else {
+                1.0
+            };
+
+            if let Node::Internal(ref children) = **node {
+                stack.extend(children.nodes().iter().map(|child| (child, sharers)));
+            }
+            let usage = node.heap_usage();
+            text_bytes_sum += usage.text_bytes as f64 / sharers;
+            overhead_bytes_sum += usage.overhead_bytes as f64 / sharers;
+        }
+
+        MemUsage {
+            text_bytes: (text_bytes_sum + 0.5) as usize,
+            overhead_bytes: (overhead_bytes_sum + 0.5) as usize,
+        }
+    }
+
+    /// Returns the leaves of the tree with their text info, in order.
+    fn leaves(&self) -> Vec<(TextInfo, Arc<Node>)> {
+        let mut leaves = Vec::new();
+        let mut stack = vec![(self.root.text_info(), &self.root)];
+        while let Some((info, node)) = stack.pop() {
+            match **node {
+                Node::Leaf(_) => leaves.push((info, Arc::clone(node))),
+                Node::Internal(ref children) => {
+                    stack.extend(children.iter().rev().map(|(&info, child)| (info, child)));
+                }
+            }
+        }
+        leaves
+    }
+
     /// Iteratively replaces the root node with its child if it only has
     /// one child.
     pub(crate) fn pull_up_singular_nodes(&mut self) {
@@ -1420,6 +3118,109 @@ impl Rope {
     }
 }
 
+/// Returns whether `node` appears in the tree under `root`, starting at
+/// byte index `byte_idx`.
+///
+/// Runs in O(log N) time.
+#[cfg(feature = "std")]
+fn node_at_byte(root: &Arc<Node>, node: &Arc<Node>, byte_idx: usize) -> bool {
+    let mut current = root;
+    let mut current_byte_idx = 0;
+    loop {
+        if Arc::ptr_eq(current, node) {
+            return current_byte_idx == byte_idx;
+        }
+
+        match **current {
+            Node::Leaf(_) => return false,
+            Node::Internal(ref children) => {
+                let next = children.iter().find(|&(info, _)| {
+                    let end = current_byte_idx + info.bytes as usize;
+                    if byte_idx < end {
+                        true
+                    } else {
+                        current_byte_idx = end;
+                        false
+                    }
+                });
+                match next {
+                    Some((_, child)) => current = child,
+                    None => return false,
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether the trees under `a` and `b` hold the same text.
+///
+/// Subtrees that the trees share at the same position are skipped without
+/// looking at their text, so comparing a rope to an edited clone of itself
+/// only takes time in proportion to the edits.
+fn trees_eq(a: &Arc<Node>, b: &Arc<Node>) -> bool {
+    // The nodes of each tree that are still to be compared, as
+    // (start byte, byte length, node), with the next node last.
+    let mut stack_a = vec![(0, a.byte_count(), a)];
+    let mut stack_b = vec![(0, b.byte_count(), b)];
+
+    // All text before this byte is known to be equal.
+    let mut byte_idx = 0;
+
+    loop {
+        while let Some(&(start, len, _)) = stack_a.last() {
+            if start + len > byte_idx {
+                break;
+            }
+            stack_a.pop();
+        }
+        while let Some(&(start, len, _)) = stack_b.last() {
+            if start + len > byte_idx {
+                break;
+            }
+            stack_b.pop();
+        }
+
+        let ((start_a, len_a, node_a), (start_b, len_b, node_b)) =
+            match (stack_a.last(), stack_b.last()) {
+                (Some(&a), Some(&b)) => (a, b),
+                (None, None) => return true,
+                _ => return false,
+            };
+
+        if start_a == byte_idx && start_b == byte_idx && Arc::ptr_eq(node_a, node_b) {
+            byte_idx += len_a;
+            continue;
+        }
+
+        // Split up the larger of the nodes, since the other one may be
+        // shared with one of its children.
+        let split_a = match (&**node_a, &**node_b) {
+            (Node::Internal(_), Node::Internal(_)) => len_a >= len_b,
+            (Node::Internal(_), Node::Leaf(_)) => true,
+            (Node::Leaf(_), Node::Internal(_)) => false,
+            (Node::Leaf(text_a), Node::Leaf(text_b)) => {
+                let end = (start_a + len_a).min(start_b + len_b);
+                let bytes_a = &text_a.as_bytes()[(byte_idx - start_a)..(end - start_a)];
+                let bytes_b = &text_b.as_bytes()[(byte_idx - start_b)..(end - start_b)];
+                if bytes_a != bytes_b {
+                    return false;
+                }
+                byte_idx = end;
+                continue;
+            }
+        };
+
+        let stack = if split_a { &mut stack_a } else { &mut stack_b };
+        let (start, len, node) = stack.pop().unwrap();
+        let mut child_end = start + len;
+        for (info, child) in node.children().iter().rev() {
+            let child_len = info.bytes as usize;
+            stack.push((child_end - child_len, child_len, child));
+            child_end -= child_len;
+        }
+    }
+}
+
 //==============================================================
 // Conversion impls
 
@@ -1430,9 +3231,9 @@ impl<'a> From<&'a str> for Rope {
     }
 }
 
-impl<'a> From<std::borrow::Cow<'a, str>> for Rope {
+impl<'a> From<alloc::borrow::Cow<'a, str>> for Rope {
     #[inline]
-    fn from(text: std::borrow::Cow<'a, str>) -> Self {
+    fn from(text: alloc::borrow::Cow<'a, str>) -> Self {
         Rope::from_str(&text)
     }
 }
@@ -1444,6 +3245,16 @@ impl From<String> for Rope {
     }
 }
 
+impl<'a> TryFrom<&'a [u8]> for Rope {
+    type Error = core::str::Utf8Error;
+
+    /// Same as `Rope::from_utf8()`.
+    #[inline]
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        Rope::from_utf8(bytes)
+    }
+}
+
 /// Will share data where possible.
 ///
 /// Runs in O(log N) time.
@@ -1457,6 +3268,12 @@ impl<'a> From<RopeSlice<'a>> for Rope {
                 end_char,
                 ..
             }) => {
+                // Splitting can't produce an empty tree, so handle empty
+                // slices up front.
+                if start_char == end_char {
+                    return Rope::new();
+                }
+
                 let mut rope = Rope {
                     root: Arc::clone(node),
                 };
@@ -1505,10 +3322,10 @@ impl<'a> From<&'a Rope> for String {
     }
 }
 
-impl<'a> From<Rope> for std::borrow::Cow<'a, str> {
+impl<'a> From<Rope> for alloc::borrow::Cow<'a, str> {
     #[inline]
     fn from(r: Rope) -> Self {
-        std::borrow::Cow::Owned(String::from(r))
+        alloc::borrow::Cow::Owned(String::from(r))
     }
 }
 
@@ -1516,34 +3333,51 @@ impl<'a> From<Rope> for std::borrow::Cow<'a, str> {
 /// owned string if the contents is not contiguous in memory.
 ///
 /// Runs in best case O(1), worst case O(N).
-impl<'a> From<&'a Rope> for std::borrow::Cow<'a, str> {
+impl<'a> From<&'a Rope> for alloc::borrow::Cow<'a, str> {
     #[inline]
     fn from(r: &'a Rope) -> Self {
         if let Node::Leaf(ref text) = *r.root {
-            std::borrow::Cow::Borrowed(text)
+            alloc::borrow::Cow::Borrowed(text)
         } else {
-            std::borrow::Cow::Owned(String::from(r))
+            alloc::borrow::Cow::Owned(String::from(r))
         }
     }
 }
 
-impl<'a> FromIterator<&'a str> for Rope {
+impl FromIterator<char> for Rope {
     fn from_iter<T>(iter: T) -> Self
     where
-        T: IntoIterator<Item = &'a str>,
+        T: IntoIterator<Item = char>,
     {
+        // Collect chars into leaf-sized pieces before handing them to the
+        // builder, rather than appending them one at a time.
         let mut builder = RopeBuilder::new();
-        for chunk in iter {
-            builder.append(chunk);
+        let mut buffer = String::with_capacity(MAX_BYTES);
+        for c in iter {
+            if buffer.len() + c.len_utf8() > MAX_BYTES {
+                builder.append(&buffer);
+                buffer.clear();
+            }
+            buffer.push(c);
         }
+        builder.append(&buffer);
         builder.finish()
     }
 }
 
-impl<'a> FromIterator<std::borrow::Cow<'a, str>> for Rope {
+impl<'a> FromIterator<&'a str> for Rope {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        Rope::from_chunks(iter)
+    }
+}
+
+impl<'a> FromIterator<alloc::borrow::Cow<'a, str>> for Rope {
     fn from_iter<T>(iter: T) -> Self
     where
-        T: IntoIterator<Item = std::borrow::Cow<'a, str>>,
+        T: IntoIterator<Item = alloc::borrow::Cow<'a, str>>,
     {
         let mut builder = RopeBuilder::new();
         for chunk in iter {
@@ -1566,18 +3400,181 @@ impl FromIterator<String> for Rope {
     }
 }
 
+/// Appends the text to the end of the `Rope`.
+///
+/// The text is first built into its own `Rope` with a `RopeBuilder`, and
+/// then appended all at once, so this runs in O(M + log N) time, where M is
+/// the length of the appended text.
+impl<'a> Extend<&'a str> for Rope {
+    #[inline]
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        self.append(iter.into_iter().collect());
+    }
+}
+
+/// Same as `Extend<&str>`.
+impl Extend<char> for Rope {
+    #[inline]
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = char>,
+    {
+        self.append(iter.into_iter().collect());
+    }
+}
+
+/// Same as `Extend<&str>`.
+impl Extend<String> for Rope {
+    #[inline]
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = String>,
+    {
+        self.append(iter.into_iter().collect());
+    }
+}
+
+/// Same as `Extend<&str>`.
+impl<'a> Extend<alloc::borrow::Cow<'a, str>> for Rope {
+    #[inline]
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = alloc::borrow::Cow<'a, str>>,
+    {
+        self.append(iter.into_iter().collect());
+    }
+}
+
+/// Concatenates the pieces of text with a `RopeBuilder`.
+///
+/// Runs in O(M) time, where M is the total length of the text.
+impl<'a> core::iter::Sum<&'a str> for Rope {
+    #[inline]
+    fn sum<T>(iter: T) -> Self
+    where
+        T: Iterator<Item = &'a str>,
+    {
+        iter.collect()
+    }
+}
+
+/// Same as `Sum<&str>`.
+impl core::iter::Sum<String> for Rope {
+    #[inline]
+    fn sum<T>(iter: T) -> Self
+    where
+        T: Iterator<Item = String>,
+    {
+        iter.collect()
+    }
+}
+
+/// Concatenates the ropes with `Rope::append()`.
+///
+/// Runs in O(K log N) time, where K is the number of ropes.
+impl core::iter::Sum<Rope> for Rope {
+    #[inline]
+    fn sum<T>(iter: T) -> Self
+    where
+        T: Iterator<Item = Rope>,
+    {
+        let mut rope = Rope::new();
+        for other in iter {
+            rope.append(other);
+        }
+        rope
+    }
+}
+
 //==============================================================
 // Other impls
 
-impl std::fmt::Debug for Rope {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// Appends the text to the end of the `Rope`.
+///
+/// Runs in O(M + log N) time, where M is the length of the appended text.
+impl<'a> core::ops::Add<&'a str> for Rope {
+    type Output = Rope;
+
+    #[inline]
+    fn add(mut self, rhs: &'a str) -> Rope {
+        self += rhs;
+        self
+    }
+}
+
+/// Same as `Rope::append()`.
+///
+/// Runs in O(log N) time.
+impl core::ops::Add<Rope> for Rope {
+    type Output = Rope;
+
+    #[inline]
+    fn add(mut self, rhs: Rope) -> Rope {
+        self.append(rhs);
+        self
+    }
+}
+
+/// Appends the text to the end of the `Rope`.
+///
+/// Runs in O(M + log N) time, where M is the length of the appended text.
+impl<'a> core::ops::AddAssign<&'a str> for Rope {
+    #[inline]
+    fn add_assign(&mut self, rhs: &'a str) {
+        let end = self.len_chars();
+        self.insert(end, rhs);
+    }
+}
+
+/// Same as `Rope::append()`.
+///
+/// Runs in O(log N) time.
+impl core::ops::AddAssign<Rope> for Rope {
+    #[inline]
+    fn add_assign(&mut self, rhs: Rope) {
+        self.append(rhs);
+    }
+}
+
+/// Appends the written text to the end of the `Rope`.
+///
+/// This allows `write!()` to be used to format text directly into a rope.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use std::fmt::Write;
+///
+/// let mut rope = Rope::new();
+/// for (key, value) in &[("apples", 3), ("pears", 5)] {
+///     writeln!(rope, "{}: {}", key, value).unwrap();
+/// }
+/// assert_eq!(rope, "apples: 3\npears: 5\n");
+/// ```
+///
+/// Each write runs in O(M + log N) time, where M is the length of the
+/// written text.
+impl core::fmt::Write for Rope {
+    #[inline]
+    fn write_str(&mut self, text: &str) -> core::fmt::Result {
+        *self += text;
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for Rope {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_list().entries(self.chunks()).finish()
     }
 }
 
-impl std::fmt::Display for Rope {
+impl core::fmt::Display for Rope {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         for chunk in self.chunks() {
             write!(f, "{}", chunk)?
         }
@@ -1585,88 +3582,99 @@ impl std::fmt::Display for Rope {
     }
 }
 
-impl std::default::Default for Rope {
+impl core::default::Default for Rope {
     #[inline]
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl std::cmp::Eq for Rope {}
+impl core::cmp::Eq for Rope {}
 
-impl std::cmp::PartialEq<Rope> for Rope {
+impl core::cmp::PartialEq<Rope> for Rope {
     #[inline]
     fn eq(&self, other: &Rope) -> bool {
-        self.slice(..) == other.slice(..)
+        self.len_bytes() == other.len_bytes() && trees_eq(&self.root, &other.root)
     }
 }
 
-impl<'a> std::cmp::PartialEq<&'a str> for Rope {
+impl<'a> core::cmp::PartialEq<&'a str> for Rope {
     #[inline]
     fn eq(&self, other: &&'a str) -> bool {
         self.slice(..) == *other
     }
 }
 
-impl<'a> std::cmp::PartialEq<Rope> for &'a str {
+impl<'a> core::cmp::PartialEq<Rope> for &'a str {
     #[inline]
     fn eq(&self, other: &Rope) -> bool {
         *self == other.slice(..)
     }
 }
 
-impl std::cmp::PartialEq<str> for Rope {
+impl core::cmp::PartialEq<str> for Rope {
     #[inline]
     fn eq(&self, other: &str) -> bool {
         self.slice(..) == other
     }
 }
 
-impl std::cmp::PartialEq<Rope> for str {
+impl core::cmp::PartialEq<Rope> for str {
     #[inline]
     fn eq(&self, other: &Rope) -> bool {
         self == other.slice(..)
     }
 }
 
-impl<'a> std::cmp::PartialEq<String> for Rope {
+impl<'a> core::cmp::PartialEq<String> for Rope {
     #[inline]
     fn eq(&self, other: &String) -> bool {
         self.slice(..) == other.as_str()
     }
 }
 
-impl<'a> std::cmp::PartialEq<Rope> for String {
+impl<'a> core::cmp::PartialEq<Rope> for String {
     #[inline]
     fn eq(&self, other: &Rope) -> bool {
         self.as_str() == other.slice(..)
     }
 }
 
-impl<'a> std::cmp::PartialEq<std::borrow::Cow<'a, str>> for Rope {
+impl<'a> core::cmp::PartialEq<alloc::borrow::Cow<'a, str>> for Rope {
     #[inline]
-    fn eq(&self, other: &std::borrow::Cow<'a, str>) -> bool {
+    fn eq(&self, other: &alloc::borrow::Cow<'a, str>) -> bool {
         self.slice(..) == **other
     }
 }
 
-impl<'a> std::cmp::PartialEq<Rope> for std::borrow::Cow<'a, str> {
+impl<'a> core::cmp::PartialEq<Rope> for alloc::borrow::Cow<'a, str> {
     #[inline]
     fn eq(&self, other: &Rope) -> bool {
         **self == other.slice(..)
     }
 }
 
-impl std::cmp::Ord for Rope {
+/// Hashes the text, independent of how it's split into chunks.  Equal to
+/// the hash of a `RopeSlice` of the whole `Rope`.
+///
+/// Runs in O(N) time.
+impl core::hash::Hash for Rope {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.slice(..).hash(state)
+    }
+}
+
+impl core::cmp::Ord for Rope {
     #[inline]
-    fn cmp(&self, other: &Rope) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Rope) -> core::cmp::Ordering {
         self.slice(..).cmp(&other.slice(..))
     }
 }
 
-impl std::cmp::PartialOrd<Rope> for Rope {
+impl core::cmp::PartialOrd<Rope> for Rope {
     #[inline]
-    fn partial_cmp(&self, other: &Rope) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Rope) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -1676,7 +3684,10 @@ impl std::cmp::PartialOrd<Rope> for Rope {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use str_utils::byte_to_char_idx;
+    use slice::hash_chunks;
+    use str_utils::{byte_to_char_idx, count_chars, count_line_breaks};
+    use tree::{MAX_CHILDREN, NODE_ALLOC_SIZE};
+    use validate::ValidationErrorKind;
 
     // 127 bytes, 103 chars, 1 line
     const TEXT: &str = "Hello there!  How're you doing?  It's \
@@ -1687,6 +3698,21 @@ mod tests {
                               a fine day, isn't it?\nAren't you glad \
                               we're alive?\nこんにちは、みんなさん！";
 
+    // For building trees by hand.
+    fn leaf(text: &str) -> (TextInfo, Arc<Node>) {
+        (
+            TextInfo::from_str(text),
+            Arc::new(Node::Leaf(NodeText::from_str(text))),
+        )
+    }
+    fn internal(nodes: Vec<(TextInfo, Arc<Node>)>) -> (TextInfo, Arc<Node>) {
+        let mut children = NodeChildren::new();
+        for node in nodes {
+            children.push(node);
+        }
+        (children.combined_info(), Arc::new(Node::Internal(children)))
+    }
+
     #[test]
     fn new_01() {
         let r = Rope::new();
@@ -1696,13 +3722,181 @@ mod tests {
         r.assert_invariants();
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn new_02() {
+        // Empty ropes share their root until edited.
+        let r1 = Rope::new();
+        let mut r2 = Rope::new();
+        assert!(Arc::ptr_eq(&r1.root, &r2.root));
+
+        r2.insert(0, TEXT);
+        assert_eq!(r1, "");
+        assert_eq!(r2, TEXT);
+        r2.assert_integrity();
+        r2.assert_invariants();
+
+        r2.remove(..);
+        assert!(Arc::ptr_eq(&r1.root, &r2.root));
+        r2.insert_char(0, 'a');
+        assert_eq!(Rope::new(), "");
+        assert_eq!(r2, "a");
+    }
+
+    #[test]
+    fn default_01() {
+        #[derive(Default)]
+        struct Document {
+            text: Rope,
+        }
+
+        let mut doc = Document::default();
+        assert_eq!(doc.text, "");
+        doc.text.assert_integrity();
+        doc.text.assert_invariants();
+
+        doc.text.insert(0, TEXT);
+        let text = std::mem::take(&mut doc.text);
+        assert_eq!(text, TEXT);
+        assert_eq!(doc.text, "");
+    }
+
+    #[test]
+    fn from_str() {
+        let r = Rope::from_str(TEXT);
+        assert_eq!(r, TEXT);
+
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn from_utf8_lossy_01() {
+        let r = Rope::from_utf8_lossy(TEXT.as_bytes());
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        assert_eq!(Rope::from_utf8_lossy(b""), "");
+    }
+
+    #[test]
+    fn from_utf8_lossy_02() {
+        let mut bytes = TEXT.as_bytes().to_vec();
+        bytes[3] = 0xFF;
+        bytes[40] = 0b1100_0000;
+        bytes[41] = 0b0100_0000;
+        bytes.truncate(bytes.len() - 1);
+        bytes.extend_from_slice(b"\xF0\x9F\x98 \xE3\x81");
+
+        let r = Rope::from_utf8_lossy(&bytes);
+        assert_eq!(r, String::from_utf8_lossy(&bytes));
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn from_chunks_01() {
+        let r = Rope::from_chunks(TEXT.split_inclusive(' '));
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let chunks = ["", "Hello\r", "", "\n", "こんにちは", ""];
+        let r = Rope::from_chunks(chunks.iter().copied());
+        assert_eq!(r, "Hello\r\nこんにちは");
+        assert_eq!(r.len_lines(), 2);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        assert_eq!(Rope::from_chunks(None), "");
+    }
+
+    #[test]
+    fn from_lines_01() {
+        let r = Rope::from_lines(&["Hello\r", "こんにちは", "", "world!"], "\n");
+        assert_eq!(r, "Hello\r\nこんにちは\n\nworld!");
+        assert_eq!(r.len_lines(), 4);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let r = Rope::from_lines(vec![String::from("a"), String::new()], "\r\n");
+        assert_eq!(r, "a\r\n");
+        assert_eq!(Rope::from_lines(&[""], "\n"), "");
+        assert_eq!(Rope::from_lines(None::<&str>, "\n"), "");
+    }
+
+    #[test]
+    fn from_lines_02() {
+        // Round trip through `lines()`.
+        let r1 = Rope::from_str(TEXT_LINES);
+        let lines = r1.lines().map(|line| {
+            let line = line.to_string();
+            line.trim_end_matches('\n').to_string()
+        });
+        let r2 = Rope::from_lines(lines, "\n");
+        assert_eq!(r2, r1);
+        r2.assert_integrity();
+        r2.assert_invariants();
+    }
+
+    #[test]
+    fn from_chunks_unchecked_01() {
+        let r1 = Rope::from_str(&TEXT.repeat(4));
+        let chunks: Vec<_> = r1
+            .chunks()
+            .map(|c| (c, count_chars(c), count_line_breaks(c)))
+            .collect();
+
+        let r2 = unsafe { Rope::from_chunks_unchecked(chunks.iter().copied()) };
+        assert_eq!(r2, r1);
+        assert!(r2.chunks().eq(r1.chunks()));
+        r2.assert_integrity();
+        r2.assert_invariants();
+    }
+
+    #[test]
+    fn from_chunks_unchecked_02() {
+        // Empty and oversized chunks.
+        let text = TEXT.repeat(2);
+        let chunks = [
+            ("", 0, 0),
+            ("ab\r\n", 4, 1),
+            (text.as_str(), 206, 0),
+            ("", 0, 0),
+            ("c", 1, 0),
+        ];
+
+        let r = unsafe { Rope::from_chunks_unchecked(chunks.iter().copied()) };
+        assert_eq!(r, format!("ab\r\n{}c", text).as_str());
+        r.assert_integrity();
+        r.assert_invariants();
+
+        assert_eq!(unsafe { Rope::from_chunks_unchecked(None) }, "");
+    }
+
     #[test]
-    fn from_str() {
-        let r = Rope::from_str(TEXT);
+    fn from_utf8_01() {
+        let r = Rope::from_utf8(TEXT.as_bytes()).unwrap();
         assert_eq!(r, TEXT);
-
         r.assert_integrity();
         r.assert_invariants();
+
+        let r = Rope::try_from(&TEXT.as_bytes()[..5]).unwrap();
+        assert_eq!(r, "Hello");
+    }
+
+    #[test]
+    fn from_utf8_02() {
+        let mut bytes = TEXT.as_bytes().to_vec();
+        bytes[40] = 0b1100_0000;
+        let err = Rope::from_utf8(&bytes).unwrap_err();
+        assert_eq!(err.valid_up_to(), 40);
+        assert_eq!(err.error_len(), Some(1));
+
+        let err = Rope::try_from(&TEXT.as_bytes()[..(TEXT.len() - 1)]).unwrap_err();
+        assert_eq!(err.valid_up_to(), TEXT.len() - 3);
+        assert_eq!(err.error_len(), None);
     }
 
     #[test]
@@ -1983,6 +4177,125 @@ mod tests {
         r.remove(104..105); // Removing past the end
     }
 
+    #[test]
+    fn remove_11() {
+        // Leaves an undersized node just left of the removal, on the
+        // boundary between two of its parent's children.
+        let text = TEXT.repeat(2);
+        let mut r = Rope::new();
+        r.insert(0, &text);
+        r.remove(1..81);
+
+        assert_eq!(r.to_string(), format!("{}{}", &text[..1], &text[81..]));
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn repeat_01() {
+        let r = Rope::from_str(TEXT);
+        for &n in &[0, 1, 2, 3, 7, 8, 100] {
+            let r2 = r.repeat(n);
+            assert_eq!(r2, TEXT.repeat(n).as_str());
+            r2.assert_integrity();
+            r2.assert_invariants();
+        }
+        assert_eq!(Rope::new().repeat(10), "");
+    }
+
+    #[test]
+    fn repeat_02() {
+        // CRLF pairs formed at the seams.
+        let r = Rope::from_str("\nHi\r");
+        let r2 = r.repeat(50);
+        assert_eq!(r2, "\nHi\r".repeat(50).as_str());
+        assert_eq!(r2.len_lines(), 52);
+        r2.assert_integrity();
+        r2.assert_invariants();
+    }
+
+    #[test]
+    fn repeat_03() {
+        // The copies share their leaves.
+        let r = Rope::from_str(TEXT);
+        let r2 = r.repeat(10_000);
+        assert_eq!(r2.len_bytes(), TEXT.len() * 10_000);
+        let leaves: std::collections::HashSet<_> = r2.chunks().map(|c| c.as_ptr()).collect();
+        assert!(leaves.len() < r.chunks().count() * 10);
+    }
+
+    #[test]
+    fn split_into_01() {
+        let r = Rope::from_str(TEXT);
+        for n in 1..120 {
+            let parts = r.split_into(n);
+            assert_eq!(parts.len(), n);
+            for part in &parts {
+                assert!(part.len_chars() >= 103 / n);
+                assert!(part.len_chars() <= 103 / n + 1);
+                part.assert_integrity();
+                part.assert_invariants();
+            }
+
+            let mut joined = Rope::new();
+            for part in parts {
+                joined.append(part);
+            }
+            assert_eq!(joined, TEXT);
+        }
+    }
+
+    #[test]
+    fn split_into_02() {
+        let parts = Rope::new().split_into(3);
+        assert_eq!(parts, ["", "", ""]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn split_into_03() {
+        Rope::from_str(TEXT).split_into(0);
+    }
+
+    #[test]
+    fn split_lines_into_01() {
+        let r = Rope::from_str(TEXT_LINES);
+        assert_eq!(r.split_lines_into(1), [TEXT_LINES]);
+        assert_eq!(
+            r.split_lines_into(3),
+            [
+                "Hello there!  How're you doing?\n",
+                "It's a fine day, isn't it?\n",
+                "Aren't you glad we're alive?\nこんにちは、みんなさん！",
+            ]
+        );
+        assert_eq!(
+            r.split_lines_into(5),
+            [
+                "",
+                "Hello there!  How're you doing?\n",
+                "It's a fine day, isn't it?\n",
+                "",
+                "Aren't you glad we're alive?\nこんにちは、みんなさん！",
+            ]
+        );
+
+        for n in 1..120 {
+            let parts = r.split_lines_into(n);
+            assert_eq!(parts.len(), n);
+            let mut joined = Rope::new();
+            for part in parts {
+                // Parts only end at line starts, or the end of the text.
+                let end = joined.len_chars() + part.len_chars();
+                assert!(end == 100 || r.line_to_char(r.char_to_line(end)) == end);
+                part.assert_integrity();
+                part.assert_invariants();
+                joined.append(part);
+            }
+            assert_eq!(joined, TEXT_LINES);
+        }
+    }
+
     #[test]
     fn split_off_01() {
         let mut r = Rope::from_str(TEXT);
@@ -2191,6 +4504,137 @@ mod tests {
         r.assert_invariants();
     }
 
+    #[test]
+    fn append_08() {
+        // A leading LF with no CR before it doesn't touch the seam, so the
+        // appended rope's nodes stay shared.
+        let mut r = Rope::from_str("Hi there!");
+        let r2 = Rope::from_str(&format!("\n{}", TEXT));
+
+        r.append(r2.clone());
+        assert_eq!(r, format!("Hi there!\n{}", TEXT).as_str());
+        assert_eq!(r.shared_bytes_with(&r2), r2.len_bytes());
+
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn append_09() {
+        // Growing a rope by small appends leaves it with leaves as full as
+        // building it in one go.
+        let mut r = Rope::new();
+        let mut b = RopeBuilder::new();
+        for i in 0..100 {
+            let text = if i % 2 == 0 { "ab\r" } else { "\nこ" };
+            r.append(Rope::from_str(text));
+            b.append(text);
+        }
+        let built = b.finish();
+
+        assert_eq!(r, built);
+        assert!(r.tree_stats().leaf_count <= built.tree_stats().leaf_count);
+
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[cfg(feature = "u32_counts")]
+    #[test]
+    #[should_panic(expected = "past its maximum length")]
+    fn append_10() {
+        // Growing past 4 GB.  Appending a clone shares its nodes, so this
+        // doesn't actually use that much memory.
+        let mut r = Rope::from_str(TEXT);
+        for _ in 0..32 {
+            let len = r.len_bytes();
+            r.append(r.clone());
+            assert_eq!(r.len_bytes(), len * 2);
+        }
+    }
+
+    #[test]
+    fn insert_07() {
+        // Same for inserting at the end.
+        let mut r = Rope::new();
+        for _ in 0..100 {
+            let end = r.len_chars();
+            r.insert(end, "Hi, ");
+        }
+
+        assert_eq!(r, "Hi, ".repeat(100).as_str());
+        assert_eq!(r.tree_stats().leaf_count, 400 / MAX_BYTES + 1);
+
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn insert_08() {
+        // Inserting at the end when the last leaf is already oversized,
+        // which happens when it has no valid split point.
+        let text = "\r\n\r\n\r\nabcdefgh\r";
+        let mut r = Rope::new();
+        r.insert(0, text);
+        r.insert(6, text);
+        r.remove(29..30);
+        r.insert(19, text);
+        r.remove(0..2);
+        r.remove(34..37);
+        r.remove(16..28);
+        r.remove(17..20);
+        r.insert(13, "abc");
+        r.insert(15, text);
+        r.remove(31..34);
+        r.insert(24, "x\r");
+        r.append(Rope::from_str("\ny"));
+        r.insert(1, "\r");
+        r.insert(43, "\n");
+        r.remove(44..45);
+        assert!(r.chunks().last().unwrap().len() > MAX_BYTES);
+
+        r.insert(44, "x\r");
+        assert_eq!(
+            r,
+            "\r\r\n\r\n\r\n\r\n\r\nabcab\r\n\r\n\r\nabcx\rdefgh\rcfh\rdefgh\n\nx\r"
+        );
+        r.assert_integrity();
+    }
+
+    #[test]
+    fn mem_usage_01() {
+        let r = Rope::from_str(TEXT);
+        let node_count = r.leaves().len() + r.root.depth() + 1;
+        let usage = r.mem_usage();
+        assert_eq!(usage.text_bytes, r.len_bytes());
+        assert!(usage.total_bytes() > node_count * NODE_ALLOC_SIZE);
+        assert!(usage.total_bytes() < r.leaves().len() * 2 * NODE_ALLOC_SIZE);
+        assert_eq!(r.mem_usage_amortized(), usage);
+    }
+
+    #[test]
+    fn mem_usage_02() {
+        // Shared nodes are split between their sharers.
+        let r1 = Rope::from_str(TEXT);
+        let usage = r1.mem_usage();
+        let mut r2 = r1.clone();
+        assert_eq!(r2.mem_usage(), usage);
+        let half = usage.text_bytes.div_ceil(2);
+        assert_eq!(r1.mem_usage_amortized().text_bytes, half);
+        assert_eq!(r2.mem_usage_amortized().text_bytes, half);
+
+        // Only the edited parts belong to `r2` alone.
+        r2.insert(50, "abc");
+        let text_bytes_1 = r1.mem_usage_amortized().text_bytes;
+        let text_bytes_2 = r2.mem_usage_amortized().text_bytes;
+        assert!(text_bytes_1 < r1.len_bytes());
+        assert!(text_bytes_1 + text_bytes_2 <= r1.len_bytes() + 3 + MAX_BYTES);
+        assert!(text_bytes_1 + text_bytes_2 >= r1.len_bytes() + 3);
+
+        drop(r2);
+        assert_eq!(r1.mem_usage_amortized(), usage);
+    }
+
     #[test]
     fn shrink_to_fit_01() {
         let mut r = Rope::new();
@@ -2216,8 +4660,383 @@ mod tests {
 
         assert_eq!(r, r2);
 
-        r.assert_integrity();
-        r.assert_invariants();
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn shrink_to_fit_02() {
+        let mut r = Rope::new();
+        for i in 0..100 {
+            let len = r.len_chars();
+            r.insert(len / 2, "こんにちは zopter\r\n");
+            if i % 3 == 0 {
+                r.remove((len / 3)..(len / 3 + 5));
+            }
+        }
+        let r2 = r.clone();
+        let depth = r.root.depth();
+
+        r.shrink_to_fit();
+        assert_eq!(r, r2);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        // Full leaves, give or take a char or a CRLF pair, and a shallower
+        // tree.
+        let chunk_count = r.chunks().count();
+        for chunk in r.chunks().take(chunk_count - 1) {
+            assert!(chunk.len() >= MAX_BYTES - 3);
+        }
+        assert!(r.root.depth() < depth);
+    }
+
+    #[test]
+    fn compact_01() {
+        let mut r = Rope::from_str(&TEXT.repeat(4));
+        for i in 0..100 {
+            let idx = (i * 37) % (r.len_chars() - 3);
+            r.remove(idx..(idx + 2));
+            if i % 4 == 0 {
+                r.insert(idx, "こんにちは zopter");
+            }
+        }
+        let r2 = r.clone();
+        let chunk_count = r.chunks().count();
+        let depth = r.root.depth();
+
+        r.compact();
+        assert_eq!(r, r2);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        // No two adjacent leaves fit in one, and the tree is no deeper.
+        let chunks: Vec<_> = r.chunks().collect();
+        for pair in chunks.windows(2) {
+            assert!(pair[0].len() + pair[1].len() > MAX_BYTES);
+        }
+        assert!(chunks.len() < chunk_count);
+        assert!(r.root.depth() <= depth);
+
+        // The leaves that didn't need merging are still shared.
+        assert!(r.shared_bytes_with(&r2) > 0);
+    }
+
+    #[test]
+    fn compact_02() {
+        let mut r = Rope::new();
+        r.compact();
+        assert_eq!(r, "");
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let mut r = Rope::from_str("Hello\r\nworld!\r\n");
+        r.remove(4..6);
+        r.compact();
+        assert_eq!(r, "Hell\nworld!\r\n");
+        assert_eq!(r.len_lines(), 3);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn depth_01() {
+        assert_eq!(Rope::new().depth(), 0);
+        assert_eq!(Rope::from_str("Hello").depth(), 0);
+
+        assert_eq!(Rope::from_str(&"a".repeat(MAX_BYTES * 2)).depth(), 1);
+
+        let r = Rope::from_str(TEXT);
+        assert!(r.depth() > 1);
+        assert_eq!(r.depth(), r.root.depth());
+    }
+
+    #[test]
+    fn tree_stats_01() {
+        let stats = Rope::new().tree_stats();
+        assert_eq!(stats.depth, 0);
+        assert_eq!(stats.internal_node_count, 0);
+        assert_eq!(stats.leaf_count, 1);
+        assert_eq!(stats.average_leaf_fill, 0.0);
+
+        let mut r = Rope::from_str(TEXT);
+        let stats = r.tree_stats();
+        assert_eq!(stats.depth, r.depth());
+        assert_eq!(stats.leaf_count, r.chunks().count());
+        assert!(stats.internal_node_count > stats.leaf_count / MAX_CHILDREN);
+        assert!(stats.internal_node_count < stats.leaf_count);
+        assert!(stats.average_leaf_fill > 0.8);
+
+        for i in 0..50 {
+            r.remove(i..(i + 1));
+        }
+        assert!(r.tree_stats().average_leaf_fill < stats.average_leaf_fill);
+        r.compact();
+        assert!(r.tree_stats().average_leaf_fill > 0.5);
+    }
+
+    #[test]
+    fn rebalance_01() {
+        // An internal node with too few children, and leaves at different
+        // depths.
+        let root = internal(vec![
+            internal(vec![leaf("Hello ")]),
+            internal(vec![leaf("there! "), leaf("How're "), leaf("you ")]),
+            leaf("doing?"),
+        ]);
+        let mut r = Rope { root: root.1 };
+        let r2 = r.clone();
+        assert!(!r.is_balanced());
+
+        r.rebalance();
+        assert!(r.is_balanced());
+        assert_eq!(r, "Hello there! How're you doing?");
+        assert_eq!(r.depth(), 1);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        // The leaves are still shared.
+        assert_eq!(r.shared_bytes_with(&r2), r.len_bytes());
+    }
+
+    #[test]
+    fn rebalance_02() {
+        let mut r = Rope::from_str(TEXT);
+        for i in 0..50 {
+            r.remove(i..(i + 1));
+        }
+        assert!(r.is_balanced());
+        let r2 = r.clone();
+
+        r.rebalance();
+        assert!(r.is_balanced());
+        assert_eq!(r, r2);
+        assert!(r.depth() <= r2.depth());
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let mut r = Rope::new();
+        r.rebalance();
+        assert_eq!(r, "");
+        assert!(r.is_balanced());
+    }
+
+    #[test]
+    fn ptr_eq_01() {
+        let r1 = Rope::from_str(TEXT);
+        let mut r2 = r1.clone();
+        assert!(r1.ptr_eq(&r2));
+        assert!(!r1.ptr_eq(&Rope::from_str(TEXT)));
+
+        r2.insert(5, "a");
+        r2.remove(5..6);
+        assert_eq!(r1, r2);
+        assert!(!r1.ptr_eq(&r2));
+    }
+
+    #[test]
+    fn shared_bytes_with_01() {
+        let r1 = Rope::from_str(TEXT);
+        let mut r2 = r1.clone();
+        assert_eq!(r1.shared_bytes_with(&r2), r1.len_bytes());
+        assert_eq!(r1.shared_bytes_with(&Rope::from_str(TEXT)), 0);
+        assert_eq!(Rope::new().shared_bytes_with(&r1), 0);
+
+        // Only the leaf that's edited and the nodes above it are copied.
+        r2.insert(50, "abc");
+        let shared = r2.shared_bytes_with(&r1);
+        assert!(shared >= r1.len_bytes() - MAX_BYTES);
+        assert!(shared < r1.len_bytes());
+        assert_eq!(r1.shared_bytes_with(&r2), shared);
+    }
+
+    #[test]
+    fn shared_bytes_with_02() {
+        // Shared data is counted wherever it is in the other rope.
+        let r1 = Rope::from_str(TEXT);
+        // A full leaf, so that appending doesn't merge it with `r1`'s.
+        let mut r2 = Rope::from_str("Hi there!");
+        r2.append(r1.clone());
+        assert_eq!(r2.shared_bytes_with(&r1), r1.len_bytes());
+        assert_eq!(r1.shared_bytes_with(&r2), r1.len_bytes());
+    }
+
+    #[test]
+    fn validate_01() {
+        assert_eq!(Rope::new().validate(), Ok(()));
+        assert_eq!(Rope::from_str(TEXT).validate(), Ok(()));
+
+        let mut r = Rope::from_str(TEXT_LINES);
+        for i in 0..20 {
+            r.insert(i * 3, "\r\n");
+            r.remove((i * 4)..(i * 4 + 1));
+        }
+        assert_eq!(r.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_02() {
+        fn validate(root: (TextInfo, Arc<Node>)) -> Result<(), ValidationError> {
+            Rope { root: root.1 }.validate()
+        }
+        fn error(
+            kind: ValidationErrorKind,
+            node_path: &[usize],
+            byte_idx: usize,
+        ) -> Result<(), ValidationError> {
+            Err(ValidationError {
+                kind: kind,
+                node_path: node_path.to_vec(),
+                byte_idx: byte_idx,
+            })
+        }
+
+        let good = || internal(vec![leaf("Hello "), leaf("there!")]);
+        assert_eq!(validate(good()), Ok(()));
+
+        let mut bad_info = good();
+        if let Node::Internal(ref mut children) = *Arc::make_mut(&mut bad_info.1) {
+            children.info_mut()[1] = TextInfo::from_str("there");
+        }
+        assert_eq!(
+            validate(bad_info),
+            error(ValidationErrorKind::InconsistentInfo, &[1], 6)
+        );
+
+        assert_eq!(
+            validate(internal(vec![good(), leaf("How're you?")])),
+            error(ValidationErrorKind::Unbalanced, &[1], 12)
+        );
+        assert_eq!(
+            validate(internal(vec![good(), internal(vec![leaf("Hi")])])),
+            error(ValidationErrorKind::TooFewChildren, &[1], 12)
+        );
+        assert_eq!(
+            validate(internal(vec![leaf("Hello"), leaf(""), leaf("!")])),
+            error(ValidationErrorKind::EmptyLeaf, &[1], 5)
+        );
+        assert_eq!(
+            validate(internal(vec![leaf("Hello\r"), leaf(""), leaf("\nthere!")])),
+            error(ValidationErrorKind::EmptyLeaf, &[1], 6)
+        );
+        assert_eq!(
+            validate(internal(vec![leaf("Hello\r"), leaf("\nthere!")])),
+            error(ValidationErrorKind::SplitSeam, &[1], 6)
+        );
+
+        let err = validate(internal(vec![leaf("Hello\r"), leaf("\nthere!")])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid rope tree: CRLF pair or char split between leaves at node [1], \
+             starting at byte 6"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn assert_invariants_01() {
+        let root = internal(vec![leaf("Hello\r"), leaf("\nthere!")]);
+        Rope { root: root.1 }.assert_invariants();
+    }
+
+    #[test]
+    fn chunk_ids_01() {
+        let r = Rope::from_str(TEXT);
+        let ids: Vec<_> = r.chunk_ids().collect();
+        let chunks: Vec<_> = r.chunks().collect();
+        assert_eq!(ids.iter().map(|id| id.as_str()).collect::<Vec<_>>(), chunks);
+
+        // Clones share their chunks.
+        assert!(r.clone().chunk_ids().eq(ids.iter().cloned()));
+
+        assert_eq!(Rope::new().chunk_ids().count(), 0);
+    }
+
+    #[test]
+    fn chunk_ids_02() {
+        // Only the edited chunks get new ids, and old ids keep their text.
+        let mut r = Rope::from_str(TEXT);
+        let ids: Vec<_> = r.chunk_ids().collect();
+        r.insert(50, "a");
+        r.remove(80..81);
+
+        // The insertion splits its leaf in two.
+        let new_ids: Vec<_> = r.chunk_ids().filter(|id| !ids.contains(id)).collect();
+        assert_eq!(new_ids.len(), 3);
+        assert!(ids.len() > 10);
+        assert_eq!(ids.iter().map(|id| id.as_str()).collect::<String>(), TEXT);
+
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn chunk_id_at_byte_01() {
+        let r = Rope::from_str(TEXT);
+        for i in 0..=r.len_bytes() {
+            let (id, b, c, l) = r.chunk_id_at_byte(i);
+            let (chunk, b2, c2, l2) = r.chunk_at_byte(i);
+            assert_eq!((id.as_str(), b, c, l), (chunk, b2, c2, l2));
+        }
+        for i in 0..=r.len_chars() {
+            let (id, b, c, l) = r.chunk_id_at_char(i);
+            let (chunk, b2, c2, l2) = r.chunk_at_char(i);
+            assert_eq!((id.as_str(), b, c, l), (chunk, b2, c2, l2));
+        }
+    }
+
+    #[test]
+    fn dump_tree_01() {
+        let r = Rope {
+            root: internal(vec![leaf("Hello\r\n"), leaf("\"there\"!")]).1,
+        };
+        assert_eq!(
+            r.dump_tree(),
+            "internal, bytes 0..15, 2 children, 15 chars, 1 line breaks\n    \
+             leaf, bytes 0..7 (77% full), \"Hello\\r\\n\"\n    \
+             leaf, bytes 7..15 (88% full), \"\\\"there\\\"!\"\n"
+        );
+        assert_eq!(
+            r.dump_tree_dot(),
+            "digraph rope {\n    \
+             node [shape=box, fontname=monospace];\n    \
+             n0 [label=\"internal\\nbytes 0..15\\n2 children, 15 chars, 1 line breaks\"];\n    \
+             n1 [label=\"leaf\\nbytes 0..7 (77% full)\\n\\\"Hello\\\\r\\\\n\\\"\"];\n    \
+             n0 -> n1;\n    \
+             n2 [label=\"leaf\\nbytes 7..15 (88% full)\\n\\\"\\\\\\\"there\\\\\\\"!\\\"\"];\n    \
+             n0 -> n2;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn dump_tree_02() {
+        // Every node gets a line, with deeper nodes indented further.
+        let r = Rope::from_str(&TEXT.repeat(3));
+        let dump = r.dump_tree();
+        let stats = r.tree_stats();
+        assert_eq!(
+            dump.lines().count(),
+            stats.leaf_count + stats.internal_node_count
+        );
+        assert_eq!(
+            dump.lines()
+                .filter(|l| l.trim_start().starts_with("leaf"))
+                .count(),
+            stats.leaf_count
+        );
+        assert!(dump
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with(&" ".repeat(4 * r.depth())));
+        assert!(dump.contains("\"Hello the\""));
+
+        let dot = r.dump_tree_dot();
+        assert_eq!(
+            dot.matches(" -> ").count(),
+            stats.leaf_count + stats.internal_node_count - 1
+        );
     }
 
     #[test]
@@ -2660,6 +5479,303 @@ mod tests {
         r.slice(102..104);
     }
 
+    #[test]
+    fn starts_with_01() {
+        let r = Rope::from_str(TEXT);
+        assert!(r.starts_with(""));
+        assert!(r.starts_with("Hello there!"));
+        assert!(r.starts_with(TEXT));
+        assert!(!r.starts_with("Hello there?"));
+        assert!(!r.starts_with("ello"));
+    }
+
+    #[test]
+    fn starts_with_02() {
+        let r = Rope::from_str("");
+        assert!(r.starts_with(""));
+        assert!(!r.starts_with("a"));
+    }
+
+    #[test]
+    fn ends_with_01() {
+        let r = Rope::from_str(TEXT);
+        assert!(r.ends_with(""));
+        assert!(r.ends_with("みんなさん！"));
+        assert!(r.ends_with(TEXT));
+        assert!(!r.ends_with("みんなさん"));
+        assert!(!r.ends_with(&format!("a{}", TEXT)));
+    }
+
+    #[test]
+    fn ends_with_02() {
+        let r = Rope::from_str("");
+        assert!(r.ends_with(""));
+        assert!(!r.ends_with("a"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_to_01() {
+        let r = Rope::from_str(TEXT);
+        let mut buf = Vec::new();
+        r.write_to(&mut buf).unwrap();
+        assert_eq!(buf, TEXT.as_bytes());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_to_02() {
+        // Errors are passed through, after a partial write.
+        struct FailAfter(usize);
+        impl std::io::Write for FailAfter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(std::io::Error::other("full"));
+                }
+                let n = buf.len().min(self.0);
+                self.0 -= n;
+                Ok(n)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let r = Rope::from_str(TEXT);
+        let err = r.write_to(FailAfter(50)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        r.write_to(FailAfter(TEXT.len())).unwrap();
+    }
+
+    #[test]
+    fn into_bytes_01() {
+        let r = Rope::from_str(TEXT);
+        assert_eq!(r.into_bytes(), TEXT.as_bytes());
+        assert_eq!(Rope::new().into_bytes(), b"");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_changes_to_01() {
+        // Same-length edits only write the changed bytes.
+        let text = TEXT.repeat(20);
+        let mut r = Rope::from_str(&text);
+        let saved = r.clone();
+        let mut file = std::io::Cursor::new(text.clone().into_bytes());
+
+        assert_eq!(r.write_changes_to(&saved, &mut file).unwrap(), 0);
+
+        r.remove(1000..1002);
+        r.insert(1000, "xy");
+        r.remove(20..21);
+        r.insert(20, "ん");
+        r.remove(22..24);
+        assert_eq!(r.len_bytes(), saved.len_bytes());
+
+        let written = r.write_changes_to(&saved, &mut file).unwrap();
+        assert!(written < 10);
+        assert_eq!(file.into_inner(), r.to_string().into_bytes());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_changes_to_02() {
+        // Length changes rewrite from the first change.
+        let text = TEXT.repeat(20);
+        let mut r = Rope::from_str(&text);
+        let saved = r.clone();
+        let mut file = std::io::Cursor::new(text.clone().into_bytes());
+
+        r.insert(1500, "Hello!");
+        let written = r.write_changes_to(&saved, &mut file).unwrap();
+        let start = r.char_to_byte(1500);
+        assert_eq!(written as usize, r.len_bytes() - start);
+        assert_eq!(file.get_ref()[..], r.to_string().as_bytes()[..]);
+
+        // Shrinking, which needs truncation by the caller.
+        let saved = r.clone();
+        r.remove(100..);
+        let written = r.write_changes_to(&saved, &mut file).unwrap();
+        assert_eq!(written, 0);
+        file.get_mut().truncate(r.len_bytes());
+        assert_eq!(file.into_inner(), r.to_string().into_bytes());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_changes_to_03() {
+        // Ropes that don't share any structure.
+        let text = TEXT.repeat(5);
+        let saved = Rope::from_str(&text);
+        let r = Rope::from_str(&text.replace("fine", "good"));
+        let mut file = std::io::Cursor::new(text.into_bytes());
+
+        let written = r.write_changes_to(&saved, &mut file).unwrap();
+        assert_eq!(written, 20);
+        assert_eq!(file.into_inner(), r.to_string().into_bytes());
+    }
+
+    // A hasher whose output depends on how its input is split between
+    // `write()` calls.
+    #[derive(Default)]
+    struct SplitSensitiveHasher(u64);
+
+    impl Hasher for SplitSensitiveHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0 = self.0.wrapping_mul(31).wrapping_add(bytes.len() as u64);
+            for &b in bytes {
+                self.0 = self.0.wrapping_mul(31).wrapping_add(b as u64);
+            }
+        }
+    }
+
+    fn hash<T: std::hash::Hash>(value: &T) -> u64 {
+        let mut hasher = SplitSensitiveHasher::default();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn hash_01() {
+        // Independent of chunking, even with a hasher that isn't.
+        let text = TEXT.repeat(5);
+        let r1 = Rope::from_str(&text);
+        let mut r2 = Rope::new();
+        for c in text.chars().rev() {
+            r2.insert_char(0, c);
+        }
+        assert!(r1.chunks().ne(r2.chunks()));
+        assert_eq!(hash(&r1), hash(&r2));
+
+        // Same as a slice of the whole rope.
+        assert_eq!(hash(&r1), hash(&r1.slice(..)));
+
+        r2.remove(20..21);
+        assert_ne!(hash(&r1), hash(&r2));
+    }
+
+    #[test]
+    fn hash_02() {
+        use std::collections::HashMap;
+
+        let mut map = HashMap::new();
+        map.insert(Rope::from_str(TEXT), 1);
+        map.insert(Rope::from_str("Hello"), 2);
+
+        let mut r = Rope::from_str(&TEXT[50..]);
+        r.insert(0, &TEXT[..50]);
+        assert_eq!(map.get(&r), Some(&1));
+        assert_eq!(map.get(&Rope::from_str("Hell")), None);
+
+        // Not the same as moving text between adjacent values.
+        let a = (Rope::from_str("ab"), Rope::from_str("c"));
+        let b = (Rope::from_str("a"), Rope::from_str("bc"));
+        assert_ne!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn hash_chunks_01() {
+        // Chunks larger than the block size.
+        let text = "Hello there!  How're you doing?  ".repeat(30);
+        let mut expected = SplitSensitiveHasher::default();
+        hash_chunks(std::iter::once(text.as_str()), &mut expected);
+
+        for &split in &[1, 100, 256, 300, 512, 600] {
+            let mut hasher = SplitSensitiveHasher::default();
+            let (a, b) = text.split_at(split);
+            let (b, c) = b.split_at(200);
+            hash_chunks(vec![a, b, "", c].into_iter(), &mut hasher);
+            assert_eq!(hasher.finish(), expected.finish());
+        }
+    }
+
+    #[test]
+    fn cmp_01() {
+        // Compare with differently chunked ropes, so that chunk boundaries
+        // don't line up.
+        fn chunked_differently(text: &str) -> Rope {
+            let mut r = Rope::new();
+            for c in text.chars().rev() {
+                r.insert_char(0, c);
+            }
+            r
+        }
+
+        let texts = [
+            "",
+            "a",
+            "Hello there!",
+            "Hello there!  How're you doing?",
+            "Hello there!  How're you doing?  It's a fine day",
+            "Hello there!  How're you doing?  It's a fine dax",
+            "Hello there!  How're you doinh?",
+            "Hello there!  Howdy",
+            "Hello there!  こんにちは",
+            TEXT,
+        ];
+        for a in &texts {
+            for b in &texts {
+                let r1 = Rope::from_str(a);
+                let r2 = chunked_differently(b);
+                assert_eq!(r1.cmp(&r2), a.cmp(b), "{:?} vs {:?}", a, b);
+                assert_eq!(r2.cmp(&r1), b.cmp(a), "{:?} vs {:?}", b, a);
+                assert_eq!(r1.partial_cmp(&r2), Some(a.cmp(b)));
+            }
+        }
+    }
+
+    #[test]
+    fn cmp_02() {
+        use std::collections::BTreeSet;
+
+        let mut lines: Vec<Rope> = ["pear", "apple", "fig", "apple pie", ""]
+            .iter()
+            .map(|&t| Rope::from_str(t))
+            .collect();
+        lines.sort();
+        assert_eq!(lines, vec!["", "apple", "apple pie", "fig", "pear"]);
+
+        let set: BTreeSet<Rope> = lines.into_iter().collect();
+        assert!(set.contains(&Rope::from_str("fig")));
+        assert!(!set.contains(&Rope::from_str("fi")));
+    }
+
+    #[test]
+    fn count_char_01() {
+        let r = Rope::from_str(TEXT);
+        for &c in &[' ', 'e', '?', 'ん', '！', 'z'] {
+            let expected = TEXT.chars().filter(|&c2| c2 == c).count();
+            assert_eq!(r.count_char(.., c), expected);
+        }
+        assert_eq!(r.count_char(14..31, 'o'), 3);
+        assert_eq!(r.count_char(14..14, 'o'), 0);
+        assert_eq!(r.count_char(90.., 'ん'), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn count_char_02() {
+        let r = Rope::from_str(TEXT);
+        r.count_char(..104, 'a');
+    }
+
+    #[test]
+    fn count_line_breaks_01() {
+        let r = Rope::from_str(TEXT_LINES);
+        assert_eq!(r.count_line_breaks(..), 3);
+        assert_eq!(r.count_line_breaks(..31), 0);
+        assert_eq!(r.count_line_breaks(..32), 1);
+        assert_eq!(r.count_line_breaks(32..), 2);
+
+        let r = Rope::from_str("a\r\nb\rc\u{2028}");
+        assert_eq!(r.count_line_breaks(..), 3);
+        assert_eq!(r.count_char(.., '\n'), 1);
+    }
+
     #[test]
     fn eq_rope_01() {
         let r = Rope::from_str("");
@@ -2719,6 +5835,103 @@ mod tests {
         assert_eq!(s, r);
     }
 
+    #[test]
+    fn eq_rope_08() {
+        use std::borrow::Cow;
+
+        let r = Rope::from_str(TEXT);
+        let borrowed: Cow<str> = Cow::Borrowed(TEXT);
+        let owned: Cow<str> = Cow::Owned(TEXT.into());
+
+        assert_eq!(r, borrowed);
+        assert_eq!(borrowed, r);
+        assert_eq!(r, owned);
+        assert_eq!(owned, r);
+        assert_eq!(r, r.slice(..));
+        assert_eq!(r.slice(..), r);
+        assert_ne!(r, Cow::Borrowed(&TEXT[..50]));
+        assert_ne!(r, r.slice(..50));
+        assert_ne!(r.slice(..50), r);
+    }
+
+    #[test]
+    fn eq_rope_09() {
+        // Same byte lengths, with chunk boundaries that don't line up and
+        // fall within multi-byte chars of the other text.
+        fn chunked_differently(text: &str) -> Rope {
+            let mut r = Rope::new();
+            for c in text.chars().rev() {
+                r.insert_char(0, c);
+            }
+            r
+        }
+
+        let texts = [
+            "Hello there!  How're you doing?  こんにちは、みんなさん！",
+            "Hello there!  How're you doing?  abcdefghijklmnopqrstuvwxyz0123456789",
+            "Hello there!  How're you doing?  こんにちはabcdefghijklmnopqrstu",
+            "Hello there!  How're you doingこんにちは、みんなさん！abc",
+        ];
+        for a in &texts {
+            for b in &texts {
+                assert_eq!(a.len(), b.len());
+                let r1 = Rope::from_str(a);
+                let r2 = chunked_differently(b);
+                assert_eq!(r1 == r2, a == b);
+                assert_eq!(r2 == r1, a == b);
+                assert_eq!(r2 == *a, a == b);
+                assert_eq!(*a == r2, a == b);
+            }
+        }
+    }
+
+    #[test]
+    fn eq_rope_10() {
+        // Clones that share most of their nodes.
+        let r1 = Rope::from_str(&TEXT.repeat(50));
+        let mut r2 = r1.clone();
+        assert_eq!(r1, r2);
+
+        r2.insert(3000, "z");
+        assert_ne!(r1, r2);
+        assert_ne!(r2, r1);
+
+        // Same text, but with the nodes around the edit no longer shared.
+        r2.remove(3000..3001);
+        assert_eq!(r1, r2);
+        assert_eq!(r2, r1);
+
+        r2.remove(3000..3001);
+        r2.insert(3000, "z");
+        assert_ne!(r1, r2);
+        assert_ne!(r2, r1);
+
+        // Shared nodes at different positions.
+        let mut r3 = r1.clone();
+        r3.insert(0, "a");
+        let mut r4 = r1.clone();
+        r4.insert(r1.len_chars(), "a");
+        assert_ne!(r3, r4);
+        assert_ne!(r4, r3);
+    }
+
+    #[test]
+    fn from_01() {
+        use std::borrow::Cow;
+
+        fn make<T: Into<Rope>>(text: T) -> Rope {
+            text.into()
+        }
+
+        let r = Rope::from_str(TEXT);
+        assert_eq!(make(TEXT), r);
+        assert_eq!(make(String::from(TEXT)), r);
+        assert_eq!(make(Cow::Borrowed(TEXT)), r);
+        assert_eq!(make(Cow::Owned(String::from(TEXT))), r);
+        make(String::from(TEXT)).assert_integrity();
+        make(String::from(TEXT)).assert_invariants();
+    }
+
     #[test]
     fn to_string_01() {
         let r = Rope::from_str(TEXT);
@@ -2727,6 +5940,15 @@ mod tests {
         assert_eq!(r, s);
     }
 
+    #[test]
+    fn to_string_02() {
+        let r = Rope::from_str(TEXT);
+        let s: String = r.clone().into();
+
+        assert_eq!(r, s);
+        assert_eq!(String::from(Rope::new()), "");
+    }
+
     #[test]
     fn to_cow_01() {
         use std::borrow::Cow;
@@ -2767,6 +5989,8 @@ mod tests {
 
         assert_eq!(r1, r2);
         assert_eq!(s, r2);
+        r2.assert_integrity();
+        r2.assert_invariants();
     }
 
     #[test]
@@ -2776,6 +6000,8 @@ mod tests {
         let r2: Rope = s.into();
 
         assert_eq!(s, r2);
+        r2.assert_integrity();
+        r2.assert_invariants();
     }
 
     #[test]
@@ -2785,6 +6011,8 @@ mod tests {
         let r2: Rope = s.into();
 
         assert_eq!(s, r2);
+        r2.assert_integrity();
+        r2.assert_invariants();
     }
 
     #[test]
@@ -2794,6 +6022,42 @@ mod tests {
         let r2: Rope = s.into();
 
         assert_eq!(s, r2);
+        r2.assert_integrity();
+        r2.assert_invariants();
+    }
+
+    #[test]
+    fn from_rope_slice_05() {
+        // Short slices.
+        let r1 = Rope::from_str(TEXT);
+        let s = r1.slice(14..17);
+        let r2 = Rope::from(s);
+        let s2: String = s.into();
+
+        assert_eq!(r2, "How");
+        assert_eq!(s2, "How");
+        r2.assert_integrity();
+        r2.assert_invariants();
+    }
+
+    #[test]
+    fn from_rope_slice_06() {
+        // Empty slices at the start, middle, and end.
+        let r1 = Rope::from_str(TEXT);
+        let len = r1.len_chars();
+        for &idx in &[0, len / 2, len] {
+            let r2 = Rope::from(r1.slice(idx..idx));
+
+            assert_eq!(r2, "");
+            assert_eq!(r2.len_chars(), 0);
+            r2.assert_integrity();
+            r2.assert_invariants();
+        }
+
+        let r3 = Rope::from(r1.slice(len..));
+        assert_eq!(r3, "");
+        r3.assert_integrity();
+        r3.assert_invariants();
     }
 
     #[test]
@@ -2804,5 +6068,159 @@ mod tests {
         assert_eq!(r1, r2);
     }
 
+    #[test]
+    fn from_iter_02() {
+        let r1 = Rope::from_str(TEXT);
+        let r2: Rope = r1.chars().collect();
+        assert_eq!(r1, r2);
+        r2.assert_integrity();
+        r2.assert_invariants();
+
+        let r3: Rope = TEXT.chars().filter(|c| !c.is_whitespace()).collect();
+        let expected: String = TEXT.chars().filter(|c| !c.is_whitespace()).collect();
+        assert_eq!(r3, expected);
+        r3.assert_integrity();
+        r3.assert_invariants();
+
+        let r4: Rope = "".chars().collect();
+        assert_eq!(r4, "");
+    }
+
+    #[test]
+    fn from_iter_03() {
+        // CRLF pairs split across leaf-sized pieces.
+        let text = "a\r\n".repeat(20) + &"\r\n".repeat(20);
+        let r: Rope = text.chars().collect();
+        assert_eq!(r, text.as_str());
+        assert_eq!(r.len_lines(), 41);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn extend_01() {
+        let mut r = Rope::from_str(&TEXT[..50]);
+        r.extend(vec![&TEXT[50..60], "", &TEXT[60..]]);
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let mut r = Rope::from_str(&TEXT[..50]);
+        r.extend(TEXT[50..].chars());
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let mut r = Rope::new();
+        r.extend(TEXT.lines().map(String::from));
+        r.extend(std::iter::once(std::borrow::Cow::Borrowed("!")));
+        assert_eq!(r, format!("{}!", TEXT));
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn extend_02() {
+        // CRLF pairs split between the rope and the appended text.
+        let mut r = Rope::from_str("Hello\r");
+        r.extend(vec!["\nworld\r", "\n"]);
+        assert_eq!(r, "Hello\r\nworld\r\n");
+        assert_eq!(r.len_lines(), 3);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let mut r = Rope::from_str(TEXT);
+        r.extend(Vec::<&str>::new());
+        assert_eq!(r, TEXT);
+    }
+
+    #[test]
+    fn add_01() {
+        let r = Rope::from_str(&TEXT[..50]) + &TEXT[50..];
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let r = Rope::from_str(&TEXT[..50]) + Rope::from_str(&TEXT[50..]);
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let r = Rope::new() + "" + Rope::new();
+        assert_eq!(r, "");
+    }
+
+    #[test]
+    fn add_assign_01() {
+        let mut r = Rope::from_str("Hello\r");
+        r += "\nworld";
+        r += Rope::from_str("\r");
+        r += "\n";
+        assert_eq!(r, "Hello\r\nworld\r\n");
+        assert_eq!(r.len_lines(), 3);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn sum_01() {
+        let pieces: Vec<_> = TEXT.split_inclusive(' ').collect();
+
+        let r: Rope = pieces.iter().cloned().sum();
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let r: Rope = pieces.iter().map(|p| p.to_string()).sum();
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let r: Rope = pieces.iter().map(|p| Rope::from_str(p)).sum();
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn sum_02() {
+        let r: Rope = Vec::<&str>::new().into_iter().sum();
+        assert_eq!(r, "");
+        let r: Rope = Vec::<Rope>::new().into_iter().sum();
+        assert_eq!(r, "");
+        r.assert_integrity();
+        r.assert_invariants();
+
+        // A CRLF pair split between ropes.
+        let r: Rope = vec![Rope::from_str("a\r"), Rope::from_str("\nb")]
+            .into_iter()
+            .sum();
+        assert_eq!(r, "a\r\nb");
+        assert_eq!(r.len_lines(), 2);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn fmt_write_01() {
+        use std::fmt::Write;
+
+        let greeting = "こんにちは";
+        let mut r = Rope::from_str(TEXT);
+        for i in 0..20 {
+            write!(r, "{}: {}\r", i, greeting).unwrap();
+            r.write_char('\n').unwrap();
+        }
+
+        let mut s = String::from(TEXT);
+        for i in 0..20 {
+            write!(s, "{}: {}\r\n", i, greeting).unwrap();
+        }
+        assert_eq!(r, s);
+        assert_eq!(r.len_lines(), 21);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
     // Iterator tests are in the iter module
 }
@@ -1,11 +1,20 @@
-use std;
-use std::sync::Arc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
 
 use smallvec::SmallVec;
 
 use crlf;
 use rope::Rope;
-use tree::{Node, NodeChildren, NodeText, MAX_BYTES, MAX_CHILDREN};
+use slice::RopeSlice;
+use str_utils::{count_chars, count_line_breaks};
+use tree::{
+    assert_count_fits, Arc, Node, NodeChildren, NodeText, TextInfo, MAX_BYTES, MAX_CHILDREN,
+    MIN_CHILDREN,
+};
 
 /// An efficient incremental `Rope` builder.
 ///
@@ -40,22 +49,63 @@ use tree::{Node, NodeChildren, NodeText, MAX_BYTES, MAX_CHILDREN};
 ///
 /// assert_eq!(rope, "Hello world!\nHow's it going?");
 /// ```
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct RopeBuilder {
-    stack: SmallVec<[Arc<Node>; 4]>,
+    // The text before the last appended rope or slice, if any.
+    prefix: Option<Rope>,
+    // The leaves of the text appended since then, which are only put
+    // together into a tree when finishing.
+    leaves: Vec<(TextInfo, Arc<Node>)>,
+    // The combined text info of `leaves`.
+    leaves_info: TextInfo,
     buffer: String,
+    // The size that leaves are filled up to.
+    leaf_size: usize,
+    // An incomplete utf8 sequence left over from the last `io::Write` call.
+    partial: SmallVec<[u8; 4]>,
 }
 
 impl RopeBuilder {
     /// Creates a new RopeBuilder, ready for input.
     pub fn new() -> Self {
         RopeBuilder {
-            stack: {
-                let mut stack = SmallVec::new();
-                stack.push(Arc::new(Node::new()));
-                stack
-            },
+            prefix: None,
+            leaves: Vec::new(),
+            leaves_info: TextInfo::new(),
             buffer: String::new(),
+            leaf_size: MAX_BYTES,
+            partial: SmallVec::new(),
+        }
+    }
+
+    /// Creates a new RopeBuilder that fills the leaves of the built `Rope`
+    /// with up to `leaf_size` bytes of text each.
+    ///
+    /// By default, leaves are filled as much as they can be, which gives
+    /// the fastest iteration and the least memory overhead.  Smaller leaves
+    /// make edits a little cheaper, since less text needs to be moved
+    /// around in each leaf, at the cost of more leaves.  This only affects
+    /// how the `Rope` is built: later edits split and merge leaves as
+    /// usual.
+    ///
+    /// Leaves can't be larger than their default size (a bit under 1KB on
+    /// 64-bit platforms), so `leaf_size` is clamped to that.  It's also
+    /// clamped to a minimum of 4 bytes, so that any utf8 char fits.
+    ///
+    /// # Example
+    /// ```
+    /// # use ropey::RopeBuilder;
+    /// #
+    /// let mut builder = RopeBuilder::with_leaf_size(256);
+    /// builder.append(&"Hello world!\n".repeat(100));
+    /// let rope = builder.finish();
+    ///
+    /// assert!(rope.chunks().all(|chunk| chunk.len() <= 256));
+    /// ```
+    pub fn with_leaf_size(leaf_size: usize) -> Self {
+        RopeBuilder {
+            leaf_size: leaf_size.clamp(4, MAX_BYTES),
+            ..RopeBuilder::new()
         }
     }
 
@@ -66,21 +116,238 @@ impl RopeBuilder {
     /// desired, but larger chunks are more efficient.
     ///
     /// `chunk` must be valid utf8 text.
+    ///
+    /// # Panics
+    ///
+    /// With the `u32_counts` feature, panics if the text would be 4 GB or
+    /// larger.
     pub fn append(&mut self, chunk: &str) {
         self.append_internal(chunk, false);
     }
 
+    /// Appends `ch` to the end of the in-progress `Rope`.
+    ///
+    /// This is as efficient as appending larger chunks, since the text is
+    /// buffered into leaf-sized pieces either way.
+    #[inline]
+    pub fn append_char(&mut self, ch: char) {
+        self.append(ch.encode_utf8(&mut [0u8; 4]));
+    }
+
+    /// Appends utf8 bytes to the end of the in-progress `Rope`.
+    ///
+    /// The bytes are validated as they come in, and a multi-byte utf8
+    /// sequence can be split across calls: any incomplete sequence at the
+    /// end of `bytes` is held onto until the rest of it arrives.  This makes
+    /// it easy to build a `Rope` from byte sources like sockets and
+    /// decompressors.  It's the same as `write_all()` from this type's
+    /// `io::Write` impl.
+    ///
+    /// Note: `finish()` discards any incomplete sequence at the end, so
+    /// call `io::Write::flush()` first if this needs to be checked.
+    ///
+    /// # Example
+    /// ```
+    /// # use ropey::RopeBuilder;
+    /// #
+    /// let bytes = "こんにちは".as_bytes();
+    ///
+    /// let mut builder = RopeBuilder::new();
+    /// builder.append_bytes(&bytes[..4]).unwrap();
+    /// builder.append_bytes(&bytes[4..]).unwrap();
+    ///
+    /// assert_eq!(builder.finish(), "こんにちは");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `bytes` contains invalid utf8, returns an IO error with kind
+    /// `InvalidData`.  All of the text before the invalid bytes is still
+    /// appended.
+    #[cfg(feature = "std")]
+    pub fn append_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        io::Write::write_all(self, bytes)
+    }
+
+    /// Appends the text of `rope` to the end of the in-progress `Rope`.
+    ///
+    /// Rather than copying the text, this shares `rope`'s nodes with the
+    /// built `Rope` wherever it can, much like `Rope::append()`.  This
+    /// makes it cheap to assemble a `Rope` from large pieces of other
+    /// ropes.
+    ///
+    /// # Example
+    /// ```
+    /// # use ropey::{Rope, RopeBuilder};
+    /// #
+    /// let body = Rope::from_str("Hello world!\n");
+    ///
+    /// let mut builder = RopeBuilder::new();
+    /// builder.append("Header\n");
+    /// builder.append_rope(&body);
+    /// builder.append("Footer\n");
+    ///
+    /// assert_eq!(builder.finish(), "Header\nHello world!\nFooter\n");
+    /// ```
+    ///
+    /// Runs in O(log N) time.
+    pub fn append_rope(&mut self, rope: &Rope) {
+        self.append_slice(rope.slice(..));
+    }
+
+    /// Appends the text of `slice` to the end of the in-progress `Rope`.
+    ///
+    /// Like `append_rope()`, this shares the nodes of the slice's `Rope`
+    /// with the built `Rope` wherever it can.
+    ///
+    /// Runs in O(log N) time.
+    pub fn append_slice(&mut self, slice: RopeSlice) {
+        if slice.len_bytes() < MAX_BYTES {
+            // Too small to be worth splicing in, so just copy the text.
+            for chunk in slice.chunks() {
+                self.append(chunk);
+            }
+        } else {
+            let mut rope = self.take_rope();
+            rope.append(Rope::from(slice));
+            self.prefix = Some(rope);
+        }
+    }
+
+    /// Total number of bytes appended so far.
+    ///
+    /// This doesn't include any incomplete utf8 sequence from `io::Write`
+    /// that's still waiting for the rest of its bytes.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_bytes(&self) -> usize {
+        let prefix_bytes = self.prefix.as_ref().map_or(0, Rope::len_bytes);
+        prefix_bytes + self.leaves_info.bytes as usize + self.buffer.len()
+    }
+
+    /// Total number of chars appended so far.
+    ///
+    /// Together with `len_bytes()` and `len_lines()`, this makes it easy to
+    /// record the positions of things while building, e.g. the start of
+    /// each record when building a rope from a stream of records:
+    ///
+    /// ```
+    /// # use ropey::RopeBuilder;
+    /// #
+    /// let mut builder = RopeBuilder::new();
+    /// let mut record_starts = Vec::new();
+    ///
+    /// for record in &["Hello", "こんにちは", "Hi"] {
+    ///     record_starts.push(builder.len_chars());
+    ///     builder.append(record);
+    ///     builder.append(";");
+    /// }
+    ///
+    /// let rope = builder.finish();
+    /// assert_eq!(record_starts, [0, 6, 12]);
+    /// assert_eq!(rope.slice(record_starts[1]..), "こんにちは;Hi;");
+    /// ```
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn len_chars(&self) -> usize {
+        let prefix_chars = self.prefix.as_ref().map_or(0, Rope::len_chars);
+        prefix_chars + self.leaves_info.chars as usize + count_chars(&self.buffer)
+    }
+
+    /// Total number of lines appended so far.
+    ///
+    /// Like `Rope::len_lines()`, this counts the line after the last line
+    /// break, even when it's empty.
+    ///
+    /// Runs in O(log N) time.
+    pub fn len_lines(&self) -> usize {
+        let mut line_breaks =
+            self.leaves_info.line_breaks as usize + count_line_breaks(&self.buffer);
+        if let Some(ref prefix) = self.prefix {
+            line_breaks += prefix.len_lines() - 1;
+
+            // A CRLF pair split between the prefix and the text after it.
+            let next_text = match self.leaves.first() {
+                Some((_, leaf)) => leaf.leaf_text(),
+                None => &self.buffer,
+            };
+            if next_text.starts_with('\n') && prefix.char(prefix.len_chars() - 1) == '\r' {
+                line_breaks -= 1;
+            }
+        }
+        line_breaks + 1
+    }
+
     /// Finishes the build, and returns the `Rope`.
     ///
     /// Note: this method consumes the builder.  If you want to continue
     /// building other ropes with the same prefix, you can clone the builder
-    /// before calling `finish()`.
+    /// before calling `finish()`.  To build more ropes with the same
+    /// builder, see `finish_reset()`.
     pub fn finish(mut self) -> Rope {
         // Append the last leaf
         self.append_internal("", true);
         self.finish_internal()
     }
 
+    /// Finishes the build and returns the `Rope`, leaving the builder empty
+    /// and ready for building another `Rope`.
+    ///
+    /// The builder keeps its internal buffers, so building many ropes in a
+    /// loop with the same builder avoids reallocating them for each one.
+    ///
+    /// # Example
+    /// ```
+    /// # use ropey::RopeBuilder;
+    /// #
+    /// let mut builder = RopeBuilder::new();
+    /// let mut ropes = Vec::new();
+    ///
+    /// for name in &["Alice", "Bob"] {
+    ///     builder.append("Hello ");
+    ///     builder.append(name);
+    ///     builder.append("!");
+    ///     ropes.push(builder.finish_reset());
+    /// }
+    ///
+    /// assert_eq!(ropes[0], "Hello Alice!");
+    /// assert_eq!(ropes[1], "Hello Bob!");
+    /// ```
+    pub fn finish_reset(&mut self) -> Rope {
+        // Append the last leaf
+        self.append_internal("", true);
+        let rope = self.finish_internal();
+        self.reset();
+        rope
+    }
+
+    /// Discards the text appended so far, leaving the builder empty and
+    /// ready for building a new `Rope`.
+    ///
+    /// Like `finish_reset()`, this keeps the builder's internal buffers, as
+    /// well as its leaf size.
+    pub fn reset(&mut self) {
+        self.prefix = None;
+        self.leaves.clear();
+        self.leaves_info = TextInfo::new();
+        self.buffer.clear();
+        self.partial.clear();
+    }
+
+    /// Finishes the text appended so far into a `Rope`, and resets the
+    /// builder.
+    ///
+    /// Unlike `finish_reset()`, this keeps any incomplete utf8 sequence from
+    /// `io::Write` in the builder, to be completed by later writes.
+    pub(crate) fn take_rope(&mut self) -> Rope {
+        let partial = core::mem::take(&mut self.partial);
+        let rope = self.finish_reset();
+        self.partial = partial;
+        rope
+    }
+
     /// Builds a rope all at once from a single string slice.
     ///
     /// This avoids the creation and use of the internal buffer.  This is
@@ -95,6 +362,7 @@ impl RopeBuilder {
 
     // Internal workings of `append()`.
     fn append_internal(&mut self, chunk: &str, is_last_chunk: bool) {
+        assert_count_fits(self.len_bytes(), chunk.len());
         let mut chunk = chunk;
 
         // Repeatedly chop text off the end of the input, creating
@@ -108,39 +376,71 @@ impl RopeBuilder {
             match leaf_text {
                 NextText::None => break,
                 NextText::UseBuffer => {
-                    let leaf_text = NodeText::from_str(&self.buffer);
-                    self.append_leaf_node(Arc::new(Node::Leaf(leaf_text)));
+                    let info = TextInfo::from_str(&self.buffer);
+                    let leaf = new_leaf(&self.buffer, info);
+                    self.push_leaf(leaf);
                     self.buffer.clear();
                 }
                 NextText::String(s) => {
-                    self.append_leaf_node(Arc::new(Node::Leaf(NodeText::from_str(s))));
+                    self.push_leaf(new_leaf(s, TextInfo::from_str(s)));
                 }
             }
         }
     }
 
-    // Internal workings of `finish()`.
-    fn finish_internal(mut self) -> Rope {
-        // Zip up all the remaining nodes on the stack
-        let mut stack_idx = self.stack.len() - 1;
-        while stack_idx >= 1 {
-            let node = self.stack.pop().unwrap();
-            if let Node::Internal(ref mut children) = *Arc::make_mut(&mut self.stack[stack_idx - 1])
-            {
-                children.push((node.text_info(), node));
-            } else {
-                unreachable!();
-            }
-            stack_idx -= 1;
+    /// Appends `chunk` as a leaf of its own, using `info` as its text info
+    /// rather than computing it.
+    ///
+    /// Chunks too large for a leaf are appended with `append()` instead.
+    ///
+    /// # Safety
+    ///
+    /// `info` must be the text info of `chunk`, and `chunk` must not start
+    /// with the second half of a CRLF pair that was split from the text
+    /// appended before it.
+    pub(crate) unsafe fn append_leaf_unchecked(&mut self, chunk: &str, info: TextInfo) {
+        debug_assert_eq!(info, TextInfo::from_str(chunk));
+        assert_count_fits(self.len_bytes(), chunk.len());
+        if chunk.len() > MAX_BYTES {
+            self.append(chunk);
+            return;
+        } else if chunk.is_empty() {
+            return;
         }
 
-        // Get root and fix any right-side nodes with too few children.
-        let mut root = self.stack.pop().unwrap();
-        Arc::make_mut(&mut root).zip_fix_right();
+        // Put any text still in the buffer in a leaf of its own first.
+        if !self.buffer.is_empty() {
+            let info = TextInfo::from_str(&self.buffer);
+            let leaf = new_leaf(&self.buffer, info);
+            self.push_leaf(leaf);
+            self.buffer.clear();
+        }
+        self.push_leaf(new_leaf(chunk, info));
+    }
+
+    /// Appends an already-built leaf, sharing it with the built `Rope`.
+    ///
+    /// Any text in the buffer must be pushed first, and the leaf must not
+    /// start with the second half of a CRLF pair that was split from the
+    /// text before it.
+    pub(crate) fn push_leaf(&mut self, leaf: (TextInfo, Arc<Node>)) {
+        self.leaves_info += leaf.0;
+        self.leaves.push(leaf);
+    }
+
+    // Internal workings of `finish()`.  Takes the leaves and prefix out of
+    // the builder.
+    fn finish_internal(&mut self) -> Rope {
+        let mut rope = Rope {
+            root: self.build_tree(),
+        };
+
+        // Put back any text from before the last appended rope.
+        if let Some(mut prefix) = self.prefix.take() {
+            prefix.append(rope);
+            rope = prefix;
+        }
 
-        // Create the rope, make sure it's well-formed, and return it.
-        let mut rope = Rope { root: root };
-        rope.pull_up_singular_nodes();
         return rope;
     }
 
@@ -152,16 +452,16 @@ impl RopeBuilder {
         is_last_chunk: bool,
     ) -> (NextText<'a>, &'a str) {
         assert!(
-            self.buffer.len() < MAX_BYTES,
+            self.buffer.len() < self.leaf_size,
             "RopeBuilder: buffer is already full when receiving a chunk! \
              This should never happen!",
         );
 
         // Simplest case: empty buffer and enough in `text` for a full
         // chunk, so just chop a chunk off from `text` and use that.
-        if self.buffer.is_empty() && text.len() >= MAX_BYTES {
+        if self.buffer.is_empty() && text.len() >= self.leaf_size {
             let split_idx = crlf::find_good_split(
-                MAX_BYTES.min(text.len() - 1), // - 1 to avoid CRLF split.
+                self.leaf_size.min(text.len() - 1), // - 1 to avoid CRLF split.
                 text.as_bytes(),
                 true,
             );
@@ -169,9 +469,9 @@ impl RopeBuilder {
         }
         // If the buffer + `text` is enough for a full chunk, push enough
         // of `text` onto the buffer to fill it and use that.
-        else if (text.len() + self.buffer.len()) >= MAX_BYTES {
+        else if (text.len() + self.buffer.len()) >= self.leaf_size {
             let mut split_idx =
-                crlf::find_good_split(MAX_BYTES - self.buffer.len(), text.as_bytes(), true);
+                crlf::find_good_split(self.leaf_size - self.buffer.len(), text.as_bytes(), true);
             if split_idx == text.len() && text.as_bytes()[text.len() - 1] == 0x0D {
                 // Avoid CRLF split.
                 split_idx -= 1;
@@ -202,53 +502,211 @@ impl RopeBuilder {
         }
     }
 
-    fn append_leaf_node(&mut self, leaf: Arc<Node>) {
-        let last = self.stack.pop().unwrap();
-        match *last {
-            Node::Leaf(_) => {
-                if last.leaf_text().is_empty() {
-                    self.stack.push(leaf);
-                } else {
-                    let mut children = NodeChildren::new();
-                    children.push((last.text_info(), last));
-                    children.push((leaf.text_info(), leaf));
-                    self.stack.push(Arc::new(Node::Internal(children)));
-                }
+    // Builds a tree of internal nodes on top of the accumulated leaves,
+    // one level at a time, leaving the leaves empty.
+    //
+    // Each internal node gets as many children as it can hold, except for
+    // the last two of each level, which share their children when needed
+    // so that neither has too few.
+    fn build_tree(&mut self) -> Arc<Node> {
+        self.leaves_info = TextInfo::new();
+        if self.leaves.is_empty() {
+            return Rope::new().root;
+        } else if self.leaves.len() == 1 {
+            return self.leaves.pop().unwrap().1;
+        }
+
+        let mut level = group_nodes(self.leaves.drain(..));
+        while level.len() > 1 {
+            level = group_nodes(level.into_iter());
+        }
+        level.pop().unwrap().1
+    }
+}
+
+fn new_leaf(text: &str, info: TextInfo) -> (TextInfo, Arc<Node>) {
+    (info, Arc::new(Node::Leaf(NodeText::from_str(text))))
+}
+
+// Groups `nodes` into parent nodes, as described in `build_tree()`.
+fn group_nodes<I>(nodes: I) -> Vec<(TextInfo, Arc<Node>)>
+where
+    I: ExactSizeIterator<Item = (TextInfo, Arc<Node>)>,
+{
+    let mut nodes = nodes;
+    let mut remaining = nodes.len();
+    let mut parents = Vec::with_capacity(remaining / MAX_CHILDREN + 1);
+    while remaining > 0 {
+        let child_count = if remaining <= MAX_CHILDREN {
+            remaining
+        } else if remaining < (MAX_CHILDREN + MIN_CHILDREN) {
+            remaining - MIN_CHILDREN
+        } else {
+            MAX_CHILDREN
+        };
+
+        let mut children = NodeChildren::new();
+        for node in nodes.by_ref().take(child_count) {
+            children.push(node);
+        }
+        let info = children.combined_info();
+        parents.push((info, Arc::new(Node::Internal(children))));
+        remaining -= child_count;
+    }
+    parents
+}
+
+impl Default for RopeBuilder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Appends the written text to the end of the in-progress `Rope`.
+///
+/// This allows `write!()` to be used to stream formatted text into a rope
+/// without formatting it into a temporary `String` first.
+///
+/// # Example
+/// ```
+/// # use ropey::RopeBuilder;
+/// use std::fmt::Write;
+///
+/// let mut builder = RopeBuilder::new();
+/// for i in 1..=3 {
+///     writeln!(builder, "Line {}", i).unwrap();
+/// }
+///
+/// assert_eq!(builder.finish(), "Line 1\nLine 2\nLine 3\n");
+/// ```
+impl fmt::Write for RopeBuilder {
+    #[inline]
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.append(text);
+        Ok(())
+    }
+
+    #[inline]
+    fn write_char(&mut self, ch: char) -> fmt::Result {
+        self.append_char(ch);
+        Ok(())
+    }
+}
+
+/// Appends utf8 bytes to the in-progress `Rope`.
+///
+/// Multi-byte utf8 sequences may be split across calls to `write()`, so this
+/// can be used to build a `Rope` straight from e.g. `io::copy()`.
+///
+/// Note: text appended with `append()` in the middle of a split sequence is
+/// inserted before that sequence, so avoid mixing the two.
+///
+/// # Errors
+///
+/// - If the written data is not valid utf8, returns an error of kind
+///   `InvalidData`.  All data before the invalid bytes is still appended.
+/// - If `flush()` is called in the middle of a split sequence, returns an
+///   error of kind `InvalidData`.  `finish()` discards any such incomplete
+///   sequence, so call `flush()` first if this needs to be checked.
+#[cfg(feature = "std")]
+impl io::Write for RopeBuilder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut partial = core::mem::take(&mut self.partial);
+        let result = write_utf8(&mut partial, buf, |text| self.append(text));
+        self.partial = partial;
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        check_no_partial(&self.partial)
+    }
+}
+
+/// The guts of `io::Write` for the types that accept utf8 bytes.
+///
+/// Passes the valid utf8 text in `buf` to `append`, using `partial` to hold
+/// incomplete sequences between calls.
+#[cfg(feature = "std")]
+pub(crate) fn write_utf8<F: FnMut(&str)>(
+    partial: &mut SmallVec<[u8; 4]>,
+    buf: &[u8],
+    mut append: F,
+) -> io::Result<usize> {
+    let mut consumed = 0;
+
+    // Complete any sequence left over from the previous call.
+    if !partial.is_empty() {
+        let partial_len = partial.len();
+        let taken = (4 - partial_len).min(buf.len());
+        let mut seq: SmallVec<[u8; 8]> = SmallVec::from_slice(partial);
+        seq.extend_from_slice(&buf[..taken]);
+
+        let valid_count = match core::str::from_utf8(&seq) {
+            Ok(_) => seq.len(),
+            Err(e) if e.valid_up_to() > 0 => e.valid_up_to(),
+            Err(e) if e.error_len().is_none() => {
+                // Still incomplete.
+                *partial = SmallVec::from_slice(&seq);
+                return Ok(taken);
             }
+            Err(_) => return Err(invalid_utf8()),
+        };
 
-            Node::Internal(_) => {
-                self.stack.push(last);
-                let mut left = leaf;
-                let mut stack_idx = (self.stack.len() - 1) as isize;
-                loop {
-                    if stack_idx < 0 {
-                        // We're above the root, so do a root split.
-                        let mut children = NodeChildren::new();
-                        children.push((left.text_info(), left));
-                        self.stack.insert(0, Arc::new(Node::Internal(children)));
-                        break;
-                    } else if self.stack[stack_idx as usize].child_count() < (MAX_CHILDREN - 1) {
-                        // There's room to add a child, so do that.
-                        Arc::make_mut(&mut self.stack[stack_idx as usize])
-                            .children_mut()
-                            .push((left.text_info(), left));
-                        break;
-                    } else {
-                        // Not enough room to fit a child, so split.
-                        left = Arc::new(Node::Internal(
-                            Arc::make_mut(&mut self.stack[stack_idx as usize])
-                                .children_mut()
-                                .push_split((left.text_info(), left)),
-                        ));
-                        std::mem::swap(&mut left, &mut self.stack[stack_idx as usize]);
-                        stack_idx -= 1;
-                    }
-                }
+        append(core::str::from_utf8(&seq[..valid_count]).unwrap());
+        partial.clear();
+        consumed = valid_count - partial_len;
+    }
+
+    let rest = &buf[consumed..];
+    match core::str::from_utf8(rest) {
+        Ok(text) => {
+            append(text);
+            Ok(buf.len())
+        }
+        Err(e) => {
+            let valid_count = e.valid_up_to();
+            // The unsafe block here is reinterpreting the bytes as utf8.
+            // This is safe because the bytes being reinterpreted have
+            // already been validated as utf8 just above.
+            append(unsafe { core::str::from_utf8_unchecked(&rest[..valid_count]) });
+            consumed += valid_count;
+
+            if e.error_len().is_none() {
+                // Incomplete sequence at the end, so save it for next time.
+                *partial = SmallVec::from_slice(&rest[valid_count..]);
+                Ok(buf.len())
+            } else if consumed > 0 {
+                // Report the error on the next call.
+                Ok(consumed)
+            } else {
+                Err(invalid_utf8())
             }
         }
     }
 }
 
+/// Returns an error if `partial` holds an incomplete utf8 sequence.
+#[cfg(feature = "std")]
+pub(crate) fn check_no_partial(partial: &[u8]) -> io::Result<()> {
+    if partial.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream ended in the middle of a utf8 sequence",
+        ))
+    }
+}
+
+#[cfg(feature = "std")]
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
 enum NextText<'a> {
     None,
     UseBuffer,
@@ -292,4 +750,364 @@ mod tests {
         r.assert_integrity();
         r.assert_invariants();
     }
+
+    #[test]
+    fn default_01() {
+        let mut b = RopeBuilder::default();
+        b.append(TEXT);
+        let r = b.finish();
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let r = RopeBuilder::default().finish();
+        assert_eq!(r, "");
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn append_char_01() {
+        let mut b = RopeBuilder::new();
+        for c in TEXT.chars() {
+            b.append_char(c);
+        }
+        let r = b.finish();
+
+        assert_eq!(r, TEXT);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn fmt_write_01() {
+        use std::fmt::Write;
+
+        let mut b = RopeBuilder::new();
+        b.append("Start\r");
+        for i in 0..20 {
+            writeln!(b, "{}: {:?}", i, "こんにちは").unwrap();
+        }
+        b.write_char('!').unwrap();
+        let r = b.finish();
+
+        let mut text = String::from("Start\r");
+        for i in 0..20 {
+            writeln!(text, "{}: {:?}", i, "こんにちは").unwrap();
+        }
+        text.push('!');
+        assert_eq!(r, text.as_str());
+        assert_eq!(r.len_lines(), 22);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn len_01() {
+        let pieces = [
+            "Hello\r",
+            "\nthere",
+            "こんにちは\r",
+            "\r",
+            "\n",
+            "",
+            "\u{2028}hi",
+        ];
+        let mut b = RopeBuilder::new();
+        let mut text = String::new();
+        for (i, piece) in pieces.iter().cycle().take(40).enumerate() {
+            match i % 3 {
+                0 => b.append(piece),
+                1 => b.append_rope(&Rope::from_str(&piece.repeat(2))),
+                _ => b.append_slice(Rope::from_str(piece).slice(..)),
+            }
+            text.push_str(piece);
+            if i % 3 == 1 {
+                text.push_str(piece);
+            }
+
+            let r = Rope::from_str(&text);
+            assert_eq!(b.len_bytes(), r.len_bytes());
+            assert_eq!(b.len_chars(), r.len_chars());
+            assert_eq!(b.len_lines(), r.len_lines());
+        }
+        assert_eq!(b.finish(), text.as_str());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn len_02() {
+        // Incomplete utf8 isn't counted.
+        use std::io::Write;
+
+        let mut b = RopeBuilder::new();
+        assert_eq!((b.len_bytes(), b.len_chars(), b.len_lines()), (0, 0, 1));
+        b.write_all(&"aん".as_bytes()[..3]).unwrap();
+        assert_eq!((b.len_bytes(), b.len_chars(), b.len_lines()), (1, 1, 1));
+        b.finish_reset();
+        assert_eq!((b.len_bytes(), b.len_chars(), b.len_lines()), (0, 0, 1));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_01() {
+        // Multi-byte sequences split across writes.
+        use std::io::Write;
+
+        for size in 1..8 {
+            let mut b = RopeBuilder::new();
+            for chunk in TEXT.as_bytes().chunks(size) {
+                b.write_all(chunk).unwrap();
+            }
+            b.flush().unwrap();
+            let r = b.finish();
+
+            assert_eq!(r, TEXT);
+            r.assert_integrity();
+            r.assert_invariants();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_02() {
+        let mut b = RopeBuilder::new();
+        std::io::copy(&mut TEXT.as_bytes(), &mut b).unwrap();
+        assert_eq!(b.finish(), TEXT);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_03() {
+        // Invalid utf8.
+        use std::io::Write;
+
+        let mut b = RopeBuilder::new();
+        assert_eq!(b.write(b"abc\xFFdef").unwrap(), 3);
+        assert!(b.write(b"\xFFdef").is_err());
+        assert_eq!(b.finish(), "abc");
+
+        let mut b = RopeBuilder::new();
+        b.write_all("こ".as_bytes()).unwrap();
+        b.write_all(&"ん".as_bytes()[..2]).unwrap();
+        assert!(b.write_all(b"x").is_err());
+
+        let mut b = RopeBuilder::new();
+        b.write_all(&"ん".as_bytes()[..1]).unwrap();
+        assert!(b.flush().is_err());
+        assert_eq!(b.finish(), "");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn finish_01() {
+        // The tree is as shallow as it can be.
+        use std::io::Write;
+
+        for len in 0..300 {
+            let text = TEXT.repeat(4);
+            let text = &text[..text.char_indices().nth(len).unwrap().0];
+            let mut b = RopeBuilder::new();
+            for chunk in text.as_bytes().chunks(7) {
+                b.write_all(chunk).unwrap();
+            }
+            let r = b.finish();
+
+            assert_eq!(r, text);
+            r.assert_integrity();
+            r.assert_invariants();
+
+            let mut max_leaves = 1;
+            let mut depth = 0;
+            while max_leaves < r.chunks().count() {
+                max_leaves *= MAX_CHILDREN;
+                depth += 1;
+            }
+            assert_eq!(r.root.depth(), depth);
+        }
+    }
+
+    #[test]
+    fn with_leaf_size_01() {
+        for leaf_size in 0..(MAX_BYTES * 2) {
+            let mut b = RopeBuilder::with_leaf_size(leaf_size);
+            for chunk in TEXT.split_inclusive(' ') {
+                b.append(chunk);
+            }
+            let r = b.finish_reset();
+
+            assert_eq!(r, TEXT);
+            r.assert_integrity();
+            r.assert_invariants();
+            let max_len = leaf_size.clamp(4, MAX_BYTES);
+            assert!(r.chunks().all(|chunk| chunk.len() <= max_len));
+            assert!(r.chunks().any(|chunk| chunk.len() >= max_len - 3));
+
+            // Kept after resetting.
+            b.append(TEXT);
+            assert!(b.finish().chunks().all(|chunk| chunk.len() <= max_len));
+        }
+    }
+
+    #[test]
+    fn finish_reset_01() {
+        let mut b = RopeBuilder::new();
+        for i in 0..4 {
+            let text = TEXT.repeat(i);
+            b.append(&text);
+            let r = b.finish_reset();
+            assert_eq!(r, text.as_str());
+            r.assert_integrity();
+            r.assert_invariants();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn finish_reset_02() {
+        // Nothing carries over to the next rope.
+        use std::io::Write;
+
+        let mut b = RopeBuilder::new();
+        b.append_rope(&Rope::from_str(TEXT));
+        b.append("Hi\r");
+        b.write_all(&"ん".as_bytes()[..1]).unwrap();
+        assert_eq!(b.finish_reset(), format!("{}Hi\r", TEXT).as_str());
+
+        b.write_all(b"\nabc").unwrap();
+        let r = b.finish_reset();
+        assert_eq!(r, "\nabc");
+        assert_eq!(b.finish_reset(), "");
+        b.flush().unwrap();
+    }
+
+    #[test]
+    fn reset_01() {
+        let mut b = RopeBuilder::new();
+        b.append(TEXT);
+        b.append_rope(&Rope::from_str(TEXT));
+        b.append("Hello");
+        b.reset();
+        b.append("world");
+        assert_eq!(b.finish(), "world");
+
+        let mut b = RopeBuilder::new();
+        b.reset();
+        let r = b.finish();
+        assert_eq!(r, "");
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn append_bytes_01() {
+        for size in 1..8 {
+            let mut b = RopeBuilder::new();
+            for chunk in TEXT.as_bytes().chunks(size) {
+                b.append_bytes(chunk).unwrap();
+            }
+            let r = b.finish();
+
+            assert_eq!(r, TEXT);
+            r.assert_integrity();
+            r.assert_invariants();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn append_bytes_02() {
+        // Invalid utf8.
+        let mut b = RopeBuilder::new();
+        let err = b.append_bytes(b"abc\xFFdef").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(b.finish(), "abc");
+
+        let mut b = RopeBuilder::new();
+        b.append_bytes(&"こ".as_bytes()[..2]).unwrap();
+        assert!(b.append_bytes(b"x").is_err());
+        assert_eq!(b.finish(), "");
+    }
+
+    #[test]
+    fn append_rope_01() {
+        let r1 = Rope::from_str(&TEXT.repeat(4));
+        let r2 = Rope::from_str("Hi");
+
+        let mut b = RopeBuilder::new();
+        b.append("Start\r");
+        b.append_rope(&r1);
+        b.append_rope(&r2);
+        b.append("\n");
+        b.append_rope(&r1);
+        b.append_rope(&Rope::new());
+        let r = b.finish();
+
+        assert_eq!(
+            r,
+            format!("Start\r{}Hi\n{}", TEXT.repeat(4), TEXT.repeat(4))
+        );
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn append_rope_02() {
+        // Shares the appended rope's leaves rather than copying them.
+        let r1 = Rope::from_str(&TEXT.repeat(4));
+
+        let mut b = RopeBuilder::new();
+        b.append("abc");
+        b.append_rope(&r1);
+        let r = b.finish();
+
+        let r1_chunks: Vec<_> = r1.chunks().map(|c| c.as_ptr()).collect();
+        let shared = r
+            .chunks()
+            .filter(|c| r1_chunks.contains(&c.as_ptr()))
+            .count();
+        assert!(shared >= r1_chunks.len() - 1);
+    }
+
+    #[test]
+    fn append_slice_01() {
+        let r1 = Rope::from_str(&TEXT.repeat(4));
+
+        let mut b = RopeBuilder::new();
+        b.append_slice(r1.slice(5..300));
+        b.append("こんにちは");
+        b.append_slice(r1.slice(20..22));
+        b.append_slice(r1.slice(..0));
+        b.append_slice(r1.slice(..));
+        let r = b.finish();
+
+        let text = format!(
+            "{}こんにちは{}{}",
+            r1.slice(5..300),
+            r1.slice(20..22),
+            TEXT.repeat(4)
+        );
+        assert_eq!(r, text.as_str());
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn append_slice_02() {
+        // Mixed with `io::Write` and `clone()`.
+        use std::io::Write;
+
+        let r1 = Rope::from_str(TEXT);
+
+        let mut b = RopeBuilder::new();
+        b.write_all("こん".as_bytes()).unwrap();
+        b.append_slice(r1.slice(10..));
+        let b2 = b.clone();
+        b.write_all("に".as_bytes()).unwrap();
+
+        assert_eq!(b.finish(), format!("こん{}に", r1.slice(10..)).as_str());
+        assert_eq!(b2.finish(), format!("こん{}", r1.slice(10..)).as_str());
+    }
 }
@@ -0,0 +1,254 @@
+use std::io;
+
+use smallvec::SmallVec;
+
+use reader::seek_idx;
+use rope::Rope;
+use rope_builder::{check_no_partial, write_utf8};
+
+/// A file-like adapter for reading and editing a `Rope` in place.
+///
+/// `RopeFile` implements `io::Read`, `io::Write` and `io::Seek` over the
+/// utf8 bytes of a `Rope`, so that code written against file-shaped traits
+/// can work on a `Rope` unchanged.
+///
+/// As with a file, written text overwrites the text at the current
+/// position, extending the `Rope` if it runs past the end.  Since the
+/// `Rope` must remain valid utf8, overwriting works in whole chars: a
+/// write replaces every char that starts within the bytes it covers, which
+/// may change the length of the text if the chars are of different widths.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use std::io::{Seek, SeekFrom, Write};
+///
+/// let mut rope = Rope::from_str("Hello world!");
+/// {
+///     let mut file = rope.as_file();
+///     file.seek(SeekFrom::Start(6)).unwrap();
+///     file.write_all(b"there").unwrap();
+///     file.seek(SeekFrom::End(0)).unwrap();
+///     file.write_all(b"  How are you?").unwrap();
+/// }
+///
+/// assert_eq!(rope, "Hello there!  How are you?");
+/// ```
+///
+/// # Errors
+///
+/// - If a write starts in the middle of a char, returns an error of kind
+///   `InvalidInput`.
+/// - If the written data is not valid utf8, returns an error of kind
+///   `InvalidData`.  All data before the invalid bytes is still written.
+/// - If `flush()` or `seek()` is called in the middle of a multi-byte utf8
+///   sequence that was split across writes, returns an error of kind
+///   `InvalidData`.
+#[derive(Debug)]
+pub struct RopeFile<'a> {
+    rope: &'a mut Rope,
+    byte_idx: usize,
+    // An incomplete utf8 sequence left over from the last write.
+    partial: SmallVec<[u8; 4]>,
+}
+
+impl<'a> RopeFile<'a> {
+    pub(crate) fn new(rope: &'a mut Rope) -> RopeFile<'a> {
+        RopeFile {
+            rope: rope,
+            byte_idx: 0,
+            partial: SmallVec::new(),
+        }
+    }
+
+    /// Returns the byte index that the next read or write will start from.
+    #[inline]
+    pub fn byte_idx(&self) -> usize {
+        self.byte_idx
+    }
+
+    /// Overwrites the text at the current position with `text`.
+    fn overwrite(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        // Pad with nulls if we're past the end, like a file.
+        let len = self.rope.len_bytes();
+        if self.byte_idx > len {
+            let padding = "\0".repeat(self.byte_idx - len);
+            self.rope.insert(self.rope.len_chars(), &padding);
+        }
+
+        let start = self.rope.byte_to_char(self.byte_idx);
+        let end_byte_idx = (self.byte_idx + text.len()).min(self.rope.len_bytes());
+        let mut end = self.rope.byte_to_char(end_byte_idx);
+        if self.rope.char_to_byte(end) < end_byte_idx {
+            end += 1;
+        }
+
+        self.rope.remove(start..end);
+        self.rope.insert(start, text);
+        self.byte_idx += text.len();
+    }
+}
+
+impl<'a> io::Read for RopeFile<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.byte_idx >= self.rope.len_bytes() {
+            return Ok(0);
+        }
+
+        let (chunk, chunk_byte_idx, _, _) = self.rope.chunk_at_byte(self.byte_idx);
+        let bytes = &chunk.as_bytes()[(self.byte_idx - chunk_byte_idx)..];
+        let len = bytes.len().min(buf.len());
+        buf[..len].copy_from_slice(&bytes[..len]);
+        self.byte_idx += len;
+
+        Ok(len)
+    }
+}
+
+impl<'a> io::Write for RopeFile<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.partial.is_empty()
+            && self.byte_idx < self.rope.len_bytes()
+            && self
+                .rope
+                .char_to_byte(self.rope.byte_to_char(self.byte_idx))
+                != self.byte_idx
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "attempt to write in the middle of a char",
+            ));
+        }
+
+        let mut partial = std::mem::take(&mut self.partial);
+        let result = write_utf8(&mut partial, buf, |text| self.overwrite(text));
+        self.partial = partial;
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        check_no_partial(&self.partial)
+    }
+}
+
+impl<'a> io::Seek for RopeFile<'a> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        check_no_partial(&self.partial)?;
+        let new_idx = seek_idx(pos, self.byte_idx, self.rope.len_bytes())?;
+        self.byte_idx = new_idx;
+        Ok(new_idx as u64)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.byte_idx as u64)
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn rope_file_01() {
+        // Reading and seeking.
+        let mut r = Rope::from_str(TEXT);
+        let mut file = r.as_file();
+        let mut text = String::new();
+        file.read_to_string(&mut text).unwrap();
+        assert_eq!(text, TEXT);
+
+        let mut buf = [0u8; 6];
+        file.seek(SeekFrom::Start(14)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"How're");
+        assert_eq!(file.byte_idx(), 20);
+    }
+
+    #[test]
+    fn rope_file_02() {
+        // Overwriting.
+        let mut r = Rope::from_str(TEXT);
+        {
+            let mut file = r.as_file();
+            file.write_all(b"Howdy").unwrap();
+            file.seek(SeekFrom::Current(9)).unwrap();
+            file.write_all(b"Who").unwrap();
+        }
+        let expected = format!("Howdy{}Who{}", &TEXT[5..14], &TEXT[17..]);
+        assert_eq!(r, expected.as_str());
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn rope_file_03() {
+        // Overwriting chars of different widths.
+        let mut r = Rope::from_str("aこんb");
+        {
+            let mut file = r.as_file();
+            file.seek(SeekFrom::Start(1)).unwrap();
+            file.write_all(b"xy").unwrap();
+            assert_eq!(file.byte_idx(), 3);
+            file.write_all("é".as_bytes()).unwrap();
+        }
+        assert_eq!(r, "axyéb");
+
+        let mut r = Rope::from_str("abc");
+        r.as_file().write_all("みんな".as_bytes()).unwrap();
+        assert_eq!(r, "みんな");
+
+        let mut file = r.as_file();
+        file.seek(SeekFrom::Start(1)).unwrap();
+        assert!(file.write(b"x").is_err());
+    }
+
+    #[test]
+    fn rope_file_04() {
+        // Writing past the end, with split sequences.
+        let mut r = Rope::from_str("Hello");
+        {
+            let mut file = r.as_file();
+            file.seek(SeekFrom::End(2)).unwrap();
+            for chunk in "こんにちは".as_bytes().chunks(2) {
+                file.write_all(chunk).unwrap();
+            }
+            file.flush().unwrap();
+        }
+        assert_eq!(r, "Hello\0\0こんにちは");
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let mut file = r.as_file();
+        file.write_all(&"ん".as_bytes()[..1]).unwrap();
+        assert!(file.flush().is_err());
+        assert!(file.seek(SeekFrom::Start(0)).is_err());
+    }
+
+    #[test]
+    fn rope_file_05() {
+        // Large writes across many chunks.
+        let mut r = Rope::from_str(TEXT);
+        let text = "x".repeat(200);
+        {
+            let mut file = r.as_file();
+            file.seek(SeekFrom::Start(10)).unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        }
+        assert_eq!(r, format!("{}{}", &TEXT[..10], text).as_str());
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+}
@@ -0,0 +1,352 @@
+//! Fuzzy matching of lines, in the style of `fzf`.
+//!
+//! Only available with the `fuzzy` feature enabled.
+
+use alloc::vec::Vec;
+
+use iter::Lines;
+use slice::RopeSlice;
+
+use super::fold_case;
+
+// Scoring constants, the same as those used by `fzf`.
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_BOUNDARY: i64 = SCORE_MATCH / 2;
+const BONUS_NON_WORD: i64 = SCORE_MATCH / 2;
+const BONUS_CAMEL_123: i64 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+const BONUS_CONSECUTIVE: i64 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+/// A line that fuzzy-matched a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// The index of the matching line.
+    pub line_idx: usize,
+    /// The match's score.  Higher is better.
+    pub score: i64,
+    /// The char indices of the matched chars, relative to the start of the
+    /// `Rope`/`RopeSlice`.
+    pub positions: Vec<usize>,
+}
+
+/// An iterator over the lines of a `Rope`/`RopeSlice` that fuzzy-match a
+/// pattern.
+///
+/// A line matches if it contains all of the chars of the pattern in order,
+/// though not necessarily next to each other.  Matches are scored in the
+/// same way as `fzf`, favoring chars that are consecutive or that fall at the
+/// start of words.
+///
+/// Matching is case-insensitive unless the pattern contains an uppercase
+/// char ("smart case").  An empty pattern matches every line with a score of
+/// zero.
+///
+/// Matching lines are yielded in line order, so they typically need to be
+/// sorted by score afterwards.
+#[derive(Debug, Clone)]
+pub struct FuzzyLines<'a> {
+    lines: Lines<'a>,
+    line_idx: usize,
+    char_idx: usize,
+    pattern: Vec<char>,
+    ignore_case: bool,
+    buf: Vec<char>,
+}
+
+impl<'a> FuzzyLines<'a> {
+    pub(crate) fn new(slice: &RopeSlice<'a>, pattern: &str) -> FuzzyLines<'a> {
+        let ignore_case = !pattern.chars().any(char::is_uppercase);
+        let pattern = if ignore_case {
+            pattern.chars().map(fold_case).collect()
+        } else {
+            pattern.chars().collect()
+        };
+
+        FuzzyLines {
+            lines: slice.lines(),
+            line_idx: 0,
+            char_idx: 0,
+            pattern: pattern,
+            ignore_case: ignore_case,
+            buf: Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn fold_char(&self, c: char) -> char {
+        if self.ignore_case {
+            fold_case(c)
+        } else {
+            c
+        }
+    }
+
+    /// Finds and scores the best match of the pattern in `line`, if any.
+    ///
+    /// Returned positions are relative to the start of the line.
+    fn match_line(&mut self, line: &RopeSlice) -> Option<(i64, Vec<usize>)> {
+        if self.pattern.is_empty() {
+            return Some((0, Vec::new()));
+        }
+
+        // Find the end of the first occurrence of the pattern as a
+        // subsequence.
+        let mut end = None;
+        let mut pi = 0;
+        for (i, c) in line.chars().enumerate() {
+            if self.fold_char(c) == self.pattern[pi] {
+                pi += 1;
+                if pi == self.pattern.len() {
+                    end = Some(i + 1);
+                    break;
+                }
+            }
+        }
+        let end = end?;
+
+        // Then search backwards from there, to find the shortest match
+        // ending at the same place.
+        let mut start = end;
+        let mut chars = line.chars_at(end);
+        while pi > 0 {
+            let c = chars.prev().unwrap();
+            start -= 1;
+            if self.fold_char(c) == self.pattern[pi - 1] {
+                pi -= 1;
+            }
+        }
+
+        // Collect the match region for scoring.
+        self.buf.clear();
+        self.buf.extend(line.slice(start..end).chars());
+        let prev_class = if start > 0 {
+            CharClass::of(line.char(start - 1))
+        } else {
+            CharClass::White
+        };
+
+        Some(self.score(prev_class, start))
+    }
+
+    /// Scores the match in `buf`, which starts at `start` in the line.
+    fn score(&self, mut prev_class: CharClass, start: usize) -> (i64, Vec<usize>) {
+        let mut score = 0;
+        let mut positions = Vec::with_capacity(self.pattern.len());
+        let mut in_gap = false;
+        let mut consecutive = 0;
+        let mut first_bonus = 0;
+        let mut pi = 0;
+
+        for (i, &c) in self.buf.iter().enumerate() {
+            let class = CharClass::of(c);
+            if pi < self.pattern.len() && self.fold_char(c) == self.pattern[pi] {
+                score += SCORE_MATCH;
+                let mut bonus = CharClass::bonus(prev_class, class);
+                if consecutive == 0 {
+                    first_bonus = bonus;
+                } else {
+                    // Consecutive chars inherit the bonus of the first char
+                    // of their chunk.
+                    if bonus >= BONUS_BOUNDARY && bonus > first_bonus {
+                        first_bonus = bonus;
+                    }
+                    bonus = bonus.max(first_bonus).max(BONUS_CONSECUTIVE);
+                }
+                score += if pi == 0 {
+                    bonus * BONUS_FIRST_CHAR_MULTIPLIER
+                } else {
+                    bonus
+                };
+                positions.push(start + i);
+                in_gap = false;
+                consecutive += 1;
+                pi += 1;
+            } else {
+                score += if in_gap {
+                    SCORE_GAP_EXTENSION
+                } else {
+                    SCORE_GAP_START
+                };
+                in_gap = true;
+                consecutive = 0;
+                first_bonus = 0;
+            }
+            prev_class = class;
+        }
+
+        (score, positions)
+    }
+}
+
+impl<'a> Iterator for FuzzyLines<'a> {
+    type Item = FuzzyMatch;
+
+    /// Advances the iterator forward and returns the next matching line.
+    fn next(&mut self) -> Option<FuzzyMatch> {
+        while let Some(line) = self.lines.next() {
+            let line_idx = self.line_idx;
+            let char_idx = self.char_idx;
+            self.line_idx += 1;
+            self.char_idx += line.len_chars();
+
+            if let Some((score, mut positions)) = self.match_line(&line) {
+                for pos in &mut positions {
+                    *pos += char_idx;
+                }
+                return Some(FuzzyMatch {
+                    line_idx: line_idx,
+                    score: score,
+                    positions: positions,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+//==========================================================
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CharClass {
+    White,
+    NonWord,
+    Lower,
+    Upper,
+    Letter,
+    Number,
+}
+
+impl CharClass {
+    fn of(c: char) -> CharClass {
+        if c.is_lowercase() {
+            CharClass::Lower
+        } else if c.is_uppercase() {
+            CharClass::Upper
+        } else if c.is_numeric() {
+            CharClass::Number
+        } else if c.is_alphabetic() {
+            CharClass::Letter
+        } else if c.is_whitespace() {
+            CharClass::White
+        } else {
+            CharClass::NonWord
+        }
+    }
+
+    fn is_word(self) -> bool {
+        self != CharClass::White && self != CharClass::NonWord
+    }
+
+    /// The bonus for matching a char of class `class` that follows a char
+    /// of class `prev`.
+    fn bonus(prev: CharClass, class: CharClass) -> i64 {
+        if !prev.is_word() && class.is_word() {
+            // Start of a word.
+            BONUS_BOUNDARY
+        } else if (prev == CharClass::Lower && class == CharClass::Upper)
+            || (prev != CharClass::Number && class == CharClass::Number)
+        {
+            // camelCase or letter123.
+            BONUS_CAMEL_123
+        } else if !class.is_word() {
+            BONUS_NON_WORD
+        } else {
+            0
+        }
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rope;
+
+    // 124 bytes, 100 chars, 4 lines
+    const TEXT_LINES: &str = "Hello there!  How're you doing?\nIt's \
+                              a fine day, isn't it?\nAren't you glad \
+                              we're alive?\nこんにちは、みんなさん！";
+
+    fn matched_text(r: &Rope, m: &FuzzyMatch) -> String {
+        m.positions.iter().map(|&i| r.char(i)).collect()
+    }
+
+    #[test]
+    fn fuzzy_lines_01() {
+        let r = Rope::from_str(TEXT_LINES);
+        let matches: Vec<_> = r.fuzzy_lines("you").collect();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].line_idx, 0);
+        assert_eq!(matches[0].positions, vec![21, 22, 23]);
+        assert_eq!(matches[1].line_idx, 2);
+        assert_eq!(matched_text(&r, &matches[1]), "you");
+        for m in &matches {
+            let line_start = r.line_to_char(m.line_idx);
+            let line_end = r.line_to_char(m.line_idx + 1);
+            assert!(m.positions.iter().all(|&i| i >= line_start && i < line_end));
+        }
+    }
+
+    #[test]
+    fn fuzzy_lines_02() {
+        // Subsequences, and smart case.
+        let r = Rope::from_str(TEXT_LINES);
+        let matches: Vec<_> = r.fuzzy_lines("hwdg").collect();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matched_text(&r, &matches[0]), "Hwdg");
+
+        assert_eq!(r.fuzzy_lines("Hwdg").count(), 1);
+        assert_eq!(r.fuzzy_lines("HWDG").count(), 0);
+        assert_eq!(r.fuzzy_lines("みさ").next().unwrap().line_idx, 3);
+    }
+
+    #[test]
+    fn fuzzy_lines_03() {
+        // The first match is tightened from its end.
+        let r = Rope::from_str("aaab_ab");
+        let m = r.fuzzy_lines("ab").next().unwrap();
+        assert_eq!(m.positions, vec![2, 3]);
+    }
+
+    #[test]
+    fn fuzzy_lines_04() {
+        // Consecutive and word-start matches score higher.
+        let r = Rope::from_str("foo bar\nfxoxo\nxfoo\nfoo\n");
+        let matches: Vec<_> = r.fuzzy_lines("foo").collect();
+        assert_eq!(matches.len(), 4);
+        let scores: Vec<_> = matches.iter().map(|m| m.score).collect();
+        assert_eq!(scores[0], scores[3]);
+        assert!(scores[3] > scores[1]);
+        assert!(scores[3] > scores[2]);
+
+        let r = Rope::from_str("fileopen\nFileOpen\n");
+        let matches: Vec<_> = r.fuzzy_lines("fo").collect();
+        assert!(matches[1].score > matches[0].score);
+    }
+
+    #[test]
+    fn fuzzy_lines_05() {
+        // Empty pattern matches everything.
+        let r = Rope::from_str(TEXT_LINES);
+        let matches: Vec<_> = r.fuzzy_lines("").collect();
+        assert_eq!(matches.len(), 4);
+        assert!(matches
+            .iter()
+            .all(|m| m.score == 0 && m.positions.is_empty()));
+    }
+
+    #[test]
+    fn fuzzy_lines_sliced_01() {
+        let r = Rope::from_str(TEXT_LINES);
+        let s = r.slice(20..90);
+        let matches: Vec<_> = s.fuzzy_lines("you").collect();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].line_idx, 0);
+        assert_eq!(matches[0].positions, vec![1, 2, 3]);
+        assert_eq!(matches[1].line_idx, 2);
+    }
+}
@@ -0,0 +1,302 @@
+//! Searching in bounded increments of work.
+
+use alloc::string::String;
+use core::ops::Range;
+
+use slice::RopeSlice;
+
+use super::Matches;
+
+/// The result of polling an `IncrementalSearch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchPoll {
+    /// The next match, as a `char` index range.
+    Match(Range<usize>),
+    /// The work budget ran out before the next match or the end of the text
+    /// was reached.  Poll again to continue.
+    Pending,
+    /// There are no more matches.
+    Done,
+}
+
+/// A text search that can be run in bounded increments of work, and that
+/// survives edits to the text being searched.
+///
+/// Unlike `Matches`, an `IncrementalSearch` doesn't borrow the text it
+/// searches: the text is instead passed to each call of `poll()`.  This
+/// allows e.g. a UI to search a large `Rope` a few chunks at a time between
+/// frames, while still allowing the `Rope` to be edited in the meantime.
+/// After each edit, `edit()` must be called so that the search can
+/// reposition itself.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use ropey::search::{IncrementalSearch, SearchPoll};
+///
+/// let mut rope = Rope::from_str("one fish, two fish");
+/// let mut search = IncrementalSearch::new("fish");
+///
+/// assert_eq!(search.poll(rope.slice(..), 1), SearchPoll::Match(4..8));
+///
+/// rope.insert(9, " red fish,");
+/// search.edit(9..9);
+///
+/// assert_eq!(search.poll(rope.slice(..), 1), SearchPoll::Match(14..18));
+/// assert_eq!(search.poll(rope.slice(..), 1), SearchPoll::Match(24..28));
+/// assert_eq!(search.poll(rope.slice(..), 1), SearchPoll::Done);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IncrementalSearch {
+    pattern: String,
+    pattern_char_len: usize,
+    ignore_case: bool,
+    // Where the next poll resumes searching from.
+    char_idx: usize,
+    // The end of the last match.
+    last_end: Option<usize>,
+}
+
+impl IncrementalSearch {
+    /// Creates a new search for `pattern`, starting from the beginning of the
+    /// text.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is empty.
+    pub fn new(pattern: &str) -> IncrementalSearch {
+        IncrementalSearch::new_impl(pattern, false)
+    }
+
+    /// Like `new()`, but compares text case-insensitively.
+    ///
+    /// See the [`search`](index.html) module documentation for details on
+    /// how case is folded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is empty.
+    pub fn new_ignore_case(pattern: &str) -> IncrementalSearch {
+        IncrementalSearch::new_impl(pattern, true)
+    }
+
+    fn new_impl(pattern: &str, ignore_case: bool) -> IncrementalSearch {
+        assert!(
+            !pattern.is_empty(),
+            "Attempt to search for an empty pattern"
+        );
+
+        IncrementalSearch {
+            pattern: pattern.into(),
+            pattern_char_len: pattern.chars().count(),
+            ignore_case: ignore_case,
+            char_idx: 0,
+            last_end: None,
+        }
+    }
+
+    /// Returns the char index that the next `poll()` will resume searching
+    /// from.
+    ///
+    /// Everything before this index has already been searched, so this can
+    /// be used e.g. to display the progress of the search.
+    #[inline]
+    pub fn char_idx(&self) -> usize {
+        self.char_idx
+    }
+
+    /// Moves the search to resume from `char_idx`.
+    #[inline]
+    pub fn set_char_idx(&mut self, char_idx: usize) {
+        self.char_idx = char_idx;
+        self.last_end = None;
+    }
+
+    /// Searches `text` for the next match, moving through at most
+    /// `max_chunks` chunks of the text before returning
+    /// `SearchPoll::Pending`.
+    ///
+    /// At least one chunk is always searched, and more may be if the pattern
+    /// is longer than the chunks.
+    ///
+    /// Runs in O(M + log N) time, where M is the number of bytes searched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the search's position is past the end of `text`, which
+    /// happens if text has been removed without a corresponding call to
+    /// `edit()`.
+    pub fn poll(&mut self, text: RopeSlice, max_chunks: usize) -> SearchPoll {
+        assert!(
+            self.char_idx <= text.len_chars(),
+            "Search position past end of text: char index {}, text char length {}",
+            self.char_idx,
+            text.len_chars()
+        );
+
+        let rest = text.slice(self.char_idx..);
+        let mut matches = Matches::new(&rest, &self.pattern, self.ignore_case);
+        match matches.next_within(max_chunks.max(1)) {
+            SearchPoll::Match(range) => {
+                let range = (self.char_idx + range.start)..(self.char_idx + range.end);
+                self.char_idx = range.end;
+                self.last_end = Some(range.end);
+                SearchPoll::Match(range)
+            }
+            SearchPoll::Pending => {
+                self.char_idx += matches.resume_char_idx();
+                SearchPoll::Pending
+            }
+            SearchPoll::Done => {
+                self.char_idx = text.len_chars();
+                SearchPoll::Done
+            }
+        }
+    }
+
+    /// Updates the search's position for an edit to the text.
+    ///
+    /// `char_range` is the range of text that was removed or replaced, in
+    /// the text as it was before the edit.  For pure insertions it's the
+    /// empty range at the insertion point.
+    ///
+    /// If the edit touched text that had already been searched, the search
+    /// resumes from just before the edit, so that matches within the new
+    /// text are found.  Otherwise it's left where it was.
+    pub fn edit(&mut self, char_range: Range<usize>) {
+        assert!(char_range.start <= char_range.end);
+
+        if self.char_idx <= char_range.start {
+            return;
+        }
+
+        // Back up far enough to catch matches that start before the edit
+        // but extend into it, without overlapping the last match if it
+        // wasn't itself affected.
+        let mut char_idx = char_range.start.saturating_sub(self.pattern_char_len - 1);
+        match self.last_end {
+            Some(end) if end <= char_range.start => char_idx = char_idx.max(end),
+            _ => self.last_end = None,
+        }
+        self.char_idx = char_idx;
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rope;
+
+    fn poll_all(search: &mut IncrementalSearch, r: &Rope, max_chunks: usize) -> Vec<Range<usize>> {
+        let mut matches = Vec::new();
+        loop {
+            match search.poll(r.slice(..), max_chunks) {
+                SearchPoll::Match(range) => matches.push(range),
+                SearchPoll::Pending => {}
+                SearchPoll::Done => return matches,
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_01() {
+        // Same results as `find_iter()`, whatever the budget.
+        let text = "ab".repeat(200) + "needle" + &"ba".repeat(200) + "abこんにちはab";
+        let r = Rope::from_str(&text);
+        for pattern in &["needle", "abab", "aba", "a", "abこんにちはab", "ちはa"] {
+            let expected: Vec<_> = r.find_iter(pattern).collect();
+            for &max_chunks in &[0, 1, 2, 7, usize::MAX] {
+                let mut search = IncrementalSearch::new(pattern);
+                assert_eq!(poll_all(&mut search, &r, max_chunks), expected);
+            }
+
+            let expected: Vec<_> = r.find_iter_ignore_case(pattern).collect();
+            for &max_chunks in &[1, 3] {
+                let mut search = IncrementalSearch::new_ignore_case(pattern);
+                assert_eq!(poll_all(&mut search, &r, max_chunks), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_02() {
+        // Bounded work.
+        let text = "ab".repeat(500);
+        let r = Rope::from_str(&text);
+        let mut search = IncrementalSearch::new("needle");
+        let mut pending = 0;
+        while search.poll(r.slice(..), 4) == SearchPoll::Pending {
+            pending += 1;
+            assert!(search.char_idx() < r.len_chars());
+        }
+        assert!(pending > 10);
+        assert_eq!(search.char_idx(), r.len_chars());
+    }
+
+    #[test]
+    fn incremental_03() {
+        // Edits before the search position.
+        let mut r = Rope::from_str("xx fish xxsh fish");
+        let mut search = IncrementalSearch::new("fish");
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(3..7));
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(13..17));
+
+        // Creates a new match, and shifts the one after it.
+        r.remove(8..10);
+        r.insert(8, "fi");
+        search.edit(8..10);
+        assert_eq!(r, "xx fish fish fish");
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(8..12));
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(13..17));
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Done);
+    }
+
+    #[test]
+    fn incremental_04() {
+        // Edits after the search position, and appending after finishing.
+        let mut r = Rope::from_str("fish xx");
+        let mut search = IncrementalSearch::new("fish");
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(0..4));
+
+        r.insert(5, "fish ");
+        search.edit(5..5);
+        assert_eq!(search.char_idx(), 4);
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(5..9));
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Done);
+
+        r.insert(r.len_chars(), "fish");
+        let len = r.len_chars();
+        search.edit((len - 4)..(len - 4));
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(12..16));
+    }
+
+    #[test]
+    fn incremental_05() {
+        // Doesn't overlap an unaffected earlier match.
+        let mut r = Rope::from_str("aaaXb");
+        let mut search = IncrementalSearch::new("aa");
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(0..2));
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Done);
+
+        r.remove(3..4);
+        r.insert(3, "a");
+        search.edit(3..4);
+        assert_eq!(r, "aaaab");
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Match(2..4));
+        assert_eq!(search.poll(r.slice(..), 1), SearchPoll::Done);
+    }
+
+    #[test]
+    #[should_panic]
+    fn incremental_06() {
+        let mut r = Rope::from_str("fish fish");
+        let mut search = IncrementalSearch::new("fish");
+        search.poll(r.slice(..), 1);
+        search.poll(r.slice(..), 1);
+        r.remove(..);
+        search.poll(r.slice(..), 1);
+    }
+}
@@ -0,0 +1,561 @@
+//! Searching for text in a `Rope`'s data.
+//!
+//! The searchers in this module are created via the `find_iter*()` methods
+//! of `Rope` and `RopeSlice`.  They operate directly on the chunks of the
+//! rope, streaming across chunk boundaries as they go, so that searching
+//! never requires the text to be copied into a contiguous `String`.
+//!
+//! All match ranges are given in `char` indices relative to the start of the
+//! `Rope`/`RopeSlice` that the search was created from.
+//!
+//! For searching large texts without blocking, e.g. in a UI,
+//! [`IncrementalSearch`] can be polled in bounded increments of work.
+//!
+//! # Case-insensitive search
+//!
+//! Case-insensitive searching uses simple (one-to-one) case folding: each
+//! `char` is compared by its lowercase form if that form is a single `char`,
+//! and as itself otherwise.  This matches the behavior of the
+//! case-insensitive search mode of most text editors, and never changes the
+//! length of the text being compared, so match ranges always line up exactly
+//! with the original text.
+//!
+//! # Regular expressions
+//!
+//! With the `regex` feature enabled, `Rope` and `RopeSlice` also have a
+//! `regex_find_iter()` method, which runs a [`Regex`] directly over the
+//! rope's chunks, and `Rope` has a `regex_replace_all()` method.
+//!
+//! # Fuzzy matching
+//!
+//! With the `fuzzy` feature enabled, `Rope` and `RopeSlice` also have a
+//! `fuzzy_lines()` method for `fzf`-style fuzzy matching of lines.  See
+//! [`FuzzyLines`] for details.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use memchr::memmem::Finder;
+
+use iter::Chunks;
+use slice::RopeSlice;
+use str_utils::count_chars;
+
+#[cfg(feature = "fuzzy")]
+mod fuzzy;
+mod incremental;
+mod pattern;
+#[cfg(feature = "regex")]
+mod regex;
+
+#[cfg(feature = "fuzzy")]
+pub use self::fuzzy::{FuzzyLines, FuzzyMatch};
+pub use self::incremental::{IncrementalSearch, SearchPoll};
+pub use self::pattern::{Pattern, Split};
+#[cfg(feature = "regex")]
+pub(crate) use self::regex::replace_all as regex_replace_all;
+#[cfg(feature = "regex")]
+pub use self::regex::{BuildError, Regex, RegexMatches};
+
+/// An iterator over the non-overlapping matches of a text pattern in a
+/// `Rope`/`RopeSlice`.
+///
+/// Yields the `char` index range of each match, in order from the start of
+/// the text.
+///
+/// `Matches` isn't `Clone`, since it can hold an `FnMut` pattern.
+#[derive(Debug)]
+pub struct Matches<'a>(MatchesEnum<'a>);
+
+#[derive(Debug)]
+enum MatchesEnum<'a> {
+    Exact(Box<ExactMatches<'a>>),
+    Folded(FoldedMatches<'a>),
+    Predicate(PredicateMatches<'a>),
+}
+
+impl<'a> Matches<'a> {
+    pub(crate) fn new(slice: &RopeSlice<'a>, pattern: &str, ignore_case: bool) -> Matches<'a> {
+        assert!(
+            !pattern.is_empty(),
+            "Attempt to search for an empty pattern"
+        );
+
+        if ignore_case {
+            Matches(MatchesEnum::Folded(FoldedMatches::new(slice, pattern)))
+        } else {
+            Matches(MatchesEnum::Exact(Box::new(ExactMatches::new(
+                slice, pattern,
+            ))))
+        }
+    }
+
+    pub(crate) fn from_predicate<F>(slice: &RopeSlice<'a>, predicate: F) -> Matches<'a>
+    where
+        F: FnMut(char) -> bool + 'a,
+    {
+        Matches(MatchesEnum::Predicate(PredicateMatches::new(
+            slice, predicate,
+        )))
+    }
+}
+
+impl<'a> Matches<'a> {
+    /// Searches for the next match, moving on to at most `max_chunks` new
+    /// chunks before giving up with `SearchPoll::Pending`.
+    fn next_within(&mut self, max_chunks: usize) -> SearchPoll {
+        match self.0 {
+            MatchesEnum::Exact(ref mut matches) => matches.next_within(max_chunks),
+            MatchesEnum::Folded(ref mut matches) => matches.next_within(max_chunks),
+            MatchesEnum::Predicate(ref mut matches) => matches.next_within(max_chunks),
+        }
+    }
+
+    /// The char index that searching must resume from after a
+    /// `SearchPoll::Pending`, for no matches to be missed.
+    fn resume_char_idx(&self) -> usize {
+        match self.0 {
+            MatchesEnum::Exact(ref matches) => matches.resume_char_idx(),
+            MatchesEnum::Folded(ref matches) => matches.resume_char_idx(),
+            MatchesEnum::Predicate(ref matches) => matches.char_idx,
+        }
+    }
+}
+
+impl<'a> Iterator for Matches<'a> {
+    type Item = Range<usize>;
+
+    /// Advances the iterator forward and returns the next match.
+    #[inline]
+    fn next(&mut self) -> Option<Range<usize>> {
+        match self.next_within(usize::MAX) {
+            SearchPoll::Match(range) => Some(range),
+            SearchPoll::Pending => unreachable!(),
+            SearchPoll::Done => None,
+        }
+    }
+}
+
+//==========================================================
+
+/// Exact matching, using `memchr`'s SIMD-accelerated `memmem` within each
+/// chunk.
+///
+/// Matches that straddle a chunk boundary are found by also searching a
+/// small "seam" buffer at each boundary, made of the last
+/// `pattern.len() - 1` bytes before the boundary and the first
+/// `pattern.len() - 1` bytes after it.
+#[derive(Debug, Clone)]
+struct ExactMatches<'a> {
+    slice: RopeSlice<'a>,
+    chunks: Chunks<'a>,
+    finder: Finder<'static>,
+    pattern_char_len: usize,
+    // The current chunk and its byte index.
+    chunk: &'a [u8],
+    chunk_byte_idx: usize,
+    // The bytes preceding the current chunk, up to `pattern.len() - 1`.
+    tail: Vec<u8>,
+    // The seam buffer, and whether it's being searched instead of `chunk`.
+    seam: Vec<u8>,
+    in_seam: bool,
+    // Where to resume searching from, i.e. the end of the last match.
+    byte_idx: usize,
+}
+
+impl<'a> ExactMatches<'a> {
+    fn new(slice: &RopeSlice<'a>, pattern: &str) -> ExactMatches<'a> {
+        let mut chunks = slice.chunks();
+        let chunk = chunks.next().unwrap_or("");
+        ExactMatches {
+            slice: *slice,
+            chunks: chunks,
+            finder: Finder::new(pattern).into_owned(),
+            pattern_char_len: count_chars(pattern),
+            chunk: chunk.as_bytes(),
+            chunk_byte_idx: 0,
+            tail: Vec::new(),
+            seam: Vec::new(),
+            in_seam: false,
+            byte_idx: 0,
+        }
+    }
+
+    fn next_within(&mut self, max_chunks: usize) -> SearchPoll {
+        let overlap = self.finder.needle().len() - 1;
+        let mut chunk_count = 0;
+        loop {
+            let (haystack, hay_byte_idx) = if self.in_seam {
+                (&self.seam[..], self.chunk_byte_idx - self.tail.len())
+            } else {
+                (self.chunk, self.chunk_byte_idx)
+            };
+            let from = self
+                .byte_idx
+                .saturating_sub(hay_byte_idx)
+                .min(haystack.len());
+
+            if let Some(i) = self.finder.find(&haystack[from..]) {
+                let start = hay_byte_idx + from + i;
+                // Seam matches that start within the chunk are left for the
+                // search of the chunk itself.
+                if !self.in_seam || start < self.chunk_byte_idx {
+                    self.byte_idx = start + self.finder.needle().len();
+                    let start_char = self.slice.byte_to_char(start);
+                    return SearchPoll::Match(start_char..(start_char + self.pattern_char_len));
+                }
+            }
+
+            if self.in_seam {
+                self.in_seam = false;
+                continue;
+            }
+
+            // Move on to the next chunk, keeping the end of this one (and
+            // possibly earlier ones) as the tail of the next seam.
+            let next_chunk = match self.chunks.next() {
+                Some(chunk) => chunk.as_bytes(),
+                None => return SearchPoll::Done,
+            };
+            chunk_count += 1;
+            if chunk_count >= max_chunks && self.resume_char_idx() > 0 {
+                // Note: searching can't continue after this.
+                return SearchPoll::Pending;
+            }
+            if overlap > 0 {
+                self.tail.extend_from_slice(self.chunk);
+                let excess = self.tail.len().saturating_sub(overlap);
+                self.tail.drain(..excess);
+
+                self.seam.clear();
+                self.seam.extend_from_slice(&self.tail);
+                self.seam
+                    .extend_from_slice(&next_chunk[..overlap.min(next_chunk.len())]);
+                self.in_seam = true;
+            }
+            self.chunk_byte_idx += self.chunk.len();
+            self.chunk = next_chunk;
+        }
+    }
+
+    fn resume_char_idx(&self) -> usize {
+        // Everything up to the end of the current chunk has been searched,
+        // except for matches crossing into the next chunk.
+        let overlap = self.finder.needle().len() - 1;
+        let chunk_end = self.chunk_byte_idx + self.chunk.len();
+        let byte_idx = chunk_end.saturating_sub(overlap).max(self.byte_idx);
+        self.slice.byte_to_char(byte_idx)
+    }
+}
+
+/// Case-folded matching, using Knuth-Morris-Pratt over the `char`s of the
+/// text.
+#[derive(Debug, Clone)]
+struct FoldedMatches<'a> {
+    chunks: Chunks<'a>,
+    chunk: &'a str,
+    pattern: Vec<char>,
+    // Partial match table for the pattern.
+    table: Vec<usize>,
+    char_idx: usize,
+    matched: usize,
+}
+
+impl<'a> FoldedMatches<'a> {
+    fn new(slice: &RopeSlice<'a>, pattern: &str) -> FoldedMatches<'a> {
+        let pattern: Vec<char> = pattern.chars().map(fold_case).collect();
+        let table = build_table(&pattern);
+        let mut chunks = slice.chunks();
+        let chunk = chunks.next().unwrap_or("");
+
+        FoldedMatches {
+            chunks: chunks,
+            chunk: chunk,
+            pattern: pattern,
+            table: table,
+            char_idx: 0,
+            matched: 0,
+        }
+    }
+
+    fn next_within(&mut self, max_chunks: usize) -> SearchPoll {
+        let mut chunk_count = 0;
+        loop {
+            let mut chars = self.chunk.chars();
+            while let Some(c) = chars.next() {
+                let c = fold_case(c);
+                self.char_idx += 1;
+
+                while self.matched > 0 && self.pattern[self.matched] != c {
+                    self.matched = self.table[self.matched - 1];
+                }
+                if self.pattern[self.matched] == c {
+                    self.matched += 1;
+                }
+
+                if self.matched == self.pattern.len() {
+                    self.matched = 0;
+                    self.chunk = chars.as_str();
+                    return SearchPoll::Match((self.char_idx - self.pattern.len())..self.char_idx);
+                }
+            }
+
+            self.chunk = match self.chunks.next() {
+                Some(chunk) => chunk,
+                None => return SearchPoll::Done,
+            };
+            chunk_count += 1;
+            if chunk_count >= max_chunks && self.resume_char_idx() > 0 {
+                // Note: searching can't continue after this.
+                return SearchPoll::Pending;
+            }
+        }
+    }
+
+    fn resume_char_idx(&self) -> usize {
+        // Back up to the start of any partial match.
+        self.char_idx - self.matched
+    }
+}
+
+/// Matching of single chars that satisfy a predicate.
+struct PredicateMatches<'a> {
+    chunks: Chunks<'a>,
+    chunk: &'a str,
+    predicate: Box<dyn FnMut(char) -> bool + 'a>,
+    char_idx: usize,
+}
+
+impl<'a> PredicateMatches<'a> {
+    fn new<F>(slice: &RopeSlice<'a>, predicate: F) -> PredicateMatches<'a>
+    where
+        F: FnMut(char) -> bool + 'a,
+    {
+        let mut chunks = slice.chunks();
+        let chunk = chunks.next().unwrap_or("");
+
+        PredicateMatches {
+            chunks: chunks,
+            chunk: chunk,
+            predicate: Box::new(predicate),
+            char_idx: 0,
+        }
+    }
+
+    fn next_within(&mut self, max_chunks: usize) -> SearchPoll {
+        let mut chunk_count = 0;
+        loop {
+            let mut chars = self.chunk.chars();
+            while let Some(c) = chars.next() {
+                self.char_idx += 1;
+                if (self.predicate)(c) {
+                    self.chunk = chars.as_str();
+                    return SearchPoll::Match((self.char_idx - 1)..self.char_idx);
+                }
+            }
+
+            self.chunk = match self.chunks.next() {
+                Some(chunk) => chunk,
+                None => return SearchPoll::Done,
+            };
+            chunk_count += 1;
+            if chunk_count >= max_chunks {
+                // Note: searching can't continue after this.
+                return SearchPoll::Pending;
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Debug for PredicateMatches<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PredicateMatches")
+            .field("chunks", &self.chunks)
+            .field("chunk", &self.chunk)
+            .field("char_idx", &self.char_idx)
+            .finish()
+    }
+}
+
+//==========================================================
+
+/// Simple one-to-one case folding of a single `char`.
+#[inline]
+pub(crate) fn fold_case(c: char) -> char {
+    if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        let mut lower = c.to_lowercase();
+        match (lower.next(), lower.next()) {
+            (Some(l), None) => l,
+            _ => c,
+        }
+    }
+}
+
+/// Builds the Knuth-Morris-Pratt partial match table for `pattern`.
+///
+/// `table[i]` is the length of the longest proper prefix of
+/// `pattern[..=i]` that is also a suffix of it.
+fn build_table(pattern: &[char]) -> Vec<usize> {
+    let mut table = vec![0; pattern.len()];
+    let mut len = 0;
+    for i in 1..pattern.len() {
+        while len > 0 && pattern[i] != pattern[len] {
+            len = table[len - 1];
+        }
+        if pattern[i] == pattern[len] {
+            len += 1;
+        }
+        table[i] = len;
+    }
+    table
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    const TEXT_REPEAT: &str = "こんababにちはabこんababにちはab";
+
+    #[test]
+    fn build_table_01() {
+        let pattern: Vec<char> = "abacabab".chars().collect();
+        assert_eq!(build_table(&pattern), vec![0, 0, 1, 0, 1, 2, 3, 2]);
+    }
+
+    #[test]
+    fn fold_case_01() {
+        assert_eq!(fold_case('A'), 'a');
+        assert_eq!(fold_case('a'), 'a');
+        assert_eq!(fold_case('Ä'), 'ä');
+        assert_eq!(fold_case('Σ'), 'σ');
+        assert_eq!(fold_case('こ'), 'こ');
+        // Lowercases to multiple chars, so is left alone.
+        assert_eq!(fold_case('İ'), 'İ');
+    }
+
+    #[test]
+    fn matches_01() {
+        let r = Rope::from_str(TEXT);
+        let matches: Vec<_> = r.find_iter("you").collect();
+        assert_eq!(matches, vec![21..24, 68..71]);
+        for m in matches {
+            assert_eq!(r.slice(m), "you");
+        }
+    }
+
+    #[test]
+    fn matches_02() {
+        let r = Rope::from_str(TEXT);
+        let matches: Vec<_> = r.find_iter("みんな").collect();
+        assert_eq!(matches, vec![97..100]);
+    }
+
+    #[test]
+    fn matches_03() {
+        let r = Rope::from_str(TEXT);
+        assert_eq!(r.find_iter("not there").count(), 0);
+        assert_eq!(r.find_iter(TEXT).collect::<Vec<_>>(), vec![0..103]);
+    }
+
+    #[test]
+    fn matches_04() {
+        // Non-overlapping.
+        let r = Rope::from_str("aaaaa");
+        let matches: Vec<_> = r.find_iter("aa").collect();
+        assert_eq!(matches, vec![0..2, 2..4]);
+    }
+
+    #[test]
+    fn matches_05() {
+        // Partial matches that need to back off correctly.
+        let r = Rope::from_str("abaabacabababacabab");
+        let matches: Vec<_> = r.find_iter("abacabab").collect();
+        assert_eq!(matches, vec![3..11, 11..19]);
+    }
+
+    #[test]
+    fn matches_06() {
+        // Across chunk boundaries.
+        let text = "ab".repeat(1000) + "needle" + &"ba".repeat(1000);
+        let r = Rope::from_str(&text);
+        assert!(r.chunks().count() > 1);
+        assert_eq!(r.find("needle"), Some(2000));
+
+        let matches: Vec<_> = r.find_iter("abab").collect();
+        let expected: Vec<_> = text
+            .match_indices("abab")
+            .map(|(i, _)| i..(i + 4))
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn matches_07() {
+        // Patterns that span many chunks, in text built from many small
+        // inserts so that chunk boundaries fall all over the place.
+        let mut r = Rope::new();
+        let mut text = String::new();
+        for i in 0..200 {
+            let piece = ["ab", "こん", "a", "bab", "にちは"][i % 5];
+            r.insert(r.len_chars(), piece);
+            text.push_str(piece);
+        }
+        assert!(r.chunks().count() > 10);
+
+        for pattern in &["b", "ba", "abこ", "にちはab", "abこんabab", TEXT_REPEAT] {
+            let matches: Vec<_> = r.find_iter(pattern).collect();
+            let expected: Vec<_> = text
+                .match_indices(pattern)
+                .map(|(i, _)| {
+                    let start = text[..i].chars().count();
+                    start..(start + pattern.chars().count())
+                })
+                .collect();
+            assert_eq!(matches, expected, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn matches_sliced_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(22..80);
+        let matches: Vec<_> = s.find_iter("you").collect();
+        assert_eq!(matches, vec![46..49]);
+    }
+
+    #[test]
+    fn matches_ignore_case_01() {
+        let r = Rope::from_str(TEXT);
+        let matches: Vec<_> = r.find_iter_ignore_case("HELLO").collect();
+        assert_eq!(matches, vec![0..5]);
+        let matches: Vec<_> = r.find_iter_ignore_case("it").collect();
+        assert_eq!(matches, vec![33..35, 56..58]);
+    }
+
+    #[test]
+    fn matches_ignore_case_02() {
+        let r = Rope::from_str("ΣΊΣΥΦΟΣ σίσυφος");
+        let matches: Vec<_> = r.find_iter_ignore_case("Σίσυφοσ").collect();
+        assert_eq!(matches, vec![0..7]);
+        assert_eq!(r.find_iter_ignore_case("ΣΥΦ").count(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn matches_empty_pattern() {
+        let r = Rope::from_str(TEXT);
+        r.find_iter("");
+    }
+}
@@ -0,0 +1,239 @@
+//! Generic patterns for the search methods.
+
+use alloc::string::String;
+
+use slice::RopeSlice;
+
+use super::Matches;
+
+/// A pattern that can be searched for in a `Rope`/`RopeSlice`.
+///
+/// This is implemented for:
+///
+/// - `&str`, `&&str` and `&String`, which match the given text exactly.
+/// - `char`, which matches the given char.
+/// - `&[char]`, `[char; N]` and `&[char; N]`, which match any one of the
+///   given chars.
+/// - `FnMut(char) -> bool`, which matches any char that the function returns
+///   `true` for.
+///
+/// Text patterns must not be empty.
+pub trait Pattern<'a> {
+    /// Creates an iterator over the matches of the pattern in `slice`.
+    #[doc(hidden)]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a>;
+}
+
+impl<'a> Pattern<'a> for &str {
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        Matches::new(slice, self, false)
+    }
+}
+
+impl<'a> Pattern<'a> for &&str {
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        Matches::new(slice, self, false)
+    }
+}
+
+impl<'a> Pattern<'a> for &String {
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        Matches::new(slice, self, false)
+    }
+}
+
+impl<'a> Pattern<'a> for char {
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        let mut buf = [0u8; 4];
+        Matches::new(slice, self.encode_utf8(&mut buf), false)
+    }
+}
+
+impl<'a> Pattern<'a> for &[char] {
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        let chars = self.to_vec();
+        Matches::from_predicate(slice, move |c| chars.contains(&c))
+    }
+}
+
+impl<'a, const N: usize> Pattern<'a> for [char; N] {
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        Matches::from_predicate(slice, move |c| self.contains(&c))
+    }
+}
+
+impl<'a, const N: usize> Pattern<'a> for &[char; N] {
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        (*self).into_matches(slice)
+    }
+}
+
+impl<'a, F> Pattern<'a> for F
+where
+    F: FnMut(char) -> bool + 'a,
+{
+    #[inline]
+    fn into_matches(self, slice: &RopeSlice<'a>) -> Matches<'a> {
+        Matches::from_predicate(slice, self)
+    }
+}
+
+//==========================================================
+
+/// An iterator over the parts of a `Rope`/`RopeSlice` separated by the
+/// matches of a pattern.
+///
+/// Behaves like `str::split()`: the separators themselves aren't included,
+/// adjacent separators give empty parts, and there is always at least one
+/// part, even for empty text.
+#[derive(Debug)]
+pub struct Split<'a> {
+    slice: RopeSlice<'a>,
+    matches: Matches<'a>,
+    char_idx: usize,
+    done: bool,
+}
+
+impl<'a> Split<'a> {
+    pub(crate) fn new<P: Pattern<'a>>(slice: &RopeSlice<'a>, pattern: P) -> Split<'a> {
+        Split {
+            slice: *slice,
+            matches: pattern.into_matches(slice),
+            char_idx: 0,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Split<'a> {
+    type Item = RopeSlice<'a>;
+
+    /// Advances the iterator forward and returns the next part.
+    fn next(&mut self) -> Option<RopeSlice<'a>> {
+        if self.done {
+            return None;
+        }
+
+        match self.matches.next() {
+            Some(m) => {
+                let part = self.slice.slice(self.char_idx..m.start);
+                self.char_idx = m.end;
+                Some(part)
+            }
+            None => {
+                self.done = true;
+                Some(self.slice.slice(self.char_idx..))
+            }
+        }
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    fn expected<P: Fn(char) -> bool>(text: &str, pattern: P) -> Vec<usize> {
+        text.chars()
+            .enumerate()
+            .filter(|&(_, c)| pattern(c))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn pattern_char_01() {
+        let r = Rope::from_str(TEXT);
+        let matches: Vec<_> = r.find_iter('o').map(|m| m.start).collect();
+        assert_eq!(matches, expected(TEXT, |c| c == 'o'));
+        let matches: Vec<_> = r.find_iter('ん').map(|m| m.start).collect();
+        assert_eq!(matches, vec![92, 98, 101]);
+        assert_eq!(r.find('ん'), Some(92));
+        assert_eq!(r.find('z'), None);
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
+    fn pattern_char_set_01() {
+        let r = Rope::from_str(TEXT);
+        let set = ['?', '!', '！'];
+        let is_in_set = |c| set.contains(&c);
+        let matches: Vec<_> = r.find_iter(set).map(|m| m.start).collect();
+        assert_eq!(matches, expected(TEXT, is_in_set));
+        let matches: Vec<_> = r.find_iter(&set).map(|m| m.start).collect();
+        assert_eq!(matches, expected(TEXT, is_in_set));
+        let matches: Vec<_> = r.find_iter(&set[..]).map(|m| m.start).collect();
+        assert_eq!(matches, expected(TEXT, is_in_set));
+        assert_eq!(r.find(&[][..]), None);
+    }
+
+    #[test]
+    fn pattern_predicate_01() {
+        let r = Rope::from_str(TEXT);
+        let matches: Vec<_> = r.find_iter(char::is_uppercase).collect();
+        assert_eq!(matches, vec![0..1, 14..15, 33..34, 61..62]);
+        assert_eq!(r.find(|c: char| !c.is_ascii()), Some(91));
+
+        // Stateful predicates.
+        let mut count = 0;
+        let third_space = r.find(|c| {
+            count += (c == ' ') as usize;
+            count == 3
+        });
+        assert_eq!(third_space, Some(13));
+    }
+
+    #[test]
+    fn pattern_sliced_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(50..);
+        assert_eq!(s.find('a'), Some(24));
+        assert_eq!(s.find(char::is_whitespace), Some(5));
+        assert_eq!(s.find(&String::from("you")), Some(18));
+    }
+
+    #[test]
+    fn split_01() {
+        let r = Rope::from_str(TEXT);
+        let parts: Vec<_> = r.split(char::is_whitespace).collect();
+        let expected: Vec<_> = TEXT.split(char::is_whitespace).collect();
+        assert_eq!(parts, expected);
+
+        let parts: Vec<_> = r.split("  ").collect();
+        let expected: Vec<_> = TEXT.split("  ").collect();
+        assert_eq!(parts, expected);
+
+        let parts: Vec<_> = r.split(['?', '、']).collect();
+        let expected: Vec<_> = TEXT.split(&['?', '、'][..]).collect();
+        assert_eq!(parts, expected);
+    }
+
+    #[test]
+    fn split_02() {
+        let r = Rope::from_str("");
+        assert_eq!(r.split(',').collect::<Vec<_>>(), vec![""]);
+        let r = Rope::from_str(",a,,b,");
+        assert_eq!(r.split(',').collect::<Vec<_>>(), vec!["", "a", "", "b", ""]);
+    }
+
+    #[test]
+    fn split_sliced_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(14..31);
+        let parts: Vec<_> = s.split(' ').collect();
+        assert_eq!(parts, vec!["How're", "you", "doing?"]);
+    }
+}
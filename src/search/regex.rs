@@ -0,0 +1,506 @@
+//! Regular expression searching, via the lazy DFAs of `regex-automata`.
+//!
+//! Only available with the `regex` feature enabled.
+
+use std::ops::Range;
+
+use regex_automata::hybrid::dfa::{Cache as DfaCache, DFA};
+use regex_automata::hybrid::regex::{Cache, Regex as HybridRegex};
+use regex_automata::hybrid::LazyStateID;
+use regex_automata::nfa::thompson::pikevm::PikeVM;
+use regex_automata::util::start;
+use regex_automata::{Anchored, Input};
+
+pub use regex_automata::hybrid::BuildError;
+
+use rope::Rope;
+use rope_builder::RopeBuilder;
+use slice::RopeSlice;
+use tree::MAX_BYTES;
+
+/// A compiled regular expression, for searching `Rope`s and `RopeSlice`s.
+///
+/// Uses the syntax and leftmost-first match semantics of the `regex` crate.
+/// Searching runs a lazy DFA directly over the chunks of the rope, and
+/// capture groups (needed only for replacement) are resolved afterwards on
+/// the matched text alone.
+#[derive(Debug)]
+pub struct Regex {
+    dfa: HybridRegex,
+    pikevm: PikeVM,
+}
+
+impl Regex {
+    /// Compiles `pattern` into a `Regex`.
+    ///
+    /// Unicode word boundaries (`\b`) aren't supported by the lazy DFA, and
+    /// result in an error.  The ASCII-only `(?-u:\b)` can be used instead.
+    #[allow(clippy::result_large_err)]
+    pub fn new(pattern: &str) -> Result<Regex, BuildError> {
+        let dfa = HybridRegex::new(pattern)?;
+        // The lazy DFAs above are built from the same NFA with the same
+        // limits, so this can't fail if they didn't.
+        let pikevm = PikeVM::new(pattern).expect("Regex NFA failed to build");
+        Ok(Regex {
+            dfa: dfa,
+            pikevm: pikevm,
+        })
+    }
+}
+
+/// An iterator over the non-overlapping matches of a `Regex` in a
+/// `Rope`/`RopeSlice`.
+///
+/// Yields the `char` index range of each match, in order from the start of
+/// the text.  Matches follow the usual leftmost-first semantics of the
+/// `regex` crate, and empty matches never split a `char`.
+///
+/// The regex engine runs directly over the chunks of the rope, so no part of
+/// the text is ever copied.
+#[derive(Debug)]
+pub struct RegexMatches<'a> {
+    slice: RopeSlice<'a>,
+    regex: &'a Regex,
+    cache: Cache,
+    byte_idx: usize,
+    last_end: Option<usize>,
+}
+
+impl<'a> RegexMatches<'a> {
+    pub(crate) fn new(slice: &RopeSlice<'a>, regex: &'a Regex) -> RegexMatches<'a> {
+        RegexMatches {
+            slice: *slice,
+            regex: regex,
+            cache: regex.dfa.create_cache(),
+            byte_idx: 0,
+            last_end: None,
+        }
+    }
+
+    /// Returns the byte index range of the next match.
+    fn next_bytes(&mut self) -> Option<Range<usize>> {
+        loop {
+            if self.byte_idx > self.slice.len_bytes() {
+                return None;
+            }
+
+            let m = self.find_at(self.byte_idx)?;
+            if m.start == m.end && (Some(m.end) == self.last_end || !self.is_boundary(m.end)) {
+                // Empty matches directly after the previous match, or that
+                // would split a char, are skipped.
+                if m.end >= self.slice.len_bytes() {
+                    self.byte_idx = self.slice.len_bytes() + 1;
+                    return None;
+                }
+                self.byte_idx = self.next_boundary(m.end);
+                continue;
+            }
+
+            self.byte_idx = m.end;
+            self.last_end = Some(m.end);
+            return Some(m);
+        }
+    }
+
+    /// Finds the byte range of the leftmost-first match that starts at or
+    /// after `byte_idx`.
+    fn find_at(&mut self, byte_idx: usize) -> Option<Range<usize>> {
+        let (fwd_cache, rev_cache) = self.cache.as_parts_mut();
+        let end = find_end_fwd(&self.slice, self.regex.dfa.forward(), fwd_cache, byte_idx)?;
+        let start = find_start_rev(
+            &self.slice,
+            self.regex.dfa.reverse(),
+            rev_cache,
+            byte_idx,
+            end,
+        );
+        Some(start..end)
+    }
+
+    /// Returns the byte index of the next char boundary after `byte_idx`.
+    fn next_boundary(&self, byte_idx: usize) -> usize {
+        let char_idx = self.slice.byte_to_char(byte_idx);
+        self.slice.char_to_byte(char_idx + 1)
+    }
+
+    fn is_boundary(&self, byte_idx: usize) -> bool {
+        self.slice.char_to_byte(self.slice.byte_to_char(byte_idx)) == byte_idx
+    }
+}
+
+impl<'a> Iterator for RegexMatches<'a> {
+    type Item = Range<usize>;
+
+    /// Advances the iterator forward and returns the next match.
+    fn next(&mut self) -> Option<Range<usize>> {
+        let m = self.next_bytes()?;
+        Some(self.slice.byte_to_char(m.start)..self.slice.byte_to_char(m.end))
+    }
+}
+
+//==========================================================
+
+/// Replaces every match of `regex` in `rope` with `replacement`, expanding
+/// `$name`/`${name}` capture group references, and returns the number of
+/// matches replaced.
+pub(crate) fn replace_all(rope: &mut Rope, regex: &Regex, replacement: &str) -> usize {
+    let expand = replacement.contains('$');
+
+    // Collect the matches and their expanded replacements.
+    let mut edits = Vec::new();
+    {
+        let slice = rope.slice(..);
+        let mut matches = RegexMatches::new(&slice, regex);
+        let mut cache = regex.pikevm.create_cache();
+        let mut caps = regex.pikevm.create_captures();
+        let mut buf = String::new();
+        while let Some(m) = matches.next_bytes() {
+            let start = slice.byte_to_char(m.start);
+            let end = slice.byte_to_char(m.end);
+
+            let mut text = String::new();
+            if expand {
+                // Re-run the match with captures on just the matched text,
+                // plus a char on either side for any look-around.
+                let ctx_start = start.saturating_sub(1);
+                let ctx_end = (end + 1).min(slice.len_chars());
+                let offset = m.start - slice.char_to_byte(ctx_start);
+                buf.clear();
+                for chunk in slice.slice(ctx_start..ctx_end).chunks() {
+                    buf.push_str(chunk);
+                }
+                let input = Input::new(&buf)
+                    .span(offset..(offset + m.end - m.start))
+                    .anchored(Anchored::Yes);
+                regex.pikevm.search(&mut cache, &input, &mut caps);
+                debug_assert!(caps.is_match());
+                caps.interpolate_string_into(&buf, replacement, &mut text);
+            } else {
+                text.push_str(replacement);
+            }
+
+            edits.push((start..end, text));
+        }
+    }
+
+    // Each in-place edit clones a path of nodes through the tree but leaves
+    // everything else shared, so that's the better option when edits are
+    // sparse.  Otherwise it's faster to stream the result into a new rope.
+    let leaf_count = rope.len_bytes() / MAX_BYTES;
+    if edits.len() * 16 < leaf_count {
+        for (range, text) in edits.iter().rev() {
+            rope.remove(range.clone());
+            rope.insert(range.start, text);
+        }
+    } else {
+        let mut builder = RopeBuilder::new();
+        let mut last = 0;
+        for (range, text) in &edits {
+            for chunk in rope.slice(last..range.start).chunks() {
+                builder.append(chunk);
+            }
+            builder.append(text);
+            last = range.end;
+        }
+        for chunk in rope.slice(last..).chunks() {
+            builder.append(chunk);
+        }
+        *rope = builder.finish();
+    }
+
+    edits.len()
+}
+
+//==========================================================
+
+/// Runs the forward DFA from `byte_idx`, returning the end of the
+/// leftmost-first match, if any.
+fn find_end_fwd(
+    slice: &RopeSlice,
+    dfa: &DFA,
+    cache: &mut DfaCache,
+    byte_idx: usize,
+) -> Option<usize> {
+    let look_behind = if byte_idx > 0 {
+        Some(slice.byte(byte_idx - 1))
+    } else {
+        None
+    };
+    let config = start::Config::new()
+        .anchored(Anchored::No)
+        .look_behind(look_behind);
+    let mut sid = dfa.start_state(cache, &config).expect(GAVE_UP);
+    let mut match_end = None;
+
+    // Matches are reported by the DFA one byte late, so a match state
+    // entered on the byte at `at` means a match ending at `at`.
+    let (chunks, mut chunk_start, _, _) = slice.chunks_at_byte(byte_idx);
+    let mut at = byte_idx;
+    for chunk in chunks {
+        for &byte in &chunk.as_bytes()[(at - chunk_start)..] {
+            sid = step(dfa, cache, sid, Some(byte));
+            if sid.is_match() {
+                match_end = Some(at);
+            } else if sid.is_dead() {
+                return match_end;
+            }
+            at += 1;
+        }
+        chunk_start += chunk.len();
+    }
+
+    sid = step(dfa, cache, sid, None);
+    if sid.is_match() {
+        match_end = Some(at);
+    }
+    match_end
+}
+
+/// Runs the anchored reverse DFA backwards from `end`, returning the start
+/// of the longest match that doesn't start before `byte_idx`.
+fn find_start_rev(
+    slice: &RopeSlice,
+    dfa: &DFA,
+    cache: &mut DfaCache,
+    byte_idx: usize,
+    end: usize,
+) -> usize {
+    let look_behind = if end < slice.len_bytes() {
+        Some(slice.byte(end))
+    } else {
+        None
+    };
+    let config = start::Config::new()
+        .anchored(Anchored::Yes)
+        .look_behind(look_behind);
+    let mut sid = dfa.start_state(cache, &config).expect(GAVE_UP);
+    let mut match_start = end;
+
+    // Likewise, a match state entered on the byte at `at` means a match
+    // starting at `at + 1`.
+    let mut at = end;
+    while at > byte_idx {
+        let (chunk, chunk_start, _, _) = slice.chunk_at_byte(at - 1);
+        let from = byte_idx.max(chunk_start) - chunk_start;
+        for &byte in chunk.as_bytes()[from..(at - chunk_start)].iter().rev() {
+            at -= 1;
+            sid = step(dfa, cache, sid, Some(byte));
+            if sid.is_match() {
+                match_start = at + 1;
+            } else if sid.is_dead() {
+                return match_start;
+            }
+        }
+    }
+
+    let byte = if at > 0 {
+        Some(slice.byte(at - 1))
+    } else {
+        None
+    };
+    sid = step(dfa, cache, sid, byte);
+    if sid.is_match() {
+        match_start = at;
+    }
+    match_start
+}
+
+/// Steps the DFA on `byte`, or on end-of-input if `byte` is `None`.
+#[inline]
+fn step(dfa: &DFA, cache: &mut DfaCache, sid: LazyStateID, byte: Option<u8>) -> LazyStateID {
+    let sid = match byte {
+        Some(byte) => dfa.next_state(cache, sid, byte),
+        None => dfa.next_eoi_state(cache, sid),
+    }
+    .expect(GAVE_UP);
+    assert!(!sid.is_quit(), "Regex search quit on the text");
+    sid
+}
+
+const GAVE_UP: &str = "Regex search gave up";
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    fn expected(text: &str, pattern: &str) -> Vec<Range<usize>> {
+        let re = HybridRegex::new(pattern).unwrap();
+        let mut cache = re.create_cache();
+        re.find_iter(&mut cache, text)
+            .map(|m| {
+                let start = text[..m.start()].chars().count();
+                let end = text[..m.end()].chars().count();
+                start..end
+            })
+            .collect()
+    }
+
+    fn expected_replace(text: &str, pattern: &str, replacement: &str) -> String {
+        let re = PikeVM::new(pattern).unwrap();
+        let mut cache = re.create_cache();
+        let mut result = String::new();
+        let mut last = 0;
+        for caps in re.captures_iter(&mut cache, text) {
+            let m = caps.get_match().unwrap();
+            result.push_str(&text[last..m.start()]);
+            caps.interpolate_string_into(text, replacement, &mut result);
+            last = m.end();
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+
+    #[test]
+    fn regex_matches_01() {
+        let r = Rope::from_str(TEXT);
+        let re = Regex::new(r"[A-Z]\w+").unwrap();
+        let matches: Vec<_> = r.regex_find_iter(&re).collect();
+        assert_eq!(matches, vec![0..5, 14..17, 33..35, 61..65]);
+        assert_eq!(matches, expected(TEXT, r"[A-Z]\w+"));
+    }
+
+    #[test]
+    fn regex_matches_02() {
+        let r = Rope::from_str(TEXT);
+        let re = Regex::new("みんな|ん").unwrap();
+        let matches: Vec<_> = r.regex_find_iter(&re).collect();
+        assert_eq!(matches, vec![92..93, 97..100, 101..102]);
+    }
+
+    #[test]
+    fn regex_matches_03() {
+        // Anchors and look-around at the edges of the text.
+        let r = Rope::from_str(TEXT);
+        for pattern in &["^Hello", "！$", r"(?-u:\b)it(?-u:\b)", "^", "$", "(?m)^|$"] {
+            let re = Regex::new(pattern).unwrap();
+            let matches: Vec<_> = r.regex_find_iter(&re).collect();
+            assert_eq!(matches, expected(TEXT, pattern), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn regex_matches_04() {
+        // Empty matches.
+        let text = "aéaa\u{1F600}b";
+        let r = Rope::from_str(text);
+        for pattern in &["a*", "", "x*", "b*"] {
+            let re = Regex::new(pattern).unwrap();
+            let matches: Vec<_> = r.regex_find_iter(&re).collect();
+            assert_eq!(matches, expected(text, pattern), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn regex_matches_05() {
+        // Across chunk boundaries.
+        let text = "ab".repeat(1000) + "needle\nin a\r\nhaystack" + &"ba".repeat(1000);
+        let r = Rope::from_str(&text);
+        assert!(r.chunks().count() > 1);
+        for pattern in &["(ab)+", "ne+dle", r"(?m)^\w+$", "b*", "a|b", "(?s).{3}"] {
+            let re = Regex::new(pattern).unwrap();
+            let matches: Vec<_> = r.regex_find_iter(&re).collect();
+            assert_eq!(matches, expected(&text, pattern), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn regex_matches_sliced_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(22..80);
+        let text = s.to_string();
+        for pattern in &["you", r"^\w+", r"\w+$", "[a-z]+'[a-z]+"] {
+            let re = Regex::new(pattern).unwrap();
+            let matches: Vec<_> = s.regex_find_iter(&re).collect();
+            assert_eq!(matches, expected(&text, pattern), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn regex_new_01() {
+        assert!(Regex::new("(unclosed").is_err());
+        assert!(Regex::new(r"\bword\b").is_err());
+        assert!(Regex::new(r"(?-u:\b)word(?-u:\b)").is_ok());
+    }
+
+    #[test]
+    fn regex_replace_all_01() {
+        let mut r = Rope::from_str(TEXT);
+        let re = Regex::new("you").unwrap();
+        assert_eq!(r.regex_replace_all(&re, "we"), 2);
+        assert_eq!(r, TEXT.replace("you", "we"));
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn regex_replace_all_02() {
+        let r = Rope::from_str(TEXT);
+        let patterns = &[
+            (r"(\w+)'(\w+)", "$2'$1"),
+            (r"(?<first>[A-Z])(?<rest>\w*)", "${rest}${first}ay"),
+            ("(み)(ん)", "$2$1$$"),
+            ("", "-"),
+            (r"(?m)$", "$0."),
+            ("(x)?e", "[$1]"),
+        ];
+        for &(pattern, replacement) in patterns {
+            let mut r = r.clone();
+            let re = Regex::new(pattern).unwrap();
+            r.regex_replace_all(&re, replacement);
+            assert_eq!(
+                r,
+                expected_replace(TEXT, pattern, replacement),
+                "{}",
+                pattern
+            );
+            r.assert_integrity();
+            r.assert_invariants();
+        }
+        assert_eq!(r, TEXT);
+    }
+
+    #[test]
+    fn regex_replace_all_03() {
+        // Sparse matches in a large rope are edited in place.
+        let text = "ab".repeat(1000) + "needle in a haystack" + &"ba".repeat(1000);
+        let mut r = Rope::from_str(&text);
+        let r2 = r.clone();
+        let re = Regex::new(r"(n[a-z]+) in a (h[a-z]{7})").unwrap();
+        assert_eq!(r.regex_replace_all(&re, "$2 without a $1"), 1);
+        assert_eq!(
+            r,
+            text.replace("needle in a haystack", "haystack without a needle")
+        );
+        assert_eq!(r2, text);
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn regex_replace_all_04() {
+        // Dense matches in a large rope.
+        let text = "ab".repeat(1000) + "\r\n" + &"ba".repeat(1000);
+        let mut r = Rope::from_str(&text);
+        let re = Regex::new("b+").unwrap();
+        assert_eq!(r.regex_replace_all(&re, "\n"), 2000);
+        assert_eq!(r, expected_replace(&text, "b+", "\n"));
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn regex_replace_all_05() {
+        let mut r = Rope::from_str(TEXT);
+        let re = Regex::new("not there").unwrap();
+        assert_eq!(r.regex_replace_all(&re, "$1"), 0);
+        assert_eq!(r, TEXT);
+    }
+}
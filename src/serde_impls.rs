@@ -0,0 +1,102 @@
+//! Serde support.
+//!
+//! Only available with the `serde` feature enabled.
+//!
+//! Ropes and rope slices serialize as strings, and ropes deserialize from
+//! strings.
+
+use core::fmt;
+
+use serde::de::{Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use rope::Rope;
+use rope_builder::RopeBuilder;
+use slice::RopeSlice;
+
+impl Serialize for Rope {
+    /// Serializes the rope's text as a string.
+    ///
+    /// The text is passed to the serializer chunk by chunk via
+    /// `collect_str()`, so serializers that support it (e.g. `serde_json`)
+    /// never build a `String` of the whole text.
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> Serialize for RopeSlice<'a> {
+    /// Serializes the slice's text as a string.
+    ///
+    /// See `Rope`'s `Serialize` impl for details.
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rope {
+    /// Deserializes a rope from a string, either borrowed or owned.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Rope, D::Error> {
+        deserializer.deserialize_str(RopeVisitor)
+    }
+}
+
+struct RopeVisitor;
+
+impl<'de> Visitor<'de> for RopeVisitor {
+    type Value = Rope;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E>(self, text: &str) -> Result<Rope, E> {
+        let mut builder = RopeBuilder::new();
+        builder.append(text);
+        Ok(builder.finish())
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{self, StrDeserializer};
+    use serde::de::{Deserialize, IntoDeserializer};
+    use serde_test::{assert_de_tokens, assert_ser_tokens, assert_tokens, Token};
+
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn serde_01() {
+        let r = Rope::from_str(TEXT);
+        assert_tokens(&r, &[Token::Str(TEXT)]);
+        assert_tokens(&Rope::new(), &[Token::Str("")]);
+        assert_de_tokens(&r, &[Token::BorrowedStr(TEXT)]);
+        assert_de_tokens(&r, &[Token::String(TEXT)]);
+    }
+
+    #[test]
+    fn serde_02() {
+        let text = TEXT.repeat(10);
+        let de: StrDeserializer<value::Error> = text.as_str().into_deserializer();
+        let r = Rope::deserialize(de).unwrap();
+        assert_eq!(r, text.as_str());
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn serde_sliced_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(14..31);
+        assert_ser_tokens(&s, &[Token::Str("How're you doing?")]);
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use rope::Rope;
+
+/// A `Rope` that can be shared between threads, with readers taking
+/// snapshots of it while writers publish new revisions.
+///
+/// Taking a snapshot is lock-free, and only costs an atomic reference count
+/// increment, since cloning a `Rope` shares its data.  Snapshots are
+/// ordinary `Rope`s, so they're unaffected by later revisions, and can be
+/// held onto for as long as needed.  Writers edit a snapshot of their own
+/// and then publish it, so they never block readers either.
+///
+/// Only available with the `shared` feature enabled.
+///
+/// # Example
+/// ```
+/// # use ropey::{Rope, SharedRope};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let doc = Arc::new(SharedRope::new(Rope::from_str("Hello")));
+///
+/// let reader = {
+///     let doc = Arc::clone(&doc);
+///     thread::spawn(move || {
+///         let snapshot = doc.snapshot();
+///         assert!(snapshot == "Hello" || snapshot == "Hello world!");
+///     })
+/// };
+///
+/// doc.update(|rope| rope.insert(5, " world!"));
+/// reader.join().unwrap();
+///
+/// assert_eq!(doc.snapshot(), "Hello world!");
+/// ```
+pub struct SharedRope {
+    current: ArcSwap<Rope>,
+}
+
+impl SharedRope {
+    /// Creates a new `SharedRope`, with `rope` as its first revision.
+    pub fn new(rope: Rope) -> Self {
+        SharedRope {
+            current: ArcSwap::from_pointee(rope),
+        }
+    }
+
+    /// Returns the current revision of the `Rope`.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn snapshot(&self) -> Rope {
+        Rope::clone(&self.current.load())
+    }
+
+    /// Publishes `rope` as the new revision, replacing the current one.
+    ///
+    /// With more than one writer, this can overwrite a revision published
+    /// by another writer in the meantime.  Use `update()` to avoid that.
+    ///
+    /// Runs in O(1) time.
+    #[inline]
+    pub fn store(&self, rope: Rope) {
+        self.current.store(Arc::new(rope));
+    }
+
+    /// Edits a copy of the current revision with `edit`, and publishes it
+    /// as the new revision.  Returns the new revision.
+    ///
+    /// If another writer publishes a revision while `edit` runs, `edit` is
+    /// run again on that revision, so that no edits are lost.  So with more
+    /// than one writer, `edit` may be called more than once.
+    pub fn update<F>(&self, mut edit: F) -> Rope
+    where
+        F: FnMut(&mut Rope),
+    {
+        // The last rope that `edit` made is the one that was published.
+        let mut new = Rope::new();
+        self.current.rcu(|current| {
+            new = Rope::clone(current);
+            edit(&mut new);
+            new.clone()
+        });
+        new
+    }
+}
+
+impl Default for SharedRope {
+    #[inline]
+    fn default() -> Self {
+        SharedRope::new(Rope::new())
+    }
+}
+
+impl From<Rope> for SharedRope {
+    #[inline]
+    fn from(rope: Rope) -> Self {
+        SharedRope::new(rope)
+    }
+}
+
+impl fmt::Debug for SharedRope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SharedRope").field(&self.snapshot()).finish()
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::SharedRope;
+    use Rope;
+
+    // 127 bytes, 103 chars, 1 line
+    const TEXT: &str = "Hello there!  How're you doing?  It's \
+                        a fine day, isn't it?  Aren't you glad \
+                        we're alive?  こんにちは、みんなさん！";
+
+    #[test]
+    fn snapshot_01() {
+        let shared = SharedRope::new(Rope::from_str(TEXT));
+        let snapshot = shared.snapshot();
+
+        shared.store(Rope::from_str("Hi"));
+        assert_eq!(snapshot, TEXT);
+        assert_eq!(shared.snapshot(), "Hi");
+
+        assert_eq!(shared.update(|r| r.insert(2, "!")), "Hi!");
+        assert_eq!(snapshot, TEXT);
+        assert_eq!(shared.snapshot(), "Hi!");
+    }
+
+    #[test]
+    fn update_01() {
+        // No edits are lost with several writers.
+        let shared = Arc::new(SharedRope::default());
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        shared.update(|r| r.insert(0, "こん"));
+                    }
+                })
+            })
+            .collect();
+
+        for _ in 0..100 {
+            let snapshot = shared.snapshot();
+            assert_eq!(snapshot.len_chars() % 2, 0);
+            snapshot.assert_integrity();
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let r = shared.snapshot();
+        assert_eq!(r, "こん".repeat(400).as_str());
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+}
@@ -1,14 +1,39 @@
-use std;
-use std::ops::{Bound, RangeBounds};
-use std::sync::Arc;
-
+use alloc::string::String;
+use alloc::vec::Vec;
+use core;
+use core::hash::Hasher;
+use core::ops::{Bound, RangeBounds};
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "encoding")]
+use encoding_rs::Encoding;
+#[cfg(feature = "futures")]
+use futures_io::AsyncWrite;
+use memchr::memchr_iter;
+use memchr::memmem::Finder;
+
+#[cfg(feature = "futures")]
+use async_io::WriteToAsync;
+#[cfg(feature = "encoding")]
+use encoding::{write_encoded, Unmappable};
+use excerpt::{self, ExcerptLine};
 use iter::{Bytes, Chars, Chunks, Lines};
+#[cfg(feature = "rayon")]
+use par_iter::{ParBytes, ParChunks, ParLines};
+#[cfg(feature = "std")]
+use reader::RopeReader;
 use rope::Rope;
+#[cfg(feature = "fuzzy")]
+use search::FuzzyLines;
+use search::{Matches, Pattern, Split};
+#[cfg(feature = "regex")]
+use search::{Regex, RegexMatches};
 use str_utils::{
     byte_to_char_idx, byte_to_line_idx, char_to_byte_idx, char_to_line_idx, count_chars,
     count_line_breaks, line_to_byte_idx, line_to_char_idx,
 };
-use tree::{Count, Node};
+use tree::{Arc, Count, Node};
 
 /// An immutable view into part of a `Rope`.
 ///
@@ -117,6 +142,98 @@ impl<'a> RopeSlice<'a> {
         })
     }
 
+    //-----------------------------------------------------------------------
+    // Convenience output methods
+
+    /// Writes the contents of the `RopeSlice` to a writer.
+    ///
+    /// The chunks are written directly, so no intermediate `String` is
+    /// allocated.  To do more sophisticated text output, see the
+    /// [`Chunks`](iter/struct.Chunks.html) iterator.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the writer returns an error, `write_to` stops and returns that
+    ///   error.
+    ///
+    /// Note: some data may have been written even if an error is returned.
+    #[cfg(feature = "std")]
+    pub fn write_to<T: io::Write>(&self, mut writer: T) -> io::Result<()> {
+        for chunk in self.chunks() {
+            writer.write_all(chunk.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the contents of the `RopeSlice` to a writer, encoded with the
+    /// given encoding.
+    ///
+    /// The text is encoded chunk by chunk as it's written, without an
+    /// intermediate buffer of the whole text.  Only available with the
+    /// `encoding` feature enabled, and `encoding` is one of the encodings
+    /// of the [`encoding_rs`] crate.  No byte order mark is written.
+    ///
+    /// `unmappable` determines what happens to chars that can't be
+    /// represented in `encoding`.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the writer returns an error, `write_to_with_encoding` stops and
+    ///   returns that error.
+    /// - If a char can't be represented in `encoding` and `unmappable` is
+    ///   `Unmappable::Error`, returns an error of kind `InvalidData`.
+    ///
+    /// Note: some data may have been written even if an error is returned.
+    ///
+    /// [`encoding_rs`]: https://docs.rs/encoding_rs
+    #[cfg(feature = "encoding")]
+    pub fn write_to_with_encoding<T: io::Write>(
+        &self,
+        writer: T,
+        encoding: &'static Encoding,
+        unmappable: Unmappable,
+    ) -> io::Result<()> {
+        write_encoded(self.chunks(), writer, encoding, unmappable)
+    }
+
+    /// Writes the contents of the `RopeSlice` to an async writer.
+    ///
+    /// This is the async equivalent of [`write_to()`](#method.write_to),
+    /// returning a future that writes the text chunk by chunk, waiting
+    /// whenever the writer isn't ready for more.  The writer isn't flushed.
+    ///
+    /// Only available with the `futures` feature enabled.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Errors
+    ///
+    /// - If the writer returns an error, the future stops and returns that
+    ///   error.
+    ///
+    /// Note: some data may have been written even if an error is returned.
+    #[cfg(feature = "futures")]
+    pub fn write_to_async<T: AsyncWrite + Unpin>(&self, writer: T) -> WriteToAsync<'a, T> {
+        WriteToAsync::new(self, writer)
+    }
+
+    /// Creates a reader over the utf8 bytes of the `RopeSlice`.
+    ///
+    /// The returned reader implements `io::Read` and `io::Seek`.  See
+    /// [`RopeReader`](struct.RopeReader.html) for details.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn reader(&self) -> RopeReader<'a> {
+        RopeReader::new(*self)
+    }
+
     //-----------------------------------------------------------------------
     // Informational methods
 
@@ -684,6 +801,266 @@ impl<'a> RopeSlice<'a> {
         }
     }
 
+    /// Gets the lines containing `char_range`, for rendering diagnostic
+    /// excerpts.
+    ///
+    /// Line indices and columns are relative to the `RopeSlice`.  Otherwise
+    /// the same as `Rope::excerpt()`.
+    ///
+    /// Uses range syntax, e.g. `2..7`, `2..`, etc.
+    ///
+    /// Runs in O(M log N) time, where M is the number of lines returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, or the end
+    /// is out of bounds (i.e. `end > len_chars()`).
+    pub fn excerpt<R>(&self, char_range: R, context_lines: usize) -> Vec<ExcerptLine<'a>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = start_bound_to_num(char_range.start_bound()).unwrap_or(0);
+        let end = end_bound_to_num(char_range.end_bound()).unwrap_or_else(|| self.len_chars());
+
+        // Bounds check
+        assert!(start <= end);
+        assert!(
+            end <= self.len_chars(),
+            "Attempt to excerpt past end of RopeSlice: excerpt end {}, RopeSlice length {}",
+            end,
+            self.len_chars()
+        );
+
+        excerpt::excerpt(self, start, end, context_lines)
+    }
+
+    //-----------------------------------------------------------------------
+    // Search methods
+
+    /// Returns whether the `RopeSlice` starts with `prefix`.
+    ///
+    /// Only the chunks overlapping the prefix are examined, and no
+    /// allocation is done.
+    ///
+    /// Runs in O(M + log N) time, where M is the length of `prefix`.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        if prefix.len() > self.len_bytes() {
+            return false;
+        }
+
+        let mut prefix = prefix.as_bytes();
+        for chunk in self.chunks() {
+            if prefix.is_empty() {
+                break;
+            }
+            let len = chunk.len().min(prefix.len());
+            if chunk.as_bytes()[..len] != prefix[..len] {
+                return false;
+            }
+            prefix = &prefix[len..];
+        }
+
+        true
+    }
+
+    /// Returns whether the `RopeSlice` ends with `suffix`.
+    ///
+    /// Only the chunks overlapping the suffix are examined, and no
+    /// allocation is done.
+    ///
+    /// Runs in O(M + log N) time, where M is the length of `suffix`.
+    pub fn ends_with(&self, suffix: &str) -> bool {
+        if suffix.len() > self.len_bytes() {
+            return false;
+        }
+
+        let mut suffix = suffix.as_bytes();
+        let (mut chunks, _, _, _) = self.chunks_at_byte(self.len_bytes());
+        while let Some(chunk) = chunks.prev() {
+            if suffix.is_empty() {
+                break;
+            }
+            let len = chunk.len().min(suffix.len());
+            if chunk.as_bytes()[(chunk.len() - len)..] != suffix[(suffix.len() - len)..] {
+                return false;
+            }
+            suffix = &suffix[..(suffix.len() - len)];
+        }
+
+        true
+    }
+
+    /// Returns the char index of the first match of `pattern` in the
+    /// `RopeSlice`, or `None` if there is no match.
+    ///
+    /// `pattern` can be a `&str`, a `char`, a set of chars, or a
+    /// `FnMut(char) -> bool` predicate.  See [`Pattern`](search/trait.Pattern.html)
+    /// for details.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is an empty `&str`.
+    #[inline]
+    pub fn find<P: Pattern<'a>>(&self, pattern: P) -> Option<usize> {
+        self.find_iter(pattern).next().map(|m| m.start)
+    }
+
+    /// Like `find()`, but compares text case-insensitively.
+    ///
+    /// See the [`search`](search/index.html) module documentation for
+    /// details on how case is folded.
+    ///
+    /// Runs in O(N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is empty.
+    #[inline]
+    pub fn find_ignore_case(&self, pattern: &str) -> Option<usize> {
+        self.find_iter_ignore_case(pattern).next().map(|m| m.start)
+    }
+
+    /// Creates an iterator over the non-overlapping matches of `pattern` in
+    /// the `RopeSlice`, yielding the char index range of each match.
+    ///
+    /// `pattern` can be a `&str`, a `char`, a set of chars, or a
+    /// `FnMut(char) -> bool` predicate.  See [`Pattern`](search/trait.Pattern.html)
+    /// for details.
+    ///
+    /// Creating the iterator runs in O(M) time, where M is the length of
+    /// `pattern`.  Iterating to the end runs in O(N + M) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is an empty `&str`.
+    #[inline]
+    pub fn find_iter<P: Pattern<'a>>(&self, pattern: P) -> Matches<'a> {
+        pattern.into_matches(self)
+    }
+
+    /// Like `find_iter()`, but compares text case-insensitively.
+    ///
+    /// See the [`search`](search/index.html) module documentation for
+    /// details on how case is folded.
+    ///
+    /// Creating the iterator runs in O(M) time, where M is the length of
+    /// `pattern`.  Iterating to the end runs in O(N + M) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is empty.
+    #[inline]
+    pub fn find_iter_ignore_case(&self, pattern: &str) -> Matches<'a> {
+        Matches::new(self, pattern, true)
+    }
+
+    /// Creates an iterator over the parts of the `RopeSlice` separated by
+    /// the matches of `pattern`.
+    ///
+    /// Behaves like `str::split()`, and accepts the same patterns as
+    /// `find()`.
+    ///
+    /// Creating the iterator runs in O(M) time, where M is the length of
+    /// `pattern`.  Iterating to the end runs in O(N + M) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is an empty `&str`.
+    #[inline]
+    pub fn split<P: Pattern<'a>>(&self, pattern: P) -> Split<'a> {
+        Split::new(self, pattern)
+    }
+
+    /// Returns the number of occurrences of `c` in the given char index
+    /// range of the `RopeSlice`.
+    ///
+    /// Uses range syntax, e.g. `2..7`, `2..`, etc.
+    ///
+    /// Runs in O(M + log N) time, where M is the length of the range.  The
+    /// chunks are scanned with SIMD-accelerated byte searching, so this is
+    /// far faster than counting via `chars()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, or the end
+    /// is out of bounds (i.e. `end > len_chars()`).
+    pub fn count_char<R>(&self, char_range: R, c: char) -> usize
+    where
+        R: RangeBounds<usize>,
+    {
+        let mut buf = [0u8; 4];
+        let needle = c.encode_utf8(&mut buf).as_bytes();
+        let chunks = self.slice(char_range).chunks();
+
+        // Chunks never split a char, so each can be counted separately.
+        if needle.len() == 1 {
+            chunks
+                .map(|chunk| memchr_iter(needle[0], chunk.as_bytes()).count())
+                .sum()
+        } else {
+            let finder = Finder::new(needle);
+            chunks
+                .map(|chunk| finder.find_iter(chunk.as_bytes()).count())
+                .sum()
+        }
+    }
+
+    /// Returns the number of line breaks in the given char index range of
+    /// the `RopeSlice`.
+    ///
+    /// Line breaks are counted the same way as for `len_lines()`, with CRLF
+    /// pairs counting as a single line break.  Unlike `count_char()`, this
+    /// doesn't need to scan the text.
+    ///
+    /// Uses range syntax, e.g. `2..7`, `2..`, etc.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than the end, or the end
+    /// is out of bounds (i.e. `end > len_chars()`).
+    #[inline]
+    pub fn count_line_breaks<R>(&self, char_range: R) -> usize
+    where
+        R: RangeBounds<usize>,
+    {
+        self.slice(char_range).len_lines() - 1
+    }
+
+    /// Creates an iterator over the lines of the `RopeSlice` that
+    /// fuzzy-match `pattern`, in the style of `fzf`.
+    ///
+    /// See [`FuzzyLines`](search/struct.FuzzyLines.html) for details.
+    ///
+    /// Only available with the `fuzzy` feature enabled.
+    ///
+    /// Runs in O(log N) time.
+    #[cfg(feature = "fuzzy")]
+    #[inline]
+    pub fn fuzzy_lines(&self, pattern: &str) -> FuzzyLines<'a> {
+        FuzzyLines::new(self, pattern)
+    }
+
+    /// Creates an iterator over the non-overlapping matches of `regex` in
+    /// the `RopeSlice`, yielding the char index range of each match.
+    ///
+    /// The `RopeSlice` is treated as the entire text being searched, so
+    /// e.g. `^` matches at its start even if it's not the start of the
+    /// `Rope`.  See [`RegexMatches`](search/struct.RegexMatches.html) for
+    /// details.
+    ///
+    /// Only available with the `regex` feature enabled.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "regex")]
+    #[inline]
+    pub fn regex_find_iter(&self, regex: &'a Regex) -> RegexMatches<'a> {
+        RegexMatches::new(self, regex)
+    }
+
     //-----------------------------------------------------------------------
     // Iterator methods
 
@@ -857,7 +1234,7 @@ impl<'a> RopeSlice<'a> {
     ///
     /// Panics if `line_idx` is out of bounds (i.e. `line_idx > len_lines()`).
     #[inline]
-    pub fn lines_at(&self, line_idx: usize) -> Lines {
+    pub fn lines_at(&self, line_idx: usize) -> Lines<'a> {
         // Bounds check
         assert!(
             line_idx <= self.len_lines(),
@@ -1159,6 +1536,54 @@ impl<'a> RopeSlice<'a> {
             }
         }
     }
+
+    /// Creates a parallel iterator over the bytes of the `RopeSlice`, for
+    /// use with Rayon.
+    ///
+    /// Only available with the `rayon` feature enabled.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_bytes(&self) -> ParBytes<'a> {
+        ParBytes::new(self.par_chunks())
+    }
+
+    /// Creates a parallel iterator over the chunks of the `RopeSlice`, for
+    /// use with Rayon.
+    ///
+    /// Yields the same non-empty chunks as `chunks()`.
+    ///
+    /// Only available with the `rayon` feature enabled.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_chunks(&self) -> ParChunks<'a> {
+        match *self {
+            RopeSlice(RSEnum::Full {
+                node,
+                start_byte,
+                end_byte,
+                ..
+            }) => ParChunks::new(node, start_byte as usize, end_byte as usize),
+            RopeSlice(RSEnum::Light { text, .. }) => ParChunks::from_str(text),
+        }
+    }
+
+    /// Creates a parallel iterator over the lines of the `RopeSlice`, for
+    /// use with Rayon.
+    ///
+    /// Yields the same lines as `lines()`, and is indexed by line.
+    ///
+    /// Only available with the `rayon` feature enabled.
+    ///
+    /// Runs in O(1) time.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_lines(&self) -> ParLines<'a> {
+        ParLines::new(*self)
+    }
 }
 
 //==============================================================
@@ -1181,6 +1606,46 @@ pub(crate) fn end_bound_to_num(b: Bound<&usize>) -> Option<usize> {
     }
 }
 
+/// Feeds the bytes of `chunks` to `state` in fixed-size blocks.
+///
+/// Some hashers (e.g. ones that process each `write()` call in whole words)
+/// give different results for the same bytes depending on how they're split
+/// between calls, so the calls are made independent of the chunk boundaries.
+pub(crate) fn hash_chunks<'a, I, H>(chunks: I, state: &mut H)
+where
+    I: Iterator<Item = &'a str>,
+    H: Hasher,
+{
+    const BLOCK_SIZE: usize = 256;
+    let mut block = [0u8; BLOCK_SIZE];
+    let mut len = 0;
+
+    for chunk in chunks {
+        let mut bytes = chunk.as_bytes();
+        while !bytes.is_empty() {
+            if len == 0 && bytes.len() >= BLOCK_SIZE {
+                // Whole blocks straight from the chunk.
+                state.write(&bytes[..BLOCK_SIZE]);
+                bytes = &bytes[BLOCK_SIZE..];
+                continue;
+            }
+
+            let n = (BLOCK_SIZE - len).min(bytes.len());
+            block[len..(len + n)].copy_from_slice(&bytes[..n]);
+            len += n;
+            bytes = &bytes[n..];
+            if len == BLOCK_SIZE {
+                state.write(&block);
+                len = 0;
+            }
+        }
+    }
+
+    if len > 0 {
+        state.write(&block[..len]);
+    }
+}
+
 //==============================================================
 // Conversion impls
 
@@ -1222,13 +1687,13 @@ impl<'a> From<RopeSlice<'a>> for String {
 /// owned string if the contents is not contiguous in memory.
 ///
 /// Runs in best case O(1), worst case O(N).
-impl<'a> From<RopeSlice<'a>> for std::borrow::Cow<'a, str> {
+impl<'a> From<RopeSlice<'a>> for alloc::borrow::Cow<'a, str> {
     #[inline]
     fn from(s: RopeSlice<'a>) -> Self {
         if let Some(text) = s.as_str() {
-            std::borrow::Cow::Borrowed(text)
+            alloc::borrow::Cow::Borrowed(text)
         } else {
-            std::borrow::Cow::Owned(String::from(s))
+            alloc::borrow::Cow::Owned(String::from(s))
         }
     }
 }
@@ -1236,15 +1701,15 @@ impl<'a> From<RopeSlice<'a>> for std::borrow::Cow<'a, str> {
 //==============================================================
 // Other impls
 
-impl<'a> std::fmt::Debug for RopeSlice<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl<'a> core::fmt::Debug for RopeSlice<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_list().entries(self.chunks()).finish()
     }
 }
 
-impl<'a> std::fmt::Display for RopeSlice<'a> {
+impl<'a> core::fmt::Display for RopeSlice<'a> {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         for chunk in self.chunks() {
             write!(f, "{}", chunk)?
         }
@@ -1252,18 +1717,20 @@ impl<'a> std::fmt::Display for RopeSlice<'a> {
     }
 }
 
-impl<'a> std::cmp::Eq for RopeSlice<'a> {}
+impl<'a> core::cmp::Eq for RopeSlice<'a> {}
 
-impl<'a, 'b> std::cmp::PartialEq<RopeSlice<'b>> for RopeSlice<'a> {
+impl<'a, 'b> core::cmp::PartialEq<RopeSlice<'b>> for RopeSlice<'a> {
     fn eq(&self, other: &RopeSlice<'b>) -> bool {
         if self.len_bytes() != other.len_bytes() {
             return false;
         }
 
+        // Compared as bytes, since the chunk boundaries of one slice can
+        // fall within multi-byte chars of the other if the texts differ.
         let mut chunk_itr_1 = self.chunks();
         let mut chunk_itr_2 = other.chunks();
-        let mut chunk1 = chunk_itr_1.next().unwrap_or("");
-        let mut chunk2 = chunk_itr_2.next().unwrap_or("");
+        let mut chunk1 = chunk_itr_1.next().unwrap_or("").as_bytes();
+        let mut chunk2 = chunk_itr_2.next().unwrap_or("").as_bytes();
 
         loop {
             if chunk1.len() > chunk2.len() {
@@ -1271,18 +1738,18 @@ impl<'a, 'b> std::cmp::PartialEq<RopeSlice<'b>> for RopeSlice<'a> {
                     return false;
                 } else {
                     chunk1 = &chunk1[chunk2.len()..];
-                    chunk2 = "";
+                    chunk2 = &[];
                 }
             } else if &chunk2[..chunk1.len()] != chunk1 {
                 return false;
             } else {
                 chunk2 = &chunk2[chunk1.len()..];
-                chunk1 = "";
+                chunk1 = &[];
             }
 
             if chunk1.is_empty() {
                 if let Some(chunk) = chunk_itr_1.next() {
-                    chunk1 = chunk;
+                    chunk1 = chunk.as_bytes();
                 } else {
                     break;
                 }
@@ -1290,7 +1757,7 @@ impl<'a, 'b> std::cmp::PartialEq<RopeSlice<'b>> for RopeSlice<'a> {
 
             if chunk2.is_empty() {
                 if let Some(chunk) = chunk_itr_2.next() {
-                    chunk2 = chunk;
+                    chunk2 = chunk.as_bytes();
                 } else {
                     break;
                 }
@@ -1301,7 +1768,7 @@ impl<'a, 'b> std::cmp::PartialEq<RopeSlice<'b>> for RopeSlice<'a> {
     }
 }
 
-impl<'a, 'b> std::cmp::PartialEq<&'b str> for RopeSlice<'a> {
+impl<'a, 'b> core::cmp::PartialEq<&'b str> for RopeSlice<'a> {
     #[inline]
     fn eq(&self, other: &&'b str) -> bool {
         match *self {
@@ -1312,7 +1779,7 @@ impl<'a, 'b> std::cmp::PartialEq<&'b str> for RopeSlice<'a> {
 
                 let mut idx = 0;
                 for chunk in self.chunks() {
-                    if chunk != &other[idx..(idx + chunk.len())] {
+                    if chunk.as_bytes() != &other.as_bytes()[idx..(idx + chunk.len())] {
                         return false;
                     }
                     idx += chunk.len();
@@ -1327,72 +1794,84 @@ impl<'a, 'b> std::cmp::PartialEq<&'b str> for RopeSlice<'a> {
     }
 }
 
-impl<'a, 'b> std::cmp::PartialEq<RopeSlice<'a>> for &'b str {
+impl<'a, 'b> core::cmp::PartialEq<RopeSlice<'a>> for &'b str {
     #[inline]
     fn eq(&self, other: &RopeSlice<'a>) -> bool {
         other == self
     }
 }
 
-impl<'a> std::cmp::PartialEq<str> for RopeSlice<'a> {
+impl<'a> core::cmp::PartialEq<str> for RopeSlice<'a> {
     #[inline]
     fn eq(&self, other: &str) -> bool {
-        std::cmp::PartialEq::<&str>::eq(self, &other)
+        core::cmp::PartialEq::<&str>::eq(self, &other)
     }
 }
 
-impl<'a> std::cmp::PartialEq<RopeSlice<'a>> for str {
+impl<'a> core::cmp::PartialEq<RopeSlice<'a>> for str {
     #[inline]
     fn eq(&self, other: &RopeSlice<'a>) -> bool {
-        std::cmp::PartialEq::<&str>::eq(other, &self)
+        core::cmp::PartialEq::<&str>::eq(other, &self)
     }
 }
 
-impl<'a> std::cmp::PartialEq<String> for RopeSlice<'a> {
+impl<'a> core::cmp::PartialEq<String> for RopeSlice<'a> {
     #[inline]
     fn eq(&self, other: &String) -> bool {
         self == other.as_str()
     }
 }
 
-impl<'a> std::cmp::PartialEq<RopeSlice<'a>> for String {
+impl<'a> core::cmp::PartialEq<RopeSlice<'a>> for String {
     #[inline]
     fn eq(&self, other: &RopeSlice<'a>) -> bool {
         self.as_str() == other
     }
 }
 
-impl<'a, 'b> std::cmp::PartialEq<std::borrow::Cow<'b, str>> for RopeSlice<'a> {
+impl<'a, 'b> core::cmp::PartialEq<alloc::borrow::Cow<'b, str>> for RopeSlice<'a> {
     #[inline]
-    fn eq(&self, other: &std::borrow::Cow<'b, str>) -> bool {
+    fn eq(&self, other: &alloc::borrow::Cow<'b, str>) -> bool {
         *self == **other
     }
 }
 
-impl<'a, 'b> std::cmp::PartialEq<RopeSlice<'a>> for std::borrow::Cow<'b, str> {
+impl<'a, 'b> core::cmp::PartialEq<RopeSlice<'a>> for alloc::borrow::Cow<'b, str> {
     #[inline]
     fn eq(&self, other: &RopeSlice<'a>) -> bool {
         **self == *other
     }
 }
 
-impl<'a> std::cmp::PartialEq<Rope> for RopeSlice<'a> {
+impl<'a> core::cmp::PartialEq<Rope> for RopeSlice<'a> {
     #[inline]
     fn eq(&self, other: &Rope) -> bool {
         *self == other.slice(..)
     }
 }
 
-impl<'a> std::cmp::PartialEq<RopeSlice<'a>> for Rope {
+impl<'a> core::cmp::PartialEq<RopeSlice<'a>> for Rope {
     #[inline]
     fn eq(&self, other: &RopeSlice<'a>) -> bool {
         self.slice(..) == *other
     }
 }
 
-impl<'a> std::cmp::Ord for RopeSlice<'a> {
+/// Hashes the text, independent of how it's split into chunks.
+///
+/// Runs in O(N) time.
+impl<'a> core::hash::Hash for RopeSlice<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_chunks(self.chunks(), state);
+        // Same as `str`, so that e.g. tuples of slices don't collide when
+        // the text moves between them.
+        state.write_u8(0xff);
+    }
+}
+
+impl<'a> core::cmp::Ord for RopeSlice<'a> {
     #[allow(clippy::op_ref)] // Erroneously thinks with can directly use a slice.
-    fn cmp(&self, other: &RopeSlice<'a>) -> std::cmp::Ordering {
+    fn cmp(&self, other: &RopeSlice<'a>) -> core::cmp::Ordering {
         let mut chunk_itr_1 = self.chunks();
         let mut chunk_itr_2 = other.chunks();
         let mut chunk1 = chunk_itr_1.next().unwrap_or("").as_bytes();
@@ -1401,20 +1880,20 @@ impl<'a> std::cmp::Ord for RopeSlice<'a> {
         loop {
             if chunk1.len() >= chunk2.len() {
                 if &chunk1[..chunk2.len()] < chunk2 {
-                    return std::cmp::Ordering::Less;
+                    return core::cmp::Ordering::Less;
                 } else if &chunk1[..chunk2.len()] > chunk2 {
-                    return std::cmp::Ordering::Greater;
+                    return core::cmp::Ordering::Greater;
                 }
                 chunk1 = &chunk1[chunk2.len()..];
                 chunk2 = &[];
             } else {
                 if chunk1 < &chunk2[..chunk1.len()] {
-                    return std::cmp::Ordering::Less;
+                    return core::cmp::Ordering::Less;
                 } else if chunk1 > &chunk2[..chunk1.len()] {
-                    return std::cmp::Ordering::Greater;
+                    return core::cmp::Ordering::Greater;
                 }
-                chunk1 = &[];
                 chunk2 = &chunk2[chunk1.len()..];
+                chunk1 = &[];
             }
 
             if chunk1.is_empty() {
@@ -1435,18 +1914,18 @@ impl<'a> std::cmp::Ord for RopeSlice<'a> {
         }
 
         if self.len_bytes() > other.len_bytes() {
-            return std::cmp::Ordering::Greater;
+            return core::cmp::Ordering::Greater;
         } else if self.len_bytes() < other.len_bytes() {
-            return std::cmp::Ordering::Less;
+            return core::cmp::Ordering::Less;
         } else {
-            return std::cmp::Ordering::Equal;
+            return core::cmp::Ordering::Equal;
         }
     }
 }
 
-impl<'a, 'b> std::cmp::PartialOrd<RopeSlice<'b>> for RopeSlice<'a> {
+impl<'a, 'b> core::cmp::PartialOrd<RopeSlice<'b>> for RopeSlice<'a> {
     #[inline]
-    fn partial_cmp(&self, other: &RopeSlice<'b>) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &RopeSlice<'b>) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -2006,6 +2485,77 @@ mod tests {
         s.slice(37..39);
     }
 
+    #[test]
+    fn starts_with_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(5..98);
+        assert!(s.starts_with(""));
+        assert!(s.starts_with(" there!  How're"));
+        assert!(!s.starts_with(&TEXT[5..]));
+        assert!(!s.starts_with("Hello"));
+    }
+
+    #[test]
+    fn starts_with_02() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(43..43);
+        assert!(s.starts_with(""));
+        assert!(!s.starts_with("a"));
+    }
+
+    #[test]
+    fn ends_with_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(5..98);
+        assert!(s.ends_with(""));
+        assert!(s.ends_with("alive?  こんにちは、み"));
+        assert!(!s.ends_with("みんなさん！"));
+        assert!(!s.ends_with(&format!("a{}", TEXT)));
+    }
+
+    #[test]
+    fn ends_with_02() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(43..43);
+        assert!(s.ends_with(""));
+        assert!(!s.ends_with("a"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_to_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(5..98);
+        let mut buf = Vec::new();
+        s.write_to(&mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), s);
+
+        let mut buf = Vec::new();
+        r.slice(43..43).write_to(&mut buf).unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn count_char_01() {
+        let r = Rope::from_str(TEXT);
+        let s = r.slice(5..100);
+        let text: String = TEXT.chars().skip(5).take(95).collect();
+        for &c in &[' ', 'e', '?', 'ん', '！', 'H'] {
+            let expected = text.chars().filter(|&c2| c2 == c).count();
+            assert_eq!(s.count_char(.., c), expected);
+        }
+        assert_eq!(s.count_char(9..26, 'o'), 3);
+    }
+
+    #[test]
+    fn count_line_breaks_01() {
+        let r = Rope::from_str(TEXT_LINES);
+        let s = r.slice(20..90);
+        assert_eq!(s.count_line_breaks(..), 3);
+        assert_eq!(s.count_line_breaks(12..), 2);
+        assert_eq!(s.count_line_breaks(..11), 0);
+    }
+
     #[test]
     fn eq_str_01() {
         let r = Rope::from_str(TEXT);
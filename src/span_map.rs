@@ -0,0 +1,693 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use anchors::{shift_anchor, Bias};
+
+/// A collection of values attached to char ranges ("spans") of a text, that
+/// follow along with edits to it.
+///
+/// This is useful for things like syntax highlighting, selections, folds,
+/// or diagnostics.  Spans may overlap, and the same range may be used for
+/// more than one span.
+///
+/// Like [`Anchors`](struct.Anchors.html), a `SpanMap` doesn't hold on to
+/// the text itself.  Instead, each edit made to the text must also be
+/// passed to `edit()`, which updates the spans accordingly:
+///
+/// - The start of each span moves like an anchor with `Bias::Right`, and
+///   its end like an anchor with `Bias::Left`, so text inserted at either
+///   end of a span ends up outside of it.  Spans shrink when part of their
+///   text is removed.
+/// - Spans whose text is all removed are removed as well.
+/// - Empty spans move like an anchor with `Bias::Left`, and are never
+///   removed by edits.
+///
+/// Internally, the spans are kept in a balanced tree ordered by their start,
+/// so that edits and queries run in O(log N) time, plus time proportional
+/// to the number of spans they affect or return.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// use ropey::SpanMap;
+///
+/// let mut rope = Rope::from_str("fn main() {}");
+/// let mut highlights = SpanMap::new();
+/// highlights.insert(0..2, "keyword");
+/// highlights.insert(3..7, "function");
+///
+/// rope.insert(0, "pub ");
+/// highlights.edit(0..0, 4);
+///
+/// let at: Vec<_> = highlights.at(8).collect();
+/// assert_eq!(at, [(7..11, &"function")]);
+/// assert_eq!(rope.slice(7..11), "main");
+/// ```
+#[derive(Clone)]
+pub struct SpanMap<T> {
+    root: Link<T>,
+    len: usize,
+    // State for generating the nodes' priorities.
+    seed: u64,
+}
+
+type Link<T> = Option<Box<SpanNode<T>>>;
+
+#[derive(Clone)]
+struct SpanNode<T> {
+    start: usize,
+    end: usize,
+    // The largest `end` in this node's subtree.
+    max_end: usize,
+    // A shift of the positions in this node's children that hasn't been
+    // applied to them yet.  It's stored as a wrapping `usize` so that it can
+    // be negative.
+    shift: usize,
+    priority: u64,
+    value: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> SpanMap<T> {
+    /// Creates an empty `SpanMap`.
+    #[inline]
+    pub fn new() -> Self {
+        SpanMap {
+            root: None,
+            len: 0,
+            seed: 0x2545_F491_4F6C_DD1D,
+        }
+    }
+
+    /// Returns the number of spans.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether there are no spans.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a span over `char_range` with the given value.
+    ///
+    /// Runs in O(log N) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of `char_range` is greater than its end.
+    pub fn insert(&mut self, char_range: Range<usize>, value: T) {
+        let Range { start, end } = char_range;
+        assert!(
+            start <= end,
+            "Invalid char range {}..{}: start must be less than or equal to end",
+            start,
+            end
+        );
+
+        let node = Box::new(SpanNode {
+            start: start,
+            end: end,
+            max_end: end,
+            shift: 0,
+            priority: self.next_priority(),
+            value: value,
+            left: None,
+            right: None,
+        });
+        // Goes after the spans with the same start.
+        let (left, right) = split_after(self.root.take(), start);
+        self.root = merge(merge(left, Some(node)), right);
+        self.len += 1;
+    }
+
+    /// Removes all spans.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+
+    /// Keeps only the spans for which `f` returns true.
+    ///
+    /// Runs in O(N log N) time.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Range<usize>, &mut T) -> bool,
+    {
+        let mut nodes = Vec::with_capacity(self.len);
+        into_nodes(self.root.take(), &mut nodes);
+        for mut node in nodes {
+            if f(node.start..node.end, &mut node.value) {
+                self.root = merge(self.root.take(), Some(node));
+            } else {
+                self.len -= 1;
+            }
+        }
+    }
+
+    /// Updates the spans for an edit to the text.
+    ///
+    /// `char_range` is the range of text that was removed or replaced, in
+    /// the text as it was before the edit, and `inserted_chars` is the
+    /// length in chars of the text that replaced it.  For pure insertions
+    /// `char_range` is the empty range at the insertion point, and for pure
+    /// removals `inserted_chars` is zero.
+    ///
+    /// Runs in O(log N) time, plus O(log N) for each span that overlaps the
+    /// edit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of `char_range` is greater than its end.
+    pub fn edit(&mut self, char_range: Range<usize>, inserted_chars: usize) {
+        let Range { start, end } = char_range;
+        assert!(
+            start <= end,
+            "Invalid char range {}..{}: start must be less than or equal to end",
+            start,
+            end
+        );
+
+        // Split the spans by where they start: before the edit, within it,
+        // and after it.  Spans after it are just shifted, and the others only
+        // need updating if they reach into the edit.
+        let (mut before, rest) = split(self.root.take(), start);
+        let (within, mut after) = split_after(rest, end);
+
+        shift_ends(&mut before, start, end, inserted_chars);
+
+        let mut nodes = Vec::new();
+        into_nodes(within, &mut nodes);
+        let len = nodes.len();
+        nodes.retain(|node| node.start == node.end || node.end > end || start == end);
+        self.len -= len - nodes.len();
+        for node in &mut nodes {
+            if node.start == node.end {
+                node.start = shift_anchor(node.start, Bias::Left, start, end, inserted_chars);
+                node.end = node.start;
+            } else {
+                node.start = shift_anchor(node.start, Bias::Right, start, end, inserted_chars);
+                node.end = shift_anchor(node.end, Bias::Left, start, end, inserted_chars);
+            }
+            node.max_end = node.end;
+        }
+        // Empty spans can end up before the others.
+        nodes.sort_by_key(|node| node.start);
+        let mut within = None;
+        for node in nodes {
+            within = merge(within, Some(node));
+        }
+
+        if let Some(ref mut node) = after {
+            node.apply_shift(inserted_chars.wrapping_sub(end - start));
+        }
+
+        self.root = merge(merge(before, within), after);
+    }
+
+    /// Creates an iterator over the spans, in order of their start.
+    ///
+    /// Spans with the same start are visited in the order they were
+    /// inserted, as long as they've been edited the same way.
+    #[inline]
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Range<usize>, &'a T)> + 'a {
+        SpanIter::new(&self.root, 0, usize::MAX)
+    }
+
+    /// Creates an iterator over the spans that contain the char at
+    /// `char_idx`, in order of their start.
+    ///
+    /// Runs in O(log N) time, plus time proportional to the number of spans
+    /// visited.
+    #[inline]
+    pub fn at<'a>(&'a self, char_idx: usize) -> impl Iterator<Item = (Range<usize>, &'a T)> + 'a {
+        // Spans ending at `usize::MAX` still can't contain the char there.
+        SpanIter::new(&self.root, char_idx.saturating_add(1), char_idx)
+            .filter(move |(span, _)| span.end > char_idx)
+    }
+
+    /// Creates an iterator over the spans that overlap `char_range`, in
+    /// order of their start.
+    ///
+    /// Non-empty spans overlap the range if they share any chars with it,
+    /// or, if the range is empty, if they contain the char at its position.
+    /// Empty spans overlap the range if they're within it or at either end
+    /// of it.
+    ///
+    /// Runs in O(log N) time, plus time proportional to the number of spans
+    /// visited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of `char_range` is greater than its end.
+    pub fn overlapping<'a>(
+        &'a self,
+        char_range: Range<usize>,
+    ) -> impl Iterator<Item = (Range<usize>, &'a T)> + 'a {
+        let Range { start, end } = char_range;
+        assert!(
+            start <= end,
+            "Invalid char range {}..{}: start must be less than or equal to end",
+            start,
+            end
+        );
+
+        SpanIter::new(&self.root, start, end).filter(move |(span, _)| {
+            if span.start == span.end {
+                true
+            } else {
+                span.start < end.max(start + 1) && span.end > start
+            }
+        })
+    }
+
+    //-----------------------------------------------------------------
+    // Internal utilities.
+
+    // Returns a pseudo-random priority for a new node (xorshift64).
+    fn next_priority(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+}
+
+impl<T> Default for SpanMap<T> {
+    #[inline]
+    fn default() -> Self {
+        SpanMap::new()
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for SpanMap<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+//-------------------------------------------------------------
+
+impl<T> SpanNode<T> {
+    #[inline]
+    fn apply_shift(&mut self, shift: usize) {
+        self.start = self.start.wrapping_add(shift);
+        self.end = self.end.wrapping_add(shift);
+        self.max_end = self.max_end.wrapping_add(shift);
+        self.shift = self.shift.wrapping_add(shift);
+    }
+
+    // Applies the pending shift to the node's children.
+    #[inline]
+    fn push_down(&mut self) {
+        if self.shift != 0 {
+            if let Some(ref mut node) = self.left {
+                node.apply_shift(self.shift);
+            }
+            if let Some(ref mut node) = self.right {
+                node.apply_shift(self.shift);
+            }
+            self.shift = 0;
+        }
+    }
+
+    // Recomputes `max_end`.  The pending shift must have been pushed down.
+    #[inline]
+    fn update(&mut self) {
+        debug_assert_eq!(self.shift, 0);
+        self.max_end = self.end;
+        if let Some(ref node) = self.left {
+            self.max_end = self.max_end.max(node.max_end);
+        }
+        if let Some(ref node) = self.right {
+            self.max_end = self.max_end.max(node.max_end);
+        }
+    }
+}
+
+// Splits the tree into the spans that start before `char_idx`, and the
+// rest.
+fn split<T>(link: Link<T>, char_idx: usize) -> (Link<T>, Link<T>) {
+    match link {
+        None => (None, None),
+        Some(mut node) => {
+            node.push_down();
+            if node.start < char_idx {
+                let (left, right) = split(node.right.take(), char_idx);
+                node.right = left;
+                node.update();
+                (Some(node), right)
+            } else {
+                let (left, right) = split(node.left.take(), char_idx);
+                node.left = right;
+                node.update();
+                (left, Some(node))
+            }
+        }
+    }
+}
+
+// Splits the tree into the spans that start at or before `char_idx`, and
+// the rest.
+fn split_after<T>(link: Link<T>, char_idx: usize) -> (Link<T>, Link<T>) {
+    match char_idx.checked_add(1) {
+        Some(char_idx) => split(link, char_idx),
+        None => (link, None),
+    }
+}
+
+// Joins two trees, where all spans in `left` start before or at the same
+// position as those in `right`.
+fn merge<T>(left: Link<T>, right: Link<T>) -> Link<T> {
+    match (left, right) {
+        (None, right) => right,
+        (left, None) => left,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.push_down();
+                left.right = merge(left.right.take(), Some(right));
+                left.update();
+                Some(left)
+            } else {
+                right.push_down();
+                right.left = merge(Some(left), right.left.take());
+                right.update();
+                Some(right)
+            }
+        }
+    }
+}
+
+// Takes the tree apart into its nodes, in order.
+fn into_nodes<T>(link: Link<T>, nodes: &mut Vec<Box<SpanNode<T>>>) {
+    if let Some(mut node) = link {
+        node.push_down();
+        let right = node.right.take();
+        into_nodes(node.left.take(), nodes);
+        node.max_end = node.end;
+        nodes.push(node);
+        into_nodes(right, nodes);
+    }
+}
+
+// Updates the ends of the spans that reach into an edit, for spans that
+// start before it.
+fn shift_ends<T>(link: &mut Link<T>, start: usize, end: usize, inserted_chars: usize) {
+    if let Some(ref mut node) = *link {
+        if node.max_end <= start {
+            return;
+        }
+        node.push_down();
+        if node.end > start {
+            node.end = shift_anchor(node.end, Bias::Left, start, end, inserted_chars);
+        }
+        shift_ends(&mut node.left, start, end, inserted_chars);
+        shift_ends(&mut node.right, start, end, inserted_chars);
+        node.update();
+    }
+}
+
+//-------------------------------------------------------------
+
+// An in-order iterator over the spans that start at or before `max_start`
+// and end at or after `min_end`.
+struct SpanIter<'a, T: 'a> {
+    // Nodes whose left subtree has already been visited, along with the
+    // shift to apply to them.
+    stack: Vec<(&'a SpanNode<T>, usize)>,
+    min_end: usize,
+    max_start: usize,
+}
+
+impl<'a, T> SpanIter<'a, T> {
+    fn new(root: &'a Link<T>, min_end: usize, max_start: usize) -> SpanIter<'a, T> {
+        let mut iter = SpanIter {
+            stack: Vec::new(),
+            min_end: min_end,
+            max_start: max_start,
+        };
+        iter.push_left(root, 0);
+        iter
+    }
+
+    fn push_left(&mut self, mut link: &'a Link<T>, mut shift: usize) {
+        while let Some(ref node) = *link {
+            if node.max_end.wrapping_add(shift) < self.min_end {
+                return;
+            }
+            self.stack.push((node, shift));
+            shift = shift.wrapping_add(node.shift);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for SpanIter<'a, T> {
+    type Item = (Range<usize>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, shift)) = self.stack.pop() {
+            let start = node.start.wrapping_add(shift);
+            if start > self.max_start {
+                // All remaining spans start even later.
+                self.stack.clear();
+                return None;
+            }
+            self.push_left(&node.right, shift.wrapping_add(node.shift));
+
+            let end = node.end.wrapping_add(shift);
+            if end >= self.min_end {
+                return Some((start..end, &node.value));
+            }
+        }
+        None
+    }
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A simple pseudo-random number generator, for reproducible tests.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    // The straightforward version of `SpanMap::edit()`, on a list of spans.
+    fn edit_spans(spans: &mut Vec<(Range<usize>, usize)>, range: Range<usize>, inserted: usize) {
+        let (start, end) = (range.start, range.end);
+        spans.retain(|(span, _)| {
+            span.start == span.end || start == end || span.start < start || span.end > end
+        });
+        for &mut (ref mut span, _) in spans.iter_mut() {
+            if span.start == span.end {
+                span.start = shift_anchor(span.start, Bias::Left, start, end, inserted);
+                span.end = span.start;
+            } else {
+                span.start = shift_anchor(span.start, Bias::Right, start, end, inserted);
+                span.end = shift_anchor(span.end, Bias::Left, start, end, inserted);
+            }
+        }
+    }
+
+    fn sorted<'a, I: Iterator<Item = (Range<usize>, &'a usize)>>(
+        iter: I,
+    ) -> Vec<(usize, usize, usize)> {
+        let mut spans: Vec<_> = iter.map(|(r, &v)| (r.start, r.end, v)).collect();
+        spans.sort();
+        spans
+    }
+
+    fn assert_matches(map: &SpanMap<usize>, spans: &[(Range<usize>, usize)], rng: &mut Rng) {
+        assert_eq!(map.len(), spans.len());
+        let all: Vec<_> = map.iter().collect();
+        assert!(all.windows(2).all(|w| w[0].0.start <= w[1].0.start));
+        assert_eq!(
+            sorted(all.into_iter()),
+            sorted(spans.iter().map(|(r, v)| (r.clone(), v)))
+        );
+
+        for _ in 0..10 {
+            let p = rng.below(120);
+            let expected = spans
+                .iter()
+                .filter(|&(r, _)| r.start <= p && p < r.end)
+                .map(|(r, v)| (r.clone(), v));
+            assert_eq!(sorted(map.at(p)), sorted(expected));
+
+            let a = rng.below(120);
+            let b = a + rng.below(10);
+            let expected = spans
+                .iter()
+                .filter(|&(r, _)| {
+                    if r.start == r.end {
+                        a <= r.start && r.start <= b
+                    } else if a == b {
+                        r.start <= a && a < r.end
+                    } else {
+                        r.start < b && a < r.end
+                    }
+                })
+                .map(|(r, v)| (r.clone(), v));
+            assert_eq!(sorted(map.overlapping(a..b)), sorted(expected));
+        }
+    }
+
+    #[test]
+    fn insert_01() {
+        let mut map = SpanMap::new();
+        map.insert(5..9, 'a');
+        map.insert(0..3, 'b');
+        map.insert(5..6, 'c');
+        map.insert(2..2, 'd');
+        let all: Vec<_> = map.iter().collect();
+        assert_eq!(
+            all,
+            [(0..3, &'b'), (2..2, &'d'), (5..9, &'a'), (5..6, &'c')]
+        );
+        assert_eq!(map.len(), 4);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.iter().count(), 0);
+    }
+
+    #[test]
+    fn at_01() {
+        let mut map = SpanMap::new();
+        map.insert(0..4, 0);
+        map.insert(2..6, 1);
+        map.insert(4..4, 2);
+        map.insert(6..8, 3);
+        assert_eq!(map.at(0).collect::<Vec<_>>(), [(0..4, &0)]);
+        assert_eq!(map.at(3).collect::<Vec<_>>(), [(0..4, &0), (2..6, &1)]);
+        assert_eq!(map.at(4).collect::<Vec<_>>(), [(2..6, &1)]);
+        assert_eq!(map.at(6).collect::<Vec<_>>(), [(6..8, &3)]);
+        assert_eq!(map.at(8).count(), 0);
+
+        map.insert(7..usize::MAX, 4);
+        assert_eq!(
+            map.at(usize::MAX - 1).collect::<Vec<_>>(),
+            [(7..usize::MAX, &4)]
+        );
+        assert_eq!(map.at(usize::MAX).count(), 0);
+    }
+
+    #[test]
+    fn overlapping_01() {
+        let mut map = SpanMap::new();
+        map.insert(0..4, 0);
+        map.insert(2..6, 1);
+        map.insert(4..4, 2);
+        map.insert(6..8, 3);
+        assert_eq!(
+            map.overlapping(4..6).collect::<Vec<_>>(),
+            [(2..6, &1), (4..4, &2)]
+        );
+        assert_eq!(
+            map.overlapping(3..3).collect::<Vec<_>>(),
+            [(0..4, &0), (2..6, &1)]
+        );
+        assert_eq!(
+            map.overlapping(4..4).collect::<Vec<_>>(),
+            [(2..6, &1), (4..4, &2)]
+        );
+        assert_eq!(map.overlapping(8..10).count(), 0);
+    }
+
+    #[test]
+    fn edit_01() {
+        // Insertions.
+        let mut map = SpanMap::new();
+        map.insert(0..3, 0);
+        map.insert(3..6, 1);
+        map.insert(3..3, 2);
+        map.insert(1..5, 3);
+        map.edit(3..3, 2);
+        let all: Vec<_> = map.iter().collect();
+        assert_eq!(all, [(0..3, &0), (1..7, &3), (3..3, &2), (5..8, &1)]);
+    }
+
+    #[test]
+    fn edit_02() {
+        // Removals and replacements.
+        let mut map = SpanMap::new();
+        map.insert(0..3, 0);
+        map.insert(3..6, 1);
+        map.insert(4..4, 2);
+        map.insert(1..8, 3);
+        map.insert(5..9, 4);
+        map.edit(2..6, 0);
+        let all: Vec<_> = map.iter().collect();
+        assert_eq!(all, [(0..2, &0), (1..4, &3), (2..2, &2), (2..5, &4)]);
+
+        map.edit(1..3, 1);
+        let all: Vec<_> = map.iter().collect();
+        assert_eq!(all, [(0..1, &0), (1..1, &2), (2..3, &3), (2..4, &4)]);
+    }
+
+    #[test]
+    fn retain_01() {
+        let mut map = SpanMap::new();
+        for i in 0..20 {
+            map.insert(i..(i + 3), i);
+        }
+        map.retain(|range, value| {
+            *value *= 10;
+            range.start % 2 == 0
+        });
+        assert_eq!(map.len(), 10);
+        let all: Vec<_> = map.iter().map(|(r, &v)| (r.start, v)).collect();
+        let expected: Vec<_> = (0..10).map(|i| (i * 2, i * 20)).collect();
+        assert_eq!(all, expected);
+    }
+
+    #[test]
+    fn random_edits_01() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        let mut map = SpanMap::new();
+        let mut spans = Vec::new();
+        for i in 0..2000 {
+            if rng.below(3) != 0 || spans.len() < 10 {
+                let start = rng.below(100);
+                let end = start + rng.below(3) * rng.below(10);
+                map.insert(start..end, i);
+                spans.push((start..end, i));
+            } else {
+                let start = rng.below(100);
+                let end = start + rng.below(2) * rng.below(8);
+                let inserted = rng.below(2) * rng.below(8);
+                map.edit(start..end, inserted);
+                edit_spans(&mut spans, start..end, inserted);
+            }
+            if i % 50 == 0 {
+                map.retain(|_, value| *value % 7 != 0);
+                spans.retain(|&(_, value)| value % 7 != 0);
+            }
+            assert_matches(&map, &spans, &mut rng);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn edit_03() {
+        let mut map = SpanMap::<()>::new();
+        #[allow(clippy::reversed_empty_ranges)]
+        map.edit(3..2, 0);
+    }
+}
@@ -0,0 +1,62 @@
+/// The heap memory used by a `Rope`, as returned by `Rope::mem_usage()` and
+/// `Rope::mem_usage_amortized()`.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// let rope = Rope::from_str(&"Hello world!\n".repeat(1000));
+/// let usage = rope.mem_usage();
+///
+/// assert_eq!(usage.text_bytes, rope.len_bytes());
+/// assert!(usage.overhead_bytes > 0);
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MemUsage {
+    /// The bytes taken up by the text itself.
+    pub text_bytes: usize,
+    /// Everything else: unused space in the leaves, the internal nodes, and
+    /// the reference counts of the nodes.
+    pub overhead_bytes: usize,
+}
+
+impl MemUsage {
+    /// The total bytes of heap memory used.
+    #[inline]
+    pub fn total_bytes(&self) -> usize {
+        self.text_bytes + self.overhead_bytes
+    }
+}
+
+/// Statistics about the shape of a `Rope`'s tree, as returned by
+/// `Rope::tree_stats()`.
+///
+/// These are mostly useful for tracking how fragmented a `Rope` gets over
+/// time from the edits made to it.  `Rope::compact()` and
+/// `Rope::shrink_to_fit()` undo fragmentation.
+///
+/// # Example
+///
+/// ```
+/// # use ropey::Rope;
+/// let mut rope = Rope::from_str(&"Hello world!\n".repeat(10000));
+/// assert!(rope.tree_stats().average_leaf_fill > 0.9);
+///
+/// for i in 0..10000 {
+///     rope.remove((i * 9)..(i * 9 + 4));
+/// }
+/// assert!(rope.tree_stats().average_leaf_fill < 0.9);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TreeStats {
+    /// The levels of internal nodes above the leaves, as with
+    /// `Rope::depth()`.
+    pub depth: usize,
+    /// The number of internal nodes.
+    pub internal_node_count: usize,
+    /// The number of leaves, which hold the text.
+    pub leaf_count: usize,
+    /// How full the leaves are on average, from 0.0 to 1.0, relative to
+    /// the most text a leaf can hold.
+    pub average_leaf_fill: f64,
+}
@@ -4,14 +4,39 @@
 //! slices in ways compatible with Ropey.  They may be useful when building
 //! additional functionality on top of Ropey.
 
-use std;
+use core;
+
+use memchr::memchr3;
+
+use crlf;
+
+// Detects cpu features at runtime, which needs std.  Without std, only the
+// features that the crate is compiled for are used.
+#[cfg(feature = "std")]
+macro_rules! has_cpu_feature {
+    ($feature:tt) => {
+        is_x86_feature_detected!($feature)
+    };
+}
+#[cfg(not(feature = "std"))]
+macro_rules! has_cpu_feature {
+    ($feature:tt) => {
+        cfg!(target_feature = $feature)
+    };
+}
 
 // Get the appropriate module (if any) for sse2 types and intrinsics for the
 // platform we're compiling for.
 #[cfg(target_arch = "x86")]
-use std::arch::x86 as sse2;
+use core::arch::x86 as sse2;
 #[cfg(target_arch = "x86_64")]
-use std::arch::x86_64 as sse2;
+use core::arch::x86_64 as sse2;
+
+// Same, for avx2.
+#[cfg(target_arch = "x86")]
+use core::arch::x86 as avx2;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64 as avx2;
 
 /// Converts from byte-index to char-index in a string slice.
 ///
@@ -36,7 +61,6 @@ pub fn byte_to_char_idx(text: &str, byte_idx: usize) -> usize {
 /// Any past-the-end index will return the last line index.
 #[inline]
 pub fn byte_to_line_idx(text: &str, byte_idx: usize) -> usize {
-    use crlf;
     let mut byte_idx = byte_idx.min(text.len());
     while !text.is_char_boundary(byte_idx) {
         byte_idx -= 1;
@@ -56,7 +80,7 @@ pub fn byte_to_line_idx(text: &str, byte_idx: usize) -> usize {
 pub fn char_to_byte_idx(text: &str, char_idx: usize) -> usize {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     {
-        if is_x86_feature_detected!("sse2") {
+        if has_cpu_feature!("sse2") {
             return char_to_byte_idx_inner::<sse2::__m128i>(text, char_idx);
         }
     }
@@ -136,9 +160,23 @@ pub fn char_to_line_idx(text: &str, char_idx: usize) -> usize {
 /// Any past-the-end index will return the one-past-the-end byte index.
 #[inline]
 pub fn line_to_byte_idx(text: &str, line_idx: usize) -> usize {
+    // Hopping from line break to line break is faster for the first few
+    // lines, which is the common case (e.g. finding the end of a line).
+    if line_idx <= LINE_HOP_MAX {
+        let bytes = text.as_bytes();
+        let mut byte_idx = 0;
+        for _ in 0..line_idx {
+            match next_line_break_end(&bytes[byte_idx..]) {
+                Some(i) => byte_idx += i,
+                None => return text.len(),
+            }
+        }
+        return byte_idx;
+    }
+
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     {
-        if is_x86_feature_detected!("sse2") {
+        if has_cpu_feature!("sse2") {
             return line_to_byte_idx_inner::<sse2::__m128i>(text, line_idx);
         }
     }
@@ -197,6 +235,103 @@ pub fn line_to_char_idx(text: &str, line_idx: usize) -> usize {
     byte_to_char_idx(text, line_to_byte_idx(text, line_idx))
 }
 
+/// Counts the chars in a string slice.
+///
+/// Gives the same result as `text.chars().count()`, but uses bit-fiddling
+/// magic to do it much faster.
+#[inline]
+pub fn count_chars(text: &str) -> usize {
+    count_chars_in_bytes(text.as_bytes())
+}
+
+/// Counts the line breaks in a string slice.
+///
+/// The following unicode sequences are considered line breaks:
+/// - u{000A}        (Line Feed)
+/// - u{000B}        (Vertical Tab)
+/// - u{000C}        (Form Feed)
+/// - u{000D}        (Carriage Return)
+/// - u{000D}u{000A} (Carriage Return + Line Feed)
+/// - u{0085}        (Next Line)
+/// - u{2028}        (Line Separator)
+/// - u{2029}        (Paragraph Separator)
+///
+/// A CRLF pair counts as a single line break.
+#[inline]
+pub fn count_line_breaks(text: &str) -> usize {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if text.len() >= AVX2_MIN_LEN && has_cpu_feature!("avx2") {
+            return unsafe { count_line_breaks_avx2(text) };
+        }
+        if has_cpu_feature!("sse2") {
+            return count_line_breaks_internal::<sse2::__m128i>(text);
+        }
+    }
+
+    // Fallback for non-sse2 platforms.
+    count_line_breaks_internal::<usize>(text)
+}
+
+/// Returns whether a byte index is a segment break in a string slice.
+///
+/// Segment breaks are the char boundaries that aren't between the CR and LF
+/// of a CRLF pair.  Ropey only ever splits text between chunks at segment
+/// breaks, so code working on a rope's chunks never sees part of a char or
+/// of a CRLF pair at the edge of a chunk.
+///
+/// The start and end of the text are always segment breaks.
+///
+/// # Panics
+///
+/// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > text.len()`).
+#[inline]
+pub fn is_segment_break(text: &str, byte_idx: usize) -> bool {
+    assert!(byte_idx <= text.len(), "Index out of bounds.");
+    crlf::is_break(byte_idx, text.as_bytes())
+}
+
+/// Returns the segment break before (but not at) a byte index in a string
+/// slice.
+///
+/// See `is_segment_break()` for what a segment break is.  Returns zero if
+/// `byte_idx` is zero.
+///
+/// # Panics
+///
+/// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > text.len()`).
+#[inline]
+pub fn prev_segment_break(text: &str, byte_idx: usize) -> usize {
+    assert!(byte_idx <= text.len(), "Index out of bounds.");
+    crlf::prev_break(byte_idx, text.as_bytes())
+}
+
+/// Returns the segment break after (but not at) a byte index in a string
+/// slice.
+///
+/// See `is_segment_break()` for what a segment break is.  Returns
+/// `text.len()` if `byte_idx` is `text.len()`.
+///
+/// # Panics
+///
+/// Panics if `byte_idx` is out of bounds (i.e. `byte_idx > text.len()`).
+#[inline]
+pub fn next_segment_break(text: &str, byte_idx: usize) -> usize {
+    assert!(byte_idx <= text.len(), "Index out of bounds.");
+    crlf::next_break(byte_idx, text.as_bytes())
+}
+
+/// Returns whether the seam between two string slices would be a segment
+/// break if they were joined.
+///
+/// That is, whether `left` doesn't end with a CR that `right` continues
+/// with an LF.  See `is_segment_break()` for what a segment break is.  This
+/// is useful for checking a seam between two chunks without joining them.
+#[inline]
+pub fn seam_is_segment_break(left: &str, right: &str) -> bool {
+    left.is_empty() || right.is_empty() || crlf::seam_is_break(left.as_bytes(), right.as_bytes())
+}
+
 /// Returns the byte position just after the second-to-last line break
 /// in `text`, or zero of there is no second-to-last line break.
 ///
@@ -244,6 +379,39 @@ pub(crate) fn prev_line_end_char_idx(text: &str) -> usize {
 // Internal
 //===========================================================================
 
+/// The largest line index that `line_to_byte_idx()` finds by hopping from
+/// line break to line break, rather than by counting them in chunks.
+const LINE_HOP_MAX: usize = 2;
+
+/// Returns the byte position just after the first line break in `bytes`, or
+/// `None` if there is no line break.
+///
+/// Uses `memchr` to skip straight to the bytes that can start a line break.
+/// LF, VT and FF are always line breaks on their own, so the other line
+/// breaks only need to be looked for before the first of those.
+#[inline]
+fn next_line_break_end(bytes: &[u8]) -> Option<usize> {
+    let end = memchr3(0x0A, 0x0B, 0x0C, bytes);
+
+    let mut i = 0;
+    let search_end = end.unwrap_or(bytes.len());
+    while let Some(j) = memchr3(0x0D, 0xC2, 0xE2, &bytes[i..search_end]) {
+        i += j;
+        let rest = &bytes[(i + 1)..];
+        match bytes[i] {
+            0x0D if rest.first() == Some(&0x0A) => return Some(i + 2),
+            0x0D => return Some(i + 1),
+            0xC2 if rest.first() == Some(&0x85) => return Some(i + 2),
+            0xE2 if rest.len() >= 2 && rest[0] == 0x80 && (rest[1] >> 1) == 0x54 => {
+                return Some(i + 3);
+            }
+            _ => i += 1,
+        }
+    }
+
+    end.map(|i| i + 1)
+}
+
 /// Returns whether the given string ends in a line break or not.
 #[inline]
 pub(crate) fn ends_with_line_break(text: &str) -> bool {
@@ -264,21 +432,14 @@ pub(crate) fn ends_with_line_break(text: &str) -> bool {
     }
 }
 
-/// Uses bit-fiddling magic to count utf8 chars really quickly.
-/// We actually count the number of non-starting utf8 bytes, since
-/// they have a consistent starting two-bit pattern.  We then
-/// subtract from the byte length of the text to get the final
-/// count.
-#[inline]
-pub(crate) fn count_chars(text: &str) -> usize {
-    count_chars_in_bytes(text.as_bytes())
-}
-
 #[inline]
 pub(crate) fn count_chars_in_bytes(text: &[u8]) -> usize {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     {
-        if is_x86_feature_detected!("sse2") {
+        if text.len() >= AVX2_MIN_LEN && has_cpu_feature!("avx2") {
+            return unsafe { count_chars_avx2(text) };
+        }
+        if has_cpu_feature!("sse2") {
             return count_chars_internal::<sse2::__m128i>(text);
         }
     }
@@ -287,6 +448,26 @@ pub(crate) fn count_chars_in_bytes(text: &[u8]) -> usize {
     count_chars_internal::<usize>(text)
 }
 
+/// Texts shorter than this are counted with sse2 even when avx2 is available,
+/// since the extra unaligned bytes at the ends of the text outweigh what avx2
+/// saves on short texts.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const AVX2_MIN_LEN: usize = 256;
+
+/// Unsafe because the CPU must support avx2.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn count_chars_avx2(text: &[u8]) -> usize {
+    count_chars_internal::<avx2::__m256i>(text)
+}
+
+/// Unsafe because the CPU must support avx2.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn count_line_breaks_avx2(text: &str) -> usize {
+    count_line_breaks_internal::<avx2::__m256i>(text)
+}
+
 #[inline(always)]
 fn count_chars_internal<T: ByteChunk>(text: &[u8]) -> usize {
     // Get `middle` for more efficient chunk-based counting.
@@ -322,30 +503,6 @@ fn count_chars_internal<T: ByteChunk>(text: &[u8]) -> usize {
     text.len() - inv_count
 }
 
-/// Uses bit-fiddling magic to count line breaks really quickly.
-///
-/// The following unicode sequences are considered newlines by this function:
-/// - u{000A}        (Line Feed)
-/// - u{000B}        (Vertical Tab)
-/// - u{000C}        (Form Feed)
-/// - u{000D}        (Carriage Return)
-/// - u{000D}u{000A} (Carriage Return + Line Feed)
-/// - u{0085}        (Next Line)
-/// - u{2028}        (Line Separator)
-/// - u{2029}        (Paragraph Separator)
-#[inline]
-pub(crate) fn count_line_breaks(text: &str) -> usize {
-    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    {
-        if is_x86_feature_detected!("sse2") {
-            return count_line_breaks_internal::<sse2::__m128i>(text);
-        }
-    }
-
-    // Fallback for non-sse2 platforms.
-    count_line_breaks_internal::<usize>(text)
-}
-
 #[inline(always)]
 fn count_line_breaks_internal<T: ByteChunk>(text: &str) -> usize {
     let mut bytes = text.as_bytes();
@@ -519,7 +676,7 @@ unsafe fn count_line_breaks_in_chunk_from_ptr<T: ByteChunk>(bytes: &[u8]) -> T {
 /// of `bytes` if alignment is beyond the end of `bytes`.
 #[inline(always)]
 fn alignment_diff<T>(bytes: &[u8]) -> usize {
-    let alignment = std::mem::align_of::<T>();
+    let alignment = core::mem::align_of::<T>();
     let ptr = bytes.as_ptr() as usize;
     (alignment - ((ptr - 1) & (alignment - 1)) - 1).min(bytes.len())
 }
@@ -528,7 +685,7 @@ fn alignment_diff<T>(bytes: &[u8]) -> usize {
 
 /// Interface for working with chunks of bytes at a time, providing the
 /// operations needed for the functionality in str_utils.
-trait ByteChunk: Copy + Clone + std::fmt::Debug {
+trait ByteChunk: Copy + Clone + core::fmt::Debug {
     /// Returns the size of the chunk in bytes.
     fn size() -> usize;
 
@@ -582,17 +739,17 @@ trait ByteChunk: Copy + Clone + std::fmt::Debug {
 impl ByteChunk for usize {
     #[inline(always)]
     fn size() -> usize {
-        std::mem::size_of::<usize>()
+        core::mem::size_of::<usize>()
     }
 
     #[inline(always)]
     fn max_acc() -> usize {
-        (256 / std::mem::size_of::<usize>()) - 1
+        (256 / core::mem::size_of::<usize>()) - 1
     }
 
     #[inline(always)]
     fn splat(n: u8) -> Self {
-        const ONES: usize = std::usize::MAX / 0xFF;
+        const ONES: usize = core::usize::MAX / 0xFF;
         ONES * n as usize
     }
 
@@ -617,7 +774,7 @@ impl ByteChunk for usize {
 
     #[inline(always)]
     fn cmp_eq_byte(&self, byte: u8) -> Self {
-        const ONES: usize = std::usize::MAX / 0xFF;
+        const ONES: usize = core::usize::MAX / 0xFF;
         const ONES_HIGH: usize = ONES << 7;
         let word = *self ^ (byte as usize * ONES);
         (!(((word & !ONES_HIGH) + !ONES_HIGH) | word) & ONES_HIGH) >> 7
@@ -625,7 +782,7 @@ impl ByteChunk for usize {
 
     #[inline(always)]
     fn bytes_between_127(&self, a: u8, b: u8) -> Self {
-        const ONES: usize = std::usize::MAX / 0xFF;
+        const ONES: usize = core::usize::MAX / 0xFF;
         const ONES_HIGH: usize = ONES << 7;
         let tmp = *self & (ONES * 127);
         (((ONES * (127 + b as usize) - tmp) & !*self & (tmp + (ONES * (127 - a as usize))))
@@ -668,7 +825,7 @@ impl ByteChunk for usize {
 
     #[inline(always)]
     fn sum_bytes(&self) -> usize {
-        const ONES: usize = std::usize::MAX / 0xFF;
+        const ONES: usize = core::usize::MAX / 0xFF;
         self.wrapping_mul(ONES) >> ((Self::size() - 1) * 8)
     }
 }
@@ -677,7 +834,7 @@ impl ByteChunk for usize {
 impl ByteChunk for sse2::__m128i {
     #[inline(always)]
     fn size() -> usize {
-        std::mem::size_of::<sse2::__m128i>()
+        core::mem::size_of::<sse2::__m128i>()
     }
 
     #[inline(always)]
@@ -692,7 +849,7 @@ impl ByteChunk for sse2::__m128i {
 
     #[inline(always)]
     fn is_zero(&self) -> bool {
-        let tmp = unsafe { std::mem::transmute::<Self, (u64, u64)>(*self) };
+        let tmp = unsafe { core::mem::transmute::<Self, (u64, u64)>(*self) };
         tmp.0 == 0 && tmp.1 == 0
     }
 
@@ -751,145 +908,132 @@ impl ByteChunk for sse2::__m128i {
 
     #[inline(always)]
     fn inc_nth_from_end_lex_byte(&self, n: usize) -> Self {
-        let mut tmp = unsafe { std::mem::transmute::<Self, [u8; 16]>(*self) };
+        let mut tmp = unsafe { core::mem::transmute::<Self, [u8; 16]>(*self) };
         tmp[15 - n] += 1;
-        unsafe { std::mem::transmute::<[u8; 16], Self>(tmp) }
+        unsafe { core::mem::transmute::<[u8; 16], Self>(tmp) }
     }
 
     #[inline(always)]
     fn dec_last_lex_byte(&self) -> Self {
-        let mut tmp = unsafe { std::mem::transmute::<Self, [u8; 16]>(*self) };
+        let mut tmp = unsafe { core::mem::transmute::<Self, [u8; 16]>(*self) };
         tmp[15] -= 1;
-        unsafe { std::mem::transmute::<[u8; 16], Self>(tmp) }
+        unsafe { core::mem::transmute::<[u8; 16], Self>(tmp) }
     }
 
     #[inline(always)]
     fn sum_bytes(&self) -> usize {
-        const ONES: u64 = std::u64::MAX / 0xFF;
-        let tmp = unsafe { std::mem::transmute::<Self, (u64, u64)>(*self) };
+        const ONES: u64 = core::u64::MAX / 0xFF;
+        let tmp = unsafe { core::mem::transmute::<Self, (u64, u64)>(*self) };
         let a = tmp.0.wrapping_mul(ONES) >> (7 * 8);
         let b = tmp.1.wrapping_mul(ONES) >> (7 * 8);
         (a + b) as usize
     }
 }
 
-// AVX2, currently unused because it actually runs slower than SSE2 for most
-// of the things we're doing, oddly.
-// impl ByteChunk for x86_64::__m256i {
-//     #[inline(always)]
-//     fn size() -> usize {
-//         std::mem::size_of::<x86_64::__m256i>()
-//     }
-
-//     #[inline(always)]
-//     fn max_acc() -> usize {
-//         (256 / 8) - 1
-//     }
-
-//     #[inline(always)]
-//     fn splat(n: u8) -> Self {
-//         unsafe { x86_64::_mm256_set1_epi8(n as i8) }
-//     }
-
-//     #[inline(always)]
-//     fn is_zero(&self) -> bool {
-//         let tmp = unsafe { std::mem::transmute::<Self, (u64, u64, u64, u64)>(*self) };
-//         tmp.0 == 0 && tmp.1 == 0 && tmp.2 == 0 && tmp.3 == 0
-//     }
-
-//     #[inline(always)]
-//     fn shift_back_lex(&self, n: usize) -> Self {
-//         let mut tmp1;
-//         let tmp2 = unsafe { std::mem::transmute::<Self, [u8; 32]>(*self) };
-//         match n {
-//             0 => return *self,
-//             1 => {
-//                 tmp1 = unsafe {
-//                     std::mem::transmute::<Self, [u8; 32]>(x86_64::_mm256_srli_si256(*self, 1))
-//                 };
-//                 tmp1[15] = tmp2[16];
-//             }
-//             2 => {
-//                 tmp1 = unsafe {
-//                     std::mem::transmute::<Self, [u8; 32]>(x86_64::_mm256_srli_si256(*self, 2))
-//                 };
-//                 tmp1[15] = tmp2[17];
-//                 tmp1[14] = tmp2[16];
-//             }
-//             _ => unreachable!(),
-//         }
-//         unsafe { std::mem::transmute::<[u8; 32], Self>(tmp1) }
-//     }
-
-//     #[inline(always)]
-//     fn shr(&self, n: usize) -> Self {
-//         match n {
-//             0 => *self,
-//             1 => unsafe { x86_64::_mm256_srli_epi64(*self, 1) },
-//             2 => unsafe { x86_64::_mm256_srli_epi64(*self, 2) },
-//             3 => unsafe { x86_64::_mm256_srli_epi64(*self, 3) },
-//             4 => unsafe { x86_64::_mm256_srli_epi64(*self, 4) },
-//             _ => unreachable!(),
-//         }
-//     }
-
-//     #[inline(always)]
-//     fn cmp_eq_byte(&self, byte: u8) -> Self {
-//         let tmp = unsafe { x86_64::_mm256_cmpeq_epi8(*self, Self::splat(byte)) };
-//         unsafe { x86_64::_mm256_and_si256(tmp, Self::splat(1)) }
-//     }
-
-//     #[inline(always)]
-//     fn bytes_between_127(&self, a: u8, b: u8) -> Self {
-//         let tmp2 = unsafe { x86_64::_mm256_cmpgt_epi8(*self, Self::splat(a)) };
-//         let tmp1 = {
-//             let tmp = unsafe { x86_64::_mm256_cmpgt_epi8(*self, Self::splat(b + 1)) };
-//             unsafe { x86_64::_mm256_andnot_si256(tmp, Self::splat(0xff)) }
-//         };
-//         let tmp3 = unsafe { x86_64::_mm256_and_si256(tmp1, tmp2) };
-//         unsafe { x86_64::_mm256_and_si256(tmp3, Self::splat(1)) }
-//     }
-
-//     #[inline(always)]
-//     fn bitand(&self, other: Self) -> Self {
-//         unsafe { x86_64::_mm256_and_si256(*self, other) }
-//     }
-
-//     #[inline(always)]
-//     fn add(&self, other: Self) -> Self {
-//         unsafe { x86_64::_mm256_add_epi8(*self, other) }
-//     }
-
-//     #[inline(always)]
-//     fn sub(&self, other: Self) -> Self {
-//         unsafe { x86_64::_mm256_sub_epi8(*self, other) }
-//     }
-
-//     #[inline(always)]
-//     fn inc_nth_from_end_lex_byte(&self, n: usize) -> Self {
-//         let mut tmp = unsafe { std::mem::transmute::<Self, [u8; 32]>(*self) };
-//         tmp[31 - n] += 1;
-//         unsafe { std::mem::transmute::<[u8; 32], Self>(tmp) }
-//     }
-
-//     #[inline(always)]
-//     fn dec_last_lex_byte(&self) -> Self {
-//         let mut tmp = unsafe { std::mem::transmute::<Self, [u8; 32]>(*self) };
-//         tmp[31] -= 1;
-//         unsafe { std::mem::transmute::<[u8; 32], Self>(tmp) }
-//     }
-
-//     #[inline(always)]
-//     fn sum_bytes(&self) -> usize {
-//         const ONES: u64 = std::u64::MAX / 0xFF;
-//         let tmp = unsafe { std::mem::transmute::<Self, (u64, u64, u64, u64)>(*self) };
-//         let a = tmp.0.wrapping_mul(ONES) >> (7 * 8);
-//         let b = tmp.1.wrapping_mul(ONES) >> (7 * 8);
-//         let c = tmp.2.wrapping_mul(ONES) >> (7 * 8);
-//         let d = tmp.3.wrapping_mul(ONES) >> (7 * 8);
-//         (a + b + c + d) as usize
-//     }
-// }
+// AVX2.  The trait's methods aren't compiled with AVX2 enabled themselves, so
+// this is only fast when used from the `#[target_feature(enable = "avx2")]`
+// functions below, where everything gets inlined together.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl ByteChunk for avx2::__m256i {
+    #[inline(always)]
+    fn size() -> usize {
+        core::mem::size_of::<avx2::__m256i>()
+    }
+
+    #[inline(always)]
+    fn max_acc() -> usize {
+        // `sum_bytes()` sums with `_mm256_sad_epu8()`, so bytes can go all
+        // the way up to 255.
+        255
+    }
+
+    #[inline(always)]
+    fn splat(n: u8) -> Self {
+        unsafe { avx2::_mm256_set1_epi8(n as i8) }
+    }
+
+    #[inline(always)]
+    fn is_zero(&self) -> bool {
+        unsafe { avx2::_mm256_testz_si256(*self, *self) != 0 }
+    }
+
+    #[inline(always)]
+    fn shift_back_lex(&self, n: usize) -> Self {
+        // `_mm256_srli_si256()` shifts the two 128-bit lanes separately, so
+        // bring the bytes that cross between lanes in with `alignr`.
+        let high = unsafe { avx2::_mm256_permute2x128_si256(*self, *self, 0x81) };
+        match n {
+            0 => *self,
+            1 => unsafe { avx2::_mm256_alignr_epi8(high, *self, 1) },
+            2 => unsafe { avx2::_mm256_alignr_epi8(high, *self, 2) },
+            3 => unsafe { avx2::_mm256_alignr_epi8(high, *self, 3) },
+            4 => unsafe { avx2::_mm256_alignr_epi8(high, *self, 4) },
+            _ => unreachable!(),
+        }
+    }
+
+    #[inline(always)]
+    fn shr(&self, n: usize) -> Self {
+        match n {
+            0 => *self,
+            1 => unsafe { avx2::_mm256_srli_epi64(*self, 1) },
+            2 => unsafe { avx2::_mm256_srli_epi64(*self, 2) },
+            3 => unsafe { avx2::_mm256_srli_epi64(*self, 3) },
+            4 => unsafe { avx2::_mm256_srli_epi64(*self, 4) },
+            _ => unreachable!(),
+        }
+    }
+
+    #[inline(always)]
+    fn cmp_eq_byte(&self, byte: u8) -> Self {
+        let tmp = unsafe { avx2::_mm256_cmpeq_epi8(*self, Self::splat(byte)) };
+        unsafe { avx2::_mm256_and_si256(tmp, Self::splat(1)) }
+    }
+
+    #[inline(always)]
+    fn bytes_between_127(&self, a: u8, b: u8) -> Self {
+        let tmp1 = unsafe { avx2::_mm256_cmpgt_epi8(*self, Self::splat(a)) };
+        let tmp2 = unsafe { avx2::_mm256_cmpgt_epi8(Self::splat(b), *self) };
+        let tmp3 = unsafe { avx2::_mm256_and_si256(tmp1, tmp2) };
+        unsafe { avx2::_mm256_and_si256(tmp3, Self::splat(1)) }
+    }
+
+    #[inline(always)]
+    fn bitand(&self, other: Self) -> Self {
+        unsafe { avx2::_mm256_and_si256(*self, other) }
+    }
+
+    #[inline(always)]
+    fn add(&self, other: Self) -> Self {
+        unsafe { avx2::_mm256_add_epi8(*self, other) }
+    }
+
+    #[inline(always)]
+    fn sub(&self, other: Self) -> Self {
+        unsafe { avx2::_mm256_sub_epi8(*self, other) }
+    }
+
+    #[inline(always)]
+    fn inc_nth_from_end_lex_byte(&self, n: usize) -> Self {
+        let mut tmp = unsafe { core::mem::transmute::<Self, [u8; 32]>(*self) };
+        tmp[31 - n] += 1;
+        unsafe { core::mem::transmute::<[u8; 32], Self>(tmp) }
+    }
+
+    #[inline(always)]
+    fn dec_last_lex_byte(&self) -> Self {
+        let mut tmp = unsafe { core::mem::transmute::<Self, [u8; 32]>(*self) };
+        tmp[31] -= 1;
+        unsafe { core::mem::transmute::<[u8; 32], Self>(tmp) }
+    }
+
+    #[inline(always)]
+    fn sum_bytes(&self) -> usize {
+        let sums = unsafe { avx2::_mm256_sad_epu8(*self, Self::splat(0)) };
+        let tmp = unsafe { core::mem::transmute::<Self, (u64, u64, u64, u64)>(sums) };
+        (tmp.0 + tmp.1 + tmp.2 + tmp.3) as usize
+    }
+}
 
 //======================================================================
 
@@ -908,7 +1052,7 @@ impl ByteChunk for sse2::__m128i {
 /// - u{2029}        (Paragraph Separator)
 #[allow(unused)] // Used in tests, as reference solution.
 struct LineBreakIter<'a> {
-    byte_itr: std::str::Bytes<'a>,
+    byte_itr: core::str::Bytes<'a>,
     byte_idx: usize,
 }
 
@@ -1041,6 +1185,36 @@ mod tests {
         assert_eq!(count_line_breaks(text), LineBreakIter::new(text).count());
     }
 
+    #[test]
+    fn count_simd_01() {
+        // Every implementation, at every alignment, with line breaks
+        // straddling chunk boundaries.
+        let text = "\u{000A}Hello\u{000D}\u{000A}\u{000D}せ\u{000B}か\u{000C}い\u{0085}. \
+                    There\u{2028}is something.\u{2029}"
+            .repeat(12);
+        for i in (0..64).filter(|&i| text.is_char_boundary(i)) {
+            let t = &text[i..];
+            let bytes = t.as_bytes();
+            let chars = t.chars().count();
+            let breaks = LineBreakIter::new(t).count();
+
+            assert_eq!(chars, count_chars_internal::<usize>(bytes));
+            assert_eq!(breaks, count_line_breaks_internal::<usize>(t));
+
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            {
+                if is_x86_feature_detected!("sse2") {
+                    assert_eq!(chars, count_chars_internal::<sse2::__m128i>(bytes));
+                    assert_eq!(breaks, count_line_breaks_internal::<sse2::__m128i>(t));
+                }
+                if is_x86_feature_detected!("avx2") {
+                    assert_eq!(chars, unsafe { count_chars_avx2(bytes) });
+                    assert_eq!(breaks, unsafe { count_line_breaks_avx2(t) });
+                }
+            }
+        }
+    }
+
     #[test]
     fn byte_to_char_idx_01() {
         let text = "Hello せかい!";
@@ -1308,6 +1482,24 @@ mod tests {
         assert_eq!(124, line_to_byte_idx(TEXT_LINES, 6));
     }
 
+    #[test]
+    fn line_to_byte_idx_04() {
+        // Hopping between line breaks agrees with counting them, including
+        // for line breaks mixed with non-breaking chars that share their
+        // leading bytes.
+        let text = "\u{000A}Hello\u{000D}\u{000A}\u{000D}せ\u{000B}か\u{000C}い\u{0085}. \
+                    There\u{2028}is ©—“something”.\u{2029}\u{000D}";
+        for i in (0..text.len()).filter(|&i| text.is_char_boundary(i)) {
+            let t = &text[i..];
+            for line_idx in 0..12 {
+                assert_eq!(
+                    line_to_byte_idx_inner::<usize>(t, line_idx),
+                    line_to_byte_idx(t, line_idx)
+                );
+            }
+        }
+    }
+
     #[test]
     fn line_to_char_idx_01() {
         let text = "Hello せ\nか\nい!";
@@ -1418,4 +1610,42 @@ mod tests {
         assert_eq!(false, ends_with_line_break("\u{2028}a"));
         assert_eq!(false, ends_with_line_break("\u{2029}a"));
     }
+
+    #[test]
+    fn is_segment_break_01() {
+        let text = "a\r\nせ\r";
+        let breaks: Vec<_> = (0..=text.len())
+            .filter(|&i| is_segment_break(text, i))
+            .collect();
+        assert_eq!(breaks, vec![0, 1, 3, 6, 7]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn is_segment_break_02() {
+        is_segment_break("abc", 4);
+    }
+
+    #[test]
+    fn prev_next_segment_break_01() {
+        let text = "a\r\nせ\r";
+        assert_eq!(prev_segment_break(text, 0), 0);
+        assert_eq!(prev_segment_break(text, 2), 1);
+        assert_eq!(prev_segment_break(text, 3), 1);
+        assert_eq!(prev_segment_break(text, 5), 3);
+        assert_eq!(next_segment_break(text, 0), 1);
+        assert_eq!(next_segment_break(text, 1), 3);
+        assert_eq!(next_segment_break(text, 4), 6);
+        assert_eq!(next_segment_break(text, 7), 7);
+    }
+
+    #[test]
+    fn seam_is_segment_break_01() {
+        assert!(seam_is_segment_break("a\r", "b"));
+        assert!(seam_is_segment_break("a\n", "\n"));
+        assert!(seam_is_segment_break("a", "\nb"));
+        assert!(!seam_is_segment_break("a\r", "\nb"));
+        assert!(seam_is_segment_break("", "\n"));
+        assert!(seam_is_segment_break("\r", ""));
+    }
 }
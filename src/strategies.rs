@@ -0,0 +1,240 @@
+//! Strategies for property testing with `proptest`.
+//!
+//! Only available with the `proptest` feature enabled.
+//!
+//! The text strategies mix ASCII with the kinds of text that tend to trip up
+//! text handling code: CRLF and other line breaks, multi-byte chars, emoji,
+//! and combining marks.  The rope strategies build their ropes up from
+//! pieces inserted in a random order, so that their chunk boundaries and
+//! tree shapes vary, rather than being what `Rope::from_str()` would give.
+//!
+//! Generated values shrink towards shorter, ASCII-only text and less
+//! fragmented ropes, and indices shrink towards zero.
+//!
+//! # Example
+//!
+//! ```
+//! # #[macro_use] extern crate proptest;
+//! # extern crate ropey;
+//! use ropey::strategies::{rope, with_char_idx};
+//!
+//! proptest! {
+//!     fn split_off_and_append((mut r, char_idx) in with_char_idx(rope(0..100))) {
+//!         let text = r.to_string();
+//!         let right = r.split_off(char_idx);
+//!         r.append(right);
+//!         prop_assert_eq!(r, text.as_str());
+//!     }
+//! }
+//! # fn main() { split_off_and_append(); }
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use proptest::arbitrary::any;
+use proptest::char;
+use proptest::collection::{vec, SizeRange};
+use proptest::sample::{select, Index};
+use proptest::strategy::{Strategy, Union};
+
+use rope::Rope;
+
+/// Generates text made of `len` elements, where each element is one char,
+/// except for CRLF pairs and chars with a combining mark, which are two.
+pub fn text<L: Into<SizeRange>>(len: L) -> impl Strategy<Value = String> {
+    vec(text_element(), len).prop_map(|elements| elements.concat())
+}
+
+fn text_element() -> impl Strategy<Value = String> {
+    // The first option and the low ends of the char ranges are what values
+    // shrink towards.
+    Union::new_weighted(vec![
+        (12, char::range(' ', '~').prop_map(String::from).boxed()),
+        (
+            2,
+            select(vec!["\n", "\r\n", "\r", "\u{0085}", "\u{2028}"])
+                .prop_map(String::from)
+                .boxed(),
+        ),
+        (
+            2,
+            char::range('\u{00C0}', '\u{00FF}')
+                .prop_map(String::from)
+                .boxed(),
+        ),
+        (
+            2,
+            char::range('\u{3041}', '\u{3096}')
+                .prop_map(String::from)
+                .boxed(),
+        ),
+        (
+            1,
+            char::range('\u{1F600}', '\u{1F64F}')
+                .prop_map(String::from)
+                .boxed(),
+        ),
+        (
+            1,
+            (char::range('a', 'z'), char::range('\u{0300}', '\u{036F}'))
+                .prop_map(|(c, mark)| [c, mark].iter().collect())
+                .boxed(),
+        ),
+    ])
+}
+
+/// Generates ropes made of `len` text elements, as with `text()`.
+pub fn rope<L: Into<SizeRange>>(len: L) -> impl Strategy<Value = Rope> {
+    rope_with_text(text(len), 0..16)
+}
+
+/// Generates ropes with the text from `text`, built up from pieces of it.
+///
+/// The text is split at a number of random char indices, in the range
+/// `splits`, and the pieces are then inserted into an empty rope in a random
+/// order.  More splits give more fragmented ropes.
+pub fn rope_with_text<S, P>(text: S, splits: P) -> impl Strategy<Value = Rope>
+where
+    S: Strategy<Value = String>,
+    P: Into<SizeRange>,
+{
+    (text, vec((any::<Index>(), any::<Index>()), splits))
+        .prop_map(|(text, splits)| fragmented(&text, &splits))
+}
+
+fn fragmented(text: &str, splits: &[(Index, Index)]) -> Rope {
+    // Split the text into pieces.
+    let chars: Vec<char> = text.chars().collect();
+    let mut split_idxs: Vec<usize> = splits
+        .iter()
+        .map(|(split, _)| split.index(chars.len() + 1))
+        .collect();
+    split_idxs.sort_unstable();
+    let mut pieces = Vec::with_capacity(splits.len() + 1);
+    let mut start = 0;
+    for &end in split_idxs.iter().chain(Some(&chars.len())) {
+        pieces.push(chars[start..end].iter().collect::<String>());
+        start = end;
+    }
+
+    // Insert the pieces in a random order, each at its place among the
+    // pieces inserted so far.
+    let mut remaining: Vec<usize> = (0..pieces.len()).collect();
+    let mut inserted = vec![false; pieces.len()];
+    let mut rope = Rope::new();
+    let choices = splits
+        .iter()
+        .map(|(_, choice)| Some(choice))
+        .chain(Some(None));
+    for choice in choices {
+        let piece_i = match choice {
+            Some(choice) => remaining.remove(choice.index(remaining.len())),
+            None => remaining.pop().unwrap(),
+        };
+        let char_idx = (0..piece_i)
+            .filter(|&i| inserted[i])
+            .map(|i| pieces[i].chars().count())
+            .sum();
+        rope.insert(char_idx, &pieces[piece_i]);
+        inserted[piece_i] = true;
+    }
+
+    rope
+}
+
+/// Pairs the ropes from `ropes` with a char index in `0..=len_chars()`.
+pub fn with_char_idx<S>(ropes: S) -> impl Strategy<Value = (Rope, usize)>
+where
+    S: Strategy<Value = Rope>,
+{
+    (ropes, any::<Index>()).prop_map(|(rope, idx)| {
+        let char_idx = idx.index(rope.len_chars() + 1);
+        (rope, char_idx)
+    })
+}
+
+/// Pairs the ropes from `ropes` with a char range within `0..len_chars()`.
+pub fn with_char_range<S>(ropes: S) -> impl Strategy<Value = (Rope, Range<usize>)>
+where
+    S: Strategy<Value = Rope>,
+{
+    let idxs = (any::<Index>(), any::<Index>());
+    (ropes, idxs).prop_map(|(rope, (idx1, idx2))| {
+        let a = idx1.index(rope.len_chars() + 1);
+        let b = idx2.index(rope.len_chars() + 1);
+        (rope, a.min(b)..a.max(b))
+    })
+}
+
+/// Pairs the ropes from `ropes` with a line index in `0..len_lines()`.
+pub fn with_line_idx<S>(ropes: S) -> impl Strategy<Value = (Rope, usize)>
+where
+    S: Strategy<Value = Rope>,
+{
+    (ropes, any::<Index>()).prop_map(|(rope, idx)| {
+        let line_idx = idx.index(rope.len_lines());
+        (rope, line_idx)
+    })
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use proptest::strategy::{Just, Strategy, ValueTree};
+    use proptest::test_runner::TestRunner;
+    use proptest::{prop_assert, prop_assert_eq, proptest};
+
+    use super::*;
+
+    fn text_and_rope() -> impl Strategy<Value = (String, Rope)> {
+        text(0..200).prop_flat_map(|t| (Just(t.clone()), rope_with_text(Just(t), 0..32)))
+    }
+
+    proptest! {
+        #[test]
+        fn rope_with_text_01((ref text, ref r) in text_and_rope()) {
+            prop_assert_eq!(r, text.as_str());
+            r.assert_integrity();
+        }
+
+        #[test]
+        fn with_char_idx_01((r, char_idx) in with_char_idx(rope(0..50))) {
+            prop_assert!(char_idx <= r.len_chars());
+        }
+
+        #[test]
+        fn with_char_range_01((r, range) in with_char_range(rope(0..50))) {
+            prop_assert!(range.start <= range.end);
+            prop_assert!(range.end <= r.len_chars());
+        }
+
+        #[test]
+        fn with_line_idx_01((r, line_idx) in with_line_idx(rope(0..50))) {
+            prop_assert!(line_idx < r.len_lines());
+        }
+    }
+
+    #[test]
+    fn rope_01() {
+        // Not just the shapes that `from_str()` would give.
+        let mut runner = TestRunner::deterministic();
+        let strategy = rope(100..200);
+        let reshaped = (0..20)
+            .map(|_| strategy.new_tree(&mut runner).unwrap().current())
+            .filter(|r| !r.chunks().eq(Rope::from_str(&r.to_string()).chunks()))
+            .count();
+        assert!(reshaped > 0);
+    }
+
+    #[test]
+    fn text_01() {
+        // Shrinks to ASCII.
+        let mut runner = TestRunner::deterministic();
+        let mut tree = text(50..100).new_tree(&mut runner).unwrap();
+        while tree.simplify() {}
+        assert!(tree.current().is_ascii());
+    }
+}
@@ -3,28 +3,53 @@ mod node_children;
 mod node_text;
 mod text_info;
 
-#[cfg(not(test))]
-use std::{mem::size_of, sync::Arc};
+use core::mem::size_of;
+
+// The reference-counted pointer that nodes are shared with.  Ropey never
+// uses weak references, so with the `triomphe` feature this is
+// `triomphe::Arc`, which doesn't have a weak count.
+#[cfg(not(feature = "triomphe"))]
+pub(crate) use alloc::sync::Arc;
+#[cfg(feature = "triomphe")]
+pub(crate) use triomphe::Arc;
 
 pub(crate) use self::node::Node;
 pub(crate) use self::node_children::NodeChildren;
 pub(crate) use self::node_text::NodeText;
 pub(crate) use self::text_info::TextInfo;
 
-#[cfg(not(test))]
 const PTR_SIZE: usize = size_of::<&u8>();
+#[cfg(not(feature = "triomphe"))]
+const ARC_COUNTERS_SIZE: usize = PTR_SIZE * 2;
+#[cfg(feature = "triomphe")]
+const ARC_COUNTERS_SIZE: usize = PTR_SIZE;
 #[cfg(not(test))]
 const CHILD_INFO_SIZE: usize = size_of::<Arc<Node>>() + size_of::<TextInfo>();
 
 // Aim for nodes to be 1024 bytes minus Arc counters.  Keeping the nodes
 // multiples of large powers of two makes it easier for the memory allocator
 // to avoid fragmentation.
+//
+// The `small_nodes` and `large_nodes` features change that to 512 and 4096
+// bytes, which sizes both leaves and internal nodes: smaller nodes waste less
+// memory on small texts and small edits, and larger nodes make for shallower
+// trees and less per-leaf overhead.  `large_nodes` wins if both are enabled.
 #[cfg(not(test))]
-const TARGET_NODE_SIZE: usize = 1024 - (PTR_SIZE * 2);
+const TARGET_NODE_SIZE: usize = if cfg!(feature = "large_nodes") {
+    4096
+} else if cfg!(feature = "small_nodes") {
+    512
+} else {
+    1024
+} - ARC_COUNTERS_SIZE;
 
 // Node min/max values.
 // For testing, they're set small to trigger deeper trees.  For
-// non-testing, they're determined by TARGET_NODE_SIZE, above.
+// non-testing, they're determined by TARGET_NODE_SIZE, above, so the
+// fanout of internal nodes is whatever fits in a node: 31 children by
+// default on 64-bit platforms, and more with the `u32_counts` or
+// `large_nodes` features.  Making internal nodes wider than leaves
+// would waste memory, since both are stored in the same `Node` enum.
 // MAX_BYTES must be >= 4 to allow for 4-byte utf8 characters.
 #[cfg(test)]
 pub(crate) const MAX_CHILDREN: usize = 5;
@@ -41,5 +66,29 @@ pub(crate) const MAX_BYTES: usize = TARGET_NODE_SIZE - 1 - (PTR_SIZE * 2);
 // removals.
 pub(crate) const MIN_BYTES: usize = (MAX_BYTES / 2) - (MAX_BYTES / 32);
 
+// The size of each node's heap allocation, including its Arc counters.
+pub(crate) const NODE_ALLOC_SIZE: usize = size_of::<Node>() + ARC_COUNTERS_SIZE;
+
 // Type used for storing tree metadata, such as byte and char length.
+// The `u32_counts` feature shrinks it for ropes that stay under 4 GB, which
+// makes room for more children per internal node.
+#[cfg(not(feature = "u32_counts"))]
 pub(crate) type Count = u64;
+#[cfg(feature = "u32_counts")]
+pub(crate) type Count = u32;
+
+// Panics if growing text of `len_bytes` bytes by `added_bytes` bytes would
+// make it too large for `Count`.  This is only a real limit with the
+// `u32_counts` feature.  The edits that grow a rope check it up front, so
+// that they don't overflow partway through.
+#[inline]
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn assert_count_fits(len_bytes: usize, added_bytes: usize) {
+    assert!(
+        len_bytes as u64 + added_bytes as u64 <= Count::MAX as u64,
+        "Attempt to grow Rope past its maximum length: length {}, adding {}, maximum {}",
+        len_bytes,
+        added_bytes,
+        Count::MAX
+    );
+}
@@ -1,10 +1,11 @@
-use std;
-use std::sync::Arc;
+use core;
 
+use stats::MemUsage;
 use str_utils::{byte_to_line_idx, char_to_byte_idx};
 use tree::node_text::fix_segment_seam;
 use tree::{
-    Count, NodeChildren, NodeText, TextInfo, MAX_BYTES, MAX_CHILDREN, MIN_BYTES, MIN_CHILDREN,
+    Arc, Count, NodeChildren, NodeText, TextInfo, MAX_BYTES, MAX_CHILDREN, MIN_BYTES, MIN_CHILDREN,
+    NODE_ALLOC_SIZE,
 };
 
 #[derive(Debug, Clone)]
@@ -398,7 +399,7 @@ impl Node {
                 } else {
                     let mut r_children =
                         children.insert_split(0, (extra_node.text_info(), extra_node));
-                    std::mem::swap(children, &mut r_children);
+                    core::mem::swap(children, &mut r_children);
                     return Some(Arc::new(Node::Internal(r_children)));
                 }
             } else {
@@ -445,6 +446,31 @@ impl Node {
         }
     }
 
+    /// Returns the leaf that contains the given byte under `node`, along with
+    /// its starting byte and char indices and the index of the line that it
+    /// starts on.
+    pub fn get_leaf_at_byte(
+        node: &Arc<Node>,
+        byte_idx: usize,
+    ) -> (&Arc<Node>, usize, usize, usize) {
+        let mut node = node;
+        let mut byte_idx = byte_idx;
+        let mut info = TextInfo::new();
+
+        while let Node::Internal(ref children) = **node {
+            let (child_i, acc_info) = children.search_byte_idx(byte_idx);
+            info += acc_info;
+            node = &children.nodes()[child_i];
+            byte_idx -= acc_info.bytes as usize;
+        }
+        (
+            node,
+            info.bytes as usize,
+            info.chars as usize,
+            info.line_breaks as usize,
+        )
+    }
+
     /// Returns the chunk that contains the given byte, and the chunk's starting
     /// byte and char indices and the index of the line that the chunk starts on.
     ///
@@ -605,10 +631,9 @@ impl Node {
         }
     }
 
-    /// How many nodes deep the tree is.
+    /// How many levels of internal nodes there are above the leaves.
     ///
-    /// This counts root and leafs.  For example, a single leaf node
-    /// has depth 1.
+    /// For example, a single leaf node has depth 0.
     pub fn depth(&self) -> usize {
         let mut node = self;
         let mut depth = 0;
@@ -624,60 +649,66 @@ impl Node {
         }
     }
 
-    /// Debugging tool to make sure that all of the meta-data of the
-    /// tree is consistent with the actual data.
-    pub fn assert_integrity(&self) {
-        match *self {
-            Node::Leaf(_) => {}
-            Node::Internal(ref children) => {
-                for (info, node) in children.iter() {
-                    if *info != node.text_info() {
-                        assert_eq!(*info, node.text_info());
-                    }
-                    node.assert_integrity();
-                }
-            }
+    /// The heap memory of this node alone, not counting its children.
+    pub fn heap_usage(&self) -> MemUsage {
+        let (text_bytes, node_bytes) = match *self {
+            Node::Leaf(ref text) => (text.len(), NODE_ALLOC_SIZE + text.heap_capacity()),
+            Node::Internal(_) => (0, NODE_ALLOC_SIZE),
+        };
+        MemUsage {
+            text_bytes: text_bytes,
+            overhead_bytes: node_bytes - text_bytes,
         }
     }
 
-    /// Checks that the entire tree is the same height everywhere.
-    pub fn assert_balance(&self) -> usize {
-        // Depth, child count, and leaf node emptiness
+    /// Returns whether the tree is the same height everywhere, and all
+    /// internal nodes have the minimum number of children.
+    pub fn is_balanced(&self, is_root: bool) -> bool {
+        self.balanced_depth(is_root).is_some()
+    }
+
+    /// The depth of the tree, or `None` if it isn't balanced.
+    fn balanced_depth(&self, is_root: bool) -> Option<usize> {
         match *self {
-            Node::Leaf(_) => 1,
+            Node::Leaf(_) => Some(0),
             Node::Internal(ref children) => {
-                let first_depth = children.nodes()[0].assert_balance();
+                let min_children = if is_root { 2 } else { MIN_CHILDREN };
+                if children.len() < min_children {
+                    return None;
+                }
+
+                let depth = children.nodes()[0].balanced_depth(false)?;
                 for node in &children.nodes()[1..] {
-                    assert_eq!(node.assert_balance(), first_depth);
+                    if node.balanced_depth(false)? != depth {
+                        return None;
+                    }
                 }
-                first_depth + 1
+                Some(depth + 1)
             }
         }
     }
 
-    /// Checks that all internal nodes have the minimum number of
-    /// children and all non-root leaf nodes are non-empty.
-    pub fn assert_node_size(&self, is_root: bool) {
-        match *self {
-            Node::Leaf(ref text) => {
-                // Leaf size
-                if !is_root {
-                    assert!(text.len() > 0);
-                }
-            }
-            Node::Internal(ref children) => {
-                // Child count
-                if is_root {
-                    assert!(children.len() > 1);
-                } else {
-                    assert!(children.len() >= MIN_CHILDREN);
-                }
+    /// Returns whether the given byte position splits a CRLF pair.
+    ///
+    /// This only reads the bytes on either side of the position, so it's
+    /// much cheaper than `fix_crlf_seam()`, which copies every shared node
+    /// on its path even when there's nothing to fix.
+    pub fn splits_crlf(&self, byte_pos: usize) -> bool {
+        if byte_pos == 0 || byte_pos >= self.text_info().bytes as usize {
+            return false;
+        }
 
-                for node in children.nodes() {
-                    node.assert_node_size(false);
-                }
-            }
+        let (chunk, chunk_byte_idx, _, _) = self.get_chunk_at_byte(byte_pos - 1);
+        if chunk.as_bytes()[byte_pos - 1 - chunk_byte_idx] != 0x0D {
+            return false;
         }
+        let next_byte = if byte_pos - chunk_byte_idx < chunk.len() {
+            chunk.as_bytes()[byte_pos - chunk_byte_idx]
+        } else {
+            let (chunk, chunk_byte_idx, _, _) = self.get_chunk_at_byte(byte_pos);
+            chunk.as_bytes()[byte_pos - chunk_byte_idx]
+        };
+        next_byte == 0x0A
     }
 
     /// Checks to make sure that a boundary between leaf nodes (given as a byte
@@ -890,7 +921,7 @@ impl Node {
             loop {
                 // Do merging
                 if children.len() > 1 {
-                    let (child_i, start_info) = children.search_char_idx(char_idx);
+                    let (child_i, start_info) = search_char_idx_left(children, char_idx);
                     let end_info = start_info + children.info()[child_i];
 
                     if end_info.chars as usize == char_idx && (child_i + 1) < children.len() {
@@ -922,7 +953,7 @@ impl Node {
                 }
 
                 // Do recursion
-                let (child_i, start_info) = children.search_char_idx(char_idx);
+                let (child_i, start_info) = search_char_idx_left(children, char_idx);
                 let end_info = start_info + children.info()[child_i];
 
                 if end_info.chars as usize == char_idx && (child_i + 1) < children.len() {
@@ -948,6 +979,20 @@ impl Node {
     }
 }
 
+/// Same as `NodeChildren::search_char_idx()`, except that when `char_idx` is
+/// on the boundary between two children, it returns the left one.
+///
+/// This lets `fix_after_remove()` see the children on both sides of the
+/// removal seam, rather than only the one to its right.
+fn search_char_idx_left(children: &NodeChildren, char_idx: usize) -> (usize, TextInfo) {
+    let (child_i, start_info) = children.search_char_idx(char_idx);
+    if child_i > 0 && start_info.chars as usize == char_idx {
+        (child_i - 1, start_info - children.info()[child_i - 1])
+    } else {
+        (child_i, start_info)
+    }
+}
+
 //===========================================================================
 
 #[cfg(test)]
@@ -986,7 +1031,7 @@ mod tests {
     fn crlf_corner_case_01() {
         use super::Node;
         use std::iter;
-        use std::sync::Arc;
+        use tree::Arc;
         use tree::{NodeChildren, NodeText, MAX_BYTES};
 
         // Construct the corner case
@@ -1014,7 +1059,7 @@ mod tests {
     fn crlf_corner_case_02() {
         use super::Node;
         use std::iter;
-        use std::sync::Arc;
+        use tree::Arc;
         use tree::{NodeChildren, NodeText, MAX_BYTES};
 
         // Construct the corner case
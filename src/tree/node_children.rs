@@ -1,13 +1,15 @@
-use std::fmt;
-use std::iter::{Iterator, Zip};
-use std::slice;
-use std::sync::Arc;
+use core::fmt;
+use core::iter::{Iterator, Zip};
+use core::slice;
 
 use crlf;
-use tree::{self, Node, TextInfo, MAX_BYTES};
+use tree::{self, Arc, Node, TextInfo, MAX_BYTES};
 
 const MAX_LEN: usize = tree::MAX_CHILDREN;
 
+// The child count is stored as a `u8`, which limits how wide nodes can get.
+const _: () = assert!(MAX_LEN <= u8::MAX as usize);
+
 /// A fixed-capacity vec of child Arc-pointers and child metadata.
 ///
 /// The unsafe guts of this are implemented in NodeChildrenInternal
@@ -495,10 +497,10 @@ impl fmt::Debug for NodeChildren {
 /// and it was a pain to track down--as memory safety bugs often are.
 mod inner {
     use super::{Node, TextInfo, MAX_LEN};
-    use std::mem;
-    use std::mem::MaybeUninit;
-    use std::ptr;
-    use std::sync::Arc;
+    use core::mem;
+    use core::mem::MaybeUninit;
+    use core::ptr;
+    use tree::Arc;
 
     /// This is essentially a fixed-capacity, stack-allocated `Vec`.  However,
     /// it actually containts _two_ arrays rather than just one, but which
@@ -718,7 +720,7 @@ mod inner {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Arc;
+    use tree::Arc;
     use tree::{Node, NodeText, TextInfo};
 
     #[test]
@@ -1,8 +1,8 @@
-use std;
+use core;
 
-use std::borrow::Borrow;
-use std::ops::Deref;
-use std::str;
+use core::borrow::Borrow;
+use core::ops::Deref;
+use core::str;
 
 use crlf;
 
@@ -130,9 +130,16 @@ impl NodeText {
         self.0.inline_if_possible();
         other
     }
+
+    /// Bytes of heap memory used by the text, beyond the node itself.
+    ///
+    /// This is zero unless the text has spilled out of its inline buffer.
+    pub fn heap_capacity(&self) -> usize {
+        self.0.heap_capacity()
+    }
 }
 
-impl std::cmp::PartialEq for NodeText {
+impl core::cmp::PartialEq for NodeText {
     fn eq(&self, other: &Self) -> bool {
         let (s1, s2): (&str, &str) = (self, other);
         s1 == s2
@@ -151,14 +158,14 @@ impl<'a> PartialEq<&'a str> for NodeText {
     }
 }
 
-impl std::fmt::Display for NodeText {
-    fn fmt(&self, fm: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+impl core::fmt::Display for NodeText {
+    fn fmt(&self, fm: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
         NodeText::deref(self).fmt(fm)
     }
 }
 
-impl std::fmt::Debug for NodeText {
-    fn fmt(&self, fm: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Debug for NodeText {
+    fn fmt(&self, fm: &mut core::fmt::Formatter) -> core::fmt::Result {
         NodeText::deref(self).fmt(fm)
     }
 }
@@ -232,8 +239,8 @@ pub(crate) fn fix_segment_seam(l: &mut NodeText, r: &mut NodeText) {
 /// Try to keep this as small as possible, and implement functionality on
 /// NodeText via the safe APIs whenever possible.
 mod inner {
+    use core::{ptr, str};
     use smallvec::{Array, SmallVec};
-    use std::{ptr, str};
     use tree::MAX_BYTES;
 
     /// The backing internal buffer type for `NodeText`.
@@ -391,6 +398,15 @@ mod inner {
                 self.buffer.shrink_to_fit();
             }
         }
+
+        #[inline(always)]
+        pub fn heap_capacity(&self) -> usize {
+            if self.buffer.spilled() {
+                self.buffer.capacity()
+            } else {
+                0
+            }
+        }
     }
 
     //-----------------------------------------------------------------------
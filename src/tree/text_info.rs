@@ -1,4 +1,4 @@
-use std::ops::{Add, AddAssign, Sub, SubAssign};
+use core::ops::{Add, AddAssign, Sub, SubAssign};
 
 use str_utils::{count_chars, count_line_breaks};
 use tree::Count;
@@ -35,9 +35,9 @@ impl Add for TextInfo {
     #[inline]
     fn add(self, rhs: TextInfo) -> TextInfo {
         TextInfo {
-            bytes: self.bytes + rhs.bytes,
-            chars: self.chars + rhs.chars,
-            line_breaks: self.line_breaks + rhs.line_breaks,
+            bytes: add_count(self.bytes, rhs.bytes),
+            chars: add_count(self.chars, rhs.chars),
+            line_breaks: add_count(self.line_breaks, rhs.line_breaks),
         }
     }
 }
@@ -67,3 +67,19 @@ impl SubAssign for TextInfo {
         *self = *self - other;
     }
 }
+
+// With `u32_counts`, a `Count` can overflow before a `usize` does, so the
+// sums are checked.  The edits that grow a rope check this up front with
+// `assert_count_fits()`, so this only catches what they miss.
+#[cfg(feature = "u32_counts")]
+#[inline]
+fn add_count(a: Count, b: Count) -> Count {
+    a.checked_add(b)
+        .expect("Rope is too large for the `u32_counts` feature")
+}
+
+#[cfg(not(feature = "u32_counts"))]
+#[inline]
+fn add_count(a: Count, b: Count) -> Count {
+    a + b
+}
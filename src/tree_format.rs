@@ -0,0 +1,410 @@
+//! A binary format that stores a rope's tree structure along with its text.
+//!
+//! The format is, with all integers little-endian:
+//!
+//! - Header: the magic bytes `MAGIC`, then `MAX_BYTES` and `MAX_CHILDREN`
+//!   of the writing build as `u32`s.
+//! - The root node, where each node is either:
+//!   - Leaf: the byte `0`, the text's length in bytes as a `u32`, then the
+//!     text.
+//!   - Internal: the byte `1`, the child count as a `u32`, then for each
+//!     child its bytes, chars and line breaks as `u64`s followed by the
+//!     child node itself.
+//!
+//! Node sizes depend on the build (e.g. the pointer size), so a tree that
+//! was written with different limits than the current build's is rebuilt
+//! from its text rather than loaded as-is.  A tree that is loaded as-is must
+//! meet all of the invariants that `Rope::validate()` checks.
+
+use std::io::{self, Read};
+
+use rope::Rope;
+use rope_builder::RopeBuilder;
+use tree::{Arc, Count, Node, NodeChildren, NodeText, TextInfo, MAX_BYTES, MAX_CHILDREN};
+use validate;
+
+const MAGIC: &[u8; 8] = b"RopeyTr\x01";
+
+const LEAF: u8 = 0;
+const INTERNAL: u8 = 1;
+
+// Trees written by Ropey are far shallower than this.  Limiting the depth
+// keeps corrupt data from overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+pub(crate) fn write_tree<T: io::Write>(rope: &Rope, writer: &mut T) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(MAX_BYTES as u32).to_le_bytes())?;
+    writer.write_all(&(MAX_CHILDREN as u32).to_le_bytes())?;
+
+    // Edits can occasionally leave an internal node with too few children,
+    // which `read_tree()` would reject.
+    if validate::check_invariants(&rope.root).is_err() {
+        let mut rope = rope.clone();
+        rope.rebalance();
+        return write_node(&rope.root, writer);
+    }
+    write_node(&rope.root, writer)
+}
+
+// `Count` is only a `u64` without the `u32_counts` feature.
+#[allow(clippy::unnecessary_cast)]
+fn write_node<T: io::Write>(node: &Node, writer: &mut T) -> io::Result<()> {
+    match *node {
+        Node::Leaf(ref text) => {
+            writer.write_all(&[LEAF])?;
+            writer.write_all(&(text.len() as u32).to_le_bytes())?;
+            writer.write_all(text.as_bytes())?;
+        }
+        Node::Internal(ref children) => {
+            writer.write_all(&[INTERNAL])?;
+            writer.write_all(&(children.len() as u32).to_le_bytes())?;
+            for (info, child) in children.iter() {
+                writer.write_all(&(info.bytes as u64).to_le_bytes())?;
+                writer.write_all(&(info.chars as u64).to_le_bytes())?;
+                writer.write_all(&(info.line_breaks as u64).to_le_bytes())?;
+                write_node(child, writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn read_tree<T: io::Read>(reader: &mut T) -> io::Result<Rope> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a Ropey tree"));
+    }
+    let max_bytes = read_u32(reader)? as usize;
+    let max_children = read_u32(reader)? as usize;
+
+    if max_bytes == MAX_BYTES && max_children == MAX_CHILDREN {
+        let (root, _) = read_node(reader, 0)?;
+        // The node sizes, empty leaves and CRLF seams, which `read_node()`
+        // doesn't check.
+        if let Err(err) = validate::check_invariants(&root) {
+            return Err(invalid_data(&err.to_string()));
+        }
+        Ok(Rope { root: root })
+    } else {
+        let mut builder = RopeBuilder::new();
+        read_node_text(reader, &mut builder, 0)?;
+        Ok(builder.finish())
+    }
+}
+
+/// Reads a node, returning it along with its depth.
+fn read_node<T: io::Read>(reader: &mut T, depth: usize) -> io::Result<(Arc<Node>, usize)> {
+    if depth > MAX_DEPTH {
+        return Err(invalid_data("tree is too deep"));
+    }
+
+    match read_u8(reader)? {
+        LEAF => {
+            let text = read_leaf_text(reader)?;
+            if text.len() > MAX_BYTES {
+                return Err(invalid_data("leaf is too large"));
+            }
+            Ok((Arc::new(Node::Leaf(NodeText::from_str(&text))), 1))
+        }
+        INTERNAL => {
+            let child_count = read_u32(reader)? as usize;
+            if child_count == 0 || child_count > MAX_CHILDREN {
+                return Err(invalid_data("invalid child count"));
+            }
+
+            let mut children = NodeChildren::new();
+            let mut child_depth = None;
+            for _ in 0..child_count {
+                let info = TextInfo {
+                    bytes: read_count(reader)?,
+                    chars: read_count(reader)?,
+                    line_breaks: read_count(reader)?,
+                };
+                let (child, d) = read_node(reader, depth + 1)?;
+
+                // Only the byte counts of leaves are checked: re-counting
+                // their chars and line breaks is what the format avoids.
+                let accurate = match *child {
+                    Node::Leaf(ref text) => info.bytes == text.len() as Count,
+                    Node::Internal(ref grandchildren) => info == grandchildren.combined_info(),
+                };
+                if !accurate || *child_depth.get_or_insert(d) != d {
+                    return Err(invalid_data("inconsistent tree"));
+                }
+
+                children.push((info, child));
+            }
+
+            Ok((Arc::new(Node::Internal(children)), child_depth.unwrap() + 1))
+        }
+        _ => Err(invalid_data("invalid node type")),
+    }
+}
+
+/// Reads a node's text into `builder`, discarding its structure.
+fn read_node_text<T: io::Read>(
+    reader: &mut T,
+    builder: &mut RopeBuilder,
+    depth: usize,
+) -> io::Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(invalid_data("tree is too deep"));
+    }
+
+    match read_u8(reader)? {
+        LEAF => builder.append(&read_leaf_text(reader)?),
+        INTERNAL => {
+            let child_count = read_u32(reader)?;
+            for _ in 0..child_count {
+                let mut info = [0u8; 24];
+                reader.read_exact(&mut info)?;
+                read_node_text(reader, builder, depth + 1)?;
+            }
+        }
+        _ => return Err(invalid_data("invalid node type")),
+    }
+    Ok(())
+}
+
+fn read_leaf_text<T: io::Read>(reader: &mut T) -> io::Result<String> {
+    let len = read_u32(reader)? as u64;
+    let mut bytes = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("stream did not contain valid UTF-8"))
+}
+
+fn read_u8<T: io::Read>(reader: &mut T) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32<T: io::Read>(reader: &mut T) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<T: io::Read>(reader: &mut T) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Reads a `u64` count, checking that it fits in a `Count`.
+#[allow(clippy::unnecessary_cast)]
+fn read_count<T: io::Read>(reader: &mut T) -> io::Result<Count> {
+    let n = read_u64(reader)?;
+    if n > Count::MAX as u64 {
+        return Err(invalid_data("tree is too large"));
+    }
+    Ok(n as Count)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+//===========================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 126 bytes, 102 chars, 4 lines
+    const TEXT: &str = "Hello there!  How're you doing?\r\nIt's \
+                        a fine day, isn't it?\nAren't you glad \
+                        we're alive?\r\nこんにちは、みんなさん！";
+
+    fn round_trip(r: &Rope) -> Rope {
+        let mut data = Vec::new();
+        r.write_tree_to(&mut data).unwrap();
+        Rope::from_tree_reader(&data[..]).unwrap()
+    }
+
+    #[test]
+    fn tree_format_01() {
+        let mut r = Rope::from_str(&TEXT.repeat(10));
+        r.insert(100, "Hi!");
+        r.remove(300..320);
+
+        let r2 = round_trip(&r);
+        assert_eq!(r2, r);
+        assert_eq!(r2.len_lines(), r.len_lines());
+        r2.assert_integrity();
+        r2.assert_invariants();
+
+        // Same structure.
+        let chunks: Vec<_> = r.chunks().collect();
+        let chunks2: Vec<_> = r2.chunks().collect();
+        assert_eq!(chunks, chunks2);
+    }
+
+    #[test]
+    fn tree_format_02() {
+        let r = round_trip(&Rope::new());
+        assert_eq!(r, "");
+        r.assert_integrity();
+        r.assert_invariants();
+
+        let r = round_trip(&Rope::from_str("Hi"));
+        assert_eq!(r, "Hi");
+        r.assert_integrity();
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn tree_format_03() {
+        // Written with different node size limits.
+        let r = Rope::from_str(&TEXT.repeat(10));
+        let mut data = Vec::new();
+        r.write_tree_to(&mut data).unwrap();
+        data[8..12].copy_from_slice(&1000u32.to_le_bytes());
+
+        let r2 = Rope::from_tree_reader(&data[..]).unwrap();
+        assert_eq!(r2, r);
+        r2.assert_integrity();
+        r2.assert_invariants();
+    }
+
+    #[test]
+    fn tree_format_04() {
+        // Invalid data.
+        let r = Rope::from_str(TEXT);
+        let mut data = Vec::new();
+        r.write_tree_to(&mut data).unwrap();
+
+        // Truncated.
+        for len in &[0, 7, 16, data.len() - 1] {
+            assert!(Rope::from_tree_reader(&data[..*len]).is_err());
+        }
+
+        // Bad magic.
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        assert!(Rope::from_tree_reader(&bad[..]).is_err());
+
+        // Inconsistent byte count in the root's first child.
+        let mut bad = data.clone();
+        bad[21] ^= 1;
+        assert!(Rope::from_tree_reader(&bad[..]).is_err());
+    }
+
+    // Encodes a leaf, returning it along with its text.
+    fn leaf(text: &str) -> (String, Vec<u8>) {
+        let mut data = vec![LEAF];
+        data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        data.extend_from_slice(text.as_bytes());
+        (text.to_string(), data)
+    }
+
+    // Encodes an internal node, returning it along with its text.
+    #[allow(clippy::unnecessary_cast)]
+    fn internal(children: &[(String, Vec<u8>)]) -> (String, Vec<u8>) {
+        let mut text = String::new();
+        let mut data = vec![INTERNAL];
+        data.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        for (child_text, child_data) in children {
+            let info = TextInfo::from_str(child_text);
+            data.extend_from_slice(&(info.bytes as u64).to_le_bytes());
+            data.extend_from_slice(&(info.chars as u64).to_le_bytes());
+            data.extend_from_slice(&(info.line_breaks as u64).to_le_bytes());
+            data.extend_from_slice(child_data);
+            text.push_str(child_text);
+        }
+        (text, data)
+    }
+
+    fn read(root: (String, Vec<u8>)) -> io::Result<Rope> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&(MAX_BYTES as u32).to_le_bytes());
+        data.extend_from_slice(&(MAX_CHILDREN as u32).to_le_bytes());
+        data.extend_from_slice(&root.1);
+        Rope::from_tree_reader(&data[..])
+    }
+
+    fn assert_invalid(root: (String, Vec<u8>)) {
+        let err = read(root).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn tree_format_05() {
+        // Hand-built trees that meet the invariants.
+        let r = read(internal(&[leaf("Hello"), leaf(" world")])).unwrap();
+        assert_eq!(r, "Hello world");
+        r.assert_invariants();
+
+        let r = read(internal(&[
+            internal(&[leaf("ab\r\n"), leaf("cd")]),
+            internal(&[leaf("ef"), leaf("gh"), leaf("ij")]),
+        ]))
+        .unwrap();
+        assert_eq!(r, "ab\r\ncdefghij");
+        r.assert_invariants();
+    }
+
+    #[test]
+    fn tree_format_06() {
+        // Empty leaves other than the root.
+        assert_invalid(internal(&[leaf("Hello"), leaf("")]));
+        assert_invalid(internal(&[
+            internal(&[leaf(""), leaf("ab")]),
+            internal(&[leaf("cd"), leaf("ef")]),
+        ]));
+    }
+
+    #[test]
+    fn tree_format_07() {
+        // Internal nodes with too few children.
+        assert_invalid(internal(&[
+            internal(&[leaf("ab"), leaf("cd")]),
+            internal(&[leaf("ef")]),
+        ]));
+
+        // A root with a single child.
+        assert_invalid(internal(&[internal(&[leaf("ab"), leaf("cd")])]));
+        assert_invalid(internal(&[leaf("ab")]));
+    }
+
+    #[test]
+    fn tree_format_08() {
+        // CRLF pairs split between leaves.
+        assert_invalid(internal(&[leaf("ab\r"), leaf("\ncd")]));
+        assert_invalid(internal(&[
+            internal(&[leaf("ab"), leaf("cd\r")]),
+            internal(&[leaf("\nef"), leaf("gh")]),
+        ]));
+    }
+
+    #[test]
+    fn tree_format_09() {
+        // Trees with too few children in a node are written rebalanced, so
+        // that they can be read back.
+        fn new_leaf(text: &str) -> (TextInfo, Arc<Node>) {
+            let node = Node::Leaf(NodeText::from_str(text));
+            (TextInfo::from_str(text), Arc::new(node))
+        }
+        let mut left = NodeChildren::new();
+        left.push(new_leaf("ab"));
+        left.push(new_leaf("cd"));
+        let mut right = NodeChildren::new();
+        right.push(new_leaf("ef"));
+        let mut root = NodeChildren::new();
+        root.push((left.combined_info(), Arc::new(Node::Internal(left))));
+        root.push((right.combined_info(), Arc::new(Node::Internal(right))));
+        let r = Rope {
+            root: Arc::new(Node::Internal(root)),
+        };
+        assert!(r.validate().is_err());
+
+        let r2 = round_trip(&r);
+        assert_eq!(r2, "abcdef");
+        r2.assert_invariants();
+    }
+}
@@ -0,0 +1,164 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crlf;
+use tree::{Node, MIN_CHILDREN};
+
+/// The error returned by `Rope::validate()`, describing the first broken
+/// invariant that was found in a `Rope`'s tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Which invariant is broken.
+    pub kind: ValidationErrorKind,
+    /// The path from the root of the tree to the node where the problem
+    /// was found, as the index of the child taken at each level.  Empty for
+    /// the root itself.
+    pub node_path: Vec<usize>,
+    /// The byte index in the text where that node starts.
+    pub byte_idx: usize,
+}
+
+/// The invariants that `Rope::validate()` checks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// The text info (byte, char and line break counts) that a node keeps
+    /// for one of its children doesn't match the child's text.
+    InconsistentInfo,
+    /// The node's leaves are at a different depth than those of the nodes
+    /// before it.
+    Unbalanced,
+    /// An internal node has fewer children than the minimum.
+    TooFewChildren,
+    /// A leaf other than the root is empty.
+    EmptyLeaf,
+    /// A leaf starts with the second half of a CRLF pair, or of a char,
+    /// whose first half ends the previous leaf.
+    SplitSeam,
+}
+
+impl ValidationError {
+    fn new(kind: ValidationErrorKind, node_path: &[usize], byte_idx: usize) -> Self {
+        ValidationError {
+            kind: kind,
+            node_path: node_path.to_vec(),
+            byte_idx: byte_idx,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let problem = match self.kind {
+            ValidationErrorKind::InconsistentInfo => "text info doesn't match the node's text",
+            ValidationErrorKind::Unbalanced => "leaves at different depths",
+            ValidationErrorKind::TooFewChildren => "internal node with too few children",
+            ValidationErrorKind::EmptyLeaf => "empty leaf",
+            ValidationErrorKind::SplitSeam => "CRLF pair or char split between leaves",
+        };
+        write!(
+            f,
+            "invalid rope tree: {} at node {:?}, starting at byte {}",
+            problem, self.node_path, self.byte_idx
+        )
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+//===========================================================================
+
+/// Checks that the text info kept for each node matches its text.
+pub(crate) fn check_integrity(root: &Node) -> Result<(), ValidationError> {
+    check_info(root, &mut Vec::new(), 0)
+}
+
+/// Checks the structural invariants of the tree:
+///
+/// - The tree is the same height everywhere.
+/// - All internal nodes have the minimum number of children.
+/// - All leaf nodes are non-empty.
+/// - CRLF pairs are never split over chunk boundaries.
+pub(crate) fn check_invariants(root: &Node) -> Result<(), ValidationError> {
+    let mut checker = Checker {
+        path: Vec::new(),
+        prev_leaf: "",
+    };
+    checker.check_node(root, true, 0).map(|_| ())
+}
+
+fn check_info(node: &Node, path: &mut Vec<usize>, byte_idx: usize) -> Result<(), ValidationError> {
+    if let Node::Internal(ref children) = *node {
+        let mut child_byte_idx = byte_idx;
+        for (i, (info, child)) in children.iter().enumerate() {
+            path.push(i);
+            if *info != child.text_info() {
+                return Err(ValidationError::new(
+                    ValidationErrorKind::InconsistentInfo,
+                    path,
+                    child_byte_idx,
+                ));
+            }
+            check_info(child, path, child_byte_idx)?;
+            path.pop();
+            child_byte_idx += info.bytes as usize;
+        }
+    }
+    Ok(())
+}
+
+struct Checker<'a> {
+    path: Vec<usize>,
+    // The last non-empty leaf before the current node.
+    prev_leaf: &'a str,
+}
+
+impl<'a> Checker<'a> {
+    // Returns the depth of `node`.
+    fn check_node(
+        &mut self,
+        node: &'a Node,
+        is_root: bool,
+        byte_idx: usize,
+    ) -> Result<usize, ValidationError> {
+        match *node {
+            Node::Leaf(ref text) => {
+                if text.is_empty() {
+                    if !is_root {
+                        return Err(self.error(ValidationErrorKind::EmptyLeaf, byte_idx));
+                    }
+                } else {
+                    if !self.prev_leaf.is_empty()
+                        && !crlf::seam_is_break(self.prev_leaf.as_bytes(), text.as_bytes())
+                    {
+                        return Err(self.error(ValidationErrorKind::SplitSeam, byte_idx));
+                    }
+                    self.prev_leaf = text;
+                }
+                Ok(0)
+            }
+            Node::Internal(ref children) => {
+                let min_children = if is_root { 2 } else { MIN_CHILDREN };
+                if children.len() < min_children {
+                    return Err(self.error(ValidationErrorKind::TooFewChildren, byte_idx));
+                }
+
+                let mut depth = None;
+                let mut child_byte_idx = byte_idx;
+                for (i, (info, child)) in children.iter().enumerate() {
+                    self.path.push(i);
+                    let child_depth = self.check_node(child, false, child_byte_idx)?;
+                    if *depth.get_or_insert(child_depth) != child_depth {
+                        return Err(self.error(ValidationErrorKind::Unbalanced, child_byte_idx));
+                    }
+                    self.path.pop();
+                    child_byte_idx += info.bytes as usize;
+                }
+                Ok(depth.unwrap() + 1)
+            }
+        }
+    }
+
+    fn error(&self, kind: ValidationErrorKind, byte_idx: usize) -> ValidationError {
+        ValidationError::new(kind, &self.path, byte_idx)
+    }
+}
@@ -0,0 +1,121 @@
+#![cfg(feature = "encoding")]
+
+extern crate encoding_rs;
+extern crate ropey;
+
+use std::io::Cursor;
+
+use encoding_rs::{Encoding, ISO_2022_JP, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use ropey::encoding::Unmappable;
+use ropey::Rope;
+
+const TEXT: &str = include_str!("test_text.txt");
+
+fn encode(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+    // encoding_rs can't encode UTF-16, so do that by hand.
+    if encoding == UTF_16LE {
+        text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+    } else if encoding == UTF_16BE {
+        text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect()
+    } else {
+        let (bytes, _, had_errors) = encoding.encode(text);
+        assert!(!had_errors);
+        bytes.into_owned()
+    }
+}
+
+#[test]
+fn from_reader_with_encoding_01() {
+    let text = "Hello there!  How're you doing?\nこんにちは、みんなさん！\n".repeat(50);
+    for &encoding in &[UTF_8, SHIFT_JIS, UTF_16LE, UTF_16BE] {
+        let bytes = encode(&text, encoding);
+        let (rope, used, had_errors) =
+            Rope::from_reader_with_encoding(Cursor::new(bytes), encoding).unwrap();
+
+        assert_eq!(rope, text.as_str());
+        assert_eq!(used, encoding);
+        assert!(!had_errors);
+
+        // Make sure the tree is sound
+        rope.assert_integrity();
+        rope.assert_invariants();
+    }
+}
+
+#[test]
+fn from_reader_with_encoding_02() {
+    // Latin-1 style text.
+    let text = "Café, naïve, jalapeño.  ".repeat(100);
+    let bytes = encode(&text, WINDOWS_1252);
+    assert_eq!(bytes.len(), text.chars().count());
+
+    let (rope, _, _) = Rope::from_reader_with_encoding(Cursor::new(bytes), WINDOWS_1252).unwrap();
+    assert_eq!(rope, text.as_str());
+}
+
+#[test]
+fn from_reader_with_encoding_03() {
+    // A BOM overrides the given encoding.
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(encode(TEXT, UTF_16LE));
+    let (rope, used, _) = Rope::from_reader_with_encoding(Cursor::new(bytes), SHIFT_JIS).unwrap();
+    assert_eq!(rope, TEXT);
+    assert_eq!(used, UTF_16LE);
+
+    let mut bytes = vec![0xEF, 0xBB, 0xBF];
+    bytes.extend(TEXT.as_bytes());
+    let (rope, used, _) =
+        Rope::from_reader_with_encoding(Cursor::new(bytes), WINDOWS_1252).unwrap();
+    assert_eq!(rope, TEXT);
+    assert_eq!(used, UTF_8);
+}
+
+#[test]
+fn from_reader_with_encoding_04() {
+    // Malformed input.
+    let mut bytes = TEXT.as_bytes().to_vec();
+    bytes[6132] = 0b1100_0000;
+    bytes[6133] = 0b0100_0000;
+    let (rope, _, had_errors) =
+        Rope::from_reader_with_encoding(Cursor::new(&bytes), UTF_8).unwrap();
+    assert!(had_errors);
+    assert_eq!(rope, String::from_utf8_lossy(&bytes));
+}
+
+#[test]
+fn write_to_with_encoding_01() {
+    // Round trips.
+    let text = "Hello there!  How're you doing?\nこんにちは、みんなさん！\n".repeat(50);
+    let rope = Rope::from_str(&text);
+    for &encoding in &[UTF_8, SHIFT_JIS, ISO_2022_JP, UTF_16LE, UTF_16BE] {
+        let mut bytes = Vec::new();
+        rope.write_to_with_encoding(&mut bytes, encoding, Unmappable::Error)
+            .unwrap();
+        assert_eq!(bytes, encode(&text, encoding));
+
+        let (rope2, _, _) = Rope::from_reader_with_encoding(Cursor::new(bytes), encoding).unwrap();
+        assert_eq!(rope2, rope);
+    }
+}
+
+#[test]
+fn write_to_with_encoding_02() {
+    // Unmappable chars.
+    let rope = Rope::from_str("Café, こんにちは!");
+    let mut bytes = Vec::new();
+    let err = rope
+        .write_to_with_encoding(&mut bytes, WINDOWS_1252, Unmappable::Error)
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let mut bytes = Vec::new();
+    rope.write_to_with_encoding(&mut bytes, WINDOWS_1252, Unmappable::Replace)
+        .unwrap();
+    assert_eq!(bytes, encode("Café, ?????!", WINDOWS_1252));
+
+    let mut bytes = Vec::new();
+    rope.slice(4..9)
+        .write_to_with_encoding(&mut bytes, ISO_2022_JP, Unmappable::Replace)
+        .unwrap();
+    assert_eq!(bytes, encode(", こんに", ISO_2022_JP));
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "mmap")]
+
+extern crate ropey;
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use ropey::Rope;
+
+const TEXT: &str = include_str!("test_text.txt");
+
+fn write_temp_file(name: &str, data: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ropey_{}_{}", std::process::id(), name));
+    fs::File::create(&path).unwrap().write_all(data).unwrap();
+    path
+}
+
+#[test]
+fn from_file_01() {
+    let path = write_temp_file("from_file_01", TEXT.as_bytes());
+    let rope = unsafe { Rope::from_file(&path) }.unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(rope, TEXT);
+
+    // Make sure the tree is sound
+    rope.assert_integrity();
+    rope.assert_invariants();
+}
+
+#[test]
+fn from_file_02() {
+    // Empty file
+    let path = write_temp_file("from_file_02", b"");
+    let rope = unsafe { Rope::from_file(&path) }.unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(rope, "");
+}
+
+#[test]
+fn from_file_03() {
+    // Invalid utf8
+    let mut text = Vec::new();
+    text.extend(TEXT.as_bytes());
+    text[6132] = 0b1100_0000;
+    text[6133] = 0b0100_0000;
+
+    let path = write_temp_file("from_file_03", &text);
+    let result = unsafe { Rope::from_file(&path) };
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    // Missing file
+    let result = unsafe { Rope::from_file(&path) };
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+}
@@ -1,7 +1,9 @@
+#![cfg(feature = "std")]
+
 extern crate rand;
 extern crate ropey;
 
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 
 use ropey::Rope;
 
@@ -53,3 +55,92 @@ fn from_reader_03() {
         panic!("Should have returned an invalid data error.")
     }
 }
+
+#[test]
+fn from_reader_04() {
+    // Make text that ends in the middle of a multi-byte char.
+    let text = &"こんにちは".as_bytes()[..14];
+
+    // Try to read the data, and verify that we get the right error.
+    for &buffer_size in &[4, 5, 100] {
+        let result = Rope::from_reader_with_progress(Cursor::new(text), buffer_size, |_| {});
+        if let Err(e) = result {
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        } else {
+            panic!("Should have returned an invalid data error.")
+        }
+    }
+}
+
+#[test]
+fn from_reader_with_progress_01() {
+    for &buffer_size in &[0, 4, 5, 100, 8192, 1 << 20] {
+        let mut last_progress = 0;
+        let mut calls = 0;
+        let rope = Rope::from_reader_with_progress(Cursor::new(TEXT), buffer_size, |bytes_read| {
+            assert!(bytes_read > last_progress);
+            last_progress = bytes_read;
+            calls += 1;
+        })
+        .unwrap();
+
+        assert_eq!(rope, TEXT);
+        assert_eq!(last_progress, TEXT.len());
+        assert!(calls >= TEXT.len() / buffer_size.max(4));
+
+        // Make sure the tree is sound
+        rope.assert_integrity();
+        rope.assert_invariants();
+    }
+}
+
+#[test]
+fn from_reader_lossy_01() {
+    // Valid text is loaded unchanged.
+    let rope = Rope::from_reader_lossy(Cursor::new(TEXT)).unwrap();
+
+    assert_eq!(rope, TEXT);
+
+    // Make sure the tree is sound
+    rope.assert_integrity();
+    rope.assert_invariants();
+}
+
+#[test]
+fn from_reader_lossy_02() {
+    // Make text with utf8-invalid byte sequences in it.
+    let mut text = Vec::new();
+    text.extend(TEXT.as_bytes());
+    text[6132] = 0b1100_0000;
+    text[6133] = 0b0100_0000;
+    text[20000] = 0xFF;
+    text.extend(&"こんにちは".as_bytes()[..4]);
+
+    let rope = Rope::from_reader_lossy(Cursor::new(&text)).unwrap();
+
+    assert_eq!(rope, String::from_utf8_lossy(&text));
+
+    // Make sure the tree is sound
+    rope.assert_integrity();
+    rope.assert_invariants();
+}
+
+#[test]
+fn from_reader_lossy_03() {
+    // Invalid sequences split across reads.
+    struct OneByteReader<'a>(&'a [u8]);
+    impl<'a> Read for OneByteReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = self.0.len().min(buf.len()).min(1);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    let text = b"a\xE3\x81b\xE3\x81\x93\xF0\x9F\x98\x80\xF0\x9F\x98";
+    let rope = Rope::from_reader_lossy(OneByteReader(text)).unwrap();
+
+    assert_eq!(rope, String::from_utf8_lossy(text));
+    assert_eq!(rope, "a\u{FFFD}bこ😀\u{FFFD}");
+}
@@ -11,6 +11,17 @@ use ropey::{
     Rope,
 };
 
+// The most bytes a leaf can hold, for the node size the crate is built with.
+// `triomphe::Arc` has one counter less than `std::sync::Arc`, which leaves
+// room for more text.
+const MAX_LEAF_BYTES: usize = if cfg!(feature = "large_nodes") {
+    4096
+} else if cfg!(feature = "small_nodes") {
+    512
+} else {
+    1024
+} - if cfg!(feature = "triomphe") { 25 } else { 33 };
+
 fn string_insert(text: &mut String, char_idx: usize, text_ins: &str) {
     let byte_idx = char_to_byte_idx(text, char_idx);
     text.insert_str(byte_idx, text_ins);
@@ -249,8 +260,7 @@ proptest! {
         rope.assert_invariants();
         assert_eq!(rope, rope_clone);
 
-        let max_leaf_bytes = 1024 - 33;
-        assert!((rope.capacity() - rope.len_bytes()) <= max_leaf_bytes);
+        assert!((rope.capacity() - rope.len_bytes()) <= MAX_LEAF_BYTES);
         assert!(rope.capacity() <= capacity_before);
     }
 
@@ -272,8 +282,7 @@ proptest! {
         rope.assert_invariants();
         assert_eq!(rope, rope_clone);
 
-        let max_leaf_bytes = 1024 - 33;
-        let max_diff = max_leaf_bytes + ((rope.len_bytes() / max_leaf_bytes) * ins_text.len());
+        let max_diff = MAX_LEAF_BYTES + ((rope.len_bytes() / MAX_LEAF_BYTES) * ins_text.len());
 
         assert!((rope.capacity() - rope.len_bytes()) <= max_diff);
     }